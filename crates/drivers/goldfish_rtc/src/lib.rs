@@ -0,0 +1,68 @@
+//! qemu `virt` 机器上的 Goldfish RTC，参考 linux 的 `drivers/rtc/rtc-goldfish.c`
+
+#![no_std]
+
+use core::ptr;
+
+use klocks::{Once, SpinNoIrqMutex};
+
+/// 读取时间的低 32 位，同时锁存高 32 位
+const TIME_LOW: usize = 0x00;
+const TIME_HIGH: usize = 0x04;
+
+static RTC: Once<GoldfishRtc> = Once::new();
+
+/// 设备树中 RTC 节点的 `compatible`
+pub const COMPATIBLE: &str = "google,goldfish-rtc";
+
+pub struct GoldfishRtc {
+    /// 寄存器的虚拟地址
+    base: usize,
+    /// 读写时间需要分别访问高低两个寄存器，需要保证原子性
+    lock: SpinNoIrqMutex<()>,
+}
+
+impl GoldfishRtc {
+    /// 自 Epoch 以来的纳秒数
+    pub fn read_time_ns(&self) -> u64 {
+        let _guard = self.lock.lock();
+        // 必须先读低位，读低位时设备会锁存高位
+        let low = unsafe { self.read_reg(TIME_LOW) };
+        let high = unsafe { self.read_reg(TIME_HIGH) };
+        (u64::from(high) << 32) | u64::from(low)
+    }
+
+    pub fn set_time_ns(&self, ns: u64) {
+        let _guard = self.lock.lock();
+        // 写低位时设备才会更新时间，因此先写高位
+        unsafe {
+            self.write_reg(TIME_HIGH, (ns >> 32) as u32);
+            self.write_reg(TIME_LOW, ns as u32);
+        }
+    }
+
+    unsafe fn read_reg(&self, offset: usize) -> u32 {
+        unsafe { ptr::read_volatile((self.base + offset) as *const u32) }
+    }
+
+    unsafe fn write_reg(&self, offset: usize, value: u32) {
+        unsafe { ptr::write_volatile((self.base + offset) as *mut u32, value) }
+    }
+}
+
+/// 初始化全局的 RTC 设备，只有第一次调用有效
+///
+/// # Safety
+///
+/// `base` 必须是已经映射好的 Goldfish RTC 寄存器的虚拟地址
+pub unsafe fn init(base: usize) {
+    RTC.call_once(|| GoldfishRtc {
+        base,
+        lock: SpinNoIrqMutex::new(()),
+    });
+}
+
+/// 全局的 RTC 设备，没有探测到时为 `None`
+pub fn instance() -> Option<&'static GoldfishRtc> {
+    RTC.get()
+}
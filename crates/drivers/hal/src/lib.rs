@@ -3,8 +3,12 @@
 #[cfg(all(feature = "std", feature = "kernel"))]
 compile_error!("Feature `std` 与 `kernel` 互斥，只能开启其中之一");
 
+extern crate alloc;
+
 pub mod block_device {
-    use klocks::Once;
+    use alloc::{collections::BTreeMap, string::String, vec::Vec};
+
+    use klocks::{Once, SpinMutex};
 
     pub const BLOCK_SIZE: usize = 512;
 
@@ -14,6 +18,11 @@ pub mod block_device {
         fn read_block_cached(&self, block_id: usize, buf: &mut [u8; BLOCK_SIZE]) {
             self.read_block(block_id, buf);
         }
+
+        fn write_block(&self, block_id: usize, buf: &[u8; BLOCK_SIZE]);
+
+        /// 设备的总块数
+        fn num_blocks(&self) -> usize;
     }
 
     static BLOCK_DEVICE: Once<&'static dyn BlockDevice> = Once::new();
@@ -25,4 +34,90 @@ pub mod block_device {
     pub fn instance() -> &'static dyn BlockDevice {
         *BLOCK_DEVICE.get().unwrap()
     }
+
+    /// virtio 块设备的主设备号。linux 中是动态分配的，这里固定下来
+    pub const VIRTIO_BLK_MAJOR: u32 = 254;
+    /// 每个磁盘占用的次设备号数量，即磁盘本身加上最多 15 个分区，同 linux 的 virtio-blk
+    pub const MINORS_PER_DISK: u32 = 16;
+
+    /// 已注册的块设备，包括磁盘和分区
+    #[derive(Clone)]
+    pub struct RegisteredDevice {
+        /// 设备名，如 `vda`、`vda1`
+        pub name: String,
+        pub major: u32,
+        pub minor: u32,
+        pub device: &'static dyn BlockDevice,
+    }
+
+    /// 所有探测到的块设备，以设备名（如 `vda`）为键
+    static REGISTRY: SpinMutex<BTreeMap<String, RegisteredDevice>> = SpinMutex::new(BTreeMap::new());
+
+    /// 注册一个块设备，重名时会覆盖旧的设备
+    pub fn register(name: impl Into<String>, major: u32, minor: u32, device: &'static dyn BlockDevice) {
+        let name = name.into();
+        REGISTRY.lock().insert(
+            name.clone(),
+            RegisteredDevice {
+                name,
+                major,
+                minor,
+                device,
+            },
+        );
+    }
+
+    /// 按设备名（不含 `/dev/` 前缀）查找已注册的块设备
+    pub fn get(name: &str) -> Option<&'static dyn BlockDevice> {
+        REGISTRY.lock().get(name).map(|registered| registered.device)
+    }
+
+    /// 按主、次设备号查找已注册的块设备
+    pub fn get_by_number(major: u32, minor: u32) -> Option<&'static dyn BlockDevice> {
+        REGISTRY
+            .lock()
+            .values()
+            .find(|registered| registered.major == major && registered.minor == minor)
+            .map(|registered| registered.device)
+    }
+
+    /// 所有已注册的块设备，按设备名排序
+    pub fn all() -> Vec<RegisteredDevice> {
+        REGISTRY.lock().values().cloned().collect()
+    }
+}
+
+pub mod net_device {
+    use alloc::vec::Vec;
+
+    use klocks::Once;
+
+    /// 以太网卡
+    pub trait NetDevice: Send + Sync {
+        /// 网卡的 MAC 地址
+        fn mac_address(&self) -> [u8; 6];
+
+        /// 取出一个收到的以太网帧，没有时返回 `None`
+        fn receive(&self) -> Option<Vec<u8>>;
+
+        /// 发送队列是否还有空间
+        fn can_send(&self) -> bool;
+
+        /// 发送一个以太网帧
+        fn send(&self, frame: &[u8]);
+
+        /// 应答网卡的中断
+        fn ack_interrupt(&self);
+    }
+
+    static NET_DEVICE: Once<&'static dyn NetDevice> = Once::new();
+
+    /// 注册网卡。目前只支持一张，之后注册的会被忽略
+    pub fn init_instance(device: &'static dyn NetDevice) {
+        NET_DEVICE.call_once(|| device);
+    }
+
+    pub fn instance() -> Option<&'static dyn NetDevice> {
+        NET_DEVICE.get().copied()
+    }
 }
@@ -15,11 +15,18 @@ impl Tty {
         self.queue.pop_front()
     }
 
+    /// 是否有尚未读取的输入
+    pub fn has_byte(&self) -> bool {
+        !self.queue.is_empty()
+    }
+
     /// 注册一个任务。如果原来已经注册过，就将旧的任务唤醒。
     ///
-    /// 这是为了防止多个进程读时永久阻塞不被唤醒
+    /// 这是为了防止多个进程读时永久阻塞不被唤醒。同一个任务重复注册时则不唤醒，否则它会反复唤醒自己
     pub fn register_waker(&mut self, waker: Waker) {
-        if let Some(old_waker) = self.waker.replace(waker) {
+        if let Some(old_waker) = self.waker.replace(waker)
+            && !old_waker.will_wake(self.waker.as_ref().unwrap())
+        {
             old_waker.wake();
         }
     }
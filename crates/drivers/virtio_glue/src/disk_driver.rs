@@ -36,12 +36,15 @@ impl<H: Hal, T: Transport> DiskDriver<H, T> {
         self.caches.write().insert(block_id, *buf);
     }
 
-    // pub fn write_blocks(&self, block_id: usize, buf: &mut [u8; BLOCK_SIZE]) {
-    //     if let Err(e) = self.device.lock().write_blocks(block_id, buf) {
-    //         panic!("Failed writing virtio blocks {block_id}: {e}");
-    //     }
-    //     self.caches.write().insert(block_id, *buf);
-    // }
+    pub fn write_blocks(&self, block_id: usize, buf: &[u8; BLOCK_SIZE]) {
+        if let Err(e) = self.device.lock().write_blocks(block_id, buf) {
+            panic!("Failed writing virtio blocks {block_id}: {e}");
+        }
+        // 块缓存只缓存读过的块，这里只需要保证已缓存的块与磁盘一致
+        if let Some(block) = self.caches.write().get_mut(&block_id) {
+            block.copy_from_slice(buf);
+        }
+    }
 }
 
 // TODO: 实现可失败的 read_blocks/write_blocks
@@ -54,4 +57,12 @@ impl<H: Hal + Send + Sync, T: Transport + Send + Sync> BlockDevice for DiskDrive
     fn read_block_cached(&self, block_id: usize, buf: &mut [u8; BLOCK_SIZE]) {
         self.read_blocks_cached(block_id, buf);
     }
+
+    fn write_block(&self, block_id: usize, buf: &[u8; BLOCK_SIZE]) {
+        self.write_blocks(block_id, buf);
+    }
+
+    fn num_blocks(&self) -> usize {
+        self.device.lock().capacity() as usize
+    }
 }
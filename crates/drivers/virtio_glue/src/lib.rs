@@ -1,7 +1,11 @@
 #![no_std]
 
+extern crate alloc;
+
 mod disk_driver;
 mod hal_impl;
+mod net_driver;
 
 pub use disk_driver::DiskDriver;
 pub use hal_impl::HalImpl;
+pub use net_driver::{NetDriver, NET_BUF_LEN, NET_QUEUE_SIZE};
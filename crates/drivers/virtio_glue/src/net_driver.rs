@@ -0,0 +1,57 @@
+use alloc::vec::Vec;
+
+use hal::net_device::NetDevice;
+use klocks::SpinNoIrqMutex;
+use virtio_drivers::{device::net::VirtIONet, transport::Transport, Hal};
+
+/// virtio 网卡的队列长度
+pub const NET_QUEUE_SIZE: usize = 16;
+/// 接收缓冲区的大小，需要能放下一个完整的以太网帧
+pub const NET_BUF_LEN: usize = 2048;
+
+pub struct NetDriver<H: Hal, T: Transport> {
+    /// 中断处理函数也会访问设备，因此持锁时需要关中断
+    device: SpinNoIrqMutex<VirtIONet<H, T, NET_QUEUE_SIZE>>,
+}
+
+impl<H: Hal, T: Transport> NetDriver<H, T> {
+    pub fn new(net_device: VirtIONet<H, T, NET_QUEUE_SIZE>) -> Self {
+        Self {
+            device: SpinNoIrqMutex::new(net_device),
+        }
+    }
+}
+
+impl<H: Hal + Send + Sync, T: Transport + Send + Sync> NetDevice for NetDriver<H, T> {
+    fn mac_address(&self) -> [u8; 6] {
+        self.device.lock().mac_address()
+    }
+
+    fn receive(&self) -> Option<Vec<u8>> {
+        let mut device = self.device.lock();
+        let rx_buf = device.receive().ok()?;
+        let frame = rx_buf.packet().to_vec();
+        if let Err(e) = device.recycle_rx_buffer(rx_buf) {
+            panic!("Failed recycling virtio net rx buffer: {e}");
+        }
+        Some(frame)
+    }
+
+    fn can_send(&self) -> bool {
+        self.device.lock().can_send()
+    }
+
+    fn send(&self, frame: &[u8]) {
+        let mut device = self.device.lock();
+        let mut tx_buf = device.new_tx_buffer(frame.len());
+        tx_buf.packet_mut().copy_from_slice(frame);
+        // TODO: [low] 发送失败时应当丢弃该帧而不是 panic
+        if let Err(e) = device.send(tx_buf) {
+            panic!("Failed sending virtio net frame: {e}");
+        }
+    }
+
+    fn ack_interrupt(&self) {
+        self.device.lock().ack_interrupt();
+    }
+}
@@ -0,0 +1,134 @@
+//! `/dev/vdX` 等块设备节点，直接读写底层的块设备而不经过页缓存
+
+use alloc::boxed::Box;
+
+use defines::{
+    error::{errno, AKResult, KResult},
+    fs::{makedev, StatMode},
+    ioctl::{BLKGETSIZE, BLKGETSIZE64, BLKSSZGET},
+};
+use executor::time;
+use hal::block_device::{BlockDevice, RegisteredDevice, BLOCK_SIZE};
+use libkernel::{
+    fs::inode::{BytesInodeBackend, InodeMeta, InodeMode},
+    memory::{ReadBuffer, UserCheck, WriteBuffer},
+};
+
+pub struct BlockDeviceInode {
+    meta: InodeMeta,
+    device: &'static dyn BlockDevice,
+}
+
+impl BlockDeviceInode {
+    pub fn new(registered: &RegisteredDevice) -> Self {
+        let mut meta = InodeMeta::new(InodeMode::BlockDevice);
+        meta.set_rdev(makedev(registered.major, registered.minor));
+        let meta_inner = meta.get_inner_mut();
+        // 块设备的 `data_len` 是设备大小，使得 `lseek(SEEK_END)` 能得到正确的位置
+        meta_inner.data_len = (registered.device.num_blocks() * BLOCK_SIZE) as u64;
+        meta_inner.perm = StatMode::from_bits_truncate(0o660);
+        let curr_time = time::curr_time_spec();
+        meta_inner.access_time = curr_time;
+        meta_inner.change_time = curr_time;
+        meta_inner.modify_time = curr_time;
+        Self {
+            meta,
+            device: registered.device,
+        }
+    }
+
+    fn size(&self) -> u64 {
+        (self.device.num_blocks() * BLOCK_SIZE) as u64
+    }
+}
+
+impl BytesInodeBackend for BlockDeviceInode {
+    fn meta(&self) -> &InodeMeta {
+        &self.meta
+    }
+
+    fn read_inode_at<'a>(&'a self, buf: ReadBuffer<'a>, offset: u64) -> AKResult<'a, usize> {
+        Box::pin(async move {
+            let size = self.size();
+            if offset >= size {
+                return Ok(0);
+            }
+            let read_len = usize::min(buf.len(), (size - offset) as usize);
+            let mut user_buf;
+            let buf = match buf {
+                ReadBuffer::Kernel(buf) => &mut buf[..read_len],
+                ReadBuffer::User(buf) => unsafe {
+                    user_buf = buf.slice(0..read_len).expect("must be in bound").check_slice_mut()?;
+                    user_buf.as_bytes_mut()
+                },
+            };
+
+            let mut block = [0; BLOCK_SIZE];
+            let mut nread = 0;
+            while nread < read_len {
+                let pos = offset as usize + nread;
+                let block_offset = pos % BLOCK_SIZE;
+                let copy_len = usize::min(read_len - nread, BLOCK_SIZE - block_offset);
+                self.device.read_block(pos / BLOCK_SIZE, &mut block);
+                buf[nread..nread + copy_len].copy_from_slice(&block[block_offset..block_offset + copy_len]);
+                nread += copy_len;
+            }
+            Ok(nread)
+        })
+    }
+
+    fn write_inode_at<'a>(&'a self, buf: WriteBuffer<'a>, offset: u64) -> AKResult<'a, usize> {
+        Box::pin(async move {
+            let size = self.size();
+            if offset >= size {
+                return Err(errno::ENOSPC);
+            }
+            let write_len = usize::min(buf.len(), (size - offset) as usize);
+            let user_buf;
+            let buf = match buf.slice(0..write_len).expect("must be in bound") {
+                WriteBuffer::Kernel(buf) => buf,
+                WriteBuffer::User(buf) => {
+                    user_buf = buf.check_slice()?;
+                    &*user_buf
+                }
+            };
+
+            let mut block = [0; BLOCK_SIZE];
+            let mut nwrite = 0;
+            while nwrite < write_len {
+                let pos = offset as usize + nwrite;
+                let block_offset = pos % BLOCK_SIZE;
+                let copy_len = usize::min(write_len - nwrite, BLOCK_SIZE - block_offset);
+                // 只覆盖块的一部分时需要先读出原有内容
+                if copy_len < BLOCK_SIZE {
+                    self.device.read_block(pos / BLOCK_SIZE, &mut block);
+                }
+                block[block_offset..block_offset + copy_len].copy_from_slice(&buf[nwrite..nwrite + copy_len]);
+                self.device.write_block(pos / BLOCK_SIZE, &block);
+                nwrite += copy_len;
+            }
+            Ok(nwrite)
+        })
+    }
+
+    fn ioctl(&self, request: usize, argp: usize) -> KResult {
+        match request {
+            BLKGETSIZE64 => {
+                let size_ptr = unsafe { UserCheck::new(argp as _).ok_or(errno::EINVAL)?.check_ptr_mut()? };
+                size_ptr.write(self.size());
+                Ok(0)
+            }
+            BLKGETSIZE => {
+                let sectors_ptr = unsafe { UserCheck::new(argp as _).ok_or(errno::EINVAL)?.check_ptr_mut()? };
+                sectors_ptr.write(self.device.num_blocks());
+                Ok(0)
+            }
+            BLKSSZGET => {
+                let sector_size_ptr = unsafe { UserCheck::new(argp as _).ok_or(errno::EINVAL)?.check_ptr_mut()? };
+                sector_size_ptr.write(BLOCK_SIZE as i32);
+                Ok(0)
+            }
+            _ => Err(errno::ENOTTY),
+        }
+    }
+}
@@ -4,9 +4,13 @@
 extern crate kernel_tracer;
 extern crate alloc;
 
+mod block;
+mod mem;
+mod random;
 mod rtc;
 mod tty;
 
+use block::BlockDeviceInode;
 use defines::{error::KResult, fs::StatFsFlags};
 use ecow::EcoString;
 use libkernel::fs::{
@@ -14,6 +18,8 @@ use libkernel::fs::{
     inode::{DynBytesInode, DynBytesInodeCoercion},
     FileSystem,
 };
+use mem::{FullInode, NullInode, ZeroInode};
+use random::RandomInode;
 use rtc::RtcInode;
 use triomphe::Arc;
 use unsize::CoerceUnsize;
@@ -23,7 +29,7 @@ use self::tty::TtyInode;
 pub const FS_TYPE: &str = "devtmpfs";
 
 pub fn new_dev_fs(
-    parent: Arc<DEntryDir>,
+    parent: Option<Arc<DEntryDir>>,
     name: EcoString,
     device_path: EcoString,
     flags: StatFsFlags,
@@ -32,7 +38,7 @@ pub fn new_dev_fs(
     fs.fs_type = FS_TYPE;
     {
         let mut children = fs.root_dentry.lock_children();
-        let mut add_child = |name: &'static str, inode: Arc<DynBytesInode>| {
+        let mut add_child = |name: &str, inode: Arc<DynBytesInode>| {
             let name = EcoString::from(name);
             let child = DEntry::Bytes(Arc::new(DEntryBytes::new(
                 Arc::clone(&fs.root_dentry),
@@ -44,6 +50,17 @@ pub fn new_dev_fs(
 
         add_child("tty", Arc::new(TtyInode::new()).unsize(DynBytesInodeCoercion!()));
         add_child("rtc", Arc::new(RtcInode::new()).unsize(DynBytesInodeCoercion!()));
+        add_child("null", Arc::new(NullInode::new()).unsize(DynBytesInodeCoercion!()));
+        add_child("zero", Arc::new(ZeroInode::new()).unsize(DynBytesInodeCoercion!()));
+        add_child("full", Arc::new(FullInode::new()).unsize(DynBytesInodeCoercion!()));
+        add_child("random", Arc::new(RandomInode::new()).unsize(DynBytesInodeCoercion!()));
+        add_child("urandom", Arc::new(RandomInode::new()).unsize(DynBytesInodeCoercion!()));
+        for registered in hal::block_device::all() {
+            add_child(
+                &registered.name,
+                Arc::new(BlockDeviceInode::new(&registered)).unsize(DynBytesInodeCoercion!()),
+            );
+        }
     }
     Ok(fs)
 }
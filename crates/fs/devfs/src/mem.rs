@@ -0,0 +1,123 @@
+//! `/dev/null`、`/dev/zero`、`/dev/full`，同 linux 的 `drivers/char/mem.c`
+
+use alloc::boxed::Box;
+
+use defines::{
+    error::{errno, AKResult, KResult},
+    fs::StatMode,
+};
+use executor::time;
+use libkernel::{
+    fs::inode::{BytesInodeBackend, InodeMeta, InodeMode},
+    memory::{ReadBuffer, WriteBuffer},
+};
+
+/// 所有用户都可读写的字符设备
+pub(crate) fn new_char_device_meta() -> InodeMeta {
+    let mut meta = InodeMeta::new(InodeMode::CharDevice);
+    let meta_inner = meta.get_inner_mut();
+    meta_inner.perm = StatMode::from_bits_truncate(0o666);
+    let curr_time = time::curr_time_spec();
+    meta_inner.access_time = curr_time;
+    meta_inner.change_time = curr_time;
+    meta_inner.modify_time = curr_time;
+    meta
+}
+
+fn fill_zero(buf: ReadBuffer<'_>) -> KResult<usize> {
+    let len = buf.len();
+    match buf {
+        ReadBuffer::Kernel(buf) => buf.fill(0),
+        ReadBuffer::User(buf) => unsafe {
+            buf.check_slice_mut()?.as_bytes_mut().fill(0);
+        },
+    }
+    Ok(len)
+}
+
+/// `/dev/null`，读总是返回 EOF，写总是成功并丢弃数据
+pub struct NullInode {
+    meta: InodeMeta,
+}
+
+impl NullInode {
+    pub fn new() -> Self {
+        Self {
+            meta: new_char_device_meta(),
+        }
+    }
+}
+
+impl BytesInodeBackend for NullInode {
+    fn meta(&self) -> &InodeMeta {
+        &self.meta
+    }
+
+    fn read_inode_at<'a>(&'a self, _buf: ReadBuffer<'a>, _offset: u64) -> AKResult<'a, usize> {
+        Box::pin(async move { Ok(0) })
+    }
+
+    fn write_inode_at<'a>(&'a self, buf: WriteBuffer<'a>, _offset: u64) -> AKResult<'a, usize> {
+        Box::pin(async move { Ok(buf.len()) })
+    }
+}
+
+/// `/dev/zero`，读总是得到 0，写总是成功并丢弃数据。
+///
+/// mmap 时等价于匿名映射
+pub struct ZeroInode {
+    meta: InodeMeta,
+}
+
+impl ZeroInode {
+    pub fn new() -> Self {
+        Self {
+            meta: new_char_device_meta(),
+        }
+    }
+}
+
+impl BytesInodeBackend for ZeroInode {
+    fn meta(&self) -> &InodeMeta {
+        &self.meta
+    }
+
+    fn read_inode_at<'a>(&'a self, buf: ReadBuffer<'a>, _offset: u64) -> AKResult<'a, usize> {
+        Box::pin(async move { fill_zero(buf) })
+    }
+
+    fn write_inode_at<'a>(&'a self, buf: WriteBuffer<'a>, _offset: u64) -> AKResult<'a, usize> {
+        Box::pin(async move { Ok(buf.len()) })
+    }
+
+    fn anonymous_mmap(&self) -> bool {
+        true
+    }
+}
+
+/// `/dev/full`，读同 `/dev/zero`，写总是失败并返回 ENOSPC
+pub struct FullInode {
+    meta: InodeMeta,
+}
+
+impl FullInode {
+    pub fn new() -> Self {
+        Self {
+            meta: new_char_device_meta(),
+        }
+    }
+}
+
+impl BytesInodeBackend for FullInode {
+    fn meta(&self) -> &InodeMeta {
+        &self.meta
+    }
+
+    fn read_inode_at<'a>(&'a self, buf: ReadBuffer<'a>, _offset: u64) -> AKResult<'a, usize> {
+        Box::pin(async move { fill_zero(buf) })
+    }
+
+    fn write_inode_at<'a>(&'a self, _buf: WriteBuffer<'a>, _offset: u64) -> AKResult<'a, usize> {
+        Box::pin(async move { Err(errno::ENOSPC) })
+    }
+}
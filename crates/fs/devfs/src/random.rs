@@ -0,0 +1,110 @@
+//! `/dev/random` 和 `/dev/urandom`。
+//!
+//! 两者都由同一个内核 CSPRNG 提供数据，它在启动后首次使用时即以时钟抖动完成初始化，因此 `/dev/random` 也不会阻塞
+
+use alloc::boxed::Box;
+
+use csprng::{ChaChaRng, KEY_SIZE};
+use defines::error::{AKResult, KResult};
+use klocks::{Lazy, SpinMutex};
+use libkernel::{
+    fs::inode::{BytesInodeBackend, InodeMeta},
+    memory::{ReadBuffer, WriteBuffer},
+};
+
+use crate::mem::new_char_device_meta;
+
+static CRNG: Lazy<SpinMutex<ChaChaRng>> = Lazy::new(|| {
+    let mut rng = ChaChaRng::from_seed([0; KEY_SIZE]);
+    rng.mix(&jitter_entropy());
+    SpinMutex::new(rng)
+});
+
+/// 从时钟抖动中收集熵：反复测量一小段计算的耗时，将其低位混入熵池。
+///
+/// 耗时受缓存、中断以及宿主机调度等因素影响，难以预测
+fn jitter_entropy() -> [u8; 2 * KEY_SIZE] {
+    const ROUNDS_PER_BYTE: usize = 64;
+    let mut pool = [0u8; 2 * KEY_SIZE];
+    let mut prev = riscv_time::get_time();
+    let mut scratch = 0usize;
+    for i in 0..pool.len() * ROUNDS_PER_BYTE {
+        for j in 0..=(prev & 0xf) {
+            scratch = core::hint::black_box(scratch.wrapping_mul(31).wrapping_add(j));
+        }
+        let now = riscv_time::get_time();
+        let index = i % pool.len();
+        pool[index] = pool[index].rotate_left(3) ^ (now.wrapping_sub(prev) as u8);
+        prev = now;
+    }
+    pool
+}
+
+fn fill_random(buf: ReadBuffer<'_>) -> KResult<usize> {
+    // 每次读取都混入当前时间，代价很小
+    let mut crng = CRNG.lock();
+    crng.mix(&riscv_time::get_time().to_le_bytes());
+    match buf {
+        ReadBuffer::Kernel(buf) => {
+            crng.fill_bytes(buf);
+            Ok(buf.len())
+        }
+        ReadBuffer::User(buf) => {
+            // 访问用户内存时可能缺页，因此先生成到内核的缓冲区中，不持有锁访问用户内存
+            drop(crng);
+            const CHUNK_SIZE: usize = 256;
+            let mut chunk = [0; CHUNK_SIZE];
+            let mut nread = 0;
+            while nread < buf.len() {
+                let len = usize::min(CHUNK_SIZE, buf.len() - nread);
+                CRNG.lock().fill_bytes(&mut chunk[..len]);
+                unsafe {
+                    buf.slice(nread..nread + len)
+                        .expect("must be in bound")
+                        .check_slice_mut()?
+                        .as_bytes_mut()
+                        .copy_from_slice(&chunk[..len]);
+                }
+                nread += len;
+            }
+            Ok(nread)
+        }
+    }
+}
+
+pub struct RandomInode {
+    meta: InodeMeta,
+}
+
+impl RandomInode {
+    pub fn new() -> Self {
+        Self {
+            meta: new_char_device_meta(),
+        }
+    }
+}
+
+impl BytesInodeBackend for RandomInode {
+    fn meta(&self) -> &InodeMeta {
+        &self.meta
+    }
+
+    fn read_inode_at<'a>(&'a self, buf: ReadBuffer<'a>, _offset: u64) -> AKResult<'a, usize> {
+        Box::pin(async move { fill_random(buf) })
+    }
+
+    /// 写入的数据会被混入 CSPRNG，同 linux 一样不增加熵的估计值
+    fn write_inode_at<'a>(&'a self, buf: WriteBuffer<'a>, _offset: u64) -> AKResult<'a, usize> {
+        Box::pin(async move {
+            let len = buf.len();
+            match &buf {
+                WriteBuffer::Kernel(buf) => CRNG.lock().mix(buf),
+                WriteBuffer::User(buf) => {
+                    let data = buf.check_slice()?;
+                    CRNG.lock().mix(&data);
+                }
+            }
+            Ok(len)
+        })
+    }
+}
@@ -1,11 +1,16 @@
 use alloc::boxed::Box;
 
-use common::config::PAGE_SIZE;
-use defines::error::{errno, AKResult, KResult};
+use common::constant::NANO_PER_SEC;
+use defines::{
+    error::{errno, AKResult, KResult},
+    fs::StatMode,
+    ioctl::{RtcTime, RTC_RD_TIME, RTC_SET_TIME},
+};
 use executor::time;
 use libkernel::{
     fs::inode::{BytesInodeBackend, InodeMeta, InodeMode},
-    memory::{ReadBuffer, WriteBuffer},
+    memory::{ReadBuffer, UserCheck, WriteBuffer},
+    process,
 };
 
 pub struct RtcInode {
@@ -16,8 +21,8 @@ impl RtcInode {
     pub fn new() -> Self {
         let mut meta = InodeMeta::new(InodeMode::CharDevice);
         let meta_inner = meta.get_inner_mut();
-        // TODO: [low] hack: `rtc` 的大小设置为 `PAGE_SIZE`
-        meta_inner.data_len = PAGE_SIZE as u64;
+        // 同 linux，只有 root 可以读写硬件时钟
+        meta_inner.perm = StatMode::from_bits_truncate(0o600);
         let curr_time = time::curr_time_spec();
         meta_inner.access_time = curr_time;
         meta_inner.change_time = curr_time;
@@ -26,6 +31,14 @@ impl RtcInode {
     }
 }
 
+/// 硬件时钟的时间，没有探测到 RTC 设备时退回到系统的挂钟时间
+fn rtc_secs() -> u64 {
+    goldfish_rtc::instance().map_or_else(
+        || time::real_time().as_secs(),
+        |rtc| rtc.read_time_ns() / NANO_PER_SEC as u64,
+    )
+}
+
 impl BytesInodeBackend for RtcInode {
     fn meta(&self) -> &InodeMeta {
         &self.meta
@@ -34,7 +47,7 @@ impl BytesInodeBackend for RtcInode {
     fn read_inode_at<'a>(&'a self, buf: ReadBuffer<'a>, _offset: u64) -> AKResult<'a, usize> {
         Box::pin(async move {
             debug!("read rtc");
-            // TODO: [low] rtc 实现不正确
+            // TODO: [low] linux 中读取 rtc 会阻塞直到下一次 rtc 中断，目前不支持 rtc 中断，直接返回全 0
             let n_read = buf.len();
             match buf {
                 ReadBuffer::Kernel(buf) => buf.fill(0),
@@ -51,8 +64,27 @@ impl BytesInodeBackend for RtcInode {
         Box::pin(async move { Err(errno::EBADF) })
     }
 
-    fn ioctl(&self, _request: usize, _argp: usize) -> KResult {
-        // TODO: [low] rtc 的 ioctl 不知道该怎么实现
-        Ok(0)
+    fn ioctl(&self, request: usize, argp: usize) -> KResult {
+        match request {
+            RTC_RD_TIME => {
+                let tm_ptr = unsafe { UserCheck::new(argp as _).ok_or(errno::EINVAL)?.check_ptr_mut()? };
+                tm_ptr.write(RtcTime::from_unix_secs(rtc_secs()));
+                Ok(0)
+            }
+            RTC_SET_TIME => {
+                if !process::curr_cred().is_privileged() {
+                    return Err(errno::EACCES);
+                }
+                let tm = UserCheck::<RtcTime>::new(argp as _)
+                    .ok_or(errno::EINVAL)?
+                    .check_ptr()?
+                    .read();
+                let secs = tm.to_unix_secs().ok_or(errno::EINVAL)?;
+                let rtc = goldfish_rtc::instance().ok_or(errno::ENODEV)?;
+                rtc.set_time_ns(secs * NANO_PER_SEC as u64);
+                Ok(0)
+            }
+            _ => Err(errno::ENOTTY),
+        }
     }
 }
@@ -8,6 +8,7 @@ use core::{
 use console_output::print;
 use defines::{
     error::{errno, AKResult, KResult},
+    fs::PollEvents,
     ioctl::{
         Termios, WinSize, TCGETA, TCGETS, TCSBRK, TCSETS, TCSETSF, TCSETSW, TIOCGPGRP, TIOCGWINSZ, TIOCSPGRP,
         TIOCSWINSZ,
@@ -17,11 +18,16 @@ use executor::time;
 use kernel_tracer::Instrument;
 use klocks::SpinMutex;
 use libkernel::{
-    fs::inode::{BytesInodeBackend, InodeMeta, InodeMode},
+    fs::{
+        inode::{BytesInodeBackend, InodeMeta},
+        poll::PollTable,
+    },
     memory::{ReadBuffer, UserCheck, WriteBuffer},
 };
 use qemu_uart::TTY;
 
+use crate::mem::new_char_device_meta;
+
 pub struct TtyInode {
     meta: InodeMeta,
     inner: SpinMutex<TtyInodeInner>,
@@ -36,7 +42,7 @@ struct TtyInodeInner {
 impl TtyInode {
     pub(super) fn new() -> Self {
         TtyInode {
-            meta: InodeMeta::new(InodeMode::CharDevice),
+            meta: new_char_device_meta(),
             inner: SpinMutex::new(TtyInodeInner {
                 fg_pgid: 1,
                 win_size: WinSize {
@@ -87,12 +93,16 @@ impl BytesInodeBackend for TtyInode {
     }
 
     fn read_inode_at<'a>(&'a self, buf: ReadBuffer<'a>, _offset: u64) -> AKResult<'a, usize> {
+        self.read_stream(buf, false)
+    }
+
+    fn read_stream<'a>(&'a self, buf: ReadBuffer<'a>, nonblock: bool) -> AKResult<'a, usize> {
         let curr_time = time::curr_time_spec();
         self.meta.lock_inner_with(|inner| inner.access_time = curr_time);
         let ReadBuffer::User(buf) = buf else {
             unreachable!("why kernel read tty?");
         };
-        Box::pin(TtyFuture::new(buf).instrument(trace_span!("read_tty")))
+        Box::pin(TtyFuture::new(buf, nonblock).instrument(trace_span!("read_tty")))
     }
 
     fn write_inode_at<'a>(&'a self, buf: WriteBuffer<'a>, _offset: u64) -> AKResult<'a, usize> {
@@ -166,15 +176,29 @@ impl BytesInodeBackend for TtyInode {
             _ => todo!("[low] other tty ioctl command"),
         }
     }
+
+    fn poll(&self, table: Option<&mut PollTable<'_, '_>>) -> PollEvents {
+        // 输出总是直接写到串口，不会阻塞
+        let mut events = PollEvents::POLLOUT;
+        let mut tty = TTY.lock();
+        if tty.has_byte() {
+            events |= PollEvents::POLLIN;
+        } else if let Some(table) = table {
+            tty.register_waker(table.waker().clone());
+        }
+        events
+    }
 }
 #[must_use = "futures do nothing unless you `.await` or poll them"]
 pub struct TtyFuture {
     user_buf: UserCheck<[u8]>,
+    /// 没有输入时返回 `EAGAIN` 而不是等待
+    nonblock: bool,
 }
 
 impl TtyFuture {
-    pub fn new(user_buf: UserCheck<[u8]>) -> Self {
-        Self { user_buf }
+    pub fn new(user_buf: UserCheck<[u8]>, nonblock: bool) -> Self {
+        Self { user_buf, nonblock }
     }
 }
 
@@ -199,6 +223,8 @@ impl Future for TtyFuture {
         }
         if cnt > 0 {
             Poll::Ready(Ok(cnt))
+        } else if self.nonblock {
+            Poll::Ready(Err(errno::EAGAIN))
         } else {
             tty.register_waker(cx.waker().clone());
             Poll::Pending
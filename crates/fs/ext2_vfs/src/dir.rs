@@ -0,0 +1,265 @@
+use alloc::collections::btree_map::Entry;
+use core::ptr;
+
+use defines::error::{errno, KResult};
+use ext2::{DiskInode, FileType};
+use klocks::{SpinMutex, SpinMutexGuard};
+use libkernel::fs::{
+    dentry::{DEntry, DEntryBytes, DEntryDir},
+    inode::{
+        DirInodeBackend, DynBytesInode, DynBytesInodeCoercion, DynDirInode, DynDirInodeCoercion, DynInode, InodeMeta,
+        InodeMode,
+    },
+};
+use triomphe::Arc;
+use unsize::CoerceUnsize;
+
+use crate::{curr_disk_time, file::Ext2File, meta_to_disk, new_inode_meta, to_time_spec, CachedInode, Ext2Fs};
+
+pub(crate) struct Ext2Dir {
+    meta: InodeMeta,
+    ino: u32,
+    /// 与磁盘上的 inode 保持一致，每次修改后都会写回
+    disk_inode: SpinMutex<DiskInode>,
+    fs: Arc<Ext2Fs>,
+}
+
+impl Ext2Dir {
+    pub(crate) fn new(fs: Arc<Ext2Fs>, ino: u32, disk_inode: DiskInode) -> Self {
+        debug_assert!(disk_inode.is_dir());
+        Self {
+            meta: new_inode_meta(&disk_inode),
+            ino,
+            disk_inode: SpinMutex::new(disk_inode),
+            fs,
+        }
+    }
+
+    /// 在本目录下创建新的 inode，`mode` 需包含文件类型
+    fn create(&self, name: &str, mode: u16) -> KResult<(u32, DiskInode)> {
+        let time = curr_disk_time();
+        let mut disk_inode = self.lock_alive()?;
+        let ret = self.fs.disk.create(self.ino, &mut disk_inode, name, mode, time)?;
+        self.sync_meta(&disk_inode);
+        Ok(ret)
+    }
+
+    /// 锁住磁盘 inode。本目录已被删除时返回 `ENOENT`
+    fn lock_alive(&self) -> KResult<SpinMutexGuard<'_, DiskInode>> {
+        let disk_inode = self.disk_inode.lock();
+        if disk_inode.links_count == 0 {
+            return Err(errno::ENOENT);
+        }
+        Ok(disk_inode)
+    }
+
+    /// 目录内容变化后，同步 `InodeMeta` 中的大小和时间
+    fn sync_meta(&self, disk_inode: &DiskInode) {
+        self.meta.lock_inner_with(|inner| {
+            inner.data_len = disk_inode.size;
+            inner.modify_time = to_time_spec(disk_inode.mtime);
+            inner.change_time = to_time_spec(disk_inode.ctime);
+        });
+    }
+}
+
+impl DirInodeBackend for Ext2Dir {
+    fn meta(&self) -> &InodeMeta {
+        &self.meta
+    }
+
+    fn lookup(&self, name: &str) -> Option<DynInode> {
+        let entry = match self.fs.disk.lookup(&self.disk_inode.lock(), name) {
+            Ok(entry) => entry?,
+            Err(e) => {
+                warn!("ext2 lookup {name} failed: {e:?}");
+                return None;
+            }
+        };
+        self.fs
+            .get_inode(entry.ino)
+            .inspect_err(|e| warn!("read ext2 inode {} failed: {e:?}", entry.ino))
+            .ok()
+    }
+
+    fn mkdir(&self, name: &str) -> KResult<Arc<DynDirInode>> {
+        let (ino, disk_inode) = self.create(name, FileType::Dir.mode_bits() | 0o755)?;
+        let dir = Arc::new(Ext2Dir::new(Arc::clone(&self.fs), ino, disk_inode));
+        self.fs.insert(ino, CachedInode::Dir(Arc::clone(&dir)));
+        Ok(dir.unsize(DynDirInodeCoercion!()))
+    }
+
+    fn mknod(&self, name: &str, mode: InodeMode) -> KResult<Arc<DynBytesInode>> {
+        let file_type = match mode {
+            InodeMode::Regular => FileType::Regular,
+            InodeMode::Socket => FileType::Socket,
+            InodeMode::Fifo => FileType::Fifo,
+            InodeMode::BlockDevice => FileType::BlockDevice,
+            InodeMode::CharDevice => FileType::CharDevice,
+            InodeMode::Dir | InodeMode::SymbolLink | InodeMode::Anonymous => unreachable!(),
+        };
+        let (ino, disk_inode) = self.create(name, file_type.mode_bits() | 0o644)?;
+        let file = Arc::new(Ext2File::new(Arc::clone(&self.fs), ino, disk_inode));
+        self.fs.insert(ino, CachedInode::File(Arc::clone(&file)));
+        Ok(file.unsize(DynBytesInodeCoercion!()))
+    }
+
+    fn symlink(&self, name: &str, target: &str) -> KResult<Arc<DynBytesInode>> {
+        let time = curr_disk_time();
+        let mut disk_inode = self.lock_alive()?;
+        let (ino, link) = self.fs.disk.symlink(self.ino, &mut disk_inode, name, target, time)?;
+        self.sync_meta(&disk_inode);
+        let link = Arc::new(Ext2File::new(Arc::clone(&self.fs), ino, link));
+        self.fs.insert(ino, CachedInode::File(Arc::clone(&link)));
+        Ok(link.unsize(DynBytesInodeCoercion!()))
+    }
+
+    fn link(&self, name: &str, target: &Arc<DynBytesInode>) -> KResult<()> {
+        let target = self.fs.find_file(target).ok_or(errno::EXDEV)?;
+        let time = curr_disk_time();
+        let mut disk_inode = self.lock_alive()?;
+        let mut target_inode = target.lock_disk_inode();
+        *target_inode = self.fs.disk.link(self.ino, &mut disk_inode, name, target.ino(), time)?;
+        self.sync_meta(&disk_inode);
+        Ok(())
+    }
+
+    fn unlink(&self, name: &str) -> KResult<()> {
+        let time = curr_disk_time();
+        let mut disk_inode = self.disk_inode.lock();
+        let entry = self.fs.disk.lookup(&disk_inode, name)?.ok_or(errno::ENOENT)?;
+        // 先锁住内存中的 inode，保证它与磁盘上的一致
+        match self.fs.get_cached(entry.ino)? {
+            CachedInode::Dir(child) => {
+                let mut child_inode = child.disk_inode.lock();
+                self.fs.disk.rmdir(self.ino, &mut disk_inode, name, time)?;
+                // 空目录直接回收，内存中的 inode 也标记为已删除
+                *child_inode = self.fs.disk.read_inode(entry.ino)?;
+            }
+            CachedInode::File(child) => {
+                let mut child_inode = child.lock_disk_inode();
+                let (_, new_inode) = self.fs.disk.unlink(self.ino, &mut disk_inode, name, time)?;
+                *child_inode = new_inode;
+                if child_inode.links_count != 0 {
+                    self.sync_meta(&disk_inode);
+                    return Ok(());
+                }
+            }
+        }
+        self.fs.evict(entry.ino);
+        self.sync_meta(&disk_inode);
+        Ok(())
+    }
+
+    fn rename(&self, old_name: &str, new_dir: &Arc<DynDirInode>, new_name: &str) -> KResult<()> {
+        let new_dir = if new_dir.as_ptr().cast::<()>().addr() == ptr::from_ref(self).addr() {
+            None
+        } else {
+            Some(self.fs.find_dir(new_dir).ok_or(errno::EXDEV)?)
+        };
+        let time = curr_disk_time();
+        let mut disk_inode = self.lock_alive()?;
+        let mut new_disk_inode = new_dir.as_ref().map(|dir| dir.lock_alive()).transpose()?;
+        // 被替换的文件同 `unlink()`，先锁住内存中的 inode，保证它与磁盘上的一致
+        let target_parent = new_disk_inode.as_deref().unwrap_or(&disk_inode);
+        let target = match self.fs.disk.lookup(target_parent, new_name)? {
+            // 目标是本目录，即要将本目录下的文件移动到本目录所在的位置上
+            Some(entry) if entry.ino == self.ino => return Err(errno::ENOTEMPTY),
+            Some(entry) => Some(self.fs.get_cached(entry.ino)?),
+            None => None,
+        };
+        let target_inode = match &target {
+            Some(CachedInode::Dir(dir)) => Some(dir.disk_inode.lock()),
+            Some(CachedInode::File(file)) => Some(file.lock_disk_inode()),
+            None => None,
+        };
+
+        let new_parent = new_dir.as_ref().zip(new_disk_inode.as_deref_mut());
+        let replaced = self.fs.disk.rename(
+            self.ino,
+            &mut disk_inode,
+            old_name,
+            new_parent.map(|(dir, inode)| (dir.ino, inode)),
+            new_name,
+            time,
+        )?;
+        self.sync_meta(&disk_inode);
+        if let Some((dir, inode)) = new_dir.as_ref().zip(new_disk_inode.as_deref()) {
+            dir.sync_meta(inode);
+        }
+        let Some((ino, replaced)) = replaced else {
+            return Ok(());
+        };
+        let links_count = replaced.links_count;
+        if let Some(mut inode) = target_inode {
+            *inode = replaced;
+        }
+        if links_count == 0 {
+            self.fs.evict(ino);
+        }
+        Ok(())
+    }
+
+    fn read_dir(&self, parent: &Arc<DEntryDir>) -> KResult<()> {
+        debug!("ext2 read dir");
+        let entries = self.fs.disk.read_dir(&self.disk_inode.lock())?;
+        let mut children = parent.lock_children();
+        for entry in entries {
+            if entry.name == "." || entry.name == ".." {
+                continue;
+            }
+            let Entry::Vacant(vacant) = children.entry(entry.name) else {
+                continue;
+            };
+            let inode = match self.fs.get_inode(entry.ino) {
+                Ok(inode) => inode,
+                Err(e) => {
+                    warn!("read ext2 inode {} failed: {e:?}", entry.ino);
+                    continue;
+                }
+            };
+            let new_dentry = match inode {
+                DynInode::Dir(dir) => DEntry::Dir(Arc::new(DEntryDir::new(
+                    Some(Arc::clone(parent)),
+                    vacant.key().clone(),
+                    dir,
+                ))),
+                DynInode::Bytes(bytes) => DEntry::Bytes(Arc::new(DEntryBytes::new(
+                    Arc::clone(parent),
+                    vacant.key().clone(),
+                    bytes,
+                ))),
+            };
+            vacant.insert(new_dentry);
+        }
+        Ok(())
+    }
+
+    fn disk_space(&self) -> u64 {
+        self.disk_inode.lock().size
+    }
+
+    /// 创建、删除和重命名都直接修改磁盘上的目录项，被回收的目录项之后能从磁盘重新查找到。
+    /// inode 按 ino 缓存在 [`Ext2Fs`] 中，仍被引用时重新查找得到的是同一个 inode
+    fn dentry_reclaimable(&self) -> bool {
+        true
+    }
+
+    fn cache_negative(&self) -> bool {
+        true
+    }
+
+    fn evict_child(&self, child: DynInode) {
+        self.fs.try_evict(child);
+    }
+
+    fn write_meta(&self) -> KResult<()> {
+        let mut disk_inode = self.disk_inode.lock();
+        // 已删除的目录的 inode 已经回收，不能再写回
+        if disk_inode.links_count == 0 {
+            return Ok(());
+        }
+        meta_to_disk(&self.meta, &mut disk_inode);
+        self.fs.disk.write_inode(self.ino, &disk_inode)
+    }
+}
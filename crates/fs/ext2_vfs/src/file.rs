@@ -0,0 +1,129 @@
+use alloc::boxed::Box;
+
+use defines::error::{errno, AKResult, KResult};
+use ecow::EcoString;
+use executor::time;
+use ext2::DiskInode;
+use klocks::{SpinMutex, SpinMutexGuard};
+use libkernel::{
+    fs::inode::{BytesInodeBackend, InodeMeta, InodeMode},
+    memory::{ReadBuffer, WriteBuffer},
+};
+use triomphe::Arc;
+
+use crate::{curr_disk_time, meta_to_disk, new_inode_meta, Ext2Fs};
+
+pub(crate) struct Ext2File {
+    meta: InodeMeta,
+    ino: u32,
+    /// 与磁盘上的 inode 保持一致，每次修改后都会写回
+    disk_inode: SpinMutex<DiskInode>,
+    fs: Arc<Ext2Fs>,
+}
+
+impl Ext2File {
+    pub(crate) fn new(fs: Arc<Ext2Fs>, ino: u32, disk_inode: DiskInode) -> Self {
+        debug_assert!(!disk_inode.is_dir());
+        Self {
+            meta: new_inode_meta(&disk_inode),
+            ino,
+            disk_inode: SpinMutex::new(disk_inode),
+            fs,
+        }
+    }
+
+    pub(crate) fn lock_disk_inode(&self) -> SpinMutexGuard<'_, DiskInode> {
+        self.disk_inode.lock()
+    }
+
+    pub(crate) fn ino(&self) -> u32 {
+        self.ino
+    }
+
+    /// 将 `InodeMeta` 中的元数据同步到磁盘上的 inode 中，并写回
+    fn write_back(&self, disk_inode: &mut DiskInode) -> KResult<()> {
+        meta_to_disk(&self.meta, disk_inode);
+        self.fs.disk.write_inode(self.ino, disk_inode)
+    }
+}
+
+impl BytesInodeBackend for Ext2File {
+    fn meta(&self) -> &InodeMeta {
+        &self.meta
+    }
+
+    fn read_inode_at<'a>(&'a self, buf: ReadBuffer<'a>, offset: u64) -> AKResult<'a, usize> {
+        Box::pin(async move {
+            // 符号链接的目标路径应当通过 `read_link()` 读取
+            if self.meta.mode() == InodeMode::SymbolLink {
+                return Err(errno::EINVAL);
+            }
+            let disk_inode = self.disk_inode.lock();
+            match buf {
+                ReadBuffer::Kernel(buf) => {
+                    let nread = self.fs.disk.read_data(&disk_inode, offset, buf)?;
+                    // 页缓存读入的是整页，文件末尾之后的部分需要清零
+                    buf[nread..].fill(0);
+                    Ok(nread)
+                }
+                ReadBuffer::User(buf) => {
+                    let mut user_buf = unsafe { buf.check_slice_mut()? };
+                    self.fs.disk.read_data(&disk_inode, offset, user_buf.as_bytes_mut())
+                }
+            }
+        })
+    }
+
+    fn write_inode_at<'a>(&'a self, buf: WriteBuffer<'a>, offset: u64) -> AKResult<'a, usize> {
+        Box::pin(async move {
+            let mut disk_inode = self.disk_inode.lock();
+            let nwrite = match buf {
+                WriteBuffer::Kernel(buf) => self.fs.disk.write_data(&mut disk_inode, offset, buf)?,
+                WriteBuffer::User(buf) => self.fs.disk.write_data(&mut disk_inode, offset, &buf.check_slice()?)?,
+            };
+            self.write_back(&mut disk_inode)?;
+            Ok(nwrite)
+        })
+    }
+
+    fn read_link(&self) -> KResult<EcoString> {
+        let target = self.fs.disk.read_link(&self.disk_inode.lock())?;
+        let target = core::str::from_utf8(&target).map_err(|e| {
+            warn!("non-utf8 ext2 symlink target: {e}");
+            errno::EIO
+        })?;
+        Ok(EcoString::from(target))
+    }
+
+    fn need_writeback(&self) -> bool {
+        true
+    }
+
+    fn write_meta(&self) -> KResult<()> {
+        self.write_back(&mut self.disk_inode.lock())
+    }
+
+    fn truncate(&self, len: u64) -> KResult<()> {
+        let mut disk_inode = self.disk_inode.lock();
+        self.fs.disk.truncate(&mut disk_inode, len)?;
+        let now = time::curr_time_spec();
+        self.meta.lock_inner_with(|inner| {
+            inner.data_len = len;
+            inner.change_time = now;
+            inner.modify_time = now;
+        });
+        self.write_back(&mut disk_inode)
+    }
+}
+
+impl Drop for Ext2File {
+    fn drop(&mut self) {
+        // 链接数归零的文件在最后一个引用释放时才回收存储
+        let disk_inode = self.disk_inode.get_mut();
+        if disk_inode.links_count == 0
+            && let Err(e) = self.fs.disk.release_inode(self.ino, disk_inode, curr_disk_time())
+        {
+            warn!("release ext2 inode {} failed: {e:?}", self.ino);
+        }
+    }
+}
@@ -0,0 +1,275 @@
+#![no_std]
+
+#[macro_use]
+extern crate kernel_tracer;
+extern crate alloc;
+
+mod dir;
+mod file;
+
+use alloc::collections::BTreeMap;
+
+use defines::{
+    error::{errno, KResult},
+    fs::{StatFsFlags, StatMode},
+    misc::TimeSpec,
+};
+use ecow::EcoString;
+use executor::time;
+use ext2::{DiskInode, Ext2FileSystem, FileType, ROOT_INO};
+use hal::block_device::BlockDevice;
+use klocks::SpinMutex;
+use libkernel::fs::{
+    dentry::DEntryDir,
+    inode::{
+        BytesInodeBackend, DynBytesInode, DynBytesInodeCoercion, DynDirInode, DynDirInodeCoercion, DynInode, InodeMeta,
+        InodeMode,
+    },
+    FileSystem,
+};
+use triomphe::Arc;
+use unsize::CoerceUnsize;
+
+use crate::{dir::Ext2Dir, file::Ext2File};
+
+pub const FS_TYPE: &str = "ext2";
+
+/// inode 的 mode 中除文件类型外的权限位
+const PERM_MASK: u16 = 0o7777;
+
+/// 在 `block_device` 上构建 ext2 文件系统。
+///
+/// 作为根文件系统时 `parent` 为 `None`，挂载到某个目录下时则为挂载点的父目录
+pub fn new_ext2_fs(
+    block_device: &'static dyn BlockDevice,
+    parent: Option<Arc<DEntryDir>>,
+    name: EcoString,
+    device_path: EcoString,
+    flags: StatFsFlags,
+) -> KResult<FileSystem> {
+    let _enter = debug_span!("ext2_fs_init").entered();
+    let disk = Ext2FileSystem::new(block_device).inspect_err(|_| warn!("invalid ext2 on {device_path}"))?;
+    let fs = Arc::new(Ext2Fs {
+        disk,
+        inodes: SpinMutex::new(InodeCache::default()),
+    });
+    let DynInode::Dir(root_dir) = fs.get_inode(ROOT_INO)? else {
+        warn!("root inode of ext2 on {device_path} is not a directory");
+        return Err(errno::EINVAL);
+    };
+    let root_dentry = Arc::new(DEntryDir::new(parent, name, root_dir));
+    let mount_point = root_dentry.path();
+    Ok(FileSystem {
+        root_dentry,
+        device_path,
+        fs_type: FS_TYPE,
+        mounted_dentry: None,
+        mount_point,
+        flags,
+    })
+}
+
+/// 在 [`Ext2FileSystem`] 之上缓存已经读入内存的 inode，保证每个磁盘 inode 在内存中只有一份，
+/// 从而同一文件的多个硬链接共享页缓存和元数据
+struct Ext2Fs {
+    disk: Ext2FileSystem,
+    /// 链接数归零的 inode 会被移出，其存储在最后一个引用释放时回收；
+    /// 目录项被回收后不再被引用的 inode 也会被移出，见 [`Self::try_evict()`]
+    ///
+    /// TODO: [low] 缓存与 inode 互相持有引用，目前卸载后也不会释放
+    inodes: SpinMutex<InodeCache>,
+}
+
+#[derive(Default)]
+struct InodeCache {
+    /// 按 ext2 的 inode 号索引
+    by_ino: BTreeMap<u32, CachedInode>,
+    /// inode 的地址 -> inode 号，用于从 VFS 中的 inode 找到对应的 ext2 inode
+    by_addr: BTreeMap<usize, u32>,
+}
+
+impl InodeCache {
+    fn insert(&mut self, ino: u32, cached: CachedInode) {
+        self.by_addr.insert(cached.addr(), ino);
+        let old = self.by_ino.insert(ino, cached);
+        debug_assert!(old.is_none());
+    }
+
+    fn remove(&mut self, ino: u32) -> Option<CachedInode> {
+        let cached = self.by_ino.remove(&ino)?;
+        self.by_addr.remove(&cached.addr());
+        Some(cached)
+    }
+
+    fn get_by_addr(&self, addr: usize) -> Option<&CachedInode> {
+        self.by_addr.get(&addr).map(|ino| &self.by_ino[ino])
+    }
+}
+
+#[derive(Clone)]
+enum CachedInode {
+    Dir(Arc<Ext2Dir>),
+    File(Arc<Ext2File>),
+}
+
+impl CachedInode {
+    fn to_dyn(&self) -> DynInode {
+        match self {
+            CachedInode::Dir(dir) => DynInode::Dir(Arc::clone(dir).unsize(DynDirInodeCoercion!())),
+            CachedInode::File(file) => DynInode::Bytes(Arc::clone(file).unsize(DynBytesInodeCoercion!())),
+        }
+    }
+
+    fn addr(&self) -> usize {
+        match self {
+            CachedInode::Dir(dir) => dir.as_ptr().addr(),
+            CachedInode::File(file) => file.as_ptr().addr(),
+        }
+    }
+
+    /// 除了缓存本身，还有多少个引用
+    fn extra_refs(&self) -> usize {
+        match self {
+            CachedInode::Dir(dir) => Arc::count(dir) - 1,
+            CachedInode::File(file) => Arc::count(file) - 1,
+        }
+    }
+}
+
+/// VFS 中的 inode 的地址，与 [`CachedInode::addr()`] 对应
+fn dyn_addr(inode: &DynInode) -> usize {
+    match inode {
+        DynInode::Dir(dir) => dir.as_ptr().cast::<()>().addr(),
+        DynInode::Bytes(bytes) => bytes.as_ptr().cast::<()>().addr(),
+    }
+}
+
+impl Ext2Fs {
+    /// 获取 `ino` 对应的 inode，不在缓存中时从磁盘读入
+    fn get_cached(self: &Arc<Self>, ino: u32) -> KResult<CachedInode> {
+        let mut inodes = self.inodes.lock();
+        if let Some(cached) = inodes.by_ino.get(&ino) {
+            return Ok(cached.clone());
+        }
+        let disk_inode = self.disk.read_inode(ino)?;
+        let cached = if disk_inode.is_dir() {
+            CachedInode::Dir(Arc::new(Ext2Dir::new(Arc::clone(self), ino, disk_inode)))
+        } else {
+            CachedInode::File(Arc::new(Ext2File::new(Arc::clone(self), ino, disk_inode)))
+        };
+        inodes.insert(ino, cached.clone());
+        Ok(cached)
+    }
+
+    fn get_inode(self: &Arc<Self>, ino: u32) -> KResult<DynInode> {
+        self.get_cached(ino).map(|cached| cached.to_dyn())
+    }
+
+    /// 将新创建的 inode 加入缓存
+    fn insert(&self, ino: u32, cached: CachedInode) {
+        self.inodes.lock().insert(ino, cached);
+    }
+
+    /// inode 的链接数归零后将其移出缓存
+    fn evict(&self, ino: u32) {
+        // 移出的 inode 可能是最后一个引用，在锁外释放
+        let cached = self.inodes.lock().remove(ino);
+        drop(cached);
+    }
+
+    /// 目录项被回收后，若 `inode` 已经没有其他引用，则将其移出缓存。
+    ///
+    /// 还有脏页的文件保留在缓存中，以免写回之前再次查找时读到磁盘上的旧数据
+    fn try_evict(&self, inode: DynInode) {
+        let mut inodes = self.inodes.lock();
+        let Some(&ino) = inodes.by_addr.get(&dyn_addr(&inode)) else {
+            return;
+        };
+        let cached = &inodes.by_ino[&ino];
+        let dirty = match cached {
+            CachedInode::Dir(_) => false,
+            CachedInode::File(file) => file.meta().page_cache().has_dirty(),
+        };
+        // 引用只能来自缓存或者 `inode`，因此持有锁时检查的结果不会改变
+        if cached.extra_refs() > 1 || dirty {
+            return;
+        }
+        let cached = inodes.remove(ino);
+        drop(inodes);
+        drop(cached);
+    }
+
+    /// 找到 VFS 中的 `inode` 对应的 ext2 目录，不属于本文件系统时返回 `None`
+    fn find_dir(&self, inode: &Arc<DynDirInode>) -> Option<Arc<Ext2Dir>> {
+        let addr = inode.as_ptr().cast::<()>().addr();
+        match self.inodes.lock().get_by_addr(addr)? {
+            CachedInode::Dir(dir) => Some(Arc::clone(dir)),
+            CachedInode::File(_) => None,
+        }
+    }
+
+    /// 找到 VFS 中的 `inode` 对应的 ext2 常规文件，不属于本文件系统时返回 `None`
+    fn find_file(&self, inode: &Arc<DynBytesInode>) -> Option<Arc<Ext2File>> {
+        let addr = inode.as_ptr().cast::<()>().addr();
+        match self.inodes.lock().get_by_addr(addr)? {
+            CachedInode::File(file) => Some(Arc::clone(file)),
+            CachedInode::Dir(_) => None,
+        }
+    }
+}
+
+/// 按磁盘上 inode 的内容初始化 `InodeMeta`
+fn new_inode_meta(disk_inode: &DiskInode) -> InodeMeta {
+    let mode = match disk_inode.file_type() {
+        Some(FileType::Dir) => InodeMode::Dir,
+        Some(FileType::CharDevice) => InodeMode::CharDevice,
+        Some(FileType::BlockDevice) => InodeMode::BlockDevice,
+        Some(FileType::Fifo) => InodeMode::Fifo,
+        Some(FileType::Socket) => InodeMode::Socket,
+        Some(FileType::SymbolLink) => InodeMode::SymbolLink,
+        Some(FileType::Regular) => InodeMode::Regular,
+        None => {
+            warn!("unknown ext2 inode mode {:#o}, treat as regular file", disk_inode.mode);
+            InodeMode::Regular
+        }
+    };
+    let mut meta = InodeMeta::new(mode);
+    let meta_inner = meta.get_inner_mut();
+    meta_inner.data_len = disk_inode.size;
+    meta_inner.nlink = disk_inode.links_count as u32;
+    meta_inner.perm = StatMode::from_bits_truncate((disk_inode.mode & PERM_MASK) as u32);
+    meta_inner.uid = disk_inode.uid;
+    meta_inner.gid = disk_inode.gid;
+    meta_inner.access_time = to_time_spec(disk_inode.atime);
+    meta_inner.modify_time = to_time_spec(disk_inode.mtime);
+    meta_inner.change_time = to_time_spec(disk_inode.ctime);
+    meta
+}
+
+/// 将 `InodeMeta` 中的时间、权限和所有者同步到磁盘上的 inode 中
+fn meta_to_disk(meta: &InodeMeta, disk_inode: &mut DiskInode) {
+    meta.lock_inner_with(|inner| {
+        disk_inode.mode = (disk_inode.mode & !PERM_MASK) | (inner.perm.bits() as u16 & PERM_MASK);
+        disk_inode.uid = inner.uid;
+        disk_inode.gid = inner.gid;
+        disk_inode.atime = to_disk_time(inner.access_time);
+        disk_inode.mtime = to_disk_time(inner.modify_time);
+        disk_inode.ctime = to_disk_time(inner.change_time);
+    });
+}
+
+/// ext2 的时间戳是 32 位的秒数
+fn to_time_spec(sec: u32) -> TimeSpec {
+    TimeSpec {
+        sec: sec as i64,
+        nsec: 0,
+    }
+}
+
+fn to_disk_time(time: TimeSpec) -> u32 {
+    time.sec as u32
+}
+
+fn curr_disk_time() -> u32 {
+    to_disk_time(time::curr_time_spec())
+}
@@ -1,12 +1,13 @@
-use alloc::collections::btree_map::Entry;
+use alloc::collections::{btree_map::Entry, BTreeMap};
 
 use defines::{
     error::{errno, KResult},
     misc::TimeSpec,
 };
+use ecow::EcoString;
 use executor::time;
 use fat32::{DirEntry, DirEntryBuilder, DirEntryBuilderResult, FileAllocTable, DIR_ENTRY_SIZE, SECTOR_SIZE};
-use klocks::RwLock;
+use klocks::{RwLock, SpinMutex};
 use libkernel::fs::{
     dentry::{DEntry, DEntryBytes, DEntryDir},
     inode::{
@@ -18,7 +19,7 @@ use smallvec::{smallvec, SmallVec};
 use triomphe::Arc;
 use unsize::CoerceUnsize;
 
-use crate::file::FatFile;
+use crate::{dyn_addr, file::FatFile, CachedInode, FatFs};
 
 // TODO: 写入 FAT32 表项时，要记得高 4 位保持不变
 
@@ -26,20 +27,28 @@ pub struct FatDir {
     meta: InodeMeta,
     clusters: RwLock<SmallVec<[u32; 4]>>,
     fat: Arc<FileAllocTable>,
+    fs: Arc<FatFs>,
+    /// 还没有写回磁盘的创建、删除和重命名，查找时优先于磁盘上的目录项。`None` 表示该名字已被删除或者重命名走
+    ///
+    /// TODO: [mid] fat32 的目录修改写回磁盘之后就不再需要
+    overlay: SpinMutex<BTreeMap<EcoString, Option<DynInode>>>,
     /// 记录目录的创建时间，会同步到磁盘中
     _create_time: Option<TimeSpec>,
 }
 
 impl FatDir {
-    pub fn new_root(fat: Arc<FileAllocTable>, first_root_cluster_id: u32) -> Self {
+    pub(crate) fn new_root(fs: &Arc<FatFs>, first_root_cluster_id: u32) -> Self {
         debug!("init root dir");
         assert!(first_root_cluster_id >= 2);
+        let fat = Arc::clone(&fs.fat);
         let clusters = fat.cluster_chain(first_root_cluster_id).collect::<SmallVec<_>>();
         let meta = InodeMeta::new(InodeMode::Dir);
         let root_dir = Self {
             meta,
             clusters: RwLock::new(clusters),
             fat,
+            fs: Arc::clone(fs),
+            overlay: SpinMutex::new(BTreeMap::new()),
             _create_time: None,
         };
         root_dir.meta.lock_inner_with(|inner| {
@@ -48,8 +57,9 @@ impl FatDir {
         root_dir
     }
 
-    pub fn from_dir_entry(fat: Arc<FileAllocTable>, dir_entry: DirEntry) -> Self {
+    pub(crate) fn from_dir_entry(fs: &Arc<FatFs>, dir_entry: DirEntry) -> Self {
         debug_assert!(dir_entry.is_dir());
+        let fat = Arc::clone(&fs.fat);
         let meta = InodeMeta::new(InodeMode::Dir);
         let clusters: SmallVec<[u32; 4]> = fat.cluster_chain(dir_entry.first_cluster_id()).collect();
         let data_len = clusters_disk_space(&fat, clusters.len() as u64);
@@ -65,11 +75,14 @@ impl FatDir {
             meta,
             clusters: RwLock::new(clusters),
             fat,
+            fs: Arc::clone(fs),
+            overlay: SpinMutex::new(BTreeMap::new()),
             _create_time: None,
         }
     }
 
-    fn create(fat: Arc<FileAllocTable>, _name: &str) -> KResult<Self> {
+    fn create(fs: &Arc<FatFs>, _name: &str) -> KResult<Self> {
+        let fat = Arc::clone(&fs.fat);
         let allocated_cluster = fat.alloc_cluster(None).ok_or(errno::ENOSPC)?;
         // TODO: 初始化新目录的 '.' 和 '..' 目录项（暂不落盘）。
         let mut meta = InodeMeta::new(InodeMode::Dir);
@@ -83,11 +96,23 @@ impl FatDir {
             meta,
             clusters: RwLock::new(smallvec![allocated_cluster]),
             fat,
+            fs: Arc::clone(fs),
+            overlay: SpinMutex::new(BTreeMap::new()),
             _create_time: Some(curr_time),
         })
     }
 
-    pub fn dir_entry_iter(&self) -> impl Iterator<Item = KResult<DirEntry>> + '_ {
+    fn first_cluster(&self) -> u32 {
+        self.clusters.read()[0]
+    }
+
+    /// 是否有还没有写回磁盘的修改，有则不能移出 inode 缓存
+    pub(crate) fn modified(&self) -> bool {
+        !self.overlay.lock().is_empty()
+    }
+
+    /// 遍历磁盘上的目录项，同时返回每个目录项的最后一个（短文件名）条目在目录中的序号
+    pub fn dir_entry_iter(&self) -> impl Iterator<Item = KResult<(u32, DirEntry)>> + '_ {
         let clusters = self.clusters.read();
         let mut raw_entry_iter = core::iter::from_coroutine(
             #[coroutine]
@@ -108,24 +133,78 @@ impl FatDir {
             },
         );
 
-        core::iter::from_fn(move || {
+        let mut index = 0;
+        let mut next_raw_entry = move || {
             let entry = raw_entry_iter.next()?;
+            index += 1;
+            Some((index - 1, entry))
+        };
+        core::iter::from_fn(move || {
+            let (index, entry) = next_raw_entry()?;
             let mut builder = match DirEntryBuilder::from_entry(&entry) {
                 Ok(DirEntryBuilderResult::Builder(builder)) => builder,
-                Ok(DirEntryBuilderResult::Final(ret)) => return Some(Ok(ret)),
+                Ok(DirEntryBuilderResult::Final(ret)) => return Some(Ok((index, ret))),
                 Err(e) => return Some(Err(e)),
             };
 
             loop {
-                let entry = raw_entry_iter.next()?;
+                let (index, entry) = next_raw_entry()?;
                 builder = match builder.add_entry(&entry) {
                     Ok(DirEntryBuilderResult::Builder(builder)) => builder,
-                    Ok(DirEntryBuilderResult::Final(ret)) => return Some(Ok(ret)),
+                    Ok(DirEntryBuilderResult::Final(ret)) => return Some(Ok((index, ret))),
                     Err(e) => return Some(Err(e)),
                 }
             }
         })
     }
+
+    /// 查找磁盘上名为 `name` 的目录项，不考虑 `overlay`
+    fn lookup_disk(&self, name: &str) -> Option<DynInode> {
+        let first_cluster = self.first_cluster();
+        for dir_entry in self.dir_entry_iter() {
+            let Ok((index, dir_entry)) = dir_entry else {
+                continue;
+            };
+            if dir_entry.name() == name {
+                return Some(self.fs.get_or_load((first_cluster, index), dir_entry));
+            }
+        }
+        None
+    }
+
+    /// 目录中除了 `.` 和 `..` 之外是否没有其他目录项
+    fn is_empty(&self) -> bool {
+        let overlay = self.overlay.lock();
+        if overlay.values().any(Option::is_some) {
+            return false;
+        }
+        !self.dir_entry_iter().any(|dir_entry| {
+            dir_entry.is_ok_and(|(_, dir_entry)| {
+                let name = dir_entry.name();
+                name != "." && name != ".." && !overlay.contains_key(name)
+            })
+        })
+    }
+
+    /// 名为 `name` 的目录项将被删除或者替换。非空目录不能被删除，其 inode 也不再能被查找到
+    fn remove_entry(&self, name: &str) -> KResult<()> {
+        let Some(inode) = DirInodeBackend::lookup(self, name) else {
+            return Ok(());
+        };
+        if let DynInode::Dir(dir) = &inode
+            && !self.fs.find_dir(dir).is_some_and(|dir| dir.is_empty())
+        {
+            return Err(errno::ENOTEMPTY);
+        }
+        self.overlay.lock().insert(EcoString::from(name), None);
+        self.fs.forget(&inode);
+        Ok(())
+    }
+
+    /// 将新创建的 `inode` 加入 `overlay`
+    fn add_entry(&self, name: &str, inode: DynInode) {
+        self.overlay.lock().insert(EcoString::from(name), Some(inode));
+    }
 }
 
 fn clusters_disk_space(fat: &FileAllocTable, n_cluster: u64) -> u64 {
@@ -140,73 +219,94 @@ impl DirInodeBackend for FatDir {
     fn lookup(&self, name: &str) -> Option<DynInode> {
         let curr_time = time::curr_time_spec();
         self.meta.lock_inner_with(|inner| inner.access_time = curr_time);
-        for dir_entry in self.dir_entry_iter() {
-            let Ok(dir_entry) = dir_entry else {
-                continue;
-            };
-            if dir_entry.name() != name {
-                continue;
-            }
-
-            if dir_entry.is_dir() {
-                let fat_dir = FatDir::from_dir_entry(Arc::clone(&self.fat), dir_entry);
-                return Some(DynInode::Dir(Arc::new(fat_dir).unsize(DynDirInodeCoercion!())));
-            } else {
-                let fat_file = FatFile::from_dir_entry(Arc::clone(&self.fat), dir_entry);
-                return Some(DynInode::Bytes(Arc::new(fat_file).unsize(DynBytesInodeCoercion!())));
-            }
+        if let Some(inode) = self.overlay.lock().get(name) {
+            return inode.clone();
         }
-        None
+        self.lookup_disk(name)
     }
 
     fn mkdir(&self, name: &str) -> KResult<Arc<DynDirInode>> {
-        let fat_dir = FatDir::create(Arc::clone(&self.fat), name)?;
+        let fat_dir = Arc::new(FatDir::create(&self.fs, name)?);
         // TODO: [mid] fat32 mkdir 实际写入磁盘
-        Ok(Arc::new(fat_dir).unsize(DynDirInodeCoercion!()))
+        self.fs.insert(None, CachedInode::Dir(Arc::clone(&fat_dir)));
+        let fat_dir = fat_dir.unsize(DynDirInodeCoercion!());
+        self.add_entry(name, DynInode::Dir(Arc::clone(&fat_dir)));
+        Ok(fat_dir)
     }
 
     fn mknod(&self, _name: &str, mode: InodeMode) -> KResult<Arc<DynBytesInode>> {
         match mode {
             InodeMode::Regular => {}
             InodeMode::Dir | InodeMode::SymbolLink => unreachable!(),
-            _ => todo!("[mid] impl mknod for non-regular mode"),
+            // FAT32 只能存储常规文件和目录
+            _ => return Err(errno::EPERM),
         }
-        let fat_file = FatFile::create(Arc::clone(&self.fat))?;
+        let fat_file = Arc::new(FatFile::create(Arc::clone(&self.fat))?).unsize(DynBytesInodeCoercion!());
         // TODO: [mid] fat32 mknod 实际写入磁盘
-        Ok(Arc::new(fat_file).unsize(DynBytesInodeCoercion!()))
+        self.add_entry(name, DynInode::Bytes(Arc::clone(&fat_file)));
+        Ok(fat_file)
     }
 
-    fn unlink(&self, _name: &str) -> KResult<()> {
-        // FIXME: 实现 FatDir 的 `unlink()`
+    fn symlink(&self, _name: &str, _target: &str) -> KResult<Arc<DynBytesInode>> {
+        // FAT32 不支持符号链接
+        Err(errno::EPERM)
+    }
+
+    fn link(&self, _name: &str, _target: &Arc<DynBytesInode>) -> KResult<()> {
+        // FAT32 不支持硬链接
+        Err(errno::EPERM)
+    }
+
+    fn unlink(&self, name: &str) -> KResult<()> {
+        // TODO: [mid] fat32 unlink 实际写入磁盘
+        self.remove_entry(name)
+    }
+
+    fn rename(&self, old_name: &str, new_dir: &Arc<DynDirInode>, new_name: &str) -> KResult<()> {
+        // TODO: [mid] fat32 rename 实际写入磁盘
+        let new_dir = self.fs.find_dir(new_dir).ok_or(errno::EXDEV)?;
+        let inode = DirInodeBackend::lookup(self, old_name).ok_or(errno::ENOENT)?;
+        // 重命名为同一个文件的另一个名字时什么都不做
+        if DirInodeBackend::lookup(&*new_dir, new_name).is_some_and(|target| dyn_addr(&target) == dyn_addr(&inode)) {
+            return Ok(());
+        }
+        new_dir.remove_entry(new_name)?;
+        self.overlay.lock().insert(EcoString::from(old_name), None);
+        new_dir.add_entry(new_name, inode);
         Ok(())
     }
 
     fn read_dir(&self, parent: &Arc<DEntryDir>) -> KResult<()> {
         debug!("fat32 read dir");
         let mut children = parent.lock_children();
-        for dir_entry in self.dir_entry_iter() {
-            let Ok(mut dir_entry) = dir_entry else {
-                continue;
-            };
-
-            let Entry::Vacant(vacant) = children.entry(dir_entry.take_name()) else {
+        let overlay = self.overlay.lock().clone();
+        let first_cluster = self.first_cluster();
+        let disk_entries = self.dir_entry_iter().filter_map(|dir_entry| {
+            let (index, mut dir_entry) = dir_entry.ok()?;
+            let name = dir_entry.take_name();
+            if overlay.contains_key(&name) {
+                return None;
+            }
+            Some((name, self.fs.get_or_load((first_cluster, index), dir_entry)))
+        });
+        let overlay_entries = overlay
+            .iter()
+            .filter_map(|(name, inode)| Some((name.clone(), inode.clone()?)));
+        for (name, inode) in disk_entries.chain(overlay_entries) {
+            let Entry::Vacant(vacant) = children.entry(name) else {
                 continue;
             };
-
-            let new_dentry = if dir_entry.is_dir() {
-                let fat_dir = FatDir::from_dir_entry(Arc::clone(&self.fat), dir_entry);
-                DEntry::Dir(Arc::new(DEntryDir::new(
+            let new_dentry = match inode {
+                DynInode::Dir(dir) => DEntry::Dir(Arc::new(DEntryDir::new(
                     Some(Arc::clone(parent)),
                     vacant.key().clone(),
-                    Arc::new(fat_dir).unsize(DynDirInodeCoercion!()),
-                )))
-            } else {
-                let fat_file = FatFile::from_dir_entry(Arc::clone(&self.fat), dir_entry);
-                DEntry::Bytes(Arc::new(DEntryBytes::new(
+                    dir,
+                ))),
+                DynInode::Bytes(bytes) => DEntry::Bytes(Arc::new(DEntryBytes::new(
                     Arc::clone(parent),
                     vacant.key().clone(),
-                    Arc::new(fat_file).unsize(DynBytesInodeCoercion!()),
-                )))
+                    bytes,
+                ))),
             };
             vacant.insert(new_dentry);
         }
@@ -218,4 +318,19 @@ impl DirInodeBackend for FatDir {
     fn disk_space(&self) -> u64 {
         clusters_disk_space(&self.fat, self.clusters.read().len() as u64)
     }
+
+    /// 创建、删除和重命名都记录在 `overlay` 中，之后的查找可见。
+    /// inode 按目录项的位置缓存在 [`FatFs`] 中，仍被引用时重新查找得到的是同一个 inode
+    fn dentry_reclaimable(&self) -> bool {
+        true
+    }
+
+    /// 目录中的名字都经由 dentry 层创建，查找也区分大小写
+    fn cache_negative(&self) -> bool {
+        true
+    }
+
+    fn evict_child(&self, child: DynInode) {
+        self.fs.try_evict(child);
+    }
 }
@@ -1,8 +1,10 @@
 use alloc::boxed::Box;
+use core::sync::atomic::{AtomicBool, Ordering};
 
 use common::config::{PAGE_OFFSET_MASK, PAGE_SIZE, PAGE_SIZE_BITS};
 use defines::{
     error::{errno, AKResult, KResult},
+    fs::StatMode,
     misc::TimeSpec,
 };
 use executor::time;
@@ -21,6 +23,8 @@ pub struct FatFile {
     fat: Arc<FileAllocTable>,
     /// 记录文件的创建时间，会同步到磁盘中
     _create_time: Option<TimeSpec>,
+    /// 是否被截断过。截断后的簇链与磁盘上的目录项不一致，不能移出 inode 缓存
+    truncated: AtomicBool,
 }
 
 impl FatFile {
@@ -32,6 +36,8 @@ impl FatFile {
         let mut meta = InodeMeta::new(InodeMode::Regular);
         let meta_inner = meta.get_inner_mut();
         meta_inner.data_len = dir_entry.file_size();
+        // FAT32 没有权限位，同 linux 默认的挂载选项一样将所有文件视为可执行
+        meta_inner.perm = StatMode::from_bits_truncate(0o755);
         meta_inner.access_time = dir_entry.access_time();
         // inode 中并不存储创建时间，而 fat32 并不单独记录文件元数据改变时间
         // 此处将 fat32 的创建时间存放在 inode 的元数据改变时间中
@@ -42,6 +48,7 @@ impl FatFile {
             clusters: RwLock::new(clusters),
             fat,
             _create_time: None,
+            truncated: AtomicBool::new(false),
         }
     }
 
@@ -59,9 +66,15 @@ impl FatFile {
             clusters: RwLock::new(smallvec![allocated_cluster]),
             fat,
             _create_time: Some(curr_time),
+            truncated: AtomicBool::new(false),
         })
     }
 
+    /// 是否有还没有写回磁盘的修改，有则不能移出 inode 缓存
+    pub(crate) fn modified(&self) -> bool {
+        self.truncated.load(Ordering::Relaxed) || self.meta.page_cache().has_dirty()
+    }
+
     /// 返回对应的簇索引和簇内的扇区索引
     pub fn page_id_to_cluster_pos(&self, page_id: u64) -> (u32, u8) {
         let sector_index = (page_id * SECTOR_COUNT_PER_PAGE as u64) as u32;
@@ -106,6 +119,7 @@ impl BytesInodeBackend for FatFile {
                 },
             );
             clusters.truncate(new_cluster_count);
+            self.truncated.store(true, Ordering::Relaxed);
             let now = time::curr_time_spec();
             self.meta.lock_inner_with(|inner| {
                 inner.data_len = len;
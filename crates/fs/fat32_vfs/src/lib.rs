@@ -10,23 +10,34 @@ extern crate alloc;
 mod dir;
 mod file;
 
+use alloc::collections::BTreeMap;
+
 use defines::{
     error::{errno, KResult},
     fs::StatFsFlags,
 };
 use ecow::EcoString;
-use fat32::{BiosParameterBlock, FileAllocTable, BOOT_SECTOR_ID, SECTOR_SIZE};
+use fat32::{BiosParameterBlock, DirEntry, FileAllocTable, BOOT_SECTOR_ID, SECTOR_SIZE};
 use hal::block_device::BlockDevice;
-use libkernel::fs::{dentry::DEntryDir, inode::DynDirInodeCoercion, FileSystem};
+use klocks::SpinMutex;
+use libkernel::fs::{
+    dentry::DEntryDir,
+    inode::{DynBytesInodeCoercion, DynDirInode, DynDirInodeCoercion, DynInode},
+    FileSystem,
+};
 use triomphe::Arc;
 use unsize::CoerceUnsize;
 
-use crate::dir::FatDir;
+use crate::{dir::FatDir, file::FatFile};
 
 pub const FS_TYPE: &str = "vfat";
 
+/// 在 `block_device` 上构建 FAT32 文件系统。
+///
+/// 作为根文件系统时 `parent` 为 `None`，挂载到某个目录下时则为挂载点的父目录
 pub fn new_fat32_fs(
     block_device: &'static dyn BlockDevice,
+    parent: Option<Arc<DEntryDir>>,
     name: EcoString,
     device_path: EcoString,
     flags: StatFsFlags,
@@ -39,13 +50,19 @@ pub fn new_fat32_fs(
         BiosParameterBlock::new(&buf)?
     };
     if !is_valid_fat32_bpb(&bpb) {
+        warn!("invalid fat32 bpb on {device_path}");
         return Err(errno::EINVAL);
     }
 
     debug!("init fat");
-    let fat = Arc::new(FileAllocTable::new(block_device, &bpb)?);
-    let root_dir = Arc::new(FatDir::new_root(fat, bpb.root_cluster)).unsize(DynDirInodeCoercion!());
-    let root_dentry = Arc::new(DEntryDir::new(None, name, root_dir));
+    let fs = Arc::new(FatFs {
+        fat: Arc::new(FileAllocTable::new(block_device, &bpb)?),
+        inodes: SpinMutex::new(InodeCache::default()),
+    });
+    let root_dir = Arc::new(FatDir::new_root(&fs, bpb.root_cluster));
+    fs.insert(None, CachedInode::Dir(Arc::clone(&root_dir)));
+    let root_dir = root_dir.unsize(DynDirInodeCoercion!());
+    let root_dentry = Arc::new(DEntryDir::new(parent, name, root_dir));
     let mount_point = root_dentry.path();
     Ok(FileSystem {
         root_dentry,
@@ -57,6 +74,156 @@ pub fn new_fat32_fs(
     })
 }
 
+/// 目录项在磁盘上的位置，即所在目录的首簇号和目录项在目录中的序号。
+///
+/// FAT32 没有 inode 号，空文件也不占用簇，因此以目录项的位置标识磁盘上的文件
+type EntryPos = (u32, u32);
+
+/// 在 [`FileAllocTable`] 之上缓存已经读入内存的 inode，保证磁盘上的每个目录项在内存中只有一个 inode，
+/// 从而目录项被回收后，重新查找得到的仍是同一个 inode
+struct FatFs {
+    fat: Arc<FileAllocTable>,
+    /// 目录项被回收后不再被引用、也没有未写回的修改的 inode 会被移出，见 [`Self::try_evict()`]
+    ///
+    /// TODO: [low] 缓存与 inode 互相持有引用，目前卸载后也不会释放
+    inodes: SpinMutex<InodeCache>,
+}
+
+#[derive(Default)]
+struct InodeCache {
+    /// 按 inode 的地址索引，包括根目录和新创建的目录
+    by_addr: BTreeMap<usize, CachedEntry>,
+    /// 磁盘上的目录项位置 -> inode 的地址
+    by_pos: BTreeMap<EntryPos, usize>,
+}
+
+struct CachedEntry {
+    /// 新创建的 inode 还不在磁盘上，为 `None`
+    pos: Option<EntryPos>,
+    inode: CachedInode,
+}
+
+impl InodeCache {
+    fn remove(&mut self, addr: usize) -> Option<CachedInode> {
+        let entry = self.by_addr.remove(&addr)?;
+        if let Some(pos) = entry.pos {
+            self.by_pos.remove(&pos);
+        }
+        Some(entry.inode)
+    }
+}
+
+#[derive(Clone)]
+enum CachedInode {
+    Dir(Arc<FatDir>),
+    File(Arc<FatFile>),
+}
+
+impl CachedInode {
+    fn to_dyn(&self) -> DynInode {
+        match self {
+            CachedInode::Dir(dir) => DynInode::Dir(Arc::clone(dir).unsize(DynDirInodeCoercion!())),
+            CachedInode::File(file) => DynInode::Bytes(Arc::clone(file).unsize(DynBytesInodeCoercion!())),
+        }
+    }
+
+    fn addr(&self) -> usize {
+        match self {
+            CachedInode::Dir(dir) => dir.as_ptr().addr(),
+            CachedInode::File(file) => file.as_ptr().addr(),
+        }
+    }
+
+    /// 除了缓存本身，还有多少个引用
+    fn extra_refs(&self) -> usize {
+        match self {
+            CachedInode::Dir(dir) => Arc::count(dir) - 1,
+            CachedInode::File(file) => Arc::count(file) - 1,
+        }
+    }
+
+    fn modified(&self) -> bool {
+        match self {
+            CachedInode::Dir(dir) => dir.modified(),
+            CachedInode::File(file) => file.modified(),
+        }
+    }
+}
+
+/// VFS 中的 inode 的地址，与 [`CachedInode::addr()`] 对应
+fn dyn_addr(inode: &DynInode) -> usize {
+    match inode {
+        DynInode::Dir(dir) => dir.as_ptr().cast::<()>().addr(),
+        DynInode::Bytes(bytes) => bytes.as_ptr().cast::<()>().addr(),
+    }
+}
+
+impl FatFs {
+    /// 获取位于 `pos` 的目录项 `dir_entry` 对应的 inode，不在缓存中时创建
+    fn get_or_load(self: &Arc<Self>, pos: EntryPos, dir_entry: DirEntry) -> DynInode {
+        let mut inodes = self.inodes.lock();
+        if let Some(addr) = inodes.by_pos.get(&pos) {
+            return inodes.by_addr[addr].inode.to_dyn();
+        }
+        let cached = if dir_entry.is_dir() {
+            CachedInode::Dir(Arc::new(FatDir::from_dir_entry(self, dir_entry)))
+        } else {
+            CachedInode::File(Arc::new(FatFile::from_dir_entry(Arc::clone(&self.fat), dir_entry)))
+        };
+        let inode = cached.to_dyn();
+        inodes.by_pos.insert(pos, cached.addr());
+        inodes.by_addr.insert(
+            cached.addr(),
+            CachedEntry {
+                pos: Some(pos),
+                inode: cached,
+            },
+        );
+        inode
+    }
+
+    /// 将不是从磁盘读入的 inode 加入缓存，如根目录和新创建的目录
+    fn insert(&self, pos: Option<EntryPos>, cached: CachedInode) {
+        let mut inodes = self.inodes.lock();
+        if let Some(pos) = pos {
+            inodes.by_pos.insert(pos, cached.addr());
+        }
+        inodes.by_addr.insert(cached.addr(), CachedEntry { pos, inode: cached });
+    }
+
+    /// 目录项被删除后，将其 inode 移出缓存。inode 在最后一个引用释放时回收
+    fn forget(&self, inode: &DynInode) {
+        // 移出的 inode 可能是最后一个引用，在锁外释放
+        let cached = self.inodes.lock().remove(dyn_addr(inode));
+        drop(cached);
+    }
+
+    /// 目录项被回收后，若 `inode` 已经没有其他引用，且没有未写回的修改，则将其移出缓存
+    fn try_evict(&self, inode: DynInode) {
+        let addr = dyn_addr(&inode);
+        let mut inodes = self.inodes.lock();
+        let Some(entry) = inodes.by_addr.get(&addr) else {
+            return;
+        };
+        // 引用只能来自缓存或者 `inode`，因此持有锁时检查的结果不会改变
+        if entry.inode.extra_refs() > 1 || entry.inode.modified() {
+            return;
+        }
+        let cached = inodes.remove(addr);
+        drop(inodes);
+        drop(cached);
+    }
+
+    /// 找到 VFS 中的 `inode` 对应的 FAT32 目录，不属于本文件系统时返回 `None`
+    fn find_dir(&self, inode: &Arc<DynDirInode>) -> Option<Arc<FatDir>> {
+        let addr = inode.as_ptr().cast::<()>().addr();
+        match &self.inodes.lock().by_addr.get(&addr)?.inode {
+            CachedInode::Dir(dir) => Some(Arc::clone(dir)),
+            CachedInode::File(_) => None,
+        }
+    }
+}
+
 fn is_valid_fat32_bpb(bpb: &BiosParameterBlock) -> bool {
     // FAT32 基础字段检查：几何参数、计数字段、FAT32 专属字段
     if bpb.sector_size as usize != SECTOR_SIZE
@@ -0,0 +1,18 @@
+use core::fmt::Write;
+
+use ecow::EcoString;
+use libkernel::hart;
+
+/// `/proc/cpuinfo`，格式同 linux 在 riscv 上的实现
+pub fn show() -> EcoString {
+    let mut ret = EcoString::new();
+    for (processor, cpu) in hart::cpu_info().iter().enumerate() {
+        writeln!(
+            ret,
+            "processor\t: {processor}\nhart\t\t: {}\nisa\t\t: {}\nmmu\t\t: {}\n",
+            cpu.hart_id, cpu.isa, cpu.mmu,
+        )
+        .expect("should not fail");
+    }
+    ret
+}
@@ -0,0 +1,29 @@
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use ecow::{eco_format, EcoString};
+use libkernel::hart::{self, Hart};
+
+/// `/proc/interrupts`，每行为一个中断源在各个 hart 上的中断次数
+pub fn show() -> EcoString {
+    let harts = hart::online_harts().collect::<Vec<_>>();
+    let mut ret = EcoString::from("    ");
+    for hart in &harts {
+        write!(ret, " {:>10}", eco_format!("CPU{}", hart.hart_id())).expect("should not fail");
+    }
+    ret.push('\n');
+
+    let mut write_line = |irq: usize, count: &dyn Fn(&Hart) -> usize, chip: &str, name: &str| {
+        write!(ret, "{irq:>3}:").expect("should not fail");
+        for hart in &harts {
+            write!(ret, " {:>10}", count(hart)).expect("should not fail");
+        }
+        writeln!(ret, "  {chip} {irq:>3} Edge      {name}").expect("should not fail");
+    };
+    // 5 为 riscv 中 supervisor 时钟中断的中断号
+    write_line(5, &|hart| hart.stat.timer_irqs(), "RISC-V INTC", "riscv-timer");
+    for (irq, name) in hart::irq_names() {
+        write_line(irq, &|hart| hart.stat.external_irqs(irq), "SiFive PLIC", name);
+    }
+    ret
+}
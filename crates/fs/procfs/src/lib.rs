@@ -4,48 +4,105 @@
 extern crate kernel_tracer;
 extern crate alloc;
 
+mod cpuinfo;
+mod interrupts;
+mod loadavg;
 mod meminfo;
 mod mounts;
+mod pid;
+mod root;
+mod seq_file;
+mod stat;
+mod sys;
+mod uptime;
+mod version;
 
-use defines::{error::KResult, fs::StatFsFlags};
+use common::config::{CLOCK_FREQ, TICKS_PER_SEC};
+use defines::{
+    error::KResult,
+    fs::{StatFsFlags, StatMode},
+};
 use ecow::EcoString;
+use executor::time;
 use libkernel::fs::{
     dentry::{DEntry, DEntryBytes, DEntryDir},
-    inode::{DynBytesInode, DynBytesInodeCoercion},
+    inode::{DynBytesInodeCoercion, DynDirInodeCoercion, InodeMeta, InodeMode},
     FileSystem,
 };
-use meminfo::MeminfoInode;
-use mounts::MountsInode;
+use root::ProcRootDir;
+use seq_file::SeqFile;
+use sys::ProcSysDir;
 use triomphe::Arc;
 use unsize::CoerceUnsize;
 
 pub const FS_TYPE: &str = "proc";
 
 pub fn new_proc_fs(
-    parent: Arc<DEntryDir>,
+    parent: Option<Arc<DEntryDir>>,
     name: EcoString,
     device_path: EcoString,
     flags: StatFsFlags,
 ) -> KResult<FileSystem> {
-    let mut fs = tmpfs::new_tmp_fs(parent, name, device_path, flags)?;
-    fs.fs_type = FS_TYPE;
-    {
-        let mut children = fs.root_dentry.lock_children();
-        let mut add_child = |name: &'static str, inode: Arc<DynBytesInode>| {
-            let name = EcoString::from(name);
-            let child = DEntry::Bytes(Arc::new(DEntryBytes::new(
-                Arc::clone(&fs.root_dentry),
-                name.clone(),
-                inode,
-            )));
-            children.insert(name, child);
-        };
-
-        add_child("mounts", Arc::new(MountsInode::new()).unsize(DynBytesInodeCoercion!()));
-        add_child(
-            "meminfo",
-            Arc::new(MeminfoInode::new()).unsize(DynBytesInodeCoercion!()),
-        );
-    }
+    let root_dir = Arc::new(ProcRootDir::new()).unsize(DynDirInodeCoercion!());
+    let root_dentry = Arc::new(DEntryDir::new(parent, name, root_dir));
+    let mount_point = root_dentry.path();
+    let fs = FileSystem {
+        root_dentry,
+        device_path,
+        fs_type: FS_TYPE,
+        mounted_dentry: None,
+        mount_point,
+        flags,
+    };
+    let root = &fs.root_dentry;
+    add_seq_file(root, "mounts", mounts::show);
+    add_seq_file(root, "meminfo", meminfo::show);
+    add_seq_file(root, "cpuinfo", cpuinfo::show);
+    add_seq_file(root, "stat", stat::show);
+    add_seq_file(root, "uptime", uptime::show);
+    add_seq_file(root, "loadavg", loadavg::show);
+    add_seq_file(root, "interrupts", interrupts::show);
+    add_seq_file(root, "version", version::show);
+
+    let sys_fs = add_sys_dir(&add_sys_dir(root, "sys"), "fs");
+    add_seq_file(&sys_fs, "dentry-state", sys::show_dentry_state);
     Ok(fs)
 }
+
+/// 在 `parent` 下添加内容由 `show` 生成的文件
+fn add_seq_file(parent: &Arc<DEntryDir>, name: &'static str, show: fn() -> EcoString) {
+    let name = EcoString::from(name);
+    let inode = Arc::new(SeqFile::new(show)).unsize(DynBytesInodeCoercion!());
+    let child = DEntry::Bytes(Arc::new(DEntryBytes::new(Arc::clone(parent), name.clone(), inode)));
+    parent.lock_children().insert(name, child);
+}
+
+/// 在 `parent` 下添加内容固定的 `/proc/sys` 目录
+fn add_sys_dir(parent: &Arc<DEntryDir>, name: &'static str) -> Arc<DEntryDir> {
+    let name = EcoString::from(name);
+    let inode = Arc::new(ProcSysDir::new()).unsize(DynDirInodeCoercion!());
+    let dir = Arc::new(DEntryDir::new(Some(Arc::clone(parent)), name.clone(), inode));
+    parent.lock_children().insert(name, DEntry::Dir(Arc::clone(&dir)));
+    dir
+}
+
+/// procfs 中的文件都是只读的，目录为 `0o555`，普通文件为 `0o444`
+fn new_meta(mode: InodeMode) -> InodeMeta {
+    let mut meta = InodeMeta::new(mode);
+    let meta_inner = meta.get_inner_mut();
+    match mode {
+        InodeMode::Dir => meta_inner.perm = StatMode::from_bits_truncate(0o555),
+        InodeMode::Regular => meta_inner.perm = StatMode::from_bits_truncate(0o444),
+        _ => {}
+    }
+    let curr_time = time::curr_time_spec();
+    meta_inner.access_time = curr_time;
+    meta_inner.change_time = curr_time;
+    meta_inner.modify_time = curr_time;
+    meta
+}
+
+/// 将时钟周期数转换为 `USER_HZ` 为单位的 tick 数
+fn clock_to_ticks(clock: usize) -> usize {
+    clock / (CLOCK_FREQ / TICKS_PER_SEC)
+}
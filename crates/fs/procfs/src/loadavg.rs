@@ -0,0 +1,25 @@
+use ecow::{eco_format, EcoString};
+use executor::load_avg::{self, FIXED_1};
+use libkernel::process::PROCESS_MANAGER;
+
+/// `/proc/loadavg`，依次为 1、5、15 分钟的平均负载，可运行任务数/进程总数，最近创建的进程的 pid
+pub fn show() -> EcoString {
+    let [avg1, avg5, avg15] = load_avg::load_avg().map(|load| {
+        // 保留两位小数，同 linux 的 `LOAD_INT()` 和 `LOAD_FRAC()`
+        let load = load + FIXED_1 / 200;
+        (load / FIXED_1, (load % FIXED_1) * 100 / FIXED_1)
+    });
+    // TODO: [low] 目前以进程数作为调度实体总数，没有统计线程
+    let n_processes = PROCESS_MANAGER.lock_all().len();
+    eco_format!(
+        "{}.{:02} {}.{:02} {}.{:02} {}/{n_processes} {}\n",
+        avg1.0,
+        avg1.1,
+        avg5.0,
+        avg5.1,
+        avg15.0,
+        avg15.1,
+        executor::nr_runnable(),
+        PROCESS_MANAGER.last_pid(),
+    )
+}
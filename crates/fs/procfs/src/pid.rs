@@ -0,0 +1,510 @@
+//! `/proc/[pid]` 目录及其中的文件，内容在每次读取时根据进程的当前状态生成
+
+use alloc::{boxed::Box, vec::Vec};
+use core::fmt::Write;
+
+use common::config::PAGE_SIZE;
+use defines::error::{errno, AKResult, KResult};
+use ecow::{eco_format, EcoString};
+use libkernel::{
+    fs::{
+        dentry::{DEntry, DEntryBytes, DEntryDir},
+        file::File,
+        inode::{
+            BytesInodeBackend, DirInodeBackend, DynBytesInode, DynBytesInodeCoercion, DynDirInode, DynDirInodeCoercion,
+            DynInode, InodeMeta, InodeMode,
+        },
+    },
+    hart::local_hart,
+    memory::{MapPermission, ReadBuffer, WriteBuffer},
+    process::{Process, INITPROC_PID, PROCESS_MANAGER},
+};
+use triomphe::Arc;
+use unsize::CoerceUnsize;
+
+use crate::{new_meta, seq_file::read_generated};
+
+/// `/proc/[pid]`
+pub struct ProcessDir {
+    meta: InodeMeta,
+    pid: usize,
+}
+
+impl ProcessDir {
+    pub fn new(process: &Process) -> Self {
+        let meta = new_meta(InodeMode::Dir);
+        let cred = process.cred();
+        meta.lock_inner_with(|inner| {
+            inner.uid = cred.uid.effective;
+            inner.gid = cred.gid.effective;
+        });
+        Self {
+            meta,
+            pid: process.pid(),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum ProcessEntry {
+    Stat,
+    Status,
+    Cmdline,
+    Comm,
+    Maps,
+    Fd,
+    Exe,
+    Cwd,
+}
+
+impl ProcessEntry {
+    const ALL: [Self; 8] = [
+        Self::Stat,
+        Self::Status,
+        Self::Cmdline,
+        Self::Comm,
+        Self::Maps,
+        Self::Fd,
+        Self::Exe,
+        Self::Cwd,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Stat => "stat",
+            Self::Status => "status",
+            Self::Cmdline => "cmdline",
+            Self::Comm => "comm",
+            Self::Maps => "maps",
+            Self::Fd => "fd",
+            Self::Exe => "exe",
+            Self::Cwd => "cwd",
+        }
+    }
+
+    fn inode(self, pid: usize) -> DynInode {
+        let file = |kind| DynInode::Bytes(Arc::new(ProcessFile::new(pid, kind)).unsize(DynBytesInodeCoercion!()));
+        let link = |kind| DynInode::Bytes(Arc::new(ProcessLink::new(pid, kind)).unsize(DynBytesInodeCoercion!()));
+        match self {
+            Self::Stat => file(FileKind::Stat),
+            Self::Status => file(FileKind::Status),
+            Self::Cmdline => file(FileKind::Cmdline),
+            Self::Comm => file(FileKind::Comm),
+            Self::Maps => file(FileKind::Maps),
+            Self::Fd => DynInode::Dir(Arc::new(ProcessFdDir::new(pid)).unsize(DynDirInodeCoercion!())),
+            Self::Exe => link(LinkKind::Exe),
+            Self::Cwd => link(LinkKind::Cwd),
+        }
+    }
+}
+
+impl DirInodeBackend for ProcessDir {
+    fn meta(&self) -> &InodeMeta {
+        &self.meta
+    }
+
+    fn lookup(&self, name: &str) -> Option<DynInode> {
+        let entry = ProcessEntry::ALL.into_iter().find(|entry| entry.name() == name)?;
+        Some(entry.inode(self.pid))
+    }
+
+    fn mkdir(&self, _name: &str) -> KResult<Arc<DynDirInode>> {
+        Err(errno::EPERM)
+    }
+
+    fn mknod(&self, _name: &str, _mode: InodeMode) -> KResult<Arc<DynBytesInode>> {
+        Err(errno::EPERM)
+    }
+
+    fn symlink(&self, _name: &str, _target: &str) -> KResult<Arc<DynBytesInode>> {
+        Err(errno::EPERM)
+    }
+
+    fn link(&self, _name: &str, _target: &Arc<DynBytesInode>) -> KResult<()> {
+        Err(errno::EPERM)
+    }
+
+    fn unlink(&self, _name: &str) -> KResult<()> {
+        Err(errno::EPERM)
+    }
+
+    fn rename(&self, _old_name: &str, _new_dir: &Arc<DynDirInode>, _new_name: &str) -> KResult<()> {
+        Err(errno::EPERM)
+    }
+
+    fn read_dir(&self, parent: &Arc<DEntryDir>) -> KResult<()> {
+        let mut children = parent.lock_children();
+        for entry in ProcessEntry::ALL {
+            children
+                .entry(EcoString::from(entry.name()))
+                .or_insert_with_key(|name| new_dentry(parent, name.clone(), entry.inode(self.pid)));
+        }
+        Ok(())
+    }
+
+    fn disk_space(&self) -> u64 {
+        0
+    }
+}
+
+/// `/proc/[pid]/fd`，其中每个打开的文件描述符都是一个符号链接
+struct ProcessFdDir {
+    meta: InodeMeta,
+    pid: usize,
+}
+
+impl ProcessFdDir {
+    fn new(pid: usize) -> Self {
+        Self {
+            meta: new_meta(InodeMode::Dir),
+            pid,
+        }
+    }
+
+    fn has_fd(&self, fd: usize) -> bool {
+        PROCESS_MANAGER
+            .get(self.pid)
+            .is_some_and(|process| process.lock_inner_with(|inner| inner.fd_table.get(fd).is_some()))
+    }
+}
+
+impl DirInodeBackend for ProcessFdDir {
+    fn meta(&self) -> &InodeMeta {
+        &self.meta
+    }
+
+    fn lookup(&self, name: &str) -> Option<DynInode> {
+        let fd = name.parse().ok()?;
+        if !self.has_fd(fd) {
+            return None;
+        }
+        let link = ProcessLink::new(self.pid, LinkKind::Fd(fd));
+        Some(DynInode::Bytes(Arc::new(link).unsize(DynBytesInodeCoercion!())))
+    }
+
+    fn mkdir(&self, _name: &str) -> KResult<Arc<DynDirInode>> {
+        Err(errno::EPERM)
+    }
+
+    fn mknod(&self, _name: &str, _mode: InodeMode) -> KResult<Arc<DynBytesInode>> {
+        Err(errno::EPERM)
+    }
+
+    fn symlink(&self, _name: &str, _target: &str) -> KResult<Arc<DynBytesInode>> {
+        Err(errno::EPERM)
+    }
+
+    fn link(&self, _name: &str, _target: &Arc<DynBytesInode>) -> KResult<()> {
+        Err(errno::EPERM)
+    }
+
+    fn unlink(&self, _name: &str) -> KResult<()> {
+        Err(errno::EPERM)
+    }
+
+    fn rename(&self, _old_name: &str, _new_dir: &Arc<DynDirInode>, _new_name: &str) -> KResult<()> {
+        Err(errno::EPERM)
+    }
+
+    fn read_dir(&self, parent: &Arc<DEntryDir>) -> KResult<()> {
+        let process = PROCESS_MANAGER.get(self.pid).ok_or(errno::ENOENT)?;
+        let fds = process.lock_inner_with(|inner| inner.fd_table.iter().map(|(fd, _)| fd).collect::<Vec<_>>());
+        let mut children = parent.lock_children();
+        children.retain(|name, _| name.parse().is_ok_and(|fd| fds.contains(&fd)));
+        for fd in fds {
+            children.entry(eco_format!("{fd}")).or_insert_with_key(|name| {
+                let link = ProcessLink::new(self.pid, LinkKind::Fd(fd));
+                new_dentry(
+                    parent,
+                    name.clone(),
+                    DynInode::Bytes(Arc::new(link).unsize(DynBytesInodeCoercion!())),
+                )
+            });
+        }
+        Ok(())
+    }
+
+    fn disk_space(&self) -> u64 {
+        0
+    }
+
+    fn revalidate(&self, name: &str) -> bool {
+        name.parse().is_ok_and(|fd| self.has_fd(fd))
+    }
+}
+
+fn new_dentry(parent: &Arc<DEntryDir>, name: EcoString, inode: DynInode) -> DEntry {
+    match inode {
+        DynInode::Dir(dir) => DEntry::Dir(Arc::new(DEntryDir::new(Some(Arc::clone(parent)), name, dir))),
+        DynInode::Bytes(bytes) => DEntry::Bytes(Arc::new(DEntryBytes::new(Arc::clone(parent), name, bytes))),
+    }
+}
+
+#[derive(Clone, Copy)]
+enum FileKind {
+    Stat,
+    Status,
+    Cmdline,
+    Comm,
+    Maps,
+}
+
+/// `/proc/[pid]` 下的只读文件
+struct ProcessFile {
+    meta: InodeMeta,
+    pid: usize,
+    kind: FileKind,
+}
+
+impl ProcessFile {
+    fn new(pid: usize, kind: FileKind) -> Self {
+        Self {
+            meta: new_meta(InodeMode::Regular),
+            pid,
+            kind,
+        }
+    }
+}
+
+impl BytesInodeBackend for ProcessFile {
+    fn meta(&self) -> &InodeMeta {
+        &self.meta
+    }
+
+    fn read_inode_at<'a>(&'a self, buf: ReadBuffer<'a>, offset: u64) -> AKResult<'a, usize> {
+        Box::pin(async move {
+            // 进程已被回收
+            let process = PROCESS_MANAGER.get(self.pid).ok_or(errno::ESRCH)?;
+            let content = match self.kind {
+                FileKind::Stat => stat(&process),
+                FileKind::Status => status(&process),
+                FileKind::Cmdline => cmdline(&process),
+                FileKind::Comm => eco_format!("{}\n", comm(&process)),
+                FileKind::Maps => maps(&process),
+            };
+            read_generated(content.as_bytes(), buf, offset)
+        })
+    }
+
+    fn write_inode_at<'a>(&'a self, _buf: WriteBuffer<'a>, _offset: u64) -> AKResult<'a, usize> {
+        Box::pin(async move { Err(errno::EBADF) })
+    }
+
+    fn use_page_cache(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Clone, Copy)]
+enum LinkKind {
+    Exe,
+    Cwd,
+    Fd(usize),
+}
+
+/// `/proc/[pid]` 下的符号链接，目标在每次读取时生成
+struct ProcessLink {
+    meta: InodeMeta,
+    pid: usize,
+    kind: LinkKind,
+}
+
+impl ProcessLink {
+    fn new(pid: usize, kind: LinkKind) -> Self {
+        Self {
+            meta: new_meta(InodeMode::SymbolLink),
+            pid,
+            kind,
+        }
+    }
+}
+
+impl BytesInodeBackend for ProcessLink {
+    fn meta(&self) -> &InodeMeta {
+        &self.meta
+    }
+
+    fn read_inode_at<'a>(&'a self, _buf: ReadBuffer<'a>, _offset: u64) -> AKResult<'a, usize> {
+        Box::pin(async move { Err(errno::EINVAL) })
+    }
+
+    fn write_inode_at<'a>(&'a self, _buf: WriteBuffer<'a>, _offset: u64) -> AKResult<'a, usize> {
+        Box::pin(async move { Err(errno::EBADF) })
+    }
+
+    fn read_link(&self) -> KResult<EcoString> {
+        let process = PROCESS_MANAGER.get(self.pid).ok_or(errno::ENOENT)?;
+        process.lock_inner_with(|inner| match self.kind {
+            LinkKind::Exe => inner.exe.as_ref().map(|exe| exe.path()).ok_or(errno::ENOENT),
+            LinkKind::Cwd => Ok(inner.cwd.path()),
+            LinkKind::Fd(fd) => {
+                let file = inner.fd_table.get(fd).ok_or(errno::ENOENT)?;
+                // 管道、socket 和其他匿名文件没有路径，与 linux 一样显示为 `pipe:[ino]`、`socket:[ino]` 和 `anon_inode:[类型]`
+                Ok(match (&**file, file.dentry()) {
+                    (_, Some(dentry)) => dentry.path(),
+                    (File::Epoll(_), None) => EcoString::from("anon_inode:[eventpoll]"),
+                    (File::EventFd(_), None) => EcoString::from("anon_inode:[eventfd]"),
+                    (File::TimerFd(_), None) => EcoString::from("anon_inode:[timerfd]"),
+                    (File::SignalFd(_), None) => EcoString::from("anon_inode:[signalfd]"),
+                    (File::Inotify(_), None) => EcoString::from("anon_inode:inotify"),
+                    (File::Socket(_), None) => eco_format!("socket:[{}]", file.meta().ino()),
+                    (_, None) => eco_format!("pipe:[{}]", file.meta().ino()),
+                })
+            }
+        })
+    }
+
+    fn use_page_cache(&self) -> bool {
+        false
+    }
+}
+
+/// `/proc/self`，指向当前进程的 `/proc/[pid]`
+pub struct SelfLink {
+    meta: InodeMeta,
+}
+
+impl SelfLink {
+    pub fn new() -> Self {
+        Self {
+            meta: new_meta(InodeMode::SymbolLink),
+        }
+    }
+}
+
+impl BytesInodeBackend for SelfLink {
+    fn meta(&self) -> &InodeMeta {
+        &self.meta
+    }
+
+    fn read_inode_at<'a>(&'a self, _buf: ReadBuffer<'a>, _offset: u64) -> AKResult<'a, usize> {
+        Box::pin(async move { Err(errno::EINVAL) })
+    }
+
+    fn write_inode_at<'a>(&'a self, _buf: WriteBuffer<'a>, _offset: u64) -> AKResult<'a, usize> {
+        Box::pin(async move { Err(errno::EBADF) })
+    }
+
+    fn read_link(&self) -> KResult<EcoString> {
+        let process = local_hart().try_curr_process().ok_or(errno::ENOENT)?;
+        Ok(eco_format!("{}", process.pid()))
+    }
+}
+
+/// 进程名，同 linux 一样是可执行文件的文件名，最长 15 字节
+fn comm(process: &Process) -> EcoString {
+    let name = process.name();
+    let arg0 = name.split(' ').next().unwrap_or_default();
+    let comm = arg0.rsplit('/').next().unwrap_or_default();
+    let end = comm
+        .char_indices()
+        .map(|(i, c)| i + c.len_utf8())
+        .take_while(|&end| end <= 15)
+        .last()
+        .unwrap_or(0);
+    EcoString::from(&comm[..end])
+}
+
+/// 进程状态，目前只区分运行和僵尸
+fn state(process: &Process) -> (char, &'static str) {
+    if process.is_zombie() || process.is_exited() {
+        ('Z', "zombie")
+    } else {
+        ('R', "running")
+    }
+}
+
+/// 进程的一些统计信息，依次为父进程 pid、线程数、地址空间大小（字节）和已分配的页数
+fn statistics(process: &Process) -> (usize, usize, usize, usize) {
+    process.lock_inner_with(|inner| {
+        let ppid = inner.parent.as_ref().map_or(0, |parent| parent.pid());
+        let (mut vsize, mut rss) = (0, 0);
+        for area in inner.memory_space.user_areas() {
+            vsize += area.len() * PAGE_SIZE;
+            rss += area.unbacked_map().len();
+        }
+        (ppid, inner.threads.len(), vsize, rss)
+    })
+}
+
+fn stat(process: &Process) -> EcoString {
+    let (ppid, n_threads, vsize, rss) = statistics(process);
+    // TODO: [low] 进程组、会话、CPU 时间等暂未实现，进程组与 `getpgid()` 一致视为 INITPROC
+    let mut ret = eco_format!(
+        "{pid} ({comm}) {state} {ppid} {pgrp} {pgrp} 0 -1 0 0 0 0 0 0 0 0 0 20 0 {n_threads} 0 0 {vsize} {rss}",
+        pid = process.pid(),
+        comm = comm(process),
+        state = state(process).0,
+        pgrp = INITPROC_PID,
+    );
+    // rsslim 之后的字段都填 0
+    for _ in 0..28 {
+        ret.push_str(" 0");
+    }
+    ret.push('\n');
+    ret
+}
+
+fn status(process: &Process) -> EcoString {
+    let (ppid, n_threads, vsize, rss) = statistics(process);
+    let (umask, fd_size) = process.lock_inner_with(|inner| (inner.umask, inner.fd_table.limit()));
+    let cred = process.cred();
+    let (state, state_name) = state(process);
+    eco_format!(
+        "Name:\t{comm}\nUmask:\t{umask:04o}\nState:\t{state} ({state_name})\nTgid:\t{pid}\nPid:\t{pid}\n\
+         PPid:\t{ppid}\nUid:\t{ruid}\t{euid}\t{suid}\t{euid}\nGid:\t{rgid}\t{egid}\t{sgid}\t{egid}\n\
+         FDSize:\t{fd_size}\nVmSize:\t{vm_size} kB\nVmRSS:\t{vm_rss} kB\nThreads:\t{n_threads}\n",
+        comm = comm(process),
+        pid = process.pid(),
+        ruid = cred.uid.real,
+        euid = cred.uid.effective,
+        suid = cred.uid.saved,
+        rgid = cred.gid.real,
+        egid = cred.gid.effective,
+        sgid = cred.gid.saved,
+        vm_size = vsize / 1024,
+        vm_rss = rss * PAGE_SIZE / 1024,
+    )
+}
+
+/// 以 `\0` 结尾的各个参数。僵尸进程的为空
+fn cmdline(process: &Process) -> EcoString {
+    if process.is_zombie() {
+        return EcoString::new();
+    }
+    process.lock_inner_with(|inner| {
+        let mut ret = EcoString::new();
+        for arg in &inner.cmdline {
+            ret.push_str(arg);
+            ret.push('\0');
+        }
+        ret
+    })
+}
+
+fn maps(process: &Process) -> EcoString {
+    process.lock_inner_with(|inner| {
+        let heap_start = inner.heap_range.start.vpn_floor();
+        let mut ret = EcoString::new();
+        for area in inner.memory_space.user_areas() {
+            let range = area.vpn_range();
+            let perm = area.perm();
+            let flag = |perm_bit, c| if perm.contains(perm_bit) { c } else { '-' };
+            let (offset, ino) = area.backed_inode().map_or((0, 0), |inode| {
+                (area.backed_inode_page_id() as usize * PAGE_SIZE, inode.meta().ino())
+            });
+            let name = if range.start == heap_start { "[heap]" } else { "" };
+            writeln!(
+                ret,
+                "{:08x}-{:08x} {}{}{}p {offset:08x} 00:00 {ino} {name}",
+                range.start.page_start().0,
+                range.end.page_start().0,
+                flag(MapPermission::R, 'r'),
+                flag(MapPermission::W, 'w'),
+                flag(MapPermission::X, 'x'),
+            )
+            .expect("should not fail");
+        }
+        ret
+    })
+}
@@ -0,0 +1,109 @@
+use alloc::vec::Vec;
+
+use defines::error::{errno, KResult};
+use ecow::{eco_format, EcoString};
+use libkernel::{
+    fs::{
+        dentry::{DEntry, DEntryBytes, DEntryDir},
+        inode::{
+            DirInodeBackend, DynBytesInode, DynBytesInodeCoercion, DynDirInode, DynDirInodeCoercion, DynInode,
+            InodeMeta, InodeMode,
+        },
+    },
+    process::PROCESS_MANAGER,
+};
+use triomphe::Arc;
+use unsize::CoerceUnsize;
+
+use crate::{
+    new_meta,
+    pid::{ProcessDir, SelfLink},
+};
+
+/// `/proc` 目录。
+///
+/// 除了固定的 `mounts` 等文件之外，每个进程都有一个以 pid 命名的目录，随进程的创建和回收出现和消失
+pub struct ProcRootDir {
+    meta: InodeMeta,
+}
+
+impl ProcRootDir {
+    pub fn new() -> Self {
+        Self {
+            meta: new_meta(InodeMode::Dir),
+        }
+    }
+}
+
+impl DirInodeBackend for ProcRootDir {
+    fn meta(&self) -> &InodeMeta {
+        &self.meta
+    }
+
+    fn lookup(&self, name: &str) -> Option<DynInode> {
+        if name == "self" {
+            return Some(DynInode::Bytes(
+                Arc::new(SelfLink::new()).unsize(DynBytesInodeCoercion!()),
+            ));
+        }
+        let process = PROCESS_MANAGER.get(name.parse().ok()?)?;
+        Some(DynInode::Dir(
+            Arc::new(ProcessDir::new(&process)).unsize(DynDirInodeCoercion!()),
+        ))
+    }
+
+    fn mkdir(&self, _name: &str) -> KResult<Arc<DynDirInode>> {
+        Err(errno::EPERM)
+    }
+
+    fn mknod(&self, _name: &str, _mode: InodeMode) -> KResult<Arc<DynBytesInode>> {
+        Err(errno::EPERM)
+    }
+
+    fn symlink(&self, _name: &str, _target: &str) -> KResult<Arc<DynBytesInode>> {
+        Err(errno::EPERM)
+    }
+
+    fn link(&self, _name: &str, _target: &Arc<DynBytesInode>) -> KResult<()> {
+        Err(errno::EPERM)
+    }
+
+    fn unlink(&self, _name: &str) -> KResult<()> {
+        Err(errno::EPERM)
+    }
+
+    fn rename(&self, _old_name: &str, _new_dir: &Arc<DynDirInode>, _new_name: &str) -> KResult<()> {
+        Err(errno::EPERM)
+    }
+
+    fn read_dir(&self, parent: &Arc<DEntryDir>) -> KResult<()> {
+        let processes = PROCESS_MANAGER.lock_all().values().cloned().collect::<Vec<_>>();
+        let mut children = parent.lock_children();
+        // 去掉已被回收的进程
+        children.retain(|name, _| {
+            name.parse::<usize>()
+                .map_or(true, |pid| processes.iter().any(|process| process.pid() == pid))
+        });
+        for process in processes {
+            children
+                .entry(eco_format!("{}", process.pid()))
+                .or_insert_with_key(|name| {
+                    let inode = Arc::new(ProcessDir::new(&process)).unsize(DynDirInodeCoercion!());
+                    DEntry::Dir(Arc::new(DEntryDir::new(Some(Arc::clone(parent)), name.clone(), inode)))
+                });
+        }
+        children.entry(EcoString::from("self")).or_insert_with_key(|name| {
+            let inode = Arc::new(SelfLink::new()).unsize(DynBytesInodeCoercion!());
+            DEntry::Bytes(Arc::new(DEntryBytes::new(Arc::clone(parent), name.clone(), inode)))
+        });
+        Ok(())
+    }
+
+    fn disk_space(&self) -> u64 {
+        0
+    }
+
+    fn revalidate(&self, name: &str) -> bool {
+        name.parse().map_or(true, |pid| PROCESS_MANAGER.get(pid).is_some())
+    }
+}
@@ -0,0 +1,69 @@
+use alloc::boxed::Box;
+
+use defines::error::{errno, AKResult, KResult};
+use ecow::EcoString;
+use libkernel::{
+    fs::inode::{BytesInodeBackend, InodeMeta, InodeMode},
+    memory::{ReadBuffer, WriteBuffer},
+};
+
+use crate::new_meta;
+
+/// 类似于 linux 的 `seq_file`。每次读取时由 `show` 生成完整内容，再从 `offset` 处开始读取。
+///
+/// 内容是动态的，因此不经过页缓存，文件大小也始终为 0
+pub struct SeqFile {
+    meta: InodeMeta,
+    show: fn() -> EcoString,
+}
+
+impl SeqFile {
+    pub fn new(show: fn() -> EcoString) -> Self {
+        Self {
+            meta: new_meta(InodeMode::Regular),
+            show,
+        }
+    }
+}
+
+impl BytesInodeBackend for SeqFile {
+    fn meta(&self) -> &InodeMeta {
+        &self.meta
+    }
+
+    fn read_inode_at<'a>(&'a self, buf: ReadBuffer<'a>, offset: u64) -> AKResult<'a, usize> {
+        Box::pin(async move {
+            let content = (self.show)();
+            read_generated(content.as_bytes(), buf, offset)
+        })
+    }
+
+    fn write_inode_at<'a>(&'a self, _buf: WriteBuffer<'a>, _offset: u64) -> AKResult<'a, usize> {
+        Box::pin(async move { Err(errno::EBADF) })
+    }
+
+    fn use_page_cache(&self) -> bool {
+        false
+    }
+}
+
+/// 从 `offset` 处开始将生成的内容读入 `buf`，超出内容末尾时返回 0
+pub fn read_generated(content: &[u8], buf: ReadBuffer<'_>, offset: u64) -> KResult<usize> {
+    let Some(content) = content.get(offset as usize..) else {
+        return Ok(0);
+    };
+    let read_len = usize::min(buf.len(), content.len());
+    match buf {
+        ReadBuffer::Kernel(buf) => {
+            buf[..read_len].copy_from_slice(&content[..read_len]);
+        }
+        ReadBuffer::User(buf) => unsafe {
+            buf.slice(0..read_len)
+                .expect("must be in bound")
+                .check_slice_mut()?
+                .as_bytes_mut()
+                .copy_from_slice(&content[..read_len]);
+        },
+    }
+    Ok(read_len)
+}
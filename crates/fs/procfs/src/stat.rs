@@ -0,0 +1,55 @@
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use ecow::{eco_format, EcoString};
+use libkernel::{
+    hart::{self, Hart},
+    process::PROCESS_MANAGER,
+};
+
+use crate::clock_to_ticks;
+
+/// `/proc/stat`，时间的单位为 `USER_HZ`。
+///
+/// TODO: [low] 目前不区分 nice、iowait、softirq 等，都计为 0；启动时间 `btime` 也计为 0
+pub fn show() -> EcoString {
+    let harts = hart::online_harts().collect::<Vec<_>>();
+    let times = |hart: &Hart| {
+        [
+            clock_to_ticks(hart.stat.user_time()),
+            clock_to_ticks(hart.stat.system_time()),
+            clock_to_ticks(hart.stat.idle_time()),
+        ]
+    };
+    let mut total_times = [0; 3];
+    let mut per_hart = EcoString::new();
+    for hart in &harts {
+        let [user, system, idle] = times(hart);
+        writeln!(per_hart, "cpu{} {user} 0 {system} {idle} 0 0 0 0 0 0", hart.hart_id()).expect("should not fail");
+        total_times[0] += user;
+        total_times[1] += system;
+        total_times[2] += idle;
+    }
+    let [user, system, idle] = total_times;
+    let mut ret = eco_format!("cpu  {user} 0 {system} {idle} 0 0 0 0 0 0\n{per_hart}");
+
+    // 中断总数，之后依次是各个中断号的次数
+    let irq_count = |irq| harts.iter().map(|hart| hart.stat.external_irqs(irq)).sum::<usize>();
+    let max_irq = hart::irq_names().last().map_or(0, |&(irq, _)| irq);
+    let timer_irqs = harts.iter().map(|hart| hart.stat.timer_irqs()).sum::<usize>();
+    let total = timer_irqs + (0..=max_irq).map(irq_count).sum::<usize>();
+    write!(ret, "intr {total}").expect("should not fail");
+    for irq in 0..=max_irq {
+        write!(ret, " {}", irq_count(irq)).expect("should not fail");
+    }
+
+    writeln!(
+        ret,
+        "\nctxt {}\nbtime 0\nprocesses {}\nprocs_running {}\nprocs_blocked 0",
+        executor::nr_switches(),
+        PROCESS_MANAGER.created(),
+        executor::nr_runnable(),
+    )
+    .expect("should not fail");
+    ret
+}
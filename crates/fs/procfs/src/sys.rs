@@ -0,0 +1,76 @@
+use defines::error::{errno, KResult};
+use ecow::{eco_format, EcoString};
+use libkernel::fs::{
+    dentry::{self, DEntryDir},
+    inode::{DirInodeBackend, DynBytesInode, DynDirInode, DynInode, InodeMeta, InodeMode},
+};
+use triomphe::Arc;
+
+use crate::new_meta;
+
+/// `/proc/sys` 下的目录。内容固定，所有子目录项在创建文件系统时就放入 dentry 中
+pub struct ProcSysDir {
+    meta: InodeMeta,
+}
+
+impl ProcSysDir {
+    pub fn new() -> Self {
+        Self {
+            meta: new_meta(InodeMode::Dir),
+        }
+    }
+}
+
+impl DirInodeBackend for ProcSysDir {
+    fn meta(&self) -> &InodeMeta {
+        &self.meta
+    }
+
+    fn lookup(&self, _name: &str) -> Option<DynInode> {
+        None
+    }
+
+    fn mkdir(&self, _name: &str) -> KResult<Arc<DynDirInode>> {
+        Err(errno::EPERM)
+    }
+
+    fn mknod(&self, _name: &str, _mode: InodeMode) -> KResult<Arc<DynBytesInode>> {
+        Err(errno::EPERM)
+    }
+
+    fn symlink(&self, _name: &str, _target: &str) -> KResult<Arc<DynBytesInode>> {
+        Err(errno::EPERM)
+    }
+
+    fn link(&self, _name: &str, _target: &Arc<DynBytesInode>) -> KResult<()> {
+        Err(errno::EPERM)
+    }
+
+    fn unlink(&self, _name: &str) -> KResult<()> {
+        Err(errno::EPERM)
+    }
+
+    fn rename(&self, _old_name: &str, _new_dir: &Arc<DynDirInode>, _new_name: &str) -> KResult<()> {
+        Err(errno::EPERM)
+    }
+
+    fn read_dir(&self, _parent: &Arc<DEntryDir>) -> KResult<()> {
+        Ok(())
+    }
+
+    fn disk_space(&self) -> u64 {
+        0
+    }
+}
+
+/// `/proc/sys/fs/dentry-state`，依次为 dentry 总数、未被引用的 dentry 数、`age_limit`、`want_pages`、
+/// 负目录项数和一个保留字段。`age_limit` 同 linux 固定为 45，`want_pages` 总是 0
+pub fn show_dentry_state() -> EcoString {
+    let state = dentry::dentry_state();
+    eco_format!(
+        "{}\t{}\t45\t0\t{}\t0\n",
+        state.nr_dentry,
+        state.nr_unused,
+        state.nr_negative
+    )
+}
@@ -0,0 +1,18 @@
+use common::config::CLOCK_FREQ;
+use ecow::{eco_format, EcoString};
+use executor::time;
+use libkernel::hart;
+
+/// `/proc/uptime`，依次为系统运行时间和各个 hart 空闲时间之和，单位为秒
+pub fn show() -> EcoString {
+    let uptime = time::curr_time();
+    let idle = hart::online_harts().map(|hart| hart.stat.idle_time()).sum::<usize>();
+    let idle_centis = idle * 100 / CLOCK_FREQ;
+    eco_format!(
+        "{}.{:02} {}.{:02}\n",
+        uptime.as_secs(),
+        uptime.subsec_millis() / 10,
+        idle_centis / 100,
+        idle_centis % 100,
+    )
+}
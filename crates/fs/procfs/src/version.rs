@@ -0,0 +1,23 @@
+use core::ffi::CStr;
+
+use defines::misc::UtsName;
+use ecow::{eco_format, EcoString};
+
+/// `/proc/version`，内容与 `uname()` 一致
+pub fn show() -> EcoString {
+    let utsname = UtsName::new();
+    eco_format!(
+        "{} version {} ({}) #{}\n",
+        field(&utsname.sysname),
+        field(&utsname.release),
+        field(&utsname.nodename),
+        field(&utsname.version),
+    )
+}
+
+fn field(bytes: &[u8]) -> &str {
+    CStr::from_bytes_until_nul(bytes)
+        .ok()
+        .and_then(|s| s.to_str().ok())
+        .unwrap_or_default()
+}
@@ -1,12 +1,26 @@
 #![no_std]
 
-use defines::{error::KResult, fs::StatFsFlags};
+extern crate alloc;
+
+use alloc::boxed::Box;
+
+use common::config::{PAGE_OFFSET_MASK, PAGE_SIZE_BITS};
+use defines::{
+    error::{errno, AKResult, KResult},
+    fs::StatFsFlags,
+};
 use ecow::EcoString;
 use executor::time;
-use libkernel::fs::{
-    dentry::DEntryDir,
-    inode::{DirInodeBackend, DynBytesInode, DynDirInode, DynDirInodeCoercion, DynInode, InodeMeta, InodeMode},
-    FileSystem,
+use libkernel::{
+    fs::{
+        dentry::DEntryDir,
+        inode::{
+            BytesInodeBackend, DirInodeBackend, DynBytesInode, DynBytesInodeCoercion, DynDirInode, DynDirInodeCoercion,
+            DynInode, InodeMeta, InodeMode,
+        },
+        FileSystem,
+    },
+    memory::{ReadBuffer, WriteBuffer},
 };
 use triomphe::Arc;
 use unsize::CoerceUnsize;
@@ -15,14 +29,17 @@ use unsize::CoerceUnsize;
 
 pub const FS_TYPE: &str = "tmpfs";
 
+/// 新建一个 tmpfs。
+///
+/// 挂载到 `/` 上时 `parent` 为 `None`，挂载到某个目录下时则为挂载点的父目录
 pub fn new_tmp_fs(
-    parent: Arc<DEntryDir>,
+    parent: Option<Arc<DEntryDir>>,
     name: EcoString,
     device_path: EcoString,
     flags: StatFsFlags,
 ) -> KResult<FileSystem> {
     let root_dir = Arc::new(TmpDir::new()).unsize(DynDirInodeCoercion!());
-    let root_dentry = Arc::new(DEntryDir::new(Some(parent), name, root_dir));
+    let root_dentry = Arc::new(DEntryDir::new(parent, name, root_dir));
     let mount_point = root_dentry.path();
 
     Ok(FileSystem {
@@ -72,14 +89,31 @@ impl DirInodeBackend for TmpDir {
     }
 
     fn mknod(&self, _name: &str, mode: InodeMode) -> KResult<Arc<DynBytesInode>> {
-        debug_assert_ne!(mode, InodeMode::Dir);
-        todo!("[low] impl mknod for tmpfs");
+        match mode {
+            InodeMode::Regular => Ok(Arc::new(TmpFile::new()).unsize(DynBytesInodeCoercion!())),
+            InodeMode::Fifo | InodeMode::Socket => Ok(Arc::new(TmpSpecial::new(mode)).unsize(DynBytesInodeCoercion!())),
+            InodeMode::Dir | InodeMode::SymbolLink | InodeMode::Anonymous => unreachable!(),
+            // TODO: [low] tmpfs 还不支持设备文件
+            InodeMode::CharDevice | InodeMode::BlockDevice => Err(errno::EPERM),
+        }
+    }
+
+    fn symlink(&self, _name: &str, target: &str) -> KResult<Arc<DynBytesInode>> {
+        Ok(Arc::new(TmpSymlink::new(EcoString::from(target))).unsize(DynBytesInodeCoercion!()))
+    }
+
+    fn link(&self, _name: &str, _target: &Arc<DynBytesInode>) -> KResult<()> {
+        Ok(())
     }
 
     fn unlink(&self, _name: &str) -> KResult<()> {
         Ok(())
     }
 
+    fn rename(&self, _old_name: &str, _new_dir: &Arc<DynDirInode>, _new_name: &str) -> KResult<()> {
+        Ok(())
+    }
+
     fn read_dir(&self, _parent: &Arc<DEntryDir>) -> KResult<()> {
         Ok(())
     }
@@ -89,7 +123,132 @@ impl DirInodeBackend for TmpDir {
     }
 }
 
-#[expect(unused)]
+pub struct TmpSymlink {
+    meta: InodeMeta,
+    target: EcoString,
+}
+
+impl TmpSymlink {
+    pub fn new(target: EcoString) -> Self {
+        let mut meta = InodeMeta::new(InodeMode::SymbolLink);
+        let meta_inner = meta.get_inner_mut();
+        // 符号链接的大小是目标路径的长度
+        meta_inner.data_len = target.len() as u64;
+        let curr_time = time::curr_time_spec();
+        meta_inner.access_time = curr_time;
+        meta_inner.change_time = curr_time;
+        meta_inner.modify_time = curr_time;
+        Self { meta, target }
+    }
+}
+
+impl BytesInodeBackend for TmpSymlink {
+    fn meta(&self) -> &InodeMeta {
+        &self.meta
+    }
+
+    fn read_inode_at<'a>(&'a self, _buf: ReadBuffer<'a>, _offset: u64) -> AKResult<'a, usize> {
+        Box::pin(async move { Err(errno::EINVAL) })
+    }
+
+    fn write_inode_at<'a>(&'a self, _buf: WriteBuffer<'a>, _offset: u64) -> AKResult<'a, usize> {
+        Box::pin(async move { Err(errno::EINVAL) })
+    }
+
+    fn read_link(&self) -> KResult<EcoString> {
+        Ok(self.target.clone())
+    }
+}
+
+/// tmpfs 中的命名管道和 socket 文件。
+///
+/// 它们只是文件系统中的一个名字，数据不经过 inode：命名管道的缓冲区在 [`InodeMeta`] 中
+pub struct TmpSpecial {
+    meta: InodeMeta,
+}
+
+impl TmpSpecial {
+    pub fn new(mode: InodeMode) -> Self {
+        let mut meta = InodeMeta::new(mode);
+        let meta_inner = meta.get_inner_mut();
+        let curr_time = time::curr_time_spec();
+        meta_inner.access_time = curr_time;
+        meta_inner.change_time = curr_time;
+        meta_inner.modify_time = curr_time;
+        Self { meta }
+    }
+}
+
+impl BytesInodeBackend for TmpSpecial {
+    fn meta(&self) -> &InodeMeta {
+        &self.meta
+    }
+
+    fn read_inode_at<'a>(&'a self, _buf: ReadBuffer<'a>, _offset: u64) -> AKResult<'a, usize> {
+        Box::pin(async move { Err(errno::EINVAL) })
+    }
+
+    fn write_inode_at<'a>(&'a self, _buf: WriteBuffer<'a>, _offset: u64) -> AKResult<'a, usize> {
+        Box::pin(async move { Err(errno::EINVAL) })
+    }
+}
+
+/// tmpfs 的常规文件，以页缓存本身作为存储
 pub struct TmpFile {
     meta: InodeMeta,
 }
+
+impl TmpFile {
+    pub fn new() -> Self {
+        let mut meta = InodeMeta::new(InodeMode::Regular);
+        let meta_inner = meta.get_inner_mut();
+        let curr_time = time::curr_time_spec();
+        meta_inner.access_time = curr_time;
+        meta_inner.change_time = curr_time;
+        meta_inner.modify_time = curr_time;
+        Self { meta }
+    }
+}
+
+impl Default for TmpFile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BytesInodeBackend for TmpFile {
+    fn meta(&self) -> &InodeMeta {
+        &self.meta
+    }
+
+    /// 只有页缓存中还没有的页会走到这里，也就是从未写过的部分，全部是 0
+    fn read_inode_at<'a>(&'a self, buf: ReadBuffer<'a>, _offset: u64) -> AKResult<'a, usize> {
+        Box::pin(async move {
+            let buf_len = buf.len();
+            match buf {
+                ReadBuffer::Kernel(buf) => buf.fill(0),
+                ReadBuffer::User(buf) => unsafe { buf.check_slice_mut()? }.as_bytes_mut().fill(0),
+            }
+            Ok(buf_len)
+        })
+    }
+
+    fn write_inode_at<'a>(&'a self, buf: WriteBuffer<'a>, _offset: u64) -> AKResult<'a, usize> {
+        Box::pin(async move { Ok(buf.len()) })
+    }
+
+    fn truncate(&self, len: u64) -> KResult<()> {
+        // 缩小后再扩大时，原来最后一页中超出 `len` 的部分应当读出 0
+        if let Some(page) = self.meta.page_cache().get(len >> PAGE_SIZE_BITS) {
+            let page_offset = (len & PAGE_OFFSET_MASK as u64) as usize;
+            page.inner_page().frame_mut().as_page_bytes_mut()[page_offset..].fill(0);
+        }
+        let curr_time = time::curr_time_spec();
+        self.meta.lock_inner_with(|inner| {
+            inner.data_len = len;
+            inner.change_time = curr_time;
+            inner.modify_time = curr_time;
+        });
+        Ok(())
+    }
+}
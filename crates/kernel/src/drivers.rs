@@ -1,103 +1,241 @@
-use core::ptr::NonNull;
-
-use common::config::{self, PA_TO_VA};
-use console_output::eprintln;
-use fdt::{node::FdtNode, Fdt};
-use klocks::{Lazy, Once};
-use libkernel::memory::{self, MapPermission, VirtAddr, KERNEL_SPACE};
-use qemu_plic::Plic;
-use virtio_drivers::{
-    device::blk::VirtIOBlk,
-    transport::{
-        mmio::{MmioError, MmioTransport},
-        DeviceType, DeviceTypeError, Transport,
-    },
-};
-use virtio_glue::{DiskDriver, HalImpl};
-
-pub enum InterruptSource {
-    VirtIO = 1,
-    Uart0 = 10,
-}
-
-impl InterruptSource {
-    pub fn from_id(id: usize) -> Option<Self> {
-        match id {
-            1 => Some(Self::VirtIO),
-            10 => Some(Self::Uart0),
-            _ => None,
-        }
-    }
-}
-
-pub fn init(fdt: &Fdt<'_>) {
-    let plic = unsafe { &(*Plic::mmio()) };
-    for context in 0..(config::MAX_HART_NUM * 2) {
-        plic.set_threshold(context, 0);
-        plic.enable(InterruptSource::Uart0 as usize, context);
-        // plic.enable(InterruptSource::VirtIO as usize, context);
-    }
-    plic.set_priority(InterruptSource::Uart0 as usize, 1);
-    // plic.set_priority(InterruptSource::VirtIO as usize, 1);
-
-    for node in fdt.all_nodes() {
-        try_probe_virtio(node);
-    }
-    let Some(block_device) = BLOCK_DEVICE.get() else {
-        panic!("No boot block device");
-    };
-    hal::block_device::init_instance(block_device);
-
-    Lazy::force(&qemu_uart::UART0);
-}
-
-static BLOCK_DEVICE: Once<DiskDriver<HalImpl, MmioTransport<'static>>> = Once::new();
-
-fn try_probe_virtio(node: FdtNode<'_, '_>) {
-    if !node.compatible().is_some_and(|c| c.all().any(|s| s == "virtio,mmio")) {
-        return;
-    }
-
-    let Some(reg) = node.reg().and_then(|mut regs| regs.next()) else {
-        return;
-    };
-
-    let paddr = reg.starting_address as usize;
-    let size = reg.size.unwrap();
-    let vaddr = VirtAddr(paddr + PA_TO_VA);
-    // SAFETY: 初始化设备时只有主核在运行，且内核空间映射已经完成
-    let kernel_space = KERNEL_SPACE.as_mut_ptr();
-    unsafe {
-        (*kernel_space).kernel_map(
-            vaddr,
-            vaddr + size,
-            MapPermission::R | MapPermission::W | MapPermission::G,
-        );
-        memory::flush_tlb_range(vaddr, size);
-    }
-    let header = NonNull::new(vaddr.as_mut_ptr()).unwrap();
-    match unsafe { MmioTransport::new(header, size) } {
-        Ok(transport) => {
-            let device_type = transport.device_type();
-            eprintln!(
-                "Detected virtio MMIO device with vendor id {:#X}, device type {:?}, version {:?}",
-                transport.vendor_id(),
-                device_type,
-                transport.version(),
-            );
-            match device_type {
-                DeviceType::Block => probe_virtio_blk(transport),
-                _ => {}
-            }
-        }
-        // 无效设备类型，忽略
-        Err(MmioError::InvalidDeviceID(DeviceTypeError::InvalidDeviceType(0))) => {}
-        Err(e) => eprintln!("Error creating VirtIO MMIO transport: {}", e),
-    }
-}
-
-fn probe_virtio_blk(transport: MmioTransport<'static>) {
-    let blk = VirtIOBlk::<HalImpl, MmioTransport<'static>>::new(transport).expect("failed to create blk driver");
-    let block_device = DiskDriver::new(blk);
-    BLOCK_DEVICE.call_once(|| block_device);
-}
+use alloc::{boxed::Box, format, string::String};
+use core::{
+    ptr::NonNull,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use common::config::{self, PA_TO_VA};
+use console_output::eprintln;
+use executor::time;
+use fdt::{node::FdtNode, Fdt};
+use hal::{
+    block_device,
+    net_device::{self, NetDevice},
+};
+use klocks::{Lazy, Once};
+use libkernel::{
+    hart,
+    memory::{self, MapPermission, VirtAddr, KERNEL_SPACE},
+};
+use partition::Partition;
+use qemu_plic::Plic;
+use virtio_drivers::{
+    device::{blk::VirtIOBlk, net::VirtIONet},
+    transport::{
+        mmio::{MmioError, MmioTransport},
+        DeviceType, DeviceTypeError, Transport,
+    },
+};
+use virtio_glue::{DiskDriver, HalImpl, NetDriver, NET_BUF_LEN, NET_QUEUE_SIZE};
+
+pub enum InterruptSource {
+    Uart0,
+    VirtIONet,
+}
+
+/// 次设备号的位数，同 linux 的 `MINORBITS`
+const MINOR_BITS: u32 = 20;
+
+/// QEMU virt 平台上串口的中断号
+const UART0_IRQ: usize = 10;
+
+/// virtio 网卡的中断号，由设备树给出
+static VIRTIO_NET_IRQ: Once<usize> = Once::new();
+
+impl InterruptSource {
+    pub fn from_id(id: usize) -> Option<Self> {
+        if id == UART0_IRQ {
+            Some(Self::Uart0)
+        } else if VIRTIO_NET_IRQ.get() == Some(&id) {
+            Some(Self::VirtIONet)
+        } else {
+            None
+        }
+    }
+}
+
+pub fn init(fdt: &Fdt<'_>) {
+    let plic = unsafe { &(*Plic::mmio()) };
+    for context in 0..(config::MAX_HART_NUM * 2) {
+        plic.set_threshold(context, 0);
+    }
+    enable_irq(UART0_IRQ, "uart0");
+
+    for node in fdt.all_nodes() {
+        try_probe_virtio(node);
+        try_probe_rtc(node);
+    }
+    // 根文件系统所在的设备由启动参数 `root=` 指定，默认是第一个探测到的磁盘
+    let root = root_from_bootargs(fdt).unwrap_or("vda");
+    let Some(root_device) = block_device::get(root) else {
+        panic!("Root block device {root} not found");
+    };
+    block_device::init_instance(root_device);
+    ROOT_DEVICE_NAME.call_once(|| String::from(root));
+
+    Lazy::force(&qemu_uart::UART0);
+}
+
+/// 在 PLIC 中为所有 hart 开启中断 `irq`
+fn enable_irq(irq: usize, name: &'static str) {
+    let plic = unsafe { &(*Plic::mmio()) };
+    for context in 0..(config::MAX_HART_NUM * 2) {
+        plic.enable(irq, context);
+    }
+    plic.set_priority(irq, 1);
+    hart::register_irq(irq, name);
+}
+
+/// 已探测到的 virtio 块设备数量，用于按 `vda`、`vdb`…… 命名
+static BLOCK_DEVICE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// 根文件系统所在的块设备名，如 `vda1`
+static ROOT_DEVICE_NAME: Once<String> = Once::new();
+
+pub fn root_device_name() -> &'static str {
+    ROOT_DEVICE_NAME.get().expect("drivers should be initialized")
+}
+
+/// 从设备树 `/chosen` 节点的 `bootargs` 中解析 `root=/dev/vda1` 或 `root=vda1` 形式的启动参数
+fn root_from_bootargs<'a>(fdt: &Fdt<'a>) -> Option<&'a str> {
+    let bootargs = fdt.find_node("/chosen")?.property("bootargs")?.as_str()?;
+    let root = bootargs.split_whitespace().find_map(|arg| arg.strip_prefix("root="))?;
+    Some(root.strip_prefix("/dev/").unwrap_or(root))
+}
+
+/// 将设备树节点的第一个寄存器区域映射到内核空间，返回其虚拟地址和大小
+fn map_mmio(node: FdtNode<'_, '_>) -> Option<(VirtAddr, usize)> {
+    let reg = node.reg()?.next()?;
+    let paddr = reg.starting_address as usize;
+    let size = reg.size?;
+    let vaddr = VirtAddr(paddr + PA_TO_VA);
+    // SAFETY: 初始化设备时只有主核在运行，且内核空间映射已经完成
+    let kernel_space = KERNEL_SPACE.as_mut_ptr();
+    unsafe {
+        (*kernel_space).kernel_map(
+            vaddr,
+            vaddr + size,
+            MapPermission::R | MapPermission::W | MapPermission::G,
+        );
+        memory::flush_tlb_range(vaddr, size);
+    }
+    Some((vaddr, size))
+}
+
+fn try_probe_rtc(node: FdtNode<'_, '_>) {
+    if !node
+        .compatible()
+        .is_some_and(|c| c.all().any(|s| s == goldfish_rtc::COMPATIBLE))
+    {
+        return;
+    }
+    let Some((vaddr, _)) = map_mmio(node) else {
+        return;
+    };
+    unsafe {
+        goldfish_rtc::init(vaddr.0);
+    }
+    let rtc = goldfish_rtc::instance().expect("just initialized");
+    // 以 RTC 时间作为挂钟时间的基准
+    time::set_real_time(Duration::from_nanos(rtc.read_time_ns()));
+    eprintln!("Detected goldfish rtc at {:#x}", vaddr.0 - PA_TO_VA);
+}
+
+fn try_probe_virtio(node: FdtNode<'_, '_>) {
+    if !node.compatible().is_some_and(|c| c.all().any(|s| s == "virtio,mmio")) {
+        return;
+    }
+
+    let Some((vaddr, size)) = map_mmio(node) else {
+        return;
+    };
+    let header = NonNull::new(vaddr.as_mut_ptr()).unwrap();
+    match unsafe { MmioTransport::new(header, size) } {
+        Ok(transport) => {
+            let device_type = transport.device_type();
+            eprintln!(
+                "Detected virtio MMIO device with vendor id {:#X}, device type {:?}, version {:?}",
+                transport.vendor_id(),
+                device_type,
+                transport.version(),
+            );
+            match device_type {
+                DeviceType::Block => probe_virtio_blk(transport),
+                DeviceType::Network => probe_virtio_net(transport, node),
+                _ => {}
+            }
+        }
+        // 无效设备类型，忽略
+        Err(MmioError::InvalidDeviceID(DeviceTypeError::InvalidDeviceType(0))) => {}
+        Err(e) => eprintln!("Error creating VirtIO MMIO transport: {}", e),
+    }
+}
+
+fn probe_virtio_blk(transport: MmioTransport<'static>) {
+    let index = BLOCK_DEVICE_COUNT.fetch_add(1, Ordering::Relaxed);
+    if index >= ((1 << MINOR_BITS) / block_device::MINORS_PER_DISK) as usize {
+        eprintln!("Too many virtio block devices, device {index} ignored");
+        return;
+    }
+    let blk = VirtIOBlk::<HalImpl, MmioTransport<'static>>::new(transport).expect("failed to create blk driver");
+    let disk: &'static DiskDriver<_, _> = Box::leak(Box::new(DiskDriver::new(blk)));
+    let name = virtio_blk_name(index);
+    eprintln!("Register virtio block device as /dev/{name}");
+    let disk_minor = index as u32 * block_device::MINORS_PER_DISK;
+    for info in partition::scan(disk) {
+        if info.number >= block_device::MINORS_PER_DISK as usize {
+            eprintln!("Too many partitions on /dev/{name}, partition {} ignored", info.number);
+            continue;
+        }
+        let part_name = format!("{name}{}", info.number);
+        eprintln!(
+            "Register partition /dev/{part_name}, start sector {}, {} sectors",
+            info.start_block, info.num_blocks
+        );
+        let part = Box::leak(Box::new(Partition::new(disk, &info)));
+        block_device::register(
+            part_name,
+            block_device::VIRTIO_BLK_MAJOR,
+            disk_minor + info.number as u32,
+            part,
+        );
+    }
+    block_device::register(name, block_device::VIRTIO_BLK_MAJOR, disk_minor, disk);
+}
+
+/// 第 `index` 个 virtio 块设备的名字，同 linux 依次为 `vda` 到 `vdz`、`vdaa` 到 `vdzz`、`vdaaa` 等
+fn virtio_blk_name(mut index: usize) -> String {
+    let mut suffix = String::new();
+    loop {
+        suffix.insert(0, (b'a' + (index % 26) as u8) as char);
+        index /= 26;
+        if index == 0 {
+            break;
+        }
+        index -= 1;
+    }
+    format!("vd{suffix}")
+}
+
+fn probe_virtio_net(transport: MmioTransport<'static>, node: FdtNode<'_, '_>) {
+    if net_device::instance().is_some() {
+        eprintln!("Only one virtio net device is supported, ignored");
+        return;
+    }
+    let Some(irq) = node.interrupts().and_then(|mut irqs| irqs.next()) else {
+        eprintln!("No interrupt for virtio net device, ignored");
+        return;
+    };
+    let net = VirtIONet::<HalImpl, MmioTransport<'static>, NET_QUEUE_SIZE>::new(transport, NET_BUF_LEN)
+        .expect("failed to create net driver");
+    let nic: &'static NetDriver<_, _> = Box::leak(Box::new(NetDriver::new(net)));
+    let mac = nic.mac_address();
+    eprintln!(
+        "Register virtio net device, mac {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}, irq {irq}",
+        mac[0], mac[1], mac[2], mac[3], mac[4], mac[5]
+    );
+    net_device::init_instance(nic);
+    VIRTIO_NET_IRQ.call_once(|| irq);
+    enable_irq(irq, "virtio-net");
+}
@@ -17,7 +17,7 @@ use kernel_tracer::Instrument;
 use libkernel::{
     extern_symbols,
     fs::{dentry::DEntry, VirtFileSystem},
-    hart, memory, process,
+    hart, memory, net, process,
     thread::{Thread, ThreadStatus},
     trap::{self, TrapContext},
 };
@@ -28,7 +28,10 @@ use riscv::{
 };
 use triomphe::Arc;
 
-use crate::{drivers::InterruptSource, syscall};
+use crate::{
+    drivers::{self, InterruptSource},
+    syscall,
+};
 
 pub fn spawn_user_thread(thread: Arc<Thread>) {
     let (runnable, task) = executor::spawn_with(
@@ -44,7 +47,11 @@ pub async fn user_thread_loop() {
         // 返回用户态
         // 注意切换了控制流，但是之后回到内核态还是在这里
         trace!("enter user mode");
+        let enter_user_time = riscv_time::get_time();
         trap_return(hart::local_hart().curr_trap_context());
+        hart::local_hart()
+            .stat
+            .add_user_time(riscv_time::get_time() - enter_user_time);
         trace!("enter kernel mode");
 
         // 在内核态处理 trap。注意这里也可能切换控制流，让出 Hart 给其他线程
@@ -216,8 +223,7 @@ pub async fn user_trap_handler() -> ControlFlow<(), ()> {
         Trap::Interrupt(e) if e == Interrupt::SupervisorTimer as usize => {
             {
                 let _enter = debug_span!("timer_irq").entered();
-                time::check_timer();
-                riscv_time::set_next_trigger();
+                timer_handler();
             }
             executor::yield_now().await;
             ControlFlow::Continue(())
@@ -250,8 +256,7 @@ pub extern "C" fn kernel_trap_handler() {
         Trap::Interrupt(i) if i == Interrupt::SupervisorTimer as usize => {
             let _enter = debug_span!("timer_irq").entered();
             // TODO: 想办法通知线程让出 hart
-            time::check_timer();
-            riscv_time::set_next_trigger();
+            timer_handler();
         }
         Trap::Interrupt(i) if i == Interrupt::SupervisorExternal as usize => {
             let _enter = debug_span!("external_irq").entered();
@@ -288,6 +293,13 @@ pub fn trap_return(trap_context: NonNull<TrapContext>) {
     }
 }
 
+fn timer_handler() {
+    hart::local_hart().stat.count_timer_irq();
+    time::check_timer();
+    executor::load_avg::update_load_avg();
+    riscv_time::set_next_trigger();
+}
+
 pub fn interrupt_handler() {
     let plic = unsafe { &*Plic::mmio() };
     let hart_id = hart::local_hart().hart_id();
@@ -297,12 +309,13 @@ pub fn interrupt_handler() {
     if interrupt_id == 0 {
         return;
     }
+    hart::local_hart().stat.count_external_irq(interrupt_id);
     let Some(interrupt_source) = InterruptSource::from_id(interrupt_id) else {
         panic!("Unknown interrupt {interrupt_id}");
     };
     match interrupt_source {
         InterruptSource::Uart0 => qemu_uart::UART0.handle_irq(),
-        InterruptSource::VirtIO => todo!("[mid] virtio interrupt handler"),
+        InterruptSource::VirtIONet => net::handle_irq(),
     }
     plic.complete(context_id, interrupt_id);
 }
@@ -311,8 +324,9 @@ pub fn init_vfs() {
     debug!("Init vfs");
     let root_fs = fat32_vfs::new_fat32_fs(
         hal::block_device::instance(),
+        None,
         EcoString::from("/"),
-        EcoString::from("/dev/mmcblk0"),
+        ecow::eco_format!("/dev/{}", drivers::root_device_name()),
         StatFsFlags::empty(),
     )
     .expect("root_fs init failed");
@@ -13,6 +13,7 @@ mod lang_items;
 mod syscall;
 mod tracer;
 
+use alloc::vec::Vec;
 use core::{
     arch,
     sync::atomic::{AtomicBool, AtomicUsize, Ordering},
@@ -20,6 +21,7 @@ use core::{
 
 use common::config::{HART_START_ADDR, MAX_HART_NUM};
 use console_output::println;
+use ecow::EcoString;
 use fdt::Fdt;
 use libkernel::{extern_symbols, hart, memory, process};
 use riscv::register::sstatus::{self, FS};
@@ -49,6 +51,7 @@ pub extern "C" fn __hart_entry(hart_id: usize, fdt_ptr: usize) -> ! {
         let fdt = unsafe { Fdt::from_ptr(fdt_ptr).unwrap() };
         // drivers 依赖于 mmio 映射（其实也许可以放在 boot page table 里？）
         drivers::init(&fdt);
+        let hart_ids = probe_harts(&fdt);
         // log 实现依赖于 uart 和 virtio_block
         crate::tracer::init();
         enable_float();
@@ -66,7 +69,7 @@ pub extern "C" fn __hart_entry(hart_id: usize, fdt_ptr: usize) -> ! {
         INIT_FINISHED.store(true, Ordering::SeqCst);
 
         // 将下面的代码取消注释即可启动多核
-        for i in 0..MAX_HART_NUM {
+        for i in hart_ids {
             if i == hart_id {
                 continue;
             }
@@ -94,7 +97,11 @@ pub extern "C" fn __hart_entry(hart_id: usize, fdt_ptr: usize) -> ! {
 pub fn kernel_loop() -> ! {
     info!("Enter kernel loop");
     executor::run_until_shutdown(|| {
+        let suspend_time = riscv_time::get_time();
         sbi_rt::hart_suspend(sbi_rt::Retentive, 0, 0);
+        hart::local_hart()
+            .stat
+            .add_idle_time(riscv_time::get_time() - suspend_time);
     });
 
     info!("Exit kernel loop");
@@ -112,6 +119,29 @@ pub fn kernel_loop() -> ! {
     unreachable!()
 }
 
+/// 从设备树中读取各个 hart 的信息，返回其 hart id
+fn probe_harts(fdt: &Fdt<'_>) -> Vec<usize> {
+    let cpus = fdt
+        .cpus()
+        .map(|cpu| {
+            let property = |name| {
+                cpu.property(name)
+                    .and_then(|property| property.as_str())
+                    .unwrap_or_default()
+            };
+            hart::CpuInfo {
+                hart_id: cpu.ids().first(),
+                isa: EcoString::from(property("riscv,isa")),
+                mmu: EcoString::from(property("mmu-type").trim_start_matches("riscv,")),
+            }
+        })
+        .filter(|cpu| cpu.hart_id < MAX_HART_NUM)
+        .collect::<Vec<_>>();
+    let hart_ids = cpus.iter().map(|cpu| cpu.hart_id).collect();
+    hart::init_cpu_info(cpus);
+    hart_ids
+}
+
 fn clear_bss() {
     use extern_symbols::{ebss, sbss};
     let len = ebss as *const () as usize - sbss as *const () as usize;
@@ -4,8 +4,9 @@ use core::{ops::Deref, str::FromStr};
 use defines::{
     error::{errno, KResult},
     fs::{
-        FaccessatMode, FsStat, FstatFlags, IoVec, MountFlags, OpenFlags, PollEvents, PollFd, Renameat2Flags, Stat,
-        UnmountFlags, AT_FDCWD, NAME_MAX, SEEK_CUR, SEEK_END, SEEK_SET,
+        major, minor, EventFdFlags, FaccessatFlags, FaccessatMode, Flock, FlockOp, FsStat, FstatFlags,
+        InotifyInitFlags, InotifyMask, IoVec, MountFlags, OpenFlags, Renameat2Flags, Stat, StatMode, UnmountFlags,
+        AT_FDCWD, NAME_MAX, SEEK_CUR, SEEK_END, SEEK_SET,
     },
     misc::{TimeSpec, UTIME_NOW, UTIME_OMIT},
 };
@@ -16,12 +17,15 @@ use libkernel::{
     fs::{
         self,
         dentry::DEntry,
+        eventfd::EventFd,
         file::{DirFile, File, FileDescriptor, SeekFrom, SeekableFile},
-        inode::InodeMode,
+        inode::{InodeMeta, InodeMode},
+        inotify::{self, Inotify},
         pipe, LastComponentType, VirtFileSystem,
     },
     hart::local_hart,
     memory::{ReadBuffer, UserCheck, WriteBuffer},
+    process,
 };
 use smallvec::SmallVec;
 use triomphe::Arc;
@@ -46,15 +50,53 @@ pub fn sys_ioctl(fd: usize, request: usize, argp: usize) -> KResult {
     desc.ioctl(request, argp)
 }
 
+/// 创建文件系统节点，成功返回 0
+///
+/// 参数：
+/// - `mode` 包含文件类型和权限位。目前只支持常规文件、命名管道和 socket，不支持设备文件
+/// - `dev` 设备文件的设备号，其他类型的文件忽略
+pub fn sys_mknodat(dir_fd: usize, path: UserCheck<u8>, mode: u32, _dev: usize) -> KResult {
+    let path = path.check_cstr()?;
+    // 文件类型位，即 linux 的 `S_IFMT`
+    let file_type = StatMode::from_bits_truncate(mode & 0o170000);
+    let inode_mode = if file_type.is_empty() || file_type == StatMode::REGULAR {
+        InodeMode::Regular
+    } else if file_type == StatMode::FIFO {
+        InodeMode::Fifo
+    } else if file_type == StatMode::SOCKET {
+        InodeMode::Socket
+    } else if file_type == StatMode::CHAR_DEVICE || file_type == StatMode::BLOCK_DEVICE {
+        // TODO: [low] 还不支持创建设备文件
+        return Err(errno::EPERM);
+    } else {
+        return Err(errno::EINVAL);
+    };
+    debug!("mknod {} with mode {mode:#o}", &*path);
+    let p2i = fs::resolve_path_with_dir_fd(dir_fd, &path)?;
+    if p2i.dir.lookup(p2i.last_component).is_some() {
+        return Err(errno::EEXIST);
+    }
+    p2i.dir
+        .mknod(p2i.last_component, inode_mode, create_perm(mode & 0o7777))?;
+    Ok(0)
+}
+
 /// 创建目录。`mode` 含义同 [`sys_openat()`]
-pub fn sys_mkdirat(dir_fd: usize, path: UserCheck<u8>, _mode: usize) -> KResult {
-    // TODO: [low] 暂时未支持 mode
+pub fn sys_mkdirat(dir_fd: usize, path: UserCheck<u8>, mode: usize) -> KResult {
     let path = path.check_cstr()?;
+    // 目录的 set-group-ID 位由父目录决定，不能通过 mode 指定
+    let perm = create_perm(mode as u32 & 0o1777);
     let p2i = fs::resolve_path_with_dir_fd(dir_fd, &path)?;
-    p2i.dir.mkdir(p2i.last_component)?;
+    p2i.dir.mkdir(p2i.last_component, perm)?;
     Ok(0)
 }
 
+/// 新建文件时实际使用的权限位，即 `mode` 去掉当前进程 umask 中的位
+fn create_perm(mode: u32) -> StatMode {
+    let umask = local_hart().curr_process().lock_inner_with(|inner| inner.umask);
+    StatMode::from_bits_truncate(mode & !umask)
+}
+
 /// 根据 `whence` 和 `offset` 重新设置 `fd` 指向的文件的偏移量
 ///
 /// 成功后返回最终的偏移位置（从文件头开始算）
@@ -194,9 +236,9 @@ pub async fn sys_writev(fd: usize, mut io_vec: UserCheck<IoVec>, vec_len: usize)
 /// - `flags` 包括文件打开模式、创建标志、状态标志。
 ///     - 创建标志如 `CLOEXEC`, `CREAT` 等，仅在打开文件时发生作用
 ///     - 状态标志影响后续的 I/O 方式，而且可以动态修改
-/// - `mode` 是用于指定创建新文件时，该文件的 mode。目前应该不会用到
+/// - `mode` 是用于指定创建新文件时，该文件的权限位，会去掉 umask 中的位
 ///     - 它只会影响未来访问该文件的模式，但这一次打开该文件可以是随意的
-pub fn sys_openat(dir_fd: usize, path: UserCheck<u8>, flags: u32, mut _mode: u32) -> KResult {
+pub async fn sys_openat(dir_fd: usize, path: UserCheck<u8>, flags: u32, mode: u32) -> KResult {
     let path = path.check_cstr()?;
 
     let Some(flags) = OpenFlags::from_bits(flags) else {
@@ -209,48 +251,66 @@ pub fn sys_openat(dir_fd: usize, path: UserCheck<u8>, flags: u32, mut _mode: u32
     }
 
     // TODO: [low] OpenFlags::DIRECT 目前是被忽略的
-    // TODO: [low] 暂时未支持 mode
-    // 不是创建文件（以及临时文件）时，mode 被忽略
-    if !flags.contains(OpenFlags::CREATE) {
-        _mode = 0;
-    }
 
     // 64 位版本应当是保证可以打开大文件的
     // TODO: [low] 暂时在测试中忽略 `OpenFlags::LARGEFILE` 的检查
     // assert!(flags.contains(OpenFlags::LARGEFILE));
 
-    // 暂时先不支持这些
-    if flags.intersects(OpenFlags::ASYNC | OpenFlags::DSYNC) {
+    // 暂时先不支持这些。同 linux，`open()` 时指定的 `O_ASYNC` 不起作用，只有 `F_SETFL` 能开启信号驱动 I/O
+    if flags.contains(OpenFlags::DSYNC) {
         todo!("[low] unsupported openflags: {flags:#b}");
     }
 
-    let p2i = fs::resolve_path_with_dir_fd(dir_fd, &path)?;
+    let mut p2i = fs::resolve_path_with_dir_fd(dir_fd, &path)?;
+    // 指定了 `O_CREAT | O_EXCL` 时，即使最后一个 component 是符号链接也不跟随
+    if !flags.contains(OpenFlags::NOFOLLOW) && !flags.contains(OpenFlags::CREATE | OpenFlags::EXCL) {
+        p2i = p2i.follow_last()?;
+    }
+    // 打开命名管道时可能阻塞，不能持有用户内存的访问权限
+    drop(path);
     let new_file = if let Some(final_dentry) = p2i.dir.lookup(&p2i.last_component) {
         // 指定了必须要创建文件，但该文件已存在
         if flags.contains(OpenFlags::CREATE | OpenFlags::EXCL) {
             return Err(errno::EEXIST);
         }
 
+        let (readable, writable) = flags.read_write();
+        let mut access = FaccessatMode::empty();
+        if readable {
+            access |= FaccessatMode::R_OK;
+        }
+        if writable || flags.contains(OpenFlags::TRUNCATE) {
+            access |= FaccessatMode::W_OK;
+        }
         match final_dentry {
             DEntry::Dir(dir) => {
                 // 路径名指向一个目录，但是需要写入
                 if flags.intersects(OpenFlags::WRONLY | OpenFlags::RDWR) {
                     return Err(errno::EISDIR);
                 };
+                fs::check_access(dir.inode().meta(), access)?;
                 File::Dir(Arc::new(DirFile::new(dir)))
             }
             DEntry::Bytes(bytes) => {
+                let mode = bytes.inode().meta().mode();
+                // 只有指定了 `O_NOFOLLOW` 时最后一个 component 才可能是符号链接
+                if mode == InodeMode::SymbolLink {
+                    return Err(errno::ELOOP);
+                }
                 if flags.contains(OpenFlags::DIRECTORY) {
                     return Err(errno::ENOTDIR);
                 }
-                let mode = bytes.inode().meta().mode();
+                fs::check_access(bytes.inode().meta(), access)?;
                 if flags.contains(OpenFlags::TRUNCATE) && flags.read_write().1 && mode == InodeMode::Regular {
                     bytes.inode().resize(0)?;
+                    inotify::notify_dentry(&DEntry::Bytes(Arc::clone(&bytes)), InotifyMask::IN_MODIFY);
                 }
-                if mode == InodeMode::Regular || mode == InodeMode::BlockDevice {
-                    File::Seekable(Arc::new(SeekableFile::new(bytes)))
-                } else {
-                    File::Stream(bytes)
+                match mode {
+                    InodeMode::Regular | InodeMode::BlockDevice => File::Seekable(Arc::new(SeekableFile::new(bytes))),
+                    InodeMode::Fifo => File::Pipe(Arc::new(pipe::open_fifo(bytes, flags).await?)),
+                    // 同 linux，socket 文件只能通过 `connect()` 等使用，不能直接打开
+                    InodeMode::Socket => return Err(errno::ENXIO),
+                    _ => File::Stream(bytes),
                 }
             }
         }
@@ -261,7 +321,10 @@ pub fn sys_openat(dir_fd: usize, path: UserCheck<u8>, flags: u32, mut _mode: u32
         }
 
         debug!("create {} under {}", p2i.last_component, p2i.dir.name());
-        let dentry = p2i.dir.mknod(p2i.last_component, InodeMode::Regular)?;
+        // 新建的文件总是可以按 `flags` 打开，即使 `mode` 不允许
+        let dentry = p2i
+            .dir
+            .mknod(p2i.last_component, InodeMode::Regular, create_perm(mode & 0o7777))?;
         File::Seekable(Arc::new(SeekableFile::new(dentry)))
     };
 
@@ -272,15 +335,58 @@ pub fn sys_openat(dir_fd: usize, path: UserCheck<u8>, flags: u32, mut _mode: u32
     Ok(ret_fd)
 }
 
-pub fn sys_close(fd: usize) -> KResult {
+pub async fn sys_close(fd: usize) -> KResult {
     let process = local_hart().curr_process();
-    if process.lock_inner_with(|inner| inner.fd_table.remove(fd)).is_none() {
+    let Some(file) = process.lock_inner_with(|inner| inner.fd_table.remove(fd)) else {
         return Err(errno::EBADF);
+    };
+
+    // NOTE: 关闭时写回不是 POSIX 要求的，但目前还没有后台写回，这样至少能让正常关闭的文件落盘
+    if let Err(e) = file.sync().await {
+        warn!("sync {} failed on close: {e:?}", file.debug_name());
     }
+    // 同 POSIX，关闭文件的任意一个 fd 都会释放进程在该文件上的记录锁
+    file.release_record_locks(process.pid());
 
-    // TODO: [low] 还要释放相关的记录锁
-    // TODO: [mid] 如果文件被 `unlink()` 了且当前 fd 是最后一个引用该文件的，则要删除该文件
+    Ok(0)
+}
+
+/// 对 `fd` 对应的整个文件加上或者释放建议性锁。
+///
+/// 锁属于打开的文件，通过 `dup()` 或者 `fork()` 共享同一个打开的文件的 fd 也共享锁；
+/// 引用该打开的文件的所有 fd 都关闭后锁被释放
+///
+/// 参数：
+/// - `operation` [`FlockOp`]，`LOCK_SH`、`LOCK_EX` 和 `LOCK_UN` 之一，前两者可以与 `LOCK_NB` 组合
+pub async fn sys_flock(fd: usize, operation: u32) -> KResult {
+    let Some(op) = FlockOp::from_bits(operation) else {
+        return Err(errno::EINVAL);
+    };
+    let exclusive = match op.difference(FlockOp::LOCK_NB) {
+        FlockOp::LOCK_SH => Some(false),
+        FlockOp::LOCK_EX => Some(true),
+        FlockOp::LOCK_UN => None,
+        _ => return Err(errno::EINVAL),
+    };
+    let desc = local_hart()
+        .curr_process()
+        .lock_inner_with(|inner| inner.fd_table.get(fd).cloned())
+        .ok_or(errno::EBADF)?;
+    debug!("flock {op:?} on fd {fd}({})", desc.debug_name());
+    desc.flock(exclusive, op.contains(FlockOp::LOCK_NB)).await?;
+    Ok(0)
+}
 
+/// 将文件在页缓存中的修改写回磁盘
+pub async fn sys_fsync(fd: usize) -> KResult {
+    let file = local_hart()
+        .curr_process()
+        .lock_inner()
+        .fd_table
+        .get(fd)
+        .ok_or(errno::EBADF)?
+        .clone();
+    file.sync().await?;
     Ok(0)
 }
 
@@ -295,8 +401,8 @@ pub fn sys_pipe2(pipe_fd: UserCheck<[i32; 2]>, flags: u32) -> KResult {
         todo!("[low] unsupported OpenFlags: {flags:#b}");
     };
     let (read_end, write_end) = pipe::make_pipe();
-    let read_end = FileDescriptor::new(File::Pipe(read_end), flags.with_read_only());
-    let write_end = FileDescriptor::new(File::Pipe(write_end), flags.with_write_only());
+    let read_end = FileDescriptor::new(File::Pipe(Arc::new(read_end)), flags.with_read_only());
+    let write_end = FileDescriptor::new(File::Pipe(Arc::new(write_end)), flags.with_write_only());
     let fds = local_hart()
         .curr_process()
         .lock_inner_with(|inner| inner.fd_table.add_many([read_end, write_end]))
@@ -307,6 +413,82 @@ pub fn sys_pipe2(pipe_fd: UserCheck<[i32; 2]>, flags: u32) -> KResult {
     Ok(0)
 }
 
+/// 创建一个用于事件通知的 eventfd，返回其文件描述符
+///
+/// 参数：
+/// - `init_value` 是计数器的初始值
+/// - `flags` 见 [`EventFdFlags`]，有未知的位时返回 `EINVAL`
+pub fn sys_eventfd2(init_value: u32, flags: u32) -> KResult {
+    let flags = EventFdFlags::from_bits(flags).ok_or(errno::EINVAL)?;
+    let eventfd = EventFd::new(u64::from(init_value), flags.contains(EventFdFlags::SEMAPHORE));
+    let open_flags = OpenFlags::from_bits_truncate(flags.difference(EventFdFlags::SEMAPHORE).bits()) | OpenFlags::RDWR;
+    let fd = local_hart()
+        .curr_process()
+        .lock_inner_with(|inner| {
+            inner
+                .fd_table
+                .add(FileDescriptor::new(File::EventFd(Arc::new(eventfd)), open_flags))
+        })
+        .ok_or(errno::EMFILE)?;
+    Ok(fd as isize)
+}
+
+/// 创建一个 inotify 实例，返回其文件描述符
+///
+/// 参数：
+/// - `flags` 可以是 `IN_NONBLOCK` 和 `IN_CLOEXEC`，有未知的位时返回 `EINVAL`
+pub fn sys_inotify_init1(flags: u32) -> KResult {
+    let flags = InotifyInitFlags::from_bits(flags).ok_or(errno::EINVAL)?;
+    let open_flags = OpenFlags::from_bits_truncate(flags.bits()) | OpenFlags::RDONLY;
+    let fd = local_hart()
+        .curr_process()
+        .lock_inner_with(|inner| {
+            inner
+                .fd_table
+                .add(FileDescriptor::new(File::Inotify(Arc::new(Inotify::new())), open_flags))
+        })
+        .ok_or(errno::EMFILE)?;
+    Ok(fd as isize)
+}
+
+/// 监视 `path` 指向的文件上的事件，返回监视描述符。已经在监视时返回原来的监视描述符
+///
+/// 参数：
+/// - `fd` 是 inotify 实例，不是 inotify 时返回 `EINVAL`
+/// - `path` 是要监视的文件，要求有读权限。指定了 `IN_DONT_FOLLOW` 时不跟随最后的符号链接
+/// - `mask` 见 [`InotifyMask`]，至少要包含一种事件
+pub fn sys_inotify_add_watch(fd: usize, path: UserCheck<u8>, mask: u32) -> KResult {
+    let mask = InotifyMask::from_bits_truncate(mask);
+    let Some(File::Inotify(inotify)) = local_hart()
+        .curr_process()
+        .lock_inner_with(|inner| inner.fd_table.get(fd).map(Deref::deref).cloned())
+    else {
+        return Err(errno::EINVAL);
+    };
+    let path = path.check_cstr()?;
+    debug!("inotify add watch {} with mask {mask:?}", &*path);
+    let mut p2i = fs::resolve_path_with_dir_fd(AT_FDCWD, &path)?;
+    if !mask.contains(InotifyMask::IN_DONT_FOLLOW) {
+        p2i = p2i.follow_last()?;
+    }
+    let dentry = p2i.dir.lookup(p2i.last_component).ok_or(errno::ENOENT)?;
+    fs::check_access(dentry.meta(), FaccessatMode::R_OK)?;
+    let wd = inotify.add_watch(dentry, mask)?;
+    Ok(wd as isize)
+}
+
+/// 移除 inotify 实例 `fd` 中的监视描述符 `wd`，`wd` 无效时返回 `EINVAL`
+pub fn sys_inotify_rm_watch(fd: usize, wd: i32) -> KResult {
+    let Some(File::Inotify(inotify)) = local_hart()
+        .curr_process()
+        .lock_inner_with(|inner| inner.fd_table.get(fd).map(Deref::deref).cloned())
+    else {
+        return Err(errno::EINVAL);
+    };
+    inotify.rm_watch(wd)?;
+    Ok(0)
+}
+
 /// 获取目录项信息
 pub fn sys_getdents64(fd: usize, buf: UserCheck<[u8]>) -> KResult {
     let process = local_hart().curr_process();
@@ -325,7 +507,7 @@ pub fn sys_getdents64(fd: usize, buf: UserCheck<[u8]>) -> KResult {
 /// - `fd` 是指定的文件描述符
 /// - `cmd` 指定需要进行的操作
 /// - `arg` 是该操作可选的参数
-pub fn sys_fcntl64(fd: usize, cmd: usize, arg: usize) -> KResult {
+pub async fn sys_fcntl64(fd: usize, cmd: usize, arg: usize) -> KResult {
     // 未说明返回值的命令成功时都返回 0
     /// 复制该 fd 到大于等于 `arg` 的第一个可用 fd。成功后返回新的 fd
     const F_DUPFD: usize = 0;
@@ -339,14 +521,43 @@ pub fn sys_fcntl64(fd: usize, cmd: usize, arg: usize) -> KResult {
     // 下面两个是文件状态标志操作
     /// 返回文件访问模式和文件状态标志，`arg` 将被忽略
     const F_GETFL: usize = 3;
-    // /// 将文件状态标志设置为 `arg` 指定的值。
-    // ///
-    // /// 在 Linux 上，此命令只能更改 `O_APPEND`、`O_ASYNC`、`O_DIRECT`、`O_NOATIME`` 和 `O_NONBLOCK` 标志。
-    // /// 无法更改 `O_DSYNC` 和 `O_SYNC` 标志。
-    // const F_SETFL: i32 = 4;
+    /// 将文件状态标志设置为 `arg` 指定的值。
+    ///
+    /// 目前只能更改 `O_APPEND`、`O_ASYNC` 和 `O_NONBLOCK` 标志，其余的位被忽略。
+    /// 状态标志属于打开的文件描述，因此对共享它的所有 fd 生效
+    const F_SETFL: usize = 4;
+    /// 将管道的容量设置为至少 `arg` 字节，返回实际设置的容量
+    const F_SETPIPE_SZ: usize = 1031;
+    /// 返回管道的容量，`arg` 将被忽略
+    const F_GETPIPE_SZ: usize = 1032;
+    // 下面三个是 POSIX 记录锁操作，`arg` 指向一个 [`Flock`]
+    /// 检查 `arg` 描述的锁能否加上。能则将其 `l_type` 置为 `F_UNLCK`，否则写回一个冲突的锁
+    const F_GETLK: usize = 5;
+    /// 加锁或者解锁，与其他进程的锁冲突时返回 `EAGAIN`
+    const F_SETLK: usize = 6;
+    /// 同 `F_SETLK`，但冲突时阻塞
+    const F_SETLKW: usize = 7;
 
     debug!("fd: {fd}, cmd: {cmd:#x}, arg: {arg:#x}");
 
+    if let F_GETLK | F_SETLK | F_SETLKW = cmd {
+        let process = local_hart().curr_process();
+        let pid = process.pid();
+        let desc = process
+            .lock_inner_with(|inner| inner.fd_table.get(fd).cloned())
+            .ok_or(errno::EBADF)?;
+        let flock_ptr = UserCheck::new(arg as *mut Flock).ok_or(errno::EFAULT)?;
+        let mut flock = flock_ptr.check_ptr()?.read();
+        debug!("record lock {flock:?} on fd {fd}({})", desc.debug_name());
+        if cmd == F_GETLK {
+            desc.get_record_lock(&mut flock, pid).await?;
+            unsafe { flock_ptr.check_ptr_mut()?.write(flock) }
+        } else {
+            desc.set_record_lock(&flock, pid, cmd == F_SETLKW).await?;
+        }
+        return Ok(0);
+    }
+
     let process = local_hart().curr_process();
     let mut inner = process.lock_inner();
 
@@ -367,7 +578,7 @@ pub fn sys_fcntl64(fd: usize, cmd: usize, arg: usize) -> KResult {
         F_GETFD => {
             let desc = inner.fd_table.get(fd).ok_or(errno::EBADF)?;
             debug!("get the CLOEXEC flag of fd {fd}({})", desc.debug_name());
-            if desc.flags().contains(OpenFlags::CLOEXEC) {
+            if desc.close_on_exec() {
                 Ok(1)
             } else {
                 Ok(0)
@@ -384,9 +595,32 @@ pub fn sys_fcntl64(fd: usize, cmd: usize, arg: usize) -> KResult {
             Ok(0)
         }
         F_GETFL => {
-            let desc = inner.fd_table.get_mut(fd).ok_or(errno::EBADF)?;
+            let desc = inner.fd_table.get(fd).ok_or(errno::EBADF)?;
             debug!("get the file status flag of fd {fd}({})", desc.debug_name());
-            Ok(desc.flags().bits() as usize)
+            Ok(desc.status_flags().bits() as usize)
+        }
+        F_SETFL => {
+            let desc = inner.fd_table.get(fd).ok_or(errno::EBADF)?;
+            let flags = OpenFlags::from_bits_truncate(arg as u32);
+            debug!(
+                "set the file status flag of fd {fd}({}) to {flags:?}",
+                desc.debug_name()
+            );
+            // TODO: [low] 设置 `O_ASYNC` 后还应当在文件就绪时向 `F_SETOWN` 指定的进程发送 `SIGIO`
+            desc.set_status_flags(flags);
+            Ok(0)
+        }
+        F_SETPIPE_SZ | F_GETPIPE_SZ => {
+            let desc = inner.fd_table.get(fd).ok_or(errno::EBADF)?;
+            let File::Pipe(pipe) = &**desc else {
+                return Err(errno::EBADF);
+            };
+            if cmd == F_SETPIPE_SZ {
+                debug!("set the capacity of pipe fd {fd} to {arg}");
+                pipe.set_capacity(arg)
+            } else {
+                Ok(pipe.capacity())
+            }
         }
         _ => {
             error!("unsupported cmd: {cmd}, with arg: {arg}");
@@ -431,7 +665,9 @@ pub fn sys_dup3(old_fd: usize, new_fd: usize, flags: u32) -> KResult {
     let mut new_desc = desc.clone();
     // dup 得到的文件描述符 CLOEXEC 默认应该是 false，除非 flags 专门指定
     new_desc.set_close_on_exec(flags.contains(OpenFlags::CLOEXEC));
-    inner.fd_table.insert(new_fd, new_desc);
+    if let Some(old_desc) = inner.fd_table.insert(new_fd, new_desc) {
+        old_desc.release_record_locks(process.pid());
+    }
     Ok(new_fd)
 }
 
@@ -460,7 +696,10 @@ pub fn sys_newfstatat(dir_fd: usize, path: UserCheck<u8>, stat_buf: UserCheck<St
         let file = inner.fd_table.get(dir_fd).ok_or(errno::EBADF)?;
         fs::stat_from_meta(file.meta())
     } else {
-        let p2i = fs::resolve_path_with_dir_fd(dir_fd, &path)?;
+        let mut p2i = fs::resolve_path_with_dir_fd(dir_fd, &path)?;
+        if !flags.contains(FstatFlags::AT_SYMLINK_NOFOLLOW) {
+            p2i = p2i.follow_last()?;
+        }
         let dentry = p2i.dir.lookup(p2i.last_component).ok_or(errno::ENOENT)?;
         fs::stat_from_meta(dentry.meta())
     };
@@ -527,6 +766,43 @@ pub fn sys_unlinkat(dir_fd: usize, path: UserCheck<u8>, flags: u32) -> KResult {
     Ok(0)
 }
 
+/// 创建一个名为 `link_path`、内容为 `target` 的符号链接。`target` 不需要存在
+///
+/// 参数：
+/// - `target` 符号链接指向的路径
+/// - `new_dir_fd` 解析 `link_path` 的起始目录，参考 [`sys_openat()`]
+/// - `link_path` 要创建的符号链接的路径
+pub fn sys_symlinkat(target: UserCheck<u8>, new_dir_fd: usize, link_path: UserCheck<u8>) -> KResult {
+    let target = target.check_cstr()?;
+    let link_path = link_path.check_cstr()?;
+    debug!("symlink {} -> {}", &*link_path, &*target);
+    if target.is_empty() {
+        return Err(errno::ENOENT);
+    }
+    let p2i = fs::resolve_path_with_dir_fd(new_dir_fd, &link_path)?;
+    if p2i.last_type != LastComponentType::Normal {
+        return Err(errno::EEXIST);
+    }
+    p2i.dir.symlink(p2i.last_component, &target)?;
+    Ok(0)
+}
+
+/// 读取符号链接 `path` 的内容，写入 `buf` 中。内容不以 `\0` 结尾，超出 `buf` 的部分会被截断
+///
+/// 返回写入 `buf` 的字节数
+pub fn sys_readlinkat(dir_fd: usize, path: UserCheck<u8>, buf: UserCheck<[u8]>) -> KResult {
+    let path = path.check_cstr()?;
+    let p2i = fs::resolve_path_with_dir_fd(dir_fd, &path)?;
+    let DEntry::Bytes(link) = p2i.dir.lookup(p2i.last_component).ok_or(errno::ENOENT)? else {
+        return Err(errno::EINVAL);
+    };
+    let target = link.inode().read_link()?;
+    let len = usize::min(target.len(), buf.len());
+    let mut buf = unsafe { buf.check_slice_mut()? };
+    buf.as_bytes_mut()[..len].copy_from_slice(&target.as_bytes()[..len]);
+    Ok(len)
+}
+
 /// 创建文件的（硬）链接，成功返回 0
 ///
 /// 参数：
@@ -535,7 +811,6 @@ pub fn sys_unlinkat(dir_fd: usize, path: UserCheck<u8>, flags: u32) -> KResult {
 /// - `new_dir_fd` 新文件名所在的目录
 /// - `new_path` 文件的新名字
 /// - `flags` 可包含 `AT_SYMLINK_FOLLOW` 和 `AT_EMPTY_PATH`
-#[allow(unused)]
 pub fn sys_linkat(
     old_dir_fd: usize,
     old_path: UserCheck<u8>,
@@ -543,64 +818,159 @@ pub fn sys_linkat(
     new_path: UserCheck<u8>,
     flags: u32,
 ) -> KResult {
-    // 在 2.6.18 内核之前，应置为 0
-    if flags != 0 {
+    let flags = FstatFlags::from_bits(flags).ok_or(errno::EINVAL)?;
+    if !(FstatFlags::AT_SYMLINK_FOLLOW | FstatFlags::AT_EMPTY_PATH).contains(flags) {
         return Err(errno::EINVAL);
     }
-    todo!("[low] impl sys_linkat")
-}
+    let old_path = old_path.check_cstr()?;
+    let new_path = new_path.check_cstr()?;
+    debug!("link {} -> {}, flags {flags:?}", &*new_path, &*old_path);
 
-// TODO: [low] 完善 mount 和 umount
+    let old_dentry = if old_path.is_empty() && flags.contains(FstatFlags::AT_EMPTY_PATH) {
+        let process = local_hart().curr_process();
+        let inner = process.lock_inner();
+        inner
+            .fd_table
+            .get(old_dir_fd)
+            .ok_or(errno::EBADF)?
+            .dentry()
+            .ok_or(errno::ENOENT)?
+    } else {
+        let mut p2i = fs::resolve_path_with_dir_fd(old_dir_fd, &old_path)?;
+        // 与 `open()` 等不同，默认不跟随符号链接，而是链接到符号链接本身
+        if flags.contains(FstatFlags::AT_SYMLINK_FOLLOW) {
+            p2i = p2i.follow_last()?;
+        }
+        p2i.dir.lookup(p2i.last_component).ok_or(errno::ENOENT)?
+    };
+    // 不允许对目录创建硬链接
+    let DEntry::Bytes(old_dentry) = old_dentry else {
+        return Err(errno::EPERM);
+    };
 
-/// 卸载安装在 `target` 上的文件系统
+    let new_p2i = fs::resolve_path_with_dir_fd(new_dir_fd, &new_path)?;
+    if new_p2i.last_type != LastComponentType::Normal {
+        return Err(errno::EEXIST);
+    }
+    if !VirtFileSystem::instance().same_mounted_fs(
+        DEntry::Bytes(Arc::clone(&old_dentry)),
+        DEntry::Dir(Arc::clone(&new_p2i.dir)),
+    ) {
+        return Err(errno::EXDEV);
+    }
+    new_p2i.dir.link(new_p2i.last_component, &old_dentry)?;
+    Ok(0)
+}
+
+/// 卸载安装在 `target` 上的文件系统，需要特权
 pub fn sys_umount(target: UserCheck<u8>, flags: u32) -> KResult {
-    let Some(flags) = UnmountFlags::from_bits(flags) else {
-        todo!("[low] unsupported MountFlags: {flags:#b}");
-    };
+    let flags = UnmountFlags::from_bits(flags).ok_or(errno::EINVAL)?;
+    // 卸载时本就不跟随 `target` 末尾的符号链接，其余标志尚未支持
+    if !UnmountFlags::UMOUNT_NOFOLLOW.contains(flags) {
+        return Err(errno::EINVAL);
+    }
+    if !process::curr_cred().is_privileged() {
+        return Err(errno::EPERM);
+    }
     let target = target.check_cstr()?;
     VirtFileSystem::instance().unmount(&target, flags)?;
     Ok(0)
 }
 
 /// 将 `source` 指定的文件系统（通常是设备的路径名，但也可以是目录或文件的路径名，或者虚拟字符串）附加到路径名指定的位置（目录或文件）在目标中。
+///
+/// 指定 `MS_BIND` 时，`source` 是一个目录，它下面的目录树会在 `target` 处可见，此时 `fs_type` 被忽略。需要特权
 pub fn sys_mount(
     source: UserCheck<u8>,
     target: UserCheck<u8>,
-    fs_type: UserCheck<u8>,
+    fs_type: Option<UserCheck<u8>>,
     flags: u32,
     data: Option<UserCheck<u8>>,
 ) -> KResult {
     let source = source.check_cstr()?;
     let target = target.check_cstr()?;
-    let fs_type = FileSystemType::from_str(&fs_type.check_cstr()?)?;
-    let Some(flags) = MountFlags::from_bits(flags) else {
-        todo!("[low] unsupported MountFlags: {flags:#b}");
-    };
+    let flags = MountFlags::from_bits(flags).ok_or(errno::EINVAL)?;
+    // 只影响访问时间的更新策略和日志的标志直接忽略。只读、重新挂载、挂载传播等标志尚未支持
+    let ignored = MountFlags::MS_SILENT
+        | MountFlags::MS_NOATIME
+        | MountFlags::MS_NODIRATIME
+        | MountFlags::MS_RELATIME
+        | MountFlags::MS_STRICTATIME
+        | MountFlags::MS_LAZYTIME;
+    if !(MountFlags::MS_BIND | MountFlags::MS_REC | ignored).contains(flags) {
+        return Err(errno::EINVAL);
+    }
+    if !process::curr_cred().is_privileged() {
+        return Err(errno::EPERM);
+    }
     if let Some(data) = data {
         let _data = data.check_cstr()?;
     }
 
+    let vfs = VirtFileSystem::instance();
+    if flags.contains(MountFlags::MS_BIND) {
+        debug!("bind mount {} under {}, flags: {flags:?}", &*source, &*target);
+        // TODO: [low] 目前只支持绑定目录，且总是相当于指定了 `MS_REC`
+        let DEntry::Dir(source) = fs::find_file(&source)? else {
+            return Err(errno::ENOTDIR);
+        };
+        vfs.bind_mount(&target, source, flags)?;
+        return Ok(0);
+    }
+
+    let fs_type = FileSystemType::from_str(&fs_type.ok_or(errno::EINVAL)?.check_cstr()?)?;
     debug!(
         "mount {} under {}, fs_type: {fs_type:?}, flags: {flags:?}",
         &*source, &*target
     );
-    VirtFileSystem::instance().mount(
-        &target,
-        &source,
-        match fs_type {
-            // TODO: [high] 挂载 vfat 时是放了一个 tmpfs 进去
-            FileSystemType::VFat | FileSystemType::TmpFs => tmpfs::new_tmp_fs,
-            FileSystemType::DevTmpFs => devfs::new_dev_fs,
-            FileSystemType::ProcFs => procfs::new_proc_fs,
-        },
-        flags,
-    )?;
+    match fs_type {
+        FileSystemType::VFat => {
+            let block_device = find_block_device(&source)?;
+            vfs.mount(
+                &target,
+                &source,
+                |parent, name, device_path, flags| {
+                    fat32_vfs::new_fat32_fs(block_device, parent, name, device_path, flags)
+                },
+                flags,
+            )?;
+        }
+        FileSystemType::Ext2 => {
+            let block_device = find_block_device(&source)?;
+            vfs.mount(
+                &target,
+                &source,
+                |parent, name, device_path, flags| {
+                    ext2_vfs::new_ext2_fs(block_device, parent, name, device_path, flags)
+                },
+                flags,
+            )?;
+        }
+        FileSystemType::TmpFs => vfs.mount(&target, &source, tmpfs::new_tmp_fs, flags)?,
+        FileSystemType::DevTmpFs => vfs.mount(&target, &source, devfs::new_dev_fs, flags)?,
+        FileSystemType::ProcFs => vfs.mount(&target, &source, procfs::new_proc_fs, flags)?,
+    }
     Ok(0)
 }
 
+/// 将 mount 的 `source` 解析为已注册的块设备。
+///
+/// 路径不存在时返回 `ENOENT`，存在但不是块设备时返回 `ENOTBLK`，设备号没有对应的设备时返回 `ENXIO`
+fn find_block_device(source: &str) -> KResult<&'static dyn block_device::BlockDevice> {
+    let DEntry::Bytes(bytes) = fs::find_file(source)? else {
+        return Err(errno::ENOTBLK);
+    };
+    let meta = bytes.inode().meta();
+    if meta.mode() != InodeMode::BlockDevice {
+        return Err(errno::ENOTBLK);
+    }
+    block_device::get_by_number(major(meta.rdev()), minor(meta.rdev())).ok_or(errno::ENXIO)
+}
+
 #[derive(Debug)]
 pub enum FileSystemType {
     VFat,
+    Ext2,
     TmpFs,
     DevTmpFs,
     ProcFs,
@@ -612,6 +982,7 @@ impl FromStr for FileSystemType {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             fat32_vfs::FS_TYPE => Ok(FileSystemType::VFat),
+            ext2_vfs::FS_TYPE => Ok(FileSystemType::Ext2),
             tmpfs::FS_TYPE => Ok(FileSystemType::TmpFs),
             devfs::FS_TYPE => Ok(FileSystemType::DevTmpFs),
             procfs::FS_TYPE => Ok(FileSystemType::ProcFs),
@@ -626,7 +997,7 @@ impl FromStr for FileSystemType {
 /// 将调用进程的当前工作目录更改为 `path` 中指定的目录
 pub fn sys_chdir(path: UserCheck<u8>) -> KResult {
     let path = path.check_cstr()?;
-    let p2i = fs::resolve_path_with_dir_fd(AT_FDCWD, &path)?;
+    let p2i = fs::resolve_path_with_dir_fd(AT_FDCWD, &path)?.follow_last()?;
     let DEntry::Dir(dir) = p2i.dir.lookup(p2i.last_component).ok_or(errno::ENOENT)? else {
         return Err(errno::ENOTDIR);
     };
@@ -678,79 +1049,6 @@ pub fn sys_getcwd(buf: UserCheck<[u8]>) -> KResult {
     Ok(ret)
 }
 
-/// 等待一组文件描述符上的事件。返回就绪的文件描述符数量，如果超时则返回 0
-///
-/// 如果任何文件描述符都没有发生请求的事件且没有错误，则将阻塞直到：
-///
-/// - 文件描述符准备就绪
-///     - 这意味着请求的操作不会阻塞
-///     - 因此，对于常规文件、块设备和其他没有合理轮询语义的文件总是立即返回为可供读写的状态
-/// - 调用被信号中断
-/// - 超时 (`timeout`) 到期
-///
-/// 参数：
-/// - `fds` 描述感兴趣的所有文件描述符及事件，同时也是返回事件的输出参数
-///     - 如果长度超过 `RLIMIT_NOFILE` 指定的 rlimit，则返回 EINVAL
-/// - `timeout` 如果为 `NULL` 则意味着无限的超时。为负返回 `EINVAL`
-/// - `signal_mask` 指定在阻塞期间忽略的信号
-/// - `sig_set_size` 是信号集的大小。似乎没有用
-#[allow(unused)]
-pub fn sys_ppoll(
-    fds: UserCheck<[PollFd]>,
-    timeout: Option<UserCheck<TimeSpec>>,
-    signal_mask: Option<UserCheck<u64>>,
-    sig_set_size: usize,
-) -> KResult {
-    // TODO: [mid] ppoll 要考虑 `timeout`
-    let _timeout = if let Some(timeout) = timeout {
-        let timeout = timeout.check_ptr()?.read();
-        if timeout < TimeSpec::default() {
-            return Err(errno::EINVAL);
-        }
-        Some(timeout)
-    } else {
-        None
-    };
-
-    let process = local_hart().curr_process();
-    let mut inner = process.lock_inner();
-    if fds.len() >= inner.fd_table.limit() {
-        return Err(errno::EINVAL);
-    }
-    let mut fds = unsafe { fds.check_slice_mut()? };
-    let mut ret = 0;
-    for poll_fd in fds.iter_mut() {
-        let mut poll_fd_val = poll_fd.read();
-        if poll_fd_val.fd < 0 {
-            poll_fd_val.revents = 0;
-            poll_fd.write(poll_fd_val);
-            continue;
-        }
-        if let Some(fd) = inner.fd_table.get(poll_fd_val.fd as usize) {
-            let Some(events) = PollEvents::from_bits(poll_fd_val.events) else {
-                todo!("[low] unsupported poll events: {:#b}", poll_fd_val.events);
-            };
-            #[expect(clippy::match_same_arms, reason = "现在只是暂未实现，以后会不同的")]
-            match &**fd {
-                // TODO: 轮询机制尚未实现，一律返回 ok
-                File::Stream(_) | File::Pipe(_) => {
-                    poll_fd_val.revents = (events & (PollEvents::POLLIN | PollEvents::POLLOUT)).bits();
-                    ret += 1;
-                }
-                // 目录、常规文件和块设备没有合理的轮询语义，因此直接返回
-                File::Dir(_) | File::Seekable(_) => {
-                    poll_fd_val.revents = (events & (PollEvents::POLLIN | PollEvents::POLLOUT)).bits();
-                    ret += 1;
-                }
-            }
-        } else {
-            poll_fd_val.revents = PollEvents::POLLNVAL.bits();
-        }
-        poll_fd.write(poll_fd_val);
-    }
-    Ok(ret)
-}
-
 /// 将数据从 `in_fd` 指向的文件复制到 `out_fd` 指向的文件，传输成功则返回写入的字节数（同 `read`、`write`，可能少于请求的字节数）
 ///
 /// 参数：
@@ -791,19 +1089,14 @@ pub async fn sys_sendfile64(out_fd: usize, in_fd: usize, offset_ptr: Option<User
 pub fn sys_statfs64(path: UserCheck<u8>, buf: UserCheck<FsStat>) -> KResult {
     let path = path.check_cstr()?;
     debug!("path {}", &*path);
-    let p2i = fs::path_walk(Arc::clone(VirtFileSystem::instance().root_dir()), &path)?;
-    let mut dentry = p2i.dir.lookup(p2i.last_component).ok_or(errno::ENOENT)?;
+    let p2i = fs::path_walk(VirtFileSystem::instance().root_dir(), &path)?.follow_last()?;
+    let dentry = p2i.dir.lookup(p2i.last_component).ok_or(errno::ENOENT)?;
 
-    let mount_table = VirtFileSystem::instance().lock_mount_table();
-    let fs = loop {
-        if let Some(fs) = mount_table.get(&dentry) {
-            break fs;
-        }
-        dentry = match dentry {
-            DEntry::Dir(dir) => DEntry::Dir(Arc::clone(dir.parent().expect("should not reach root fs"))),
-            DEntry::Bytes(bytes) => DEntry::Dir(Arc::clone(bytes.parent())),
-        }
-    };
+    let vfs = VirtFileSystem::instance();
+    let mount_root = vfs.mounted_root_of(dentry);
+    let mount_table = vfs.lock_mount_table();
+    // 文件系统已被卸载
+    let fs = mount_table.get(&mount_root).ok_or(errno::ENOENT)?;
 
     // TODO: [low] statfs 没有完整正确实现
     let buf = unsafe { buf.check_ptr_mut()? };
@@ -816,15 +1109,186 @@ pub fn sys_statfs64(path: UserCheck<u8>, buf: UserCheck<FsStat>) -> KResult {
     Ok(0)
 }
 
-/// 检查用户的权限
-pub fn sys_faccessat(dir_fd: usize, path: UserCheck<u8>, mode: u32) -> KResult {
-    // TODO: [low] 未正确实现 `faccessat`。
-    let _mode = FaccessatMode::from_bits(mode).ok_or(errno::EINVAL)?;
-    let p2i = fs::resolve_path_with_dir_fd(dir_fd, &path.check_cstr()?)?;
-    p2i.dir.lookup(p2i.last_component).ok_or(errno::ENOENT)?;
+/// 检查调用进程能否以 `mode` 方式访问 `path`，`mode` 为 `F_OK` 时只检查文件是否存在。
+///
+/// 与其他系统调用不同，默认以进程的真实用户 ID 和真实组 ID 进行检查。
+///
+/// `flags` 可包含 `AT_EACCESS`、`AT_SYMLINK_NOFOLLOW` 和 `AT_EMPTY_PATH`，只有 `faccessat2` 才会传入
+pub fn sys_faccessat(dir_fd: usize, path: UserCheck<u8>, mode: u32, flags: u32) -> KResult {
+    let mode = FaccessatMode::from_bits(mode).ok_or(errno::EINVAL)?;
+    let flags = FaccessatFlags::from_bits(flags).ok_or(errno::EINVAL)?;
+    let path = path.check_cstr()?;
+    let file = if path.is_empty() && flags.contains(FaccessatFlags::AT_EMPTY_PATH) {
+        let process = local_hart().curr_process();
+        let inner = process.lock_inner();
+        inner
+            .fd_table
+            .get(dir_fd)
+            .ok_or(errno::EBADF)?
+            .dentry()
+            .ok_or(errno::ENOENT)?
+    } else {
+        let mut p2i = fs::resolve_path_with_dir_fd(dir_fd, &path)?;
+        if !flags.contains(FaccessatFlags::AT_SYMLINK_NOFOLLOW) {
+            p2i = p2i.follow_last()?;
+        }
+        p2i.dir.lookup(p2i.last_component).ok_or(errno::ENOENT)?
+    };
+    let cred = process::curr_cred();
+    // TODO: [low] 路径解析过程中的搜索权限仍是按有效 ID 检查的
+    let (uid, gid) = if flags.contains(FaccessatFlags::AT_EACCESS) {
+        (cred.uid.effective, cred.gid.effective)
+    } else {
+        (cred.uid.real, cred.gid.real)
+    };
+    file.meta().check_access(uid, gid, mode)?;
+    Ok(0)
+}
+
+/// 修改 `fd` 指向的文件的权限位，参考 [`sys_fchmodat()`]
+pub fn sys_fchmod(fd: usize, mode: u32) -> KResult {
+    let file = local_hart()
+        .curr_process()
+        .lock_inner()
+        .fd_table
+        .get(fd)
+        .ok_or(errno::EBADF)?
+        .clone();
+    chmod(file.meta(), mode)?;
+    if let Some(dentry) = file.dentry() {
+        dentry.write_meta()?;
+    }
+    Ok(0)
+}
+
+/// 修改 `path` 指向的文件的权限位，会跟随符号链接。
+///
+/// 只有文件的所有者和特权用户可以修改
+pub fn sys_fchmodat(dir_fd: usize, path: UserCheck<u8>, mode: u32) -> KResult {
+    let path = path.check_cstr()?;
+    let p2i = fs::resolve_path_with_dir_fd(dir_fd, &path)?.follow_last()?;
+    let dentry = p2i.dir.lookup(p2i.last_component).ok_or(errno::ENOENT)?;
+    chmod(dentry.meta(), mode)?;
+    dentry.write_meta()?;
     Ok(0)
 }
 
+/// 修改 `fd` 指向的文件的所有者和组，参考 [`sys_fchownat()`]
+pub fn sys_fchown(fd: usize, owner: u32, group: u32) -> KResult {
+    let file = local_hart()
+        .curr_process()
+        .lock_inner()
+        .fd_table
+        .get(fd)
+        .ok_or(errno::EBADF)?
+        .clone();
+    chown(file.meta(), owner, group)?;
+    if let Some(dentry) = file.dentry() {
+        dentry.write_meta()?;
+    }
+    Ok(0)
+}
+
+/// 修改 `path` 指向的文件的所有者和组。成功返回 0
+///
+/// 参数：
+/// - `owner`、`group` 为 -1 时表示不修改
+/// - `flags` 可包含 `AT_SYMLINK_NOFOLLOW` 和 `AT_EMPTY_PATH`
+pub fn sys_fchownat(dir_fd: usize, path: UserCheck<u8>, owner: u32, group: u32, flags: u32) -> KResult {
+    let flags = FstatFlags::from_bits(flags).ok_or(errno::EINVAL)?;
+    if !(FstatFlags::AT_SYMLINK_NOFOLLOW | FstatFlags::AT_EMPTY_PATH).contains(flags) {
+        return Err(errno::EINVAL);
+    }
+    let path = path.check_cstr()?;
+    let dentry = if path.is_empty() && flags.contains(FstatFlags::AT_EMPTY_PATH) {
+        let process = local_hart().curr_process();
+        let inner = process.lock_inner();
+        inner
+            .fd_table
+            .get(dir_fd)
+            .ok_or(errno::EBADF)?
+            .dentry()
+            .ok_or(errno::ENOENT)?
+    } else {
+        let mut p2i = fs::resolve_path_with_dir_fd(dir_fd, &path)?;
+        if !flags.contains(FstatFlags::AT_SYMLINK_NOFOLLOW) {
+            p2i = p2i.follow_last()?;
+        }
+        p2i.dir.lookup(p2i.last_component).ok_or(errno::ENOENT)?
+    };
+    chown(dentry.meta(), owner, group)?;
+    dentry.write_meta()?;
+    Ok(0)
+}
+
+/// 修改 `meta` 的权限位。非特权用户只能修改自己的文件，且不属于文件所在的组时 set-group-ID 位会被清除
+fn chmod(meta: &InodeMeta, mode: u32) -> KResult<()> {
+    let cred = process::curr_cred();
+    let mut perm = StatMode::from_bits_truncate(mode & 0o7777);
+    let now = time::curr_time_spec();
+    meta.lock_inner_with(|inner| {
+        if !cred.is_privileged() {
+            if cred.uid.effective != inner.uid {
+                return Err(errno::EPERM);
+            }
+            if cred.gid.effective != inner.gid {
+                perm.remove(StatMode::S_ISGID);
+            }
+        }
+        inner.perm = perm;
+        inner.change_time = now;
+        Ok(())
+    })
+}
+
+/// 修改 `meta` 的所有者和组，`owner`、`group` 为 -1 时表示不修改。
+///
+/// 非特权用户只能修改自己的文件，且不能修改所有者，只能将组修改为自己所在的组。
+/// 非目录文件被修改后，其 set-user-ID 位（以及可执行时的 set-group-ID 位）会被清除
+fn chown(meta: &InodeMeta, owner: u32, group: u32) -> KResult<()> {
+    let owner = (owner != u32::MAX).then_some(owner);
+    let group = (group != u32::MAX).then_some(group);
+    if owner.is_none() && group.is_none() {
+        return Ok(());
+    }
+    let cred = process::curr_cred();
+    let now = time::curr_time_spec();
+    let is_dir = meta.mode() == InodeMode::Dir;
+    meta.lock_inner_with(|inner| {
+        if !cred.is_privileged() {
+            let is_owner = cred.uid.effective == inner.uid;
+            if !is_owner || owner.is_some_and(|owner| owner != inner.uid) {
+                return Err(errno::EPERM);
+            }
+            if group.is_some_and(|group| group != inner.gid && group != cred.gid.effective) {
+                return Err(errno::EPERM);
+            }
+        }
+        if let Some(owner) = owner {
+            inner.uid = owner;
+        }
+        if let Some(group) = group {
+            inner.gid = group;
+        }
+        if !is_dir {
+            inner.perm.remove(StatMode::S_ISUID);
+            if inner.perm.contains(StatMode::S_IXGRP) {
+                inner.perm.remove(StatMode::S_ISGID);
+            }
+        }
+        inner.change_time = now;
+        Ok(())
+    })
+}
+
+/// 设置当前进程的 umask，返回原来的 umask，永不失败
+pub fn sys_umask(mask: u32) -> KResult {
+    let old = local_hart()
+        .curr_process()
+        .lock_inner_with(|inner| core::mem::replace(&mut inner.umask, mask & 0o777));
+    Ok(old as usize)
+}
+
 pub fn sys_utimensat(
     dir_fd: usize,
     path: UserCheck<u8>,
@@ -836,7 +1300,10 @@ pub fn sys_utimensat(
     if path.is_empty() && !flags.contains(FstatFlags::AT_EMPTY_PATH) {
         return Err(errno::ENOENT);
     }
-    let p2i = fs::resolve_path_with_dir_fd(dir_fd, &path)?;
+    let mut p2i = fs::resolve_path_with_dir_fd(dir_fd, &path)?;
+    if !flags.contains(FstatFlags::AT_SYMLINK_NOFOLLOW) {
+        p2i = p2i.follow_last()?;
+    }
     let file = p2i.dir.lookup(p2i.last_component).ok_or(errno::ENOENT)?;
     let new_atime;
     let new_mtime;
@@ -48,10 +48,14 @@ pub fn sys_mmap(addr: usize, len: usize, prot: u32, flags: u32, fd: usize, offse
     }
     let file_page_id = (offset >> PAGE_SIZE_BITS) as u64;
     debug!("prot: {prot:?}, flags: {flags:?}");
+    // 映射 `/dev/zero` 等设备等价于匿名映射，此时忽略 `offset`
+    let anonymous_device = !flags.contains(MmapFlags::MAP_ANONYMOUS) && is_anonymous_device(fd);
     let vpn = if flags.contains(MmapFlags::MAP_SHARED) {
-        if flags.contains(MmapFlags::MAP_ANONYMOUS) {
-            // 共享匿名映射，似乎是存在的。调用后 fork 出来的子进程可以共享该区域
-            todo!("[low] impl shared anonymous mapping")
+        if flags.contains(MmapFlags::MAP_ANONYMOUS) || anonymous_device {
+            // 共享匿名映射，调用后 fork 出来的子进程可以共享该区域。
+            // TODO: [low] 可以以 tmpfs 文件作为后备实现，但有文件后备的区域目前还不支持缺页
+            warn!("shared anonymous mapping is unsupported");
+            return Err(errno::ENODEV);
         } else {
             // 有文件作为后备的共享映射
             shared_file_map(addr, len, prot, flags, fd, file_page_id)?
@@ -63,7 +67,9 @@ pub fn sys_mmap(addr: usize, len: usize, prot: u32, flags: u32, fd: usize, offse
             return Err(errno::EINVAL);
         }
 
-        if flags.contains(MmapFlags::MAP_ANONYMOUS) {
+        if anonymous_device {
+            private_anonymous_map(addr, len, prot, flags)?
+        } else if flags.contains(MmapFlags::MAP_ANONYMOUS) {
             // 私有匿名映射
             if fd != usize::MAX || offset != 0 {
                 warn!("fd must be -1 and offset must be 0 for anonyous mapping");
@@ -77,6 +83,15 @@ pub fn sys_mmap(addr: usize, len: usize, prot: u32, flags: u32, fd: usize, offse
     Ok(vpn.page_start().0)
 }
 
+fn is_anonymous_device(fd: usize) -> bool {
+    local_hart().curr_process().lock_inner_with(|inner| {
+        inner
+            .fd_table
+            .get(fd)
+            .is_some_and(|desc| matches!(&**desc, File::Stream(stream) if stream.inode().anonymous_mmap()))
+    })
+}
+
 /// 私有匿名映射，没有底层文件。内容全部初始化为 0
 ///
 /// 如果 addr 没有对齐到页边界或者
@@ -105,7 +120,7 @@ fn shared_file_map(
     );
 
     {
-        let fd_flags = desc.flags();
+        let fd_flags = desc.status_flags();
         let (readable, writable) = fd_flags.read_write();
         if desc.meta().mode() != InodeMode::Regular
             || !readable
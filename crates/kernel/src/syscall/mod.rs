@@ -1,6 +1,8 @@
 mod fs;
 mod memory;
 mod misc;
+mod net;
+mod poll;
 mod process;
 mod signal;
 mod thread;
@@ -14,6 +16,8 @@ use fs::*;
 use libkernel::{hart::local_hart, memory::UserCheck, process::exit_process};
 use memory::*;
 use misc::*;
+use net::*;
+use poll::*;
 use process::*;
 use signal::*;
 use thread::*;
@@ -27,7 +31,7 @@ pub async fn syscall(id: usize, args: [usize; 6]) -> isize {
         || (id == WRITE || id == WRITEV) && (args[0] == 1 || args[0] == 2)
         || (id == PPOLL && args[1] == 1)
         || [
-            GETPGID, GETPID, GETPPID, GETUID, GETUID, SETPGID, GETEUID, UNAME, EXIT, EXIT_GROUP, GETTID,
+            GETPGID, GETPID, GETPPID, GETUID, GETGID, SETPGID, GETEUID, GETEGID, UNAME, EXIT, EXIT_GROUP, GETTID,
         ]
         .contains(&id);
     // 一些比较成熟的 syscall 也可以适当降低日志等级
@@ -72,20 +76,38 @@ async fn syscall_impl(id: usize, args: [usize; 6]) -> KResult {
         GETCWD => sys_getcwd(UserCheck::new_slice(args[0] as _, args[1]).ok_or(errno::EINVAL)?),
         DUP => sys_dup(args[0]),
         DUP3 => sys_dup3(args[0], args[1], args[2] as _),
-        FCNTL64 => sys_fcntl64(args[0], args[1], args[2]),
+        FCNTL64 => sys_fcntl64(args[0], args[1], args[2]).await,
+        FLOCK => sys_flock(args[0], args[1] as _).await,
         IOCTL => sys_ioctl(args[0], args[1], args[2]),
+        MKNODAT => sys_mknodat(
+            args[0],
+            UserCheck::new(args[1] as _).ok_or(errno::EINVAL)?,
+            args[2] as _,
+            args[3],
+        ),
         MKDIRAT => sys_mkdirat(args[0], UserCheck::new(args[1] as _).ok_or(errno::EINVAL)?, args[2]),
         UNLINKAT => sys_unlinkat(
             args[0],
             UserCheck::new(args[1] as _).ok_or(errno::EINVAL)?,
             args[2] as _,
         ),
-        // LINKAT => sys_linkat(args[1] as _, args[3] as _),
+        SYMLINKAT => sys_symlinkat(
+            UserCheck::new(args[0] as _).ok_or(errno::EINVAL)?,
+            args[1],
+            UserCheck::new(args[2] as _).ok_or(errno::EINVAL)?,
+        ),
+        LINKAT => sys_linkat(
+            args[0],
+            UserCheck::new(args[1] as _).ok_or(errno::EINVAL)?,
+            args[2],
+            UserCheck::new(args[3] as _).ok_or(errno::EINVAL)?,
+            args[4] as _,
+        ),
         UMOUNT => sys_umount(UserCheck::new(args[0] as _).ok_or(errno::EINVAL)?, args[1] as _),
         MOUNT => sys_mount(
             UserCheck::new(args[0] as _).ok_or(errno::EINVAL)?,
             UserCheck::new(args[1] as _).ok_or(errno::EINVAL)?,
-            UserCheck::new(args[2] as _).ok_or(errno::EINVAL)?,
+            UserCheck::new(args[2] as _),
             args[3] as _,
             UserCheck::new(args[4] as _),
         ),
@@ -97,15 +119,40 @@ async fn syscall_impl(id: usize, args: [usize; 6]) -> KResult {
             args[0],
             UserCheck::new(args[1] as _).ok_or(errno::EINVAL)?,
             args[2] as _,
+            0,
         ),
-        CHDIR => sys_chdir(UserCheck::new(args[0] as _).ok_or(errno::EINVAL)?),
-        OPENAT => sys_openat(
+        FACCESSAT2 => sys_faccessat(
+            args[0],
+            UserCheck::new(args[1] as _).ok_or(errno::EINVAL)?,
+            args[2] as _,
+            args[3] as _,
+        ),
+        FCHMOD => sys_fchmod(args[0], args[1] as _),
+        FCHMODAT => sys_fchmodat(
+            args[0],
+            UserCheck::new(args[1] as _).ok_or(errno::EINVAL)?,
+            args[2] as _,
+        ),
+        FCHOWN => sys_fchown(args[0], args[1] as _, args[2] as _),
+        FCHOWNAT => sys_fchownat(
             args[0],
             UserCheck::new(args[1] as _).ok_or(errno::EINVAL)?,
             args[2] as _,
             args[3] as _,
+            args[4] as _,
         ),
-        CLOSE => sys_close(args[0]),
+        UMASK => sys_umask(args[0] as _),
+        CHDIR => sys_chdir(UserCheck::new(args[0] as _).ok_or(errno::EINVAL)?),
+        OPENAT => {
+            sys_openat(
+                args[0],
+                UserCheck::new(args[1] as _).ok_or(errno::EINVAL)?,
+                args[2] as _,
+                args[3] as _,
+            )
+            .await
+        }
+        CLOSE => sys_close(args[0]).await,
         PIPE2 => sys_pipe2(UserCheck::new(args[0] as _).ok_or(errno::EINVAL)?, args[1] as _),
         GETDENTS64 => sys_getdents64(
             args[0],
@@ -129,11 +176,64 @@ async fn syscall_impl(id: usize, args: [usize; 6]) -> KResult {
         READV => sys_readv(args[0], UserCheck::new(args[1] as _).ok_or(errno::EINVAL)?, args[2]).await,
         WRITEV => sys_writev(args[0], UserCheck::new(args[1] as _).ok_or(errno::EINVAL)?, args[2]).await,
         SENDFILE64 => sys_sendfile64(args[0], args[1], UserCheck::new(args[2] as _), args[3]).await,
-        PPOLL => sys_ppoll(
-            UserCheck::new_slice(args[0] as _, args[1]).ok_or(errno::EINVAL)?,
-            UserCheck::new(args[2] as _),
+        PPOLL => {
+            sys_ppoll(
+                UserCheck::new_slice(args[0] as _, args[1]).ok_or(errno::EINVAL)?,
+                UserCheck::new(args[2] as _),
+                UserCheck::new(args[3] as _),
+                args[4],
+            )
+            .await
+        }
+        PSELECT6 => {
+            sys_pselect6(
+                args[0] as _,
+                UserCheck::new(args[1] as _),
+                UserCheck::new(args[2] as _),
+                UserCheck::new(args[3] as _),
+                UserCheck::new(args[4] as _),
+                UserCheck::new(args[5] as _),
+            )
+            .await
+        }
+        EVENTFD2 => sys_eventfd2(args[0] as _, args[1] as _),
+        INOTIFY_INIT1 => sys_inotify_init1(args[0] as _),
+        INOTIFY_ADD_WATCH => sys_inotify_add_watch(
+            args[0],
+            UserCheck::new(args[1] as _).ok_or(errno::EFAULT)?,
+            args[2] as _,
+        ),
+        INOTIFY_RM_WATCH => sys_inotify_rm_watch(args[0], args[1] as _),
+        SIGNALFD4 => sys_signalfd4(
+            args[0] as _,
+            UserCheck::new(args[1] as _).ok_or(errno::EFAULT)?,
+            args[2],
+            args[3] as _,
+        ),
+        TIMERFD_CREATE => sys_timerfd_create(args[0], args[1] as _),
+        TIMERFD_SETTIME => sys_timerfd_settime(
+            args[0],
+            args[1] as _,
+            UserCheck::new(args[2] as _).ok_or(errno::EFAULT)?,
             UserCheck::new(args[3] as _),
-            args[4],
+        ),
+        TIMERFD_GETTIME => sys_timerfd_gettime(args[0], UserCheck::new(args[1] as _).ok_or(errno::EFAULT)?),
+        EPOLL_CREATE1 => sys_epoll_create1(args[0] as _),
+        EPOLL_CTL => sys_epoll_ctl(args[0], args[1], args[2], UserCheck::new(args[3] as _)),
+        EPOLL_PWAIT => {
+            sys_epoll_pwait(
+                args[0],
+                UserCheck::new_slice(args[1] as _, args[2]).ok_or(errno::EFAULT)?,
+                args[3] as _,
+                UserCheck::new(args[4] as _),
+                args[5],
+            )
+            .await
+        }
+        READLINKAT => sys_readlinkat(
+            args[0],
+            UserCheck::new(args[1] as _).ok_or(errno::EINVAL)?,
+            UserCheck::new_slice(args[2] as _, args[3]).ok_or(errno::EINVAL)?,
         ),
         NEWFSTATAT => sys_newfstatat(
             args[0],
@@ -142,6 +242,7 @@ async fn syscall_impl(id: usize, args: [usize; 6]) -> KResult {
             args[3],
         ),
         NEWFSTAT => sys_newfstat(args[0], UserCheck::new(args[1] as _).ok_or(errno::EINVAL)?),
+        FSYNC => sys_fsync(args[0]).await,
         UTIMENSAT => sys_utimensat(
             args[0],
             UserCheck::new(args[1] as _).ok_or(errno::EINVAL)?,
@@ -152,6 +253,7 @@ async fn syscall_impl(id: usize, args: [usize; 6]) -> KResult {
         EXIT_GROUP => sys_exit_group(args[0] as _),
         SET_TID_ADDRESS => sys_set_tid_address(args[0] as _),
         NANOSLEEP => sys_nanosleep(UserCheck::new(args[0] as _).ok_or(errno::EINVAL)?).await,
+        CLOCK_SETTIME => sys_clock_settime(args[0] as _, UserCheck::new(args[1] as _).ok_or(errno::EINVAL)?),
         CLOCK_GETTIME => sys_clock_gettime(args[0] as _, UserCheck::new(args[1] as _).ok_or(errno::EINVAL)?),
         SYSLOG => sys_syslog(
             args[0] as _,
@@ -173,11 +275,82 @@ async fn syscall_impl(id: usize, args: [usize; 6]) -> KResult {
         GETPGID => sys_getpgid(args[0]),
         UNAME => sys_uname(UserCheck::new(args[0] as _).ok_or(errno::EINVAL)?),
         GET_TIME_OF_DAY => sys_get_time_of_day(UserCheck::new(args[0] as _).ok_or(errno::EINVAL)?, args[1]),
+        SETTIMEOFDAY => sys_settimeofday(UserCheck::new(args[0] as _), args[1]),
         GETPID => sys_getpid(),
         GETPPID => sys_getppid(),
-        GETUID | GETEUID | GETGID | GETEGID => Ok(0), // TODO: 目前不实现用户和用户组相关的部分
+        GETUID => sys_getuid(),
+        GETEUID => sys_geteuid(),
+        GETGID => sys_getgid(),
+        GETEGID => sys_getegid(),
+        SETUID => sys_setuid(args[0] as _),
+        SETGID => sys_setgid(args[0] as _),
+        SETREUID => sys_setreuid(args[0] as _, args[1] as _),
+        SETREGID => sys_setregid(args[0] as _, args[1] as _),
+        SETRESUID => sys_setresuid(args[0] as _, args[1] as _, args[2] as _),
+        SETRESGID => sys_setresgid(args[0] as _, args[1] as _, args[2] as _),
+        GETRESUID => sys_getresuid(
+            UserCheck::new(args[0] as _).ok_or(errno::EINVAL)?,
+            UserCheck::new(args[1] as _).ok_or(errno::EINVAL)?,
+            UserCheck::new(args[2] as _).ok_or(errno::EINVAL)?,
+        ),
+        GETRESGID => sys_getresgid(
+            UserCheck::new(args[0] as _).ok_or(errno::EINVAL)?,
+            UserCheck::new(args[1] as _).ok_or(errno::EINVAL)?,
+            UserCheck::new(args[2] as _).ok_or(errno::EINVAL)?,
+        ),
         GETTID => sys_gettid(),
         SYSINFO => sys_sysinfo(UserCheck::new(args[0] as _).ok_or(errno::EINVAL)?),
+        SOCKET => sys_socket(args[0] as _, args[1] as _, args[2] as _),
+        SOCKETPAIR => sys_socketpair(
+            args[0] as _,
+            args[1] as _,
+            args[2] as _,
+            UserCheck::new(args[3] as _).ok_or(errno::EFAULT)?,
+        ),
+        BIND => sys_bind(args[0], args[1], args[2]),
+        LISTEN => sys_listen(args[0], args[1] as _),
+        ACCEPT => sys_accept4(args[0], args[1], UserCheck::new(args[2] as _), 0).await,
+        ACCEPT4 => sys_accept4(args[0], args[1], UserCheck::new(args[2] as _), args[3] as _).await,
+        CONNECT => sys_connect(args[0], args[1], args[2]).await,
+        GETSOCKNAME => sys_getsockname(args[0], args[1], UserCheck::new(args[2] as _).ok_or(errno::EFAULT)?),
+        GETPEERNAME => sys_getpeername(args[0], args[1], UserCheck::new(args[2] as _).ok_or(errno::EFAULT)?),
+        SENDTO => sys_sendto(args[0], args[1], args[2], args[3] as _, args[4], args[5]).await,
+        RECVFROM => {
+            sys_recvfrom(
+                args[0],
+                args[1],
+                args[2],
+                args[3] as _,
+                args[4],
+                UserCheck::new(args[5] as _),
+            )
+            .await
+        }
+        SETSOCKOPT => sys_setsockopt(args[0], args[1] as _, args[2] as _, args[3], args[4]),
+        GETSOCKOPT => sys_getsockopt(
+            args[0],
+            args[1] as _,
+            args[2] as _,
+            args[3],
+            UserCheck::new(args[4] as _).ok_or(errno::EFAULT)?,
+        ),
+        SHUTDOWN => sys_shutdown(args[0], args[1] as _),
+        SENDMSG => {
+            sys_sendmsg(
+                args[0],
+                UserCheck::new(args[1] as _).ok_or(errno::EFAULT)?,
+                args[2] as _,
+            )
+            .await
+        }
+        RECVMSG => {
+            sys_recvmsg(
+                args[0],
+                UserCheck::new(args[1] as _).ok_or(errno::EFAULT)?,
+                args[2] as _,
+            )
+            .await
+        }
         BRK => sys_brk(args[0]),
         MUNMAP => sys_munmap(args[0], args[1]),
         CLONE => sys_clone(args[0], args[1], args[2], args[3], args[4]),
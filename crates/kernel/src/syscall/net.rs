@@ -0,0 +1,465 @@
+use alloc::{vec, vec::Vec};
+use core::mem::size_of;
+
+use defines::{
+    error::{errno, KResult},
+    fs::OpenFlags,
+    net::{
+        cmsg_align, cmsg_len, cmsg_space, CmsgHdr, MsgFlags, MsgHdr, SocketFlags, SCM_CREDENTIALS, SCM_RIGHTS, SHUT_RD,
+        SHUT_RDWR, SHUT_WR, SOCK_TYPE_MASK, SOL_SOCKET,
+    },
+};
+use libkernel::{
+    fs::file::{File, FileDescriptor},
+    hart::local_hart,
+    memory::{ReadBuffer, UserCheck, WriteBuffer},
+    net::{Socket, SocketAddr},
+};
+use triomphe::Arc;
+
+/// 用户传入的地址的最大长度，即 `struct sockaddr_storage` 的大小
+const SOCKADDR_MAX_LEN: usize = 128;
+/// `sendmsg()` 和 `recvmsg()` 的 iovec 的最大个数，同 linux 的 `UIO_MAXIOV`
+const UIO_MAXIOV: usize = 1024;
+/// 一条 `SCM_RIGHTS` 控制消息最多传递的文件数，同 linux 的 `SCM_MAX_FD`
+const SCM_MAX_FD: usize = 253;
+/// 控制消息缓冲区的最大长度，同 linux 的 `/proc/sys/net/core/optmem_max`
+const OPTMEM_MAX: usize = 20480;
+
+/// 取出 `fd` 对应的 socket 以及是否设置了 `O_NONBLOCK`。不是 socket 时返回 `ENOTSOCK`
+fn get_socket(fd: usize) -> KResult<(Arc<Socket>, bool)> {
+    local_hart().curr_process().lock_inner_with(|inner| {
+        let desc = inner.fd_table.get(fd).ok_or(errno::EBADF)?;
+        let File::Socket(socket) = &**desc else {
+            return Err(errno::ENOTSOCK);
+        };
+        Ok((Arc::clone(socket), desc.status_flags().contains(OpenFlags::NONBLOCK)))
+    })
+}
+
+/// 将新建的 socket 加入文件描述符表
+fn install_socket(socket: Socket, flags: SocketFlags) -> KResult<usize> {
+    let flags = OpenFlags::from_bits_truncate(flags.bits()) | OpenFlags::RDWR;
+    local_hart()
+        .curr_process()
+        .lock_inner_with(|inner| {
+            inner
+                .fd_table
+                .add(FileDescriptor::new(File::Socket(Arc::new(socket)), flags))
+        })
+        .ok_or(errno::EMFILE)
+}
+
+/// 读取用户传入的地址，`addr_len` 超过 [`SOCKADDR_MAX_LEN`] 时返回 `EINVAL`
+fn read_sockaddr(addr: usize, addr_len: usize) -> KResult<SocketAddr> {
+    if addr_len > SOCKADDR_MAX_LEN {
+        return Err(errno::EINVAL);
+    }
+    let addr = UserCheck::new_slice(addr as *mut u8, addr_len).ok_or(errno::EFAULT)?;
+    SocketAddr::from_bytes(&addr.check_slice()?)
+}
+
+/// 将地址写回用户，`addr_len` 输入时是缓冲区的长度，输出时是地址的实际长度。
+///
+/// 同 linux，缓冲区不够时地址会被截断。`addr` 为空时什么也不做
+fn write_sockaddr(addr: usize, addr_len: Option<UserCheck<u32>>, sockaddr: &SocketAddr) -> KResult<()> {
+    let (Some(addr), Some(addr_len)) = (UserCheck::new(addr as *mut u8), addr_len) else {
+        return Ok(());
+    };
+    let buf_len = addr_len.check_ptr()?.read() as i32;
+    if buf_len < 0 {
+        return Err(errno::EINVAL);
+    }
+    let bytes = sockaddr.to_bytes();
+    let len = usize::min(buf_len as usize, bytes.len());
+    if len > 0 {
+        let addr = UserCheck::new_slice(addr.addr().get() as *mut u8, len).ok_or(errno::EFAULT)?;
+        unsafe { addr.check_slice_mut()? }
+            .as_bytes_mut()
+            .copy_from_slice(&bytes[..len]);
+    }
+    unsafe { addr_len.check_ptr_mut()? }.write(bytes.len() as u32);
+    Ok(())
+}
+
+/// 创建一个 socket，返回其文件描述符
+///
+/// 参数：
+/// - `domain` 是地址族，支持 `AF_UNIX` 和 `AF_INET`
+/// - `socket_type` 的低位是 `SOCK_STREAM` 或 `SOCK_DGRAM`，高位可以是 `SOCK_NONBLOCK` 和 `SOCK_CLOEXEC`
+/// - `protocol` 为 0 表示地址族和类型对应的默认协议
+pub fn sys_socket(domain: u32, socket_type: u32, protocol: i32) -> KResult {
+    let flags = SocketFlags::from_bits(socket_type & !SOCK_TYPE_MASK).ok_or(errno::EINVAL)?;
+    let domain = u16::try_from(domain).map_err(|_| errno::EAFNOSUPPORT)?;
+    let socket = Socket::new(domain, socket_type & SOCK_TYPE_MASK, protocol)?;
+    let fd = install_socket(socket, flags)?;
+    Ok(fd as isize)
+}
+
+/// 创建一对相互连接的 socket，文件描述符写入 `sv`。参数同 [`sys_socket()`]
+pub fn sys_socketpair(domain: u32, socket_type: u32, protocol: i32, sv: UserCheck<[i32; 2]>) -> KResult {
+    let sv = unsafe { sv.check_ptr_mut()? };
+    let flags = SocketFlags::from_bits(socket_type & !SOCK_TYPE_MASK).ok_or(errno::EINVAL)?;
+    let domain = u16::try_from(domain).map_err(|_| errno::EAFNOSUPPORT)?;
+    let (a, b) = Socket::new_pair(domain, socket_type & SOCK_TYPE_MASK, protocol)?;
+    let open_flags = OpenFlags::from_bits_truncate(flags.bits()) | OpenFlags::RDWR;
+    let fds = local_hart()
+        .curr_process()
+        .lock_inner_with(|inner| {
+            inner.fd_table.add_many([
+                FileDescriptor::new(File::Socket(Arc::new(a)), open_flags),
+                FileDescriptor::new(File::Socket(Arc::new(b)), open_flags),
+            ])
+        })
+        .ok_or(errno::EMFILE)?;
+    sv.write([fds[0] as i32, fds[1] as i32]);
+    Ok(0)
+}
+
+/// 为 socket 绑定地址。unix socket 的地址只有 `sun_family` 时会自动绑定一个抽象地址
+pub fn sys_bind(fd: usize, addr: usize, addr_len: usize) -> KResult {
+    let (socket, _) = get_socket(fd)?;
+    socket.bind(read_sockaddr(addr, addr_len)?)?;
+    Ok(0)
+}
+
+/// 开始监听连接。`backlog` 超过 `SOMAXCONN` 或者为负数时视为 `SOMAXCONN`
+pub fn sys_listen(fd: usize, backlog: i32) -> KResult {
+    let (socket, _) = get_socket(fd)?;
+    socket.listen(backlog as u32 as usize)?;
+    Ok(0)
+}
+
+/// 接受一个连接，返回新的 socket 的文件描述符，对端地址写入 `addr`。
+///
+/// `flags` 可以是 `SOCK_NONBLOCK` 和 `SOCK_CLOEXEC`
+pub async fn sys_accept4(fd: usize, addr: usize, addr_len: Option<UserCheck<u32>>, flags: u32) -> KResult {
+    let flags = SocketFlags::from_bits(flags).ok_or(errno::EINVAL)?;
+    let (socket, nonblock) = get_socket(fd)?;
+    let accepted = socket.accept(nonblock).await?;
+    let peer_addr = accepted.peer_addr()?;
+    let fd = install_socket(accepted, flags)?;
+    write_sockaddr(addr, addr_len, &peer_addr)?;
+    Ok(fd as isize)
+}
+
+/// 连接到 `addr`
+pub async fn sys_connect(fd: usize, addr: usize, addr_len: usize) -> KResult {
+    let (socket, nonblock) = get_socket(fd)?;
+    socket.connect(read_sockaddr(addr, addr_len)?, nonblock).await?;
+    Ok(0)
+}
+
+/// 获取 socket 绑定的地址
+pub fn sys_getsockname(fd: usize, addr: usize, addr_len: UserCheck<u32>) -> KResult {
+    let (socket, _) = get_socket(fd)?;
+    write_sockaddr(addr, Some(addr_len), &socket.local_addr())?;
+    Ok(0)
+}
+
+/// 获取连接的对端的地址，未连接时返回 `ENOTCONN`
+pub fn sys_getpeername(fd: usize, addr: usize, addr_len: UserCheck<u32>) -> KResult {
+    let (socket, _) = get_socket(fd)?;
+    write_sockaddr(addr, Some(addr_len), &socket.peer_addr()?)?;
+    Ok(0)
+}
+
+/// 发送数据，`dest_addr` 为空时发送给连接的对端。返回发送的字节数
+pub async fn sys_sendto(fd: usize, buf: usize, len: usize, flags: u32, dest_addr: usize, addr_len: usize) -> KResult {
+    let flags = MsgFlags::from_bits_truncate(flags);
+    let (socket, nonblock) = get_socket(fd)?;
+    let to = if dest_addr == 0 {
+        None
+    } else {
+        Some(read_sockaddr(dest_addr, addr_len)?)
+    };
+    let buf = match UserCheck::new_slice(buf as *mut u8, len) {
+        Some(buf) => WriteBuffer::User(buf),
+        None if len == 0 => WriteBuffer::Kernel(&[]),
+        None => return Err(errno::EFAULT),
+    };
+    let nwrite = socket.send(buf, Vec::new(), to, flags, nonblock).await?;
+    Ok(nwrite as isize)
+}
+
+/// 接收数据，发送者的地址写入 `src_addr`。返回接收的字节数
+pub async fn sys_recvfrom(
+    fd: usize,
+    buf: usize,
+    len: usize,
+    flags: u32,
+    src_addr: usize,
+    addr_len: Option<UserCheck<u32>>,
+) -> KResult {
+    let flags = MsgFlags::from_bits_truncate(flags);
+    let (socket, nonblock) = get_socket(fd)?;
+    let mut empty = [];
+    let buf = match UserCheck::new_slice(buf as *mut u8, len) {
+        Some(buf) => ReadBuffer::User(buf),
+        None if len == 0 => ReadBuffer::Kernel(&mut empty),
+        None => return Err(errno::EFAULT),
+    };
+    let result = socket.recv(buf, flags, nonblock).await?;
+    if let Some(from) = &result.from {
+        write_sockaddr(src_addr, addr_len, from)?;
+    }
+    Ok(result.len as isize)
+}
+
+/// 读取 `msg` 中的 iovec 数组，返回每一段的用户缓冲区
+fn read_iovecs(msg: &MsgHdr) -> KResult<Vec<UserCheck<[u8]>>> {
+    if msg.msg_iovlen > UIO_MAXIOV {
+        return Err(errno::EMSGSIZE);
+    }
+    if msg.msg_iovlen == 0 {
+        return Ok(Vec::new());
+    }
+    let mut iov_ptr = UserCheck::new(msg.msg_iov).ok_or(errno::EFAULT)?;
+    let mut iovs = Vec::with_capacity(msg.msg_iovlen);
+    for _ in 0..msg.msg_iovlen {
+        let iov = iov_ptr.check_ptr()?.read();
+        if iov.iov_len != 0 {
+            iovs.push(UserCheck::new_slice(iov.iov_base, iov.iov_len).ok_or(errno::EFAULT)?);
+        }
+        iov_ptr = iov_ptr.add(1).ok_or(errno::EFAULT)?;
+    }
+    Ok(iovs)
+}
+
+/// 解析控制消息，返回 `SCM_RIGHTS` 传递的文件
+fn read_rights(msg: &MsgHdr) -> KResult<Vec<FileDescriptor>> {
+    if msg.msg_controllen == 0 {
+        return Ok(Vec::new());
+    }
+    if msg.msg_controllen > OPTMEM_MAX {
+        return Err(errno::ENOBUFS);
+    }
+    let control = UserCheck::new_slice(msg.msg_control, msg.msg_controllen).ok_or(errno::EFAULT)?;
+    let control = control.check_slice()?.to_vec();
+    let mut rights = Vec::new();
+    let mut offset = 0;
+    while offset + size_of::<CmsgHdr>() <= control.len() {
+        // SAFETY: 长度已经检查过了，`CmsgHdr` 的任意位模式都是合法的
+        let header = unsafe { control.as_ptr().add(offset).cast::<CmsgHdr>().read_unaligned() };
+        if header.cmsg_len < cmsg_len(0) || header.cmsg_len > control.len() - offset {
+            return Err(errno::EINVAL);
+        }
+        let data = &control[offset + cmsg_len(0)..offset + header.cmsg_len];
+        match (header.cmsg_level, header.cmsg_type) {
+            (SOL_SOCKET, SCM_RIGHTS) => {
+                let fds = data
+                    .chunks_exact(size_of::<i32>())
+                    .map(|fd| i32::from_ne_bytes(fd.try_into().unwrap()));
+                if rights.len() + data.len() / size_of::<i32>() > SCM_MAX_FD {
+                    return Err(errno::EINVAL);
+                }
+                local_hart().curr_process().lock_inner_with(|inner| {
+                    for fd in fds {
+                        let desc = usize::try_from(fd)
+                            .ok()
+                            .and_then(|fd| inner.fd_table.get(fd))
+                            .ok_or(errno::EBADF)?;
+                        rights.push(desc.clone());
+                    }
+                    Ok(())
+                })?;
+            }
+            // TODO: [low] 暂不支持传递进程凭证，直接忽略
+            (SOL_SOCKET, SCM_CREDENTIALS) => {}
+            _ => return Err(errno::EINVAL),
+        }
+        offset += cmsg_align(header.cmsg_len);
+    }
+    Ok(rights)
+}
+
+/// 将收到的文件加入文件描述符表，并写入 `SCM_RIGHTS` 控制消息。
+///
+/// 返回控制消息的长度以及是否被截断，放不下或者超过文件描述符上限的文件会被关闭
+fn write_rights(msg: &MsgHdr, rights: Vec<FileDescriptor>, cloexec: bool) -> KResult<(usize, bool)> {
+    if rights.is_empty() {
+        return Ok((0, false));
+    }
+    if msg.msg_control.is_null() || msg.msg_controllen < cmsg_len(0) {
+        return Ok((0, true));
+    }
+    let max_fds = (msg.msg_controllen - cmsg_len(0)) / size_of::<i32>();
+    let total = rights.len();
+    let fds = local_hart().curr_process().lock_inner_with(|inner| {
+        rights
+            .into_iter()
+            .take(max_fds)
+            .map_while(|mut desc| {
+                desc.set_close_on_exec(cloexec);
+                inner.fd_table.add(desc).map(|fd| fd as i32)
+            })
+            .collect::<Vec<_>>()
+    });
+    let header = CmsgHdr {
+        cmsg_len: cmsg_len(fds.len() * size_of::<i32>()),
+        cmsg_level: SOL_SOCKET,
+        cmsg_type: SCM_RIGHTS,
+    };
+    let mut bytes = Vec::with_capacity(header.cmsg_len);
+    bytes.extend_from_slice(&header.cmsg_len.to_ne_bytes());
+    bytes.extend_from_slice(&header.cmsg_level.to_ne_bytes());
+    bytes.extend_from_slice(&header.cmsg_type.to_ne_bytes());
+    for fd in &fds {
+        bytes.extend_from_slice(&fd.to_ne_bytes());
+    }
+    let control = UserCheck::new_slice(msg.msg_control, bytes.len()).ok_or(errno::EFAULT)?;
+    unsafe { control.check_slice_mut()? }
+        .as_bytes_mut()
+        .copy_from_slice(&bytes);
+    let control_len = usize::min(cmsg_space(fds.len() * size_of::<i32>()), msg.msg_controllen);
+    Ok((control_len, fds.len() < total))
+}
+
+/// 发送一条消息，可以由多段缓冲区组成，并通过控制消息 `SCM_RIGHTS` 传递文件描述符。返回发送的字节数
+pub async fn sys_sendmsg(fd: usize, msg: UserCheck<MsgHdr>, flags: u32) -> KResult {
+    let flags = MsgFlags::from_bits_truncate(flags);
+    let (socket, nonblock) = get_socket(fd)?;
+    // `MsgHdr` 中有裸指针，不能跨越 await，因此限制在块中
+    let (to, iovs, rights) = {
+        let msg = msg.check_ptr()?.read();
+        let to = if msg.msg_name.is_null() || msg.msg_namelen == 0 {
+            None
+        } else {
+            Some(read_sockaddr(msg.msg_name as usize, msg.msg_namelen as usize)?)
+        };
+        (to, read_iovecs(&msg)?, read_rights(&msg)?)
+    };
+    let data;
+    let buf = match iovs.as_slice() {
+        [] => WriteBuffer::Kernel(&[]),
+        [iov] => WriteBuffer::User(iov.slice(0..iov.len()).expect("in bound")),
+        // 多段缓冲区先拼接起来，以保证数据报的完整
+        _ => {
+            let mut bytes = Vec::new();
+            for iov in &iovs {
+                bytes.extend_from_slice(&iov.check_slice()?);
+            }
+            data = bytes;
+            WriteBuffer::Kernel(&data)
+        }
+    };
+    let nwrite = socket.send(buf, rights, to, flags, nonblock).await?;
+    Ok(nwrite as isize)
+}
+
+/// 接收一条消息，数据依次写入多段缓冲区，发送者地址、`SCM_RIGHTS` 传递的文件和 `msg_flags` 写回 `msg`。
+///
+/// 返回接收的字节数。`flags` 中的 `MSG_CMSG_CLOEXEC` 会为收到的文件描述符设置 close-on-exec
+pub async fn sys_recvmsg(fd: usize, msg_ptr: UserCheck<MsgHdr>, flags: u32) -> KResult {
+    let flags = MsgFlags::from_bits_truncate(flags);
+    let (socket, nonblock) = get_socket(fd)?;
+    let iovs = read_iovecs(&msg_ptr.check_ptr()?.read())?;
+    let (result, data) = match iovs.as_slice() {
+        [] => (socket.recv(ReadBuffer::Kernel(&mut []), flags, nonblock).await?, None),
+        [iov] => {
+            let buf = ReadBuffer::User(iov.slice(0..iov.len()).expect("in bound"));
+            (socket.recv(buf, flags, nonblock).await?, None)
+        }
+        // 多段缓冲区先接收到内核中，长度不超过一次接收最多能得到的字节数
+        _ => {
+            let total = iovs
+                .iter()
+                .try_fold(0usize, |total, iov| total.checked_add(iov.len()))
+                .ok_or(errno::EINVAL)?;
+            let mut data = vec![0; usize::min(total, socket.recv_capacity())];
+            let result = socket.recv(ReadBuffer::Kernel(&mut data), flags, nonblock).await?;
+            (result, Some(data))
+        }
+    };
+    // 多段缓冲区时将数据分散写入各段
+    if let Some(data) = data {
+        let mut rest = &data[..usize::min(result.len, data.len())];
+        for iov in &iovs {
+            if rest.is_empty() {
+                break;
+            }
+            let len = usize::min(iov.len(), rest.len());
+            let dst = iov.slice(0..len).expect("in bound");
+            unsafe { dst.check_slice_mut()? }
+                .as_bytes_mut()
+                .copy_from_slice(&rest[..len]);
+            rest = &rest[len..];
+        }
+    }
+
+    // 其余字段在接收完成后再读取，因为 `MsgHdr` 中有裸指针，不能跨越 await
+    let mut msg = msg_ptr.check_ptr()?.read();
+    let mut msg_flags = MsgFlags::empty();
+    if result.truncated {
+        msg_flags |= MsgFlags::TRUNC;
+    }
+    match &result.from {
+        Some(from) if !msg.msg_name.is_null() => {
+            let bytes = from.to_bytes();
+            let len = usize::min(msg.msg_namelen as usize, bytes.len());
+            if len > 0 {
+                let name = UserCheck::new_slice(msg.msg_name, len).ok_or(errno::EFAULT)?;
+                unsafe { name.check_slice_mut()? }
+                    .as_bytes_mut()
+                    .copy_from_slice(&bytes[..len]);
+            }
+            msg.msg_namelen = bytes.len() as u32;
+        }
+        _ => msg.msg_namelen = 0,
+    }
+    let (control_len, control_truncated) = write_rights(&msg, result.rights, flags.contains(MsgFlags::CMSG_CLOEXEC))?;
+    if control_truncated {
+        msg_flags |= MsgFlags::CTRUNC;
+    }
+    msg.msg_controllen = control_len;
+    msg.msg_flags = msg_flags.bits();
+    unsafe { msg_ptr.check_ptr_mut()? }.write(msg);
+    Ok(result.len as isize)
+}
+
+/// 关闭 socket 的读方向（`SHUT_RD`）、写方向（`SHUT_WR`）或者全部（`SHUT_RDWR`）
+pub fn sys_shutdown(fd: usize, how: i32) -> KResult {
+    let (socket, _) = get_socket(fd)?;
+    let (read, write) = match how {
+        SHUT_RD => (true, false),
+        SHUT_WR => (false, true),
+        SHUT_RDWR => (true, true),
+        _ => return Err(errno::EINVAL),
+    };
+    socket.shutdown(read, write)?;
+    Ok(0)
+}
+
+/// 读取 socket 选项。`opt_len` 输入时是缓冲区的长度，输出时是实际写入的长度，选项值会被截断
+pub fn sys_getsockopt(fd: usize, level: i32, name: i32, opt_val: usize, opt_len: UserCheck<u32>) -> KResult {
+    let (socket, _) = get_socket(fd)?;
+    let buf_len = opt_len.check_ptr()?.read() as i32;
+    if buf_len < 0 {
+        return Err(errno::EINVAL);
+    }
+    let value = socket.get_option(level, name)?;
+    let len = usize::min(buf_len as usize, value.len());
+    if len > 0 {
+        let opt_val = UserCheck::new_slice(opt_val as *mut u8, len).ok_or(errno::EFAULT)?;
+        unsafe { opt_val.check_slice_mut()? }
+            .as_bytes_mut()
+            .copy_from_slice(&value[..len]);
+    }
+    unsafe { opt_len.check_ptr_mut()? }.write(len as u32);
+    Ok(0)
+}
+
+/// 设置 socket 选项
+pub fn sys_setsockopt(fd: usize, level: i32, name: i32, opt_val: usize, opt_len: usize) -> KResult {
+    let (socket, _) = get_socket(fd)?;
+    if opt_len > OPTMEM_MAX {
+        return Err(errno::EINVAL);
+    }
+    let value = match UserCheck::new_slice(opt_val as *mut u8, opt_len) {
+        Some(value) => value.check_slice()?.to_vec(),
+        None if opt_len == 0 => Vec::new(),
+        None => return Err(errno::EFAULT),
+    };
+    socket.set_option(level, name, &value)?;
+    Ok(0)
+}
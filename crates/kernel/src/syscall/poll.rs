@@ -0,0 +1,382 @@
+use alloc::{vec, vec::Vec};
+use core::{
+    future::{self, Future},
+    mem::size_of,
+    pin::Pin,
+    task::Poll,
+    time::Duration,
+};
+
+use defines::{
+    error::{errno, KResult},
+    fs::{
+        EpollEvent, EpollEvents, OpenFlags, PollEvents, PollFd, PselectSigMask, EPOLL_CTL_ADD, EPOLL_CTL_DEL,
+        EPOLL_CTL_MOD, NFDBITS,
+    },
+    misc::TimeSpec,
+    signal::SIGSET_SIZE_BYTES,
+};
+use executor::time;
+use libkernel::{
+    fs::{
+        epoll::Epoll,
+        file::{File, FileDescriptor},
+        poll::PollTable,
+    },
+    hart::local_hart,
+    memory::UserCheck,
+    signal::KSignalSet,
+};
+use triomphe::Arc;
+
+/// 等待一组文件描述符上的事件。返回就绪的文件描述符数量，如果超时则返回 0
+///
+/// 如果任何文件描述符都没有发生请求的事件且没有错误，则将阻塞直到：
+///
+/// - 文件描述符准备就绪
+///     - 这意味着请求的操作不会阻塞
+///     - 因此，对于常规文件、块设备和其他没有合理轮询语义的文件总是立即返回为可供读写的状态
+/// - 调用被信号中断，此时返回 `EINTR`
+/// - 超时 (`timeout`) 到期
+///
+/// 参数：
+/// - `fds` 描述感兴趣的所有文件描述符及事件，同时也是返回事件的输出参数
+///     - 如果长度超过 `RLIMIT_NOFILE` 指定的 rlimit，则返回 EINVAL
+/// - `timeout` 如果为 `NULL` 则意味着无限的超时。为负返回 `EINVAL`
+/// - `signal_mask` 如果非 `NULL`，则在等待期间临时替换线程的信号掩码
+/// - `sig_set_size` 是信号集的大小
+pub async fn sys_ppoll(
+    fds: UserCheck<[PollFd]>,
+    timeout: Option<UserCheck<TimeSpec>>,
+    signal_mask: Option<UserCheck<u64>>,
+    sig_set_size: usize,
+) -> KResult {
+    let timeout = match timeout {
+        Some(timeout) => Some(Duration::try_from(timeout.check_ptr()?.read())?),
+        None => None,
+    };
+
+    let mut files = Vec::with_capacity(fds.len());
+    let mut invalid_fds = Vec::new();
+    {
+        let process = local_hart().curr_process();
+        let inner = process.lock_inner();
+        if fds.len() > inner.fd_table.limit() {
+            return Err(errno::EINVAL);
+        }
+        let mut user_fds = unsafe { fds.check_slice_mut()? };
+        for (i, poll_fd) in user_fds.iter_mut().enumerate() {
+            let poll_fd = poll_fd.read();
+            // 负数的 fd 被忽略
+            let desc = if poll_fd.fd < 0 {
+                None
+            } else if let Some(desc) = inner.fd_table.get(poll_fd.fd as usize) {
+                Some(desc.clone())
+            } else {
+                invalid_fds.push(i);
+                None
+            };
+            let events = PollEvents::from_bits_truncate(poll_fd.events) | PollEvents::POLLERR | PollEvents::POLLHUP;
+            files.push((desc, events));
+        }
+    }
+
+    if let Some(signal_mask) = signal_mask {
+        if sig_set_size != SIGSET_SIZE_BYTES {
+            return Err(errno::EINVAL);
+        }
+        let signal_mask = KSignalSet::from_user(signal_mask.check_ptr()?.read());
+        local_hart().curr_thread().set_temporary_signal_mask(signal_mask);
+    }
+
+    // 未打开的 fd 总是报告 `POLLNVAL`，因此不需要等待
+    let timeout = if invalid_fds.is_empty() {
+        timeout
+    } else {
+        Some(Duration::ZERO)
+    };
+    let mut revents = poll_files(&files, timeout).await?;
+    for i in invalid_fds {
+        revents[i] = PollEvents::POLLNVAL;
+    }
+
+    let mut user_fds = unsafe { fds.check_slice_mut()? };
+    let mut ret = 0;
+    for (poll_fd, revents) in user_fds.iter_mut().zip(revents) {
+        let mut poll_fd_val = poll_fd.read();
+        poll_fd_val.revents = revents.bits();
+        poll_fd.write(poll_fd_val);
+        if !revents.is_empty() {
+            ret += 1;
+        }
+    }
+    Ok(ret)
+}
+
+/// 同 [`sys_ppoll()`]，但以位图 (`fd_set`) 描述感兴趣的文件描述符。返回三个集合中就绪的位的总数，超时则返回 0
+///
+/// riscv64 上没有 `select`，libc 的 `select()` 也是通过它实现的。
+///
+/// 参数：
+/// - `nfds` 是三个集合中最大的 fd 加一，为负返回 `EINVAL`。超过进程文件描述符上限的部分被忽略
+/// - `read_fds`、`write_fds`、`except_fds` 分别是关心可读、可写和异常情况的 fd 集合，可以为 `NULL`。
+///   同时也是输出参数，返回时只保留就绪的 fd
+///     - 集合中有未打开的 fd 时返回 `EBADF`
+/// - `timeout` 如果为 `NULL` 则意味着无限的超时。同 linux，返回时会被更新为剩余的时间
+/// - `signal_mask` 见 [`PselectSigMask`]
+pub async fn sys_pselect6(
+    nfds: i32,
+    read_fds: Option<UserCheck<u64>>,
+    write_fds: Option<UserCheck<u64>>,
+    except_fds: Option<UserCheck<u64>>,
+    timeout: Option<UserCheck<TimeSpec>>,
+    signal_mask: Option<UserCheck<PselectSigMask>>,
+) -> KResult {
+    /// 同 linux 的 `POLLIN_SET`、`POLLOUT_SET` 和 `POLLEX_SET`，对应三个集合
+    const SELECT_EVENTS: [PollEvents; 3] = [
+        PollEvents::POLLIN.union(PollEvents::POLLHUP).union(PollEvents::POLLERR),
+        PollEvents::POLLOUT.union(PollEvents::POLLERR),
+        PollEvents::POLLPRI,
+    ];
+
+    let nfds = usize::try_from(nfds).map_err(|_| errno::EINVAL)?;
+    let timeout_duration = match &timeout {
+        Some(timeout) => Some(Duration::try_from(timeout.check_ptr()?.read())?),
+        None => None,
+    };
+
+    let process = local_hart().curr_process();
+    let nfds = nfds.min(process.lock_inner_with(|inner| inner.fd_table.limit()));
+    let nwords = nfds.div_ceil(NFDBITS);
+    let fd_sets = [read_fds, write_fds, except_fds]
+        .map(|set| set.and_then(|set| UserCheck::new_slice(set.addr().get() as *mut u64, nwords)));
+    let mut in_sets: [Vec<u64>; 3] = Default::default();
+    for (in_set, fd_set) in in_sets.iter_mut().zip(&fd_sets) {
+        *in_set = match fd_set {
+            // 集合同时是输出参数，因此直接检查是否可写
+            Some(fd_set) => unsafe { fd_set.check_slice_mut()? }
+                .iter_mut()
+                .map(|word| word.read())
+                .collect(),
+            None => vec![0; nwords],
+        };
+    }
+
+    let mut fds = Vec::new();
+    let mut files = Vec::new();
+    process.lock_inner_with(|inner| {
+        for word in 0..nwords {
+            let mut bits = in_sets[0][word] | in_sets[1][word] | in_sets[2][word];
+            while bits != 0 {
+                let fd = word * NFDBITS + bits.trailing_zeros() as usize;
+                bits &= bits - 1;
+                if fd >= nfds {
+                    break;
+                }
+                let mut events = PollEvents::empty();
+                for (in_set, select_events) in in_sets.iter().zip(SELECT_EVENTS) {
+                    if in_set[word] & (1 << (fd % NFDBITS)) != 0 {
+                        events |= select_events;
+                    }
+                }
+                let desc = inner.fd_table.get(fd).ok_or(errno::EBADF)?;
+                fds.push(fd);
+                files.push((Some(desc.clone()), events));
+            }
+        }
+        KResult::Ok(())
+    })?;
+    drop(process);
+
+    if let Some(signal_mask) = signal_mask {
+        let signal_mask = signal_mask.check_ptr()?.read();
+        if let Some(ss) = UserCheck::<u64>::new(signal_mask.ss as _) {
+            if signal_mask.ss_len != SIGSET_SIZE_BYTES {
+                return Err(errno::EINVAL);
+            }
+            let signal_mask = KSignalSet::from_user(ss.check_ptr()?.read());
+            local_hart().curr_thread().set_temporary_signal_mask(signal_mask);
+        }
+    }
+
+    let start = time::curr_time();
+    let revents = poll_files(&files, timeout_duration).await;
+    if let Some(timeout) = timeout
+        && let Some(timeout_duration) = timeout_duration
+    {
+        let remaining = timeout_duration.saturating_sub(time::curr_time() - start);
+        unsafe { timeout.check_ptr_mut()? }.write(TimeSpec::from(remaining));
+    }
+    // 被信号中断时不修改集合
+    let revents = revents?;
+
+    let mut out_sets = [vec![0; nwords], vec![0; nwords], vec![0; nwords]];
+    let mut ret = 0;
+    for (fd, revents) in fds.into_iter().zip(revents) {
+        let (word, bit) = (fd / NFDBITS, 1 << (fd % NFDBITS));
+        for ((in_set, out_set), select_events) in in_sets.iter().zip(&mut out_sets).zip(SELECT_EVENTS) {
+            if in_set[word] & bit != 0 && revents.intersects(select_events) {
+                out_set[word] |= bit;
+                ret += 1;
+            }
+        }
+    }
+    for (fd_set, out_set) in fd_sets.iter().zip(out_sets) {
+        if let Some(fd_set) = fd_set {
+            let mut user_set = unsafe { fd_set.check_slice_mut()? };
+            for (user_word, word) in user_set.iter_mut().zip(out_set) {
+                user_word.write(word);
+            }
+        }
+    }
+    Ok(ret)
+}
+
+/// 等待 `files` 中任一文件上感兴趣的事件就绪，返回每个文件就绪的事件。
+///
+/// `files` 中为 `None` 的项被忽略。超时后返回全部为空的事件；被未屏蔽的信号中断时返回 `EINTR`。
+/// `timeout` 为 `None` 表示无限等待
+pub async fn poll_files(
+    files: &[(Option<FileDescriptor>, PollEvents)],
+    timeout: Option<Duration>,
+) -> KResult<Vec<PollEvents>> {
+    let thread = Arc::clone(&local_hart().curr_thread_arc());
+    let mut sleep = timeout.map(time::sleep);
+    let mut listeners = Vec::new();
+    let mut revents = vec![PollEvents::empty(); files.len()];
+    future::poll_fn(|cx| {
+        // 上一轮登记的监听者已经没用了
+        listeners.clear();
+        let mut table = PollTable::new(cx, &mut listeners);
+        // 先登记再检查，以免错过检查之后才到达的信号
+        table.listen(thread.signal_event());
+        let mut ready = false;
+        for ((desc, events), file_revents) in files.iter().zip(&mut revents) {
+            let Some(desc) = desc else {
+                continue;
+            };
+            // 已经有文件就绪时就不会再等待了，不需要登记唤醒
+            let table = if ready { None } else { Some(&mut table) };
+            *file_revents = desc.poll(table) & *events;
+            ready |= !file_revents.is_empty();
+        }
+        if ready {
+            return Poll::Ready(Ok(()));
+        }
+        if thread.has_unmasked_signal() {
+            return Poll::Ready(Err(errno::EINTR));
+        }
+        if let Some(sleep) = &mut sleep
+            && Pin::new(sleep).poll(cx).is_ready()
+        {
+            return Poll::Ready(Ok(()));
+        }
+        Poll::Pending
+    })
+    .await?;
+    Ok(revents)
+}
+
+/// 创建一个 epoll 实例，返回其文件描述符。
+///
+/// `flags` 只能为 0 或 `EPOLL_CLOEXEC`（即 `O_CLOEXEC`），否则返回 `EINVAL`
+pub fn sys_epoll_create1(flags: u32) -> KResult {
+    let flags = OpenFlags::from_bits(flags)
+        .filter(|flags| OpenFlags::CLOEXEC.contains(*flags))
+        .ok_or(errno::EINVAL)?;
+    let desc = FileDescriptor::new(File::Epoll(Arc::new(Epoll::new())), flags | OpenFlags::RDWR);
+    let fd = local_hart()
+        .curr_process()
+        .lock_inner_with(|inner| inner.fd_table.add(desc))
+        .ok_or(errno::EMFILE)?;
+    Ok(fd as isize)
+}
+
+/// 修改 epoll 实例 `epfd` 的兴趣列表。
+///
+/// 参数：
+/// - `op` 为 `EPOLL_CTL_ADD`、`EPOLL_CTL_MOD` 或 `EPOLL_CTL_DEL`
+/// - `fd` 是要监视的文件描述符。常规文件和目录没有合理的轮询语义，返回 `EPERM`
+/// - `event` 是感兴趣的事件和数据，`EPOLL_CTL_DEL` 时被忽略
+pub fn sys_epoll_ctl(epfd: usize, op: usize, fd: usize, event: Option<UserCheck<EpollEvent>>) -> KResult {
+    let (epoll, file) = local_hart().curr_process().lock_inner_with(|inner| {
+        let epoll = inner.fd_table.get(epfd).ok_or(errno::EBADF)?;
+        let file = inner.fd_table.get(fd).ok_or(errno::EBADF)?;
+        let File::Epoll(epoll) = &**epoll else {
+            return Err(errno::EINVAL);
+        };
+        Ok((Arc::clone(epoll), File::clone(file)))
+    })?;
+    if epfd == fd {
+        return Err(errno::EINVAL);
+    }
+    if matches!(file, File::Dir(_) | File::Seekable(_)) {
+        return Err(errno::EPERM);
+    }
+
+    let read_event = || -> KResult<(EpollEvents, u64)> {
+        let event = event.ok_or(errno::EFAULT)?.check_ptr()?.read();
+        Ok((EpollEvents::from_bits_truncate(event.events), event.data))
+    };
+    match op {
+        EPOLL_CTL_ADD => {
+            let (events, data) = read_event()?;
+            epoll.add(fd, file, events, data)?;
+        }
+        EPOLL_CTL_MOD => {
+            let (events, data) = read_event()?;
+            // `EPOLLEXCLUSIVE` 只能在加入时指定
+            if events.contains(EpollEvents::EPOLLEXCLUSIVE) {
+                return Err(errno::EINVAL);
+            }
+            epoll.modify(fd, events, data)?;
+        }
+        EPOLL_CTL_DEL => epoll.delete(fd)?,
+        _ => return Err(errno::EINVAL),
+    }
+    Ok(0)
+}
+
+/// 等待 epoll 实例 `epfd` 中的文件就绪，返回写入 `events` 的事件数量，超时则返回 0。
+///
+/// 参数：
+/// - `events` 用于返回事件，其长度即 `maxevents`，必须大于 0
+/// - `timeout` 单位为毫秒，为负则意味着无限的超时
+/// - `signal_mask` 如果非 `NULL`，则在等待期间临时替换线程的信号掩码
+/// - `sig_set_size` 是信号集的大小
+pub async fn sys_epoll_pwait(
+    epfd: usize,
+    events: UserCheck<[EpollEvent]>,
+    timeout: i32,
+    signal_mask: Option<UserCheck<u64>>,
+    sig_set_size: usize,
+) -> KResult {
+    // 负数的 `maxevents` 会变成很大的长度
+    if events.is_empty() || events.len() > i32::MAX as usize / size_of::<EpollEvent>() {
+        return Err(errno::EINVAL);
+    }
+    let desc = local_hart()
+        .curr_process()
+        .lock_inner_with(|inner| inner.fd_table.get(epfd).cloned())
+        .ok_or(errno::EBADF)?;
+    let File::Epoll(epoll) = &*desc else {
+        return Err(errno::EINVAL);
+    };
+    let timeout = u64::try_from(timeout).ok().map(Duration::from_millis);
+
+    if let Some(signal_mask) = signal_mask {
+        if sig_set_size != SIGSET_SIZE_BYTES {
+            return Err(errno::EINVAL);
+        }
+        let signal_mask = KSignalSet::from_user(signal_mask.check_ptr()?.read());
+        local_hart().curr_thread().set_temporary_signal_mask(signal_mask);
+    }
+
+    let ready = epoll.wait(events.len(), timeout).await?;
+    let events = events.slice(0..ready.len()).expect("in bound");
+    let mut user_events = unsafe { events.check_slice_mut()? };
+    for (user_event, event) in user_events.iter_mut().zip(&ready) {
+        user_event.write(*event);
+    }
+    Ok(ready.len() as isize)
+}
@@ -6,6 +6,7 @@ use core::num::NonZeroUsize;
 use atomic::Ordering;
 use defines::{
     error::{errno, KResult},
+    fs::FaccessatMode,
     misc::{CloneFlags, WaitFlags},
 };
 use ecow::EcoString;
@@ -14,7 +15,7 @@ use libkernel::{
     fs::{self, dentry::DEntry, inode::InodeMode},
     hart::local_hart,
     memory::UserCheck,
-    process::{exit_process, INITPROC_PID, PROCESS_MANAGER},
+    process::{self, exit_process, IdSet, INITPROC_PID, PROCESS_MANAGER},
     signal::Signal,
 };
 use triomphe::Arc;
@@ -57,6 +58,93 @@ pub fn sys_getppid() -> KResult {
     Ok(ppid)
 }
 
+/// 返回当前进程的真实用户 ID，永不失败
+pub fn sys_getuid() -> KResult {
+    Ok(process::curr_cred().uid.real as usize)
+}
+
+/// 返回当前进程的有效用户 ID，永不失败
+pub fn sys_geteuid() -> KResult {
+    Ok(process::curr_cred().uid.effective as usize)
+}
+
+/// 返回当前进程的真实组 ID，永不失败
+pub fn sys_getgid() -> KResult {
+    Ok(process::curr_cred().gid.real as usize)
+}
+
+/// 返回当前进程的有效组 ID，永不失败
+pub fn sys_getegid() -> KResult {
+    Ok(process::curr_cred().gid.effective as usize)
+}
+
+/// 设置当前进程的用户 ID。特权进程会同时设置真实、有效和保存的用户 ID，
+/// 否则只能将有效用户 ID 设置为真实用户 ID 或保存的用户 ID
+pub fn sys_setuid(uid: u32) -> KResult {
+    local_hart().curr_process().lock_cred_with(|cred| cred.setuid(uid))?;
+    Ok(0)
+}
+
+/// 设置当前进程的组 ID，规则与 [`sys_setuid()`] 相同
+pub fn sys_setgid(gid: u32) -> KResult {
+    local_hart().curr_process().lock_cred_with(|cred| cred.setgid(gid))?;
+    Ok(0)
+}
+
+/// 设置当前进程的真实和有效用户 ID，为 -1 的参数表示不修改
+pub fn sys_setreuid(ruid: u32, euid: u32) -> KResult {
+    local_hart()
+        .curr_process()
+        .lock_cred_with(|cred| cred.setreuid(optional_id(ruid), optional_id(euid)))?;
+    Ok(0)
+}
+
+/// 设置当前进程的真实和有效组 ID，为 -1 的参数表示不修改
+pub fn sys_setregid(rgid: u32, egid: u32) -> KResult {
+    local_hart()
+        .curr_process()
+        .lock_cred_with(|cred| cred.setregid(optional_id(rgid), optional_id(egid)))?;
+    Ok(0)
+}
+
+/// 设置当前进程的真实、有效和保存的用户 ID，为 -1 的参数表示不修改
+pub fn sys_setresuid(ruid: u32, euid: u32, suid: u32) -> KResult {
+    local_hart()
+        .curr_process()
+        .lock_cred_with(|cred| cred.setresuid(optional_id(ruid), optional_id(euid), optional_id(suid)))?;
+    Ok(0)
+}
+
+/// 设置当前进程的真实、有效和保存的组 ID，为 -1 的参数表示不修改
+pub fn sys_setresgid(rgid: u32, egid: u32, sgid: u32) -> KResult {
+    local_hart()
+        .curr_process()
+        .lock_cred_with(|cred| cred.setresgid(optional_id(rgid), optional_id(egid), optional_id(sgid)))?;
+    Ok(0)
+}
+
+/// 获取当前进程的真实、有效和保存的用户 ID，分别写入三个指针
+pub fn sys_getresuid(ruid: UserCheck<u32>, euid: UserCheck<u32>, suid: UserCheck<u32>) -> KResult {
+    write_id_set(process::curr_cred().uid, ruid, euid, suid)
+}
+
+/// 获取当前进程的真实、有效和保存的组 ID，分别写入三个指针
+pub fn sys_getresgid(rgid: UserCheck<u32>, egid: UserCheck<u32>, sgid: UserCheck<u32>) -> KResult {
+    write_id_set(process::curr_cred().gid, rgid, egid, sgid)
+}
+
+/// 用户传入的 -1 表示不修改对应的 ID
+fn optional_id(id: u32) -> Option<u32> {
+    (id != u32::MAX).then_some(id)
+}
+
+fn write_id_set(ids: IdSet, real: UserCheck<u32>, effective: UserCheck<u32>, saved: UserCheck<u32>) -> KResult {
+    unsafe { real.check_ptr_mut()?.write(ids.real) };
+    unsafe { effective.check_ptr_mut()?.write(ids.effective) };
+    unsafe { saved.check_ptr_mut()?.write(ids.saved) };
+    Ok(0)
+}
+
 /// 创建子任务，通过 flags 进行精确控制。父进程返回子进程 pid，子进程返回 0。
 ///
 /// TODO: 完善 `sys_clone()` 及文档
@@ -181,13 +269,20 @@ pub async fn sys_execve(pathname: UserCheck<u8>, argv: UserCheck<usize>, envp: O
         if bytes.inode().meta().mode() != InodeMode::Regular {
             return Err(errno::EACCES);
         }
+        fs::check_access(bytes.inode().meta(), FaccessatMode::X_OK)?;
         (bytes, args, envs)
     };
 
     let argc = args.len();
     let elf_data = fs::read_file(bytes.inode()).await?;
     debug!("args = {args:?}");
-    local_hart().curr_process().exec(&elf_data, args, envs)?;
+    let (perm, owner, group) = bytes
+        .inode()
+        .meta()
+        .lock_inner_with(|inner| (inner.perm, inner.uid, inner.gid));
+    let process = local_hart().curr_process();
+    process.exec(Arc::clone(&bytes), &elf_data, args, envs)?;
+    process.lock_cred_with(|cred| cred.exec(perm, owner, group));
     Ok(argc)
 }
 
@@ -1,13 +1,21 @@
+use core::ops::Deref;
+
 use defines::{
     error::{errno, KResult},
+    fs::OpenFlags,
     signal::{KSignalAction, SignalActionFlags, SIGSET_SIZE_BYTES},
 };
 use libkernel::{
+    fs::{
+        file::{File, FileDescriptor},
+        signalfd::SignalFd,
+    },
     hart::local_hart,
     memory::UserCheck,
     process::{exit_process, PROCESS_MANAGER},
     signal::{KSignalSet, SigProcMaskHow, Signal, SignalContext},
 };
+use triomphe::Arc;
 
 /// 设置当前**进程**在收到特定信号时的行为
 ///
@@ -158,3 +166,38 @@ pub fn sys_kill(pid: isize, signum: usize) -> KResult {
     }
     Ok(0)
 }
+
+/// 创建或修改一个用于接收信号的 signalfd，返回其文件描述符
+///
+/// 参数：
+/// - `fd` 为 -1 时创建新的 signalfd，否则修改已有的 signalfd 关心的信号集。不是 signalfd 返回 `EINVAL`
+/// - `mask` 是关心的信号集，其中的 `SIGKILL` 和 `SIGSTOP` 会被忽略
+/// - `size_mask` 是信号集的大小，必须等于内核的信号集大小
+/// - `flags` 可以是 `SFD_NONBLOCK` 和 `SFD_CLOEXEC`，即 `O_NONBLOCK` 和 `O_CLOEXEC`
+pub fn sys_signalfd4(fd: i32, mask: UserCheck<u64>, size_mask: usize, flags: u32) -> KResult {
+    if size_mask != SIGSET_SIZE_BYTES {
+        return Err(errno::EINVAL);
+    }
+    let flags = OpenFlags::from_bits(flags)
+        .filter(|flags| (OpenFlags::NONBLOCK | OpenFlags::CLOEXEC).contains(*flags))
+        .ok_or(errno::EINVAL)?;
+    let mask = KSignalSet::from_user(mask.check_ptr()?.read());
+
+    let process = local_hart().curr_process();
+    if fd == -1 {
+        let desc = FileDescriptor::new(File::SignalFd(Arc::new(SignalFd::new(mask))), flags | OpenFlags::RDONLY);
+        let fd = process
+            .lock_inner_with(|inner| inner.fd_table.add(desc))
+            .ok_or(errno::EMFILE)?;
+        return Ok(fd as isize);
+    }
+    let fd = usize::try_from(fd).map_err(|_| errno::EBADF)?;
+    process.lock_inner_with(|inner| match inner.fd_table.get(fd).map(Deref::deref) {
+        Some(File::SignalFd(signalfd)) => {
+            signalfd.set_mask(mask);
+            Ok(fd as isize)
+        }
+        Some(_) => Err(errno::EINVAL),
+        None => Err(errno::EBADF),
+    })
+}
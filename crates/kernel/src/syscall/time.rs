@@ -1,14 +1,26 @@
-use core::time::Duration;
+use core::{ops::Deref, time::Duration};
 
-use common::constant::{MICRO_PER_SEC, NANO_PER_SEC};
 use defines::{
-    error::KResult,
-    misc::{TimeSpec, TimeVal, Tms},
+    error::{errno, KResult},
+    fs::{OpenFlags, TimerFdSetFlags},
+    misc::{
+        ITimerSpec, TimeSpec, TimeVal, Tms, CLOCK_BOOTTIME, CLOCK_MONOTONIC, CLOCK_MONOTONIC_COARSE,
+        CLOCK_MONOTONIC_RAW, CLOCK_REALTIME, CLOCK_REALTIME_COARSE,
+    },
 };
 use executor::time;
-use libkernel::memory::UserCheck;
+use libkernel::{
+    fs::{
+        file::{File, FileDescriptor},
+        timerfd::TimerFd,
+    },
+    hart::local_hart,
+    memory::UserCheck,
+    process,
+};
+use triomphe::Arc;
 
-/// 获取自 Epoch 以来所过的时间（不过目前实现中似乎是自开机或复位以来时间）
+/// 获取自 Epoch 以来所过的时间
 ///
 /// 参数：
 /// - `ts` 要设置的时间值
@@ -17,33 +29,62 @@ pub fn sys_get_time_of_day(tv: UserCheck<TimeVal>, _tz: usize) -> KResult {
     // 根据 man 所言，时区参数 `tz` 已经过时了，通常应当是 `NULL`。
     assert_eq!(_tz, 0);
     let tv = unsafe { tv.check_ptr_mut()? };
-    let us = riscv_time::get_time_us();
+    let now = time::real_time();
     tv.write(TimeVal {
-        sec: us / MICRO_PER_SEC,
-        usec: us % MICRO_PER_SEC,
+        sec: now.as_secs() as usize,
+        usec: now.subsec_micros() as usize,
     });
     Ok(0)
 }
 
-/// 全局时钟，或者说挂钟
-const CLOCK_REALTIME: usize = 0;
+/// 设置挂钟时间，需要特权。`tv` 为 NULL 时什么也不做
+///
+/// 参数：
+/// - `tv` 新的时间
+/// - `tz` 时区结构，已经过时，忽略
+pub fn sys_settimeofday(tv: Option<UserCheck<TimeVal>>, _tz: usize) -> KResult {
+    let Some(tv) = tv else {
+        return Ok(0);
+    };
+    let tv = tv.check_ptr()?.read();
+    if tv.usec >= 1_000_000 {
+        return Err(errno::EINVAL);
+    }
+    set_real_time(Duration::new(tv.sec as u64, tv.usec as u32 * 1000))
+}
 
 /// 同样是获取时间，不过 `TimeSpec` 精度为 ns。
 ///
-/// 可以有不同的时钟，但目前只支持挂钟 (`CLOCK_REALTIME`)。
+/// 支持挂钟和单调时钟，暂不支持进程、线程的 CPU 时间等时钟
 ///
 /// 参数：
-/// - `clock_id` 时钟 id，目前仅为 `CLOCK_READTIME`
+/// - `clock_id` 时钟 id
 /// - `tp` 指向要设置的用户指针
-pub fn sys_clock_gettime(_clock_id: usize, ts: UserCheck<TimeSpec>) -> KResult {
-    // TODO: 目前只考虑挂钟时间
-    assert_eq!(_clock_id, CLOCK_REALTIME);
+pub fn sys_clock_gettime(clock_id: usize, ts: UserCheck<TimeSpec>) -> KResult {
+    let now = match clock_id {
+        CLOCK_REALTIME | CLOCK_REALTIME_COARSE => time::real_time(),
+        CLOCK_MONOTONIC | CLOCK_MONOTONIC_RAW | CLOCK_MONOTONIC_COARSE | CLOCK_BOOTTIME => time::curr_time(),
+        _ => return Err(errno::EINVAL),
+    };
     let ts = unsafe { ts.check_ptr_mut()? };
-    let ns = riscv_time::get_time_ns();
-    ts.write(TimeSpec {
-        sec: (ns / NANO_PER_SEC) as i64,
-        nsec: (ns % NANO_PER_SEC) as i64,
-    });
+    ts.write(TimeSpec::from(now));
+    Ok(0)
+}
+
+/// 设置时钟，只能设置挂钟，且需要特权
+pub fn sys_clock_settime(clock_id: usize, ts: UserCheck<TimeSpec>) -> KResult {
+    if clock_id != CLOCK_REALTIME {
+        return Err(errno::EINVAL);
+    }
+    let ts = ts.check_ptr()?.read();
+    set_real_time(Duration::try_from(ts)?)
+}
+
+fn set_real_time(now: Duration) -> KResult {
+    if !process::curr_cred().is_privileged() {
+        return Err(errno::EPERM);
+    }
+    time::set_real_time(now);
     Ok(0)
 }
 
@@ -74,3 +115,78 @@ pub async fn sys_nanosleep(req: UserCheck<TimeSpec>) -> KResult {
     time::sleep(req).await;
     Ok(0)
 }
+
+/// 创建一个定时器文件，返回其文件描述符。定时器到期后文件可读，读出的是到期次数
+///
+/// 参数：
+/// - `clock_id` 支持 `CLOCK_REALTIME`、`CLOCK_MONOTONIC` 和 `CLOCK_BOOTTIME`，否则返回 `EINVAL`
+/// - `flags` 可以是 `TFD_NONBLOCK` 和 `TFD_CLOEXEC`，即 `O_NONBLOCK` 和 `O_CLOEXEC`
+pub fn sys_timerfd_create(clock_id: usize, flags: u32) -> KResult {
+    let realtime = match clock_id {
+        CLOCK_REALTIME => true,
+        CLOCK_MONOTONIC | CLOCK_BOOTTIME => false,
+        _ => return Err(errno::EINVAL),
+    };
+    let flags = OpenFlags::from_bits(flags)
+        .filter(|flags| (OpenFlags::NONBLOCK | OpenFlags::CLOEXEC).contains(*flags))
+        .ok_or(errno::EINVAL)?;
+    let desc = FileDescriptor::new(
+        File::TimerFd(Arc::new(TimerFd::new(realtime))),
+        flags | OpenFlags::RDONLY,
+    );
+    let fd = local_hart()
+        .curr_process()
+        .lock_inner_with(|inner| inner.fd_table.add(desc))
+        .ok_or(errno::EMFILE)?;
+    Ok(fd as isize)
+}
+
+/// 启动或停止定时器
+///
+/// 参数：
+/// - `fd` 是定时器文件
+/// - `flags` 见 [`TimerFdSetFlags`]
+/// - `new_value` 是新的设置，其中 `value` 为 0 表示停止定时器
+/// - `old_value` 如果非 NULL，则将旧的设置写入其中
+pub fn sys_timerfd_settime(
+    fd: usize,
+    flags: u32,
+    new_value: UserCheck<ITimerSpec>,
+    old_value: Option<UserCheck<ITimerSpec>>,
+) -> KResult {
+    let flags = TimerFdSetFlags::from_bits(flags).ok_or(errno::EINVAL)?;
+    // TODO: [low] 支持 `TFD_TIMER_CANCEL_ON_SET`，挂钟被修改时取消定时器
+    let new_value = new_value.check_ptr()?.read();
+    let value = Duration::try_from(new_value.value)?;
+    let interval = Duration::try_from(new_value.interval)?;
+    let timerfd = get_timerfd(fd)?;
+    let (old_remaining, old_interval) = timerfd.set(value, interval, flags.contains(TimerFdSetFlags::ABSTIME));
+    if let Some(old_value) = old_value {
+        unsafe { old_value.check_ptr_mut()? }.write(ITimerSpec {
+            interval: TimeSpec::from(old_interval),
+            value: TimeSpec::from(old_remaining),
+        });
+    }
+    Ok(0)
+}
+
+/// 获取定时器当前的设置，其中 `value` 是距离下一次到期的时间
+pub fn sys_timerfd_gettime(fd: usize, curr_value: UserCheck<ITimerSpec>) -> KResult {
+    let (remaining, interval) = get_timerfd(fd)?.get();
+    unsafe { curr_value.check_ptr_mut()? }.write(ITimerSpec {
+        interval: TimeSpec::from(interval),
+        value: TimeSpec::from(remaining),
+    });
+    Ok(0)
+}
+
+/// 获取 `fd` 对应的定时器文件。`fd` 未打开返回 `EBADF`，不是定时器文件返回 `EINVAL`
+fn get_timerfd(fd: usize) -> KResult<Arc<TimerFd>> {
+    local_hart()
+        .curr_process()
+        .lock_inner_with(|inner| match inner.fd_table.get(fd).map(Deref::deref) {
+            Some(File::TimerFd(timerfd)) => Ok(Arc::clone(timerfd)),
+            Some(_) => Err(errno::EINVAL),
+            None => Err(errno::EBADF),
+        })
+}
@@ -1,14 +1,27 @@
-use alloc::collections::{btree_map::Entry, BTreeMap};
-use core::hash::Hash;
-
-use defines::error::{errno, KResult};
+use alloc::{
+    collections::{btree_map::Entry, BTreeMap},
+    vec::Vec,
+};
+use core::{
+    hash::Hash,
+    sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+};
+
+use defines::{
+    error::{errno, KResult},
+    fs::{FaccessatMode, InotifyMask, StatMode},
+};
 use ecow::EcoString;
 use executor::time;
 use klocks::{SpinMutex, SpinMutexGuard};
 use smallvec::SmallVec;
 use triomphe::Arc;
 
-use super::inode::{DynBytesInode, DynDirInode, DynInode, InodeMeta, InodeMode};
+use super::{
+    inode::{DynBytesInode, DynDirInode, DynInode, InodeMeta, InodeMode},
+    inotify,
+};
+use crate::{fs, process};
 
 #[derive(Clone)]
 pub enum DEntry {
@@ -55,6 +68,251 @@ impl DEntry {
     pub fn is_dir(&self) -> bool {
         matches!(self, DEntry::Dir(_))
     }
+
+    pub fn path(&self) -> EcoString {
+        match self {
+            DEntry::Dir(dir) => dir.path(),
+            DEntry::Bytes(bytes) => bytes.path(),
+        }
+    }
+
+    /// 将 inode 元数据（权限、所有者、时间）的修改写回后备存储
+    pub fn write_meta(&self) -> KResult<()> {
+        match self {
+            DEntry::Dir(dir) => dir.inode.write_meta(),
+            DEntry::Bytes(bytes) => bytes.inode.write_meta(),
+        }
+    }
+
+    fn inode(&self) -> DynInode {
+        match self {
+            DEntry::Dir(dir) => DynInode::Dir(Arc::clone(&dir.inode)),
+            DEntry::Bytes(bytes) => DynInode::Bytes(Arc::clone(&bytes.inode)),
+        }
+    }
+
+    fn cache(&self) -> &CacheState {
+        match self {
+            DEntry::Dir(dir) => &dir.cache,
+            DEntry::Bytes(bytes) => &bytes.cache,
+        }
+    }
+
+    /// 除了所在目录之外没有其他引用。对目录来说，这也意味着没有缓存的子目录项
+    fn is_unused(&self) -> bool {
+        match self {
+            DEntry::Dir(dir) => Arc::count(dir) == 1,
+            DEntry::Bytes(bytes) => Arc::count(bytes) == 1,
+        }
+    }
+}
+
+/// 可回收的目录项（包括负目录项）超过该数量时，回收其中最久未使用的部分
+const MAX_RECLAIMABLE: usize = 4096;
+/// 每次回收的目标数量
+const RECLAIM_TARGET: usize = MAX_RECLAIMABLE / 4 * 3;
+
+/// 全局的目录项缓存统计和回收状态。
+///
+/// 目录项本身仍然按目录树缓存在各个 [`DEntryDir`] 中，回收时遍历整棵树，近似 LRU 地丢弃未被引用的目录项
+struct DEntryCache {
+    nr_dentry: AtomicUsize,
+    /// 可以被回收的正目录项数，见 [`CacheState::reclaimable`]
+    nr_reclaimable: AtomicUsize,
+    nr_negative: AtomicUsize,
+    /// 逻辑时钟，用于记录目录项最近一次使用的先后
+    clock: AtomicU64,
+    /// 可回收的目录项超过该数量时才开始回收
+    next_shrink: AtomicUsize,
+    shrinking: AtomicBool,
+}
+
+static DCACHE: DEntryCache = DEntryCache {
+    nr_dentry: AtomicUsize::new(0),
+    nr_reclaimable: AtomicUsize::new(0),
+    nr_negative: AtomicUsize::new(0),
+    clock: AtomicU64::new(0),
+    next_shrink: AtomicUsize::new(MAX_RECLAIMABLE),
+    shrinking: AtomicBool::new(false),
+};
+
+impl DEntryCache {
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn nr_reclaimable(&self) -> usize {
+        self.nr_reclaimable.load(Ordering::Relaxed) + self.nr_negative.load(Ordering::Relaxed)
+    }
+
+    /// 可回收的目录项过多时进行回收。调用者不能持有任何目录的 `children` 锁
+    fn shrink_if_needed(&self) {
+        let nr_reclaimable = self.nr_reclaimable();
+        if nr_reclaimable <= self.next_shrink.load(Ordering::Relaxed) || self.shrinking.swap(true, Ordering::Acquire) {
+            return;
+        }
+        if let Some(vfs) = fs::VirtFileSystem::try_instance() {
+            self.shrink(&vfs.root_dir(), nr_reclaimable - RECLAIM_TARGET);
+        }
+        // 大部分目录项仍被引用时，推迟下一次回收，以免每次查找都遍历整棵目录树
+        let remaining = self.nr_reclaimable();
+        self.next_shrink.store(
+            usize::max(MAX_RECLAIMABLE, remaining + MAX_RECLAIMABLE / 4),
+            Ordering::Relaxed,
+        );
+        self.shrinking.store(false, Ordering::Release);
+    }
+
+    /// 回收 `root` 下最久未使用的至多 `count` 个目录项
+    fn shrink(&self, root: &Arc<DEntryDir>, count: usize) {
+        // 第一遍找出第 `count` 久未使用的时刻，不晚于它的都会被回收
+        let mut stamps = Vec::new();
+        for_each_dir(root, &mut |dir| {
+            let children = dir.children.lock();
+            stamps.extend(
+                children
+                    .values()
+                    .map(DEntry::cache)
+                    .filter(|cache| cache.reclaimable)
+                    .map(CacheState::last_used),
+            );
+            stamps.extend(dir.negatives.lock().0.values().copied());
+        });
+        let count = usize::min(count, stamps.len());
+        if count == 0 {
+            return;
+        }
+        let (_, &mut cutoff, _) = stamps.select_nth_unstable(count - 1);
+        drop(stamps);
+        // 第二遍按后序回收，子目录项全被回收的目录自身也可以在同一遍中被回收
+        for_each_dir(root, &mut |dir| dir.reclaim_children(cutoff));
+    }
+}
+
+/// 后序遍历 `dir` 下缓存的所有目录。
+///
+/// 不会同时持有父子目录的锁。访问一个目录时，其子目录不会被遍历过程额外引用
+fn for_each_dir(dir: &Arc<DEntryDir>, f: &mut impl FnMut(&Arc<DEntryDir>)) {
+    let subdirs = dir
+        .children
+        .lock()
+        .values()
+        .filter_map(|child| match child {
+            DEntry::Dir(subdir) => Some(Arc::clone(subdir)),
+            DEntry::Bytes(_) => None,
+        })
+        .collect::<Vec<_>>();
+    for subdir in subdirs {
+        for_each_dir(&subdir, f);
+    }
+    f(dir);
+}
+
+/// `/proc/sys/fs/dentry-state` 中的统计
+pub struct DEntryState {
+    pub nr_dentry: usize,
+    /// 未被引用、可以立即回收的目录项数
+    pub nr_unused: usize,
+    pub nr_negative: usize,
+}
+
+pub fn dentry_state() -> DEntryState {
+    let mut nr_unused = 0;
+    if let Some(vfs) = fs::VirtFileSystem::try_instance() {
+        for_each_dir(&vfs.root_dir(), &mut |dir| {
+            nr_unused += dir
+                .children
+                .lock()
+                .values()
+                .filter(|child| child.cache().reclaimable && child.is_unused())
+                .count();
+        });
+    }
+    DEntryState {
+        nr_dentry: DCACHE.nr_dentry.load(Ordering::Relaxed),
+        nr_unused,
+        nr_negative: DCACHE.nr_negative.load(Ordering::Relaxed),
+    }
+}
+
+/// dentry 在目录项缓存中的状态
+struct CacheState {
+    /// 最近一次被查找的时刻
+    last_used: AtomicU64,
+    /// 所在目录允许回收，见 [`DirInodeBackend::dentry_reclaimable()`]
+    ///
+    /// [`DirInodeBackend::dentry_reclaimable()`]: super::inode::DirInodeBackend::dentry_reclaimable
+    reclaimable: bool,
+}
+
+impl CacheState {
+    fn new(parent: Option<&DEntryDir>) -> Self {
+        let reclaimable = parent.is_some_and(|parent| parent.inode.dentry_reclaimable());
+        DCACHE.nr_dentry.fetch_add(1, Ordering::Relaxed);
+        if reclaimable {
+            DCACHE.nr_reclaimable.fetch_add(1, Ordering::Relaxed);
+        }
+        Self {
+            last_used: AtomicU64::new(DCACHE.tick()),
+            reclaimable,
+        }
+    }
+
+    fn last_used(&self) -> u64 {
+        self.last_used.load(Ordering::Relaxed)
+    }
+
+    fn touch(&self) {
+        self.last_used.store(DCACHE.tick(), Ordering::Relaxed);
+    }
+}
+
+impl Drop for CacheState {
+    fn drop(&mut self) {
+        DCACHE.nr_dentry.fetch_sub(1, Ordering::Relaxed);
+        if self.reclaimable {
+            DCACHE.nr_reclaimable.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// 目录中缓存的负目录项，即查找失败的名字及其最近一次使用的时刻
+struct Negatives(BTreeMap<EcoString, u64>);
+
+impl Negatives {
+    fn insert(&mut self, name: EcoString) {
+        if self.0.insert(name, DCACHE.tick()).is_none() {
+            DCACHE.nr_negative.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn remove(&mut self, name: &str) {
+        if self.0.remove(name).is_some() {
+            DCACHE.nr_negative.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    /// 是否缓存了 `name` 查找失败，是则更新其使用时刻
+    fn touch(&mut self, name: &str) -> bool {
+        let Some(last_used) = self.0.get_mut(name) else {
+            return false;
+        };
+        *last_used = DCACHE.tick();
+        true
+    }
+
+    /// 丢弃最近使用时刻不晚于 `cutoff` 的负目录项
+    fn reclaim(&mut self, cutoff: u64) {
+        let old_len = self.0.len();
+        self.0.retain(|_, last_used| *last_used > cutoff);
+        DCACHE.nr_negative.fetch_sub(old_len - self.0.len(), Ordering::Relaxed);
+    }
+}
+
+impl Drop for Negatives {
+    fn drop(&mut self) {
+        DCACHE.nr_negative.fetch_sub(self.0.len(), Ordering::Relaxed);
+    }
 }
 
 pub struct DEntryDir {
@@ -62,19 +320,77 @@ pub struct DEntryDir {
     name: EcoString,
     children: SpinMutex<BTreeMap<EcoString, DEntry>>,
     inode: Arc<DynDirInode>,
+    /// 绑定挂载（`MS_BIND`）得到的目录树中，对应的原目录。
+    ///
+    /// 这类目录的查找和修改都转发给原目录，本目录只缓存对应的 dentry
+    bind_source: Option<Arc<DEntryDir>>,
+    /// 只在允许缓存负目录项的非绑定目录中缓存。创建或重命名出同名的目录项时移除。
+    ///
+    /// 加锁顺序在 `children` 之后
+    negatives: SpinMutex<Negatives>,
+    /// 打开本目录的目录文件数。不为 0 时不回收子目录项，以免 `getdents` 的位置错乱
+    open_count: AtomicUsize,
+    cache: CacheState,
 }
 
 impl DEntryDir {
     pub fn new(parent: Option<Arc<DEntryDir>>, name: EcoString, inode: Arc<DynDirInode>) -> Self {
         Self {
+            cache: CacheState::new(parent.as_deref()),
             parent,
             name,
             children: SpinMutex::new(BTreeMap::new()),
             inode,
+            bind_source: None,
+            negatives: SpinMutex::new(Negatives(BTreeMap::new())),
+            open_count: AtomicUsize::new(0),
+        }
+    }
+
+    /// 创建一个绑定到 `source` 的目录，`source` 下的目录树会在本目录下可见
+    pub fn new_bind(parent: Option<Arc<DEntryDir>>, name: EcoString, source: Arc<DEntryDir>) -> Self {
+        Self {
+            cache: CacheState::new(parent.as_deref()),
+            parent,
+            name,
+            children: SpinMutex::new(BTreeMap::new()),
+            inode: Arc::clone(&source.inode),
+            bind_source: Some(source),
+            negatives: SpinMutex::new(Negatives(BTreeMap::new())),
+            open_count: AtomicUsize::new(0),
+        }
+    }
+
+    /// 本目录是否缓存查找失败的结果。绑定目录的查找转发给原目录，由原目录缓存
+    fn caches_negative(&self) -> bool {
+        self.bind_source.is_none() && self.inode.cache_negative()
+    }
+
+    /// 为原目录中的子目录 `source` 创建本目录下对应的绑定目录
+    fn bind_dir(self: &Arc<Self>, name: EcoString, source: Arc<DEntryDir>) -> Arc<DEntryDir> {
+        Arc::new(DEntryDir::new_bind(Some(Arc::clone(self)), name, source))
+    }
+
+    /// 为原目录中的文件 `source` 创建本目录下对应的 dentry，二者共享 inode
+    fn bind_bytes(self: &Arc<Self>, name: EcoString, source: &DEntryBytes) -> Arc<DEntryBytes> {
+        Arc::new(DEntryBytes::new(Arc::clone(self), name, Arc::clone(&source.inode)))
+    }
+
+    fn bind_child(self: &Arc<Self>, name: EcoString, source: DEntry) -> DEntry {
+        match source {
+            DEntry::Dir(dir) => DEntry::Dir(self.bind_dir(name, dir)),
+            DEntry::Bytes(bytes) => DEntry::Bytes(self.bind_bytes(name, &bytes)),
         }
     }
 
     pub fn lookup(self: &Arc<Self>, component: impl Into<EcoString> + AsRef<str>) -> Option<DEntry> {
+        let dentry = self.lookup_locked(component);
+        DCACHE.shrink_if_needed();
+        dentry
+    }
+
+    /// 同 [`Self::lookup()`]，但不会回收目录项，因此可以在持有其他目录的 `children` 锁时调用
+    fn lookup_locked(self: &Arc<Self>, component: impl Into<EcoString> + AsRef<str>) -> Option<DEntry> {
         fn special(parent: &Arc<DEntryDir>, component: &str) -> Option<DEntry> {
             let curr_time = time::curr_time_spec();
             parent
@@ -91,32 +407,50 @@ impl DEntryDir {
         }
         fn general(parent: &Arc<DEntryDir>, component: EcoString) -> Option<DEntry> {
             let mut children = parent.children.lock();
-            let entry = children.entry(component);
-            match entry {
-                Entry::Vacant(vacant) => {
-                    let new_inode = parent.inode.lookup(vacant.key())?;
-                    let new_dentry = match new_inode {
-                        DynInode::Dir(dir) => DEntry::Dir(Arc::new(DEntryDir::new(
-                            Some(Arc::clone(parent)),
-                            vacant.key().clone(),
-                            dir,
-                        ))),
-                        DynInode::Bytes(bytes) => DEntry::Bytes(Arc::new(DEntryBytes::new(
-                            Arc::clone(parent),
-                            vacant.key().clone(),
-                            bytes,
-                        ))),
-                    };
-                    vacant.insert(new_dentry.clone());
-                    Some(new_dentry)
+            if let Some(child) = children.get(&component) {
+                if parent.inode.revalidate(&component) {
+                    child.cache().touch();
+                    return Some(child.clone());
                 }
-                Entry::Occupied(occupied) => Some(occupied.get().clone()),
+                children.remove(&component);
+            }
+            let mut negatives = parent.negatives.lock();
+            if negatives.touch(&component) {
+                return None;
             }
+            let new_dentry = if let Some(source) = &parent.bind_source {
+                parent.bind_child(component.clone(), source.lookup_locked(component.clone())?)
+            } else {
+                let Some(inode) = parent.inode.lookup(&component) else {
+                    if parent.caches_negative() {
+                        negatives.insert(component);
+                    }
+                    return None;
+                };
+                match inode {
+                    DynInode::Dir(dir) => DEntry::Dir(Arc::new(DEntryDir::new(
+                        Some(Arc::clone(parent)),
+                        component.clone(),
+                        dir,
+                    ))),
+                    DynInode::Bytes(bytes) => {
+                        DEntry::Bytes(Arc::new(DEntryBytes::new(Arc::clone(parent), component.clone(), bytes)))
+                    }
+                }
+            };
+            children.insert(component, new_dentry.clone());
+            Some(new_dentry)
         }
         special(self, component.as_ref()).or_else(|| general(self, component.into()))
     }
 
-    pub fn mkdir(self: &Arc<Self>, component: EcoString) -> KResult<Arc<DEntryDir>> {
+    /// 创建子目录，`perm` 是已经去除了 umask 的权限位
+    pub fn mkdir(self: &Arc<Self>, component: EcoString, perm: StatMode) -> KResult<Arc<DEntryDir>> {
+        if let Some(source) = &self.bind_source {
+            let dir = self.bind_dir(component.clone(), source.mkdir(component.clone(), perm)?);
+            self.lock_children().insert(component, DEntry::Dir(Arc::clone(&dir)));
+            return Ok(dir);
+        }
         if component == "." || component == ".." {
             return Err(errno::EINVAL);
         }
@@ -125,13 +459,26 @@ impl DEntryDir {
             Entry::Vacant(vacant) => vacant,
             Entry::Occupied(_) => return Err(errno::EEXIST),
         };
+        self.may_modify()?;
         let dir = self.inode.mkdir(vacant.key())?;
+        // 子目录的 `..` 指向本目录
+        self.inode.meta().lock_inner_with(|inner| inner.nlink += 1);
+        self.init_new_inode(dir.meta(), perm);
+        dir.write_meta()?;
         let dentry = Arc::new(DEntryDir::new(Some(Arc::clone(self)), vacant.key().clone(), dir));
         vacant.insert(DEntry::Dir(Arc::clone(&dentry)));
+        self.negatives.lock().remove(dentry.name());
+        self.notify_child(InotifyMask::IN_CREATE | InotifyMask::IN_ISDIR, 0, dentry.name());
         Ok(dentry)
     }
 
-    pub fn mknod(self: &Arc<Self>, component: EcoString, mode: InodeMode) -> KResult<Arc<DEntryBytes>> {
+    /// 创建非目录文件，`perm` 是已经去除了 umask 的权限位
+    pub fn mknod(self: &Arc<Self>, component: EcoString, mode: InodeMode, perm: StatMode) -> KResult<Arc<DEntryBytes>> {
+        if let Some(source) = &self.bind_source {
+            let file = self.bind_bytes(component.clone(), &source.mknod(component.clone(), mode, perm)?);
+            self.lock_children().insert(component, DEntry::Bytes(Arc::clone(&file)));
+            return Ok(file);
+        }
         if matches!(mode, InodeMode::SymbolLink | InodeMode::Dir) || component == "." || component == ".." {
             return Err(errno::EINVAL);
         }
@@ -140,20 +487,104 @@ impl DEntryDir {
             Entry::Vacant(vacant) => vacant,
             Entry::Occupied(_) => return Err(errno::EEXIST),
         };
+        self.may_modify()?;
         let file = self.inode.mknod(vacant.key(), mode)?;
+        self.init_new_inode(file.meta(), perm);
+        file.write_meta()?;
         let dentry = Arc::new(DEntryBytes::new(Arc::clone(self), vacant.key().clone(), file));
         vacant.insert(DEntry::Bytes(dentry.clone()));
+        self.negatives.lock().remove(dentry.name());
+        self.notify_child(InotifyMask::IN_CREATE, 0, dentry.name());
+        Ok(dentry)
+    }
+
+    pub fn symlink(self: &Arc<Self>, component: EcoString, target: &str) -> KResult<Arc<DEntryBytes>> {
+        if let Some(source) = &self.bind_source {
+            let link = self.bind_bytes(component.clone(), &source.symlink(component.clone(), target)?);
+            self.lock_children().insert(component, DEntry::Bytes(Arc::clone(&link)));
+            return Ok(link);
+        }
+        if component == "." || component == ".." {
+            return Err(errno::EEXIST);
+        }
+        let mut children = self.children.lock();
+        let vacant = match children.entry(component) {
+            Entry::Vacant(vacant) => vacant,
+            Entry::Occupied(_) => return Err(errno::EEXIST),
+        };
+        self.may_modify()?;
+        let link = self.inode.symlink(vacant.key(), target)?;
+        // 符号链接本身的权限总是 0777，不受 umask 影响
+        self.init_new_inode(link.meta(), StatMode::S_IRWXU | StatMode::S_IRWXG | StatMode::S_IRWXO);
+        link.write_meta()?;
+        let dentry = Arc::new(DEntryBytes::new(Arc::clone(self), vacant.key().clone(), link));
+        vacant.insert(DEntry::Bytes(dentry.clone()));
+        self.negatives.lock().remove(dentry.name());
+        self.notify_child(InotifyMask::IN_CREATE, 0, dentry.name());
         Ok(dentry)
     }
 
+    /// 在本目录下创建名为 `component`、指向 `target` 的硬链接
+    pub fn link(self: &Arc<Self>, component: EcoString, target: &Arc<DEntryBytes>) -> KResult<Arc<DEntryBytes>> {
+        if let Some(source) = &self.bind_source {
+            let link = self.bind_bytes(component.clone(), &source.link(component.clone(), target)?);
+            self.lock_children().insert(component, DEntry::Bytes(Arc::clone(&link)));
+            return Ok(link);
+        }
+        if component == "." || component == ".." {
+            return Err(errno::EEXIST);
+        }
+        let mut children = self.children.lock();
+        let vacant = match children.entry(component) {
+            Entry::Vacant(vacant) => vacant,
+            Entry::Occupied(_) => return Err(errno::EEXIST),
+        };
+        self.may_modify()?;
+        self.inode.link(vacant.key(), target.inode())?;
+        let curr_time = time::curr_time_spec();
+        target.inode().meta().lock_inner_with(|inner| {
+            inner.nlink += 1;
+            inner.change_time = curr_time;
+        });
+        let dentry = Arc::new(DEntryBytes::new(
+            Arc::clone(self),
+            vacant.key().clone(),
+            Arc::clone(target.inode()),
+        ));
+        vacant.insert(DEntry::Bytes(dentry.clone()));
+        self.negatives.lock().remove(dentry.name());
+        self.notify_child(InotifyMask::IN_CREATE, 0, dentry.name());
+        Ok(dentry)
+    }
+
+    /// 移除本目录下名为 `name` 的非目录文件的链接。
+    ///
+    /// 文件的存储在链接数归零且没有其他引用时才由后端释放
     pub fn unlink(self: &Arc<Self>, name: &str) -> KResult<()> {
+        if let Some(source) = &self.bind_source {
+            source.unlink(name)?;
+            self.lock_children().remove(name);
+            return Ok(());
+        }
         if name == "." || name == ".." {
             return Err(errno::EINVAL);
         }
         let mut children = self.lock_children();
-        children.remove(name);
-        // TODO: [low] 其实这里要考虑硬链接之类的问题？
-        self.inode.unlink(name)
+        match children.get(name) {
+            Some(child) => self.may_delete(child.meta())?,
+            None => self.may_modify()?,
+        }
+        self.inode.unlink(name)?;
+        if let Some(child) = children.remove(name) {
+            let curr_time = time::curr_time_spec();
+            child.meta().lock_inner_with(|inner| {
+                inner.nlink = inner.nlink.saturating_sub(1);
+                inner.change_time = curr_time;
+            });
+        }
+        self.remember_negative(EcoString::from(name));
+        self.notify_child(InotifyMask::IN_DELETE, 0, name);
+        Ok(())
     }
 
     pub fn remove_dir(&self, child: Arc<DEntryDir>) -> KResult<()> {
@@ -161,18 +592,92 @@ impl DEntryDir {
             Some((self as *const DEntryDir).addr()),
             child.parent.as_ref().map(|d| d.as_ptr().addr())
         );
-        let mut children = self.lock_children();
-        // 只允许移除空目录，也就是除了 `.` 和 `..` 没有别的条目
-        if children.len() == 2 {
+        if let Some(source) = &self.bind_source {
+            let source_child = child.bind_source.clone().ok_or(errno::EBUSY)?;
+            source.remove_dir(source_child)?;
+            self.lock_children().remove(child.name());
+            return Ok(());
+        }
+        // 只允许移除空目录。磁盘文件系统的目录项可能还没有全部读入，后端还会再检查一次
+        if !child.lock_children().is_empty() {
             return Err(errno::ENOTEMPTY);
         }
+        self.may_delete(child.inode.meta())?;
+        let mut children = self.lock_children();
+        self.inode.unlink(child.name())?;
         children.remove(child.name());
+        self.remember_negative(child.name().clone());
+        self.inode
+            .meta()
+            .lock_inner_with(|inner| inner.nlink = inner.nlink.saturating_sub(1));
+        child.inode.meta().lock_inner_with(|inner| inner.nlink = 0);
+        self.notify_child(InotifyMask::IN_DELETE | InotifyMask::IN_ISDIR, 0, child.name());
+        Ok(())
+    }
+
+    /// 向本目录的 inotify 监视报告子目录项 `name` 上的事件
+    fn notify_child(&self, mask: InotifyMask, cookie: u32, name: &str) {
+        self.inode.meta().watches().notify(mask, cookie, Some(name));
+    }
+
+    /// 检查当前进程能否在本目录中创建或删除目录项，需要写和执行权限
+    fn may_modify(&self) -> KResult<()> {
+        fs::check_access(self.inode.meta(), FaccessatMode::W_OK | FaccessatMode::X_OK)
+    }
+
+    /// 检查当前进程能否删除本目录中 inode 为 `child` 的目录项。
+    ///
+    /// 设置了 sticky 位的目录中，只有目录或文件的所有者才能删除
+    fn may_delete(&self, child: &InodeMeta) -> KResult<()> {
+        self.may_modify()?;
+        let cred = process::curr_cred();
+        let (perm, dir_owner) = self.inode.meta().lock_inner_with(|inner| (inner.perm, inner.uid));
+        let child_owner = child.lock_inner_with(|inner| inner.uid);
+        let euid = cred.uid.effective;
+        if perm.contains(StatMode::S_ISVTX) && !cred.is_privileged() && euid != dir_owner && euid != child_owner {
+            return Err(errno::EPERM);
+        }
         Ok(())
     }
 
+    /// 设置本目录下新建的 inode 的权限和所有者。
+    ///
+    /// 所有者为当前进程的有效用户；若本目录设置了 set-group-ID 位，则组继承本目录的组，新目录也继承该位
+    fn init_new_inode(&self, meta: &InodeMeta, mut perm: StatMode) {
+        let cred = process::curr_cred();
+        let (dir_perm, dir_gid) = self.inode.meta().lock_inner_with(|inner| (inner.perm, inner.gid));
+        let gid = if dir_perm.contains(StatMode::S_ISGID) {
+            if meta.mode() == InodeMode::Dir {
+                perm |= StatMode::S_ISGID;
+            }
+            dir_gid
+        } else {
+            cred.gid.effective
+        };
+        meta.lock_inner_with(|inner| {
+            inner.perm = perm;
+            inner.uid = cred.uid.effective;
+            inner.gid = gid;
+        });
+    }
+
     pub fn read_dir(self: &Arc<Self>) -> KResult<()> {
         let _enter = debug_span!("read_dir", name = self.name).entered();
-        self.inode.read_dir(self)
+        let Some(source) = &self.bind_source else {
+            self.inode.read_dir(self)?;
+            DCACHE.shrink_if_needed();
+            return Ok(());
+        };
+        source.read_dir()?;
+        let source_children = source.lock_children().clone();
+        let mut children = self.lock_children();
+        for (name, child) in source_children {
+            if let Entry::Vacant(vacant) = children.entry(name) {
+                let new_dentry = self.bind_child(vacant.key().clone(), child);
+                vacant.insert(new_dentry);
+            }
+        }
+        Ok(())
     }
 
     pub fn parent(&self) -> Option<&Arc<DEntryDir>> {
@@ -187,6 +692,57 @@ impl DEntryDir {
         self.children.lock()
     }
 
+    /// 本目录被打开为目录文件，关闭前不回收子目录项
+    pub(super) fn pin_children(&self) {
+        self.open_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn unpin_children(&self) {
+        self.open_count.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// 回收最近使用时刻不晚于 `cutoff` 且未被引用的子目录项和负目录项
+    fn reclaim_children(&self, cutoff: u64) {
+        self.negatives.lock().reclaim(cutoff);
+        if self.open_count.load(Ordering::Relaxed) > 0 {
+            return;
+        }
+        // 回收的 dentry 在释放锁之后再析构
+        let mut reclaimed = Vec::new();
+        // 未被引用的 dentry 只能通过本目录再次得到，因此持有锁时检查的结果不会改变
+        self.children.lock().retain(|_, child| {
+            let cache = child.cache();
+            if !cache.reclaimable || cache.last_used() > cutoff || !child.is_unused() {
+                return true;
+            }
+            reclaimed.push(child.clone());
+            false
+        });
+        for child in reclaimed {
+            let inode = child.inode();
+            drop(child);
+            self.inode.evict_child(inode);
+        }
+    }
+
+    /// 本目录中的 `name` 被删除或重命名走后，记录为负目录项
+    fn remember_negative(&self, name: EcoString) {
+        if self.caches_negative() {
+            self.negatives.lock().insert(name);
+        }
+    }
+
+    /// 重命名到本目录中的 `name` 后，丢弃同名的负目录项
+    fn forget_negative(&self, name: &str) {
+        self.negatives.lock().remove(name);
+    }
+
+    /// 绑定目录中的重命名转发给原目录后，丢弃两端缓存的 dentry，之后查找时再重新创建
+    fn forget_bound(&self, old_name: &str, new_dir: &DEntryDir, new_name: &str) {
+        self.lock_children().remove(old_name);
+        new_dir.lock_children().remove(new_name);
+    }
+
     pub fn inode(&self) -> &Arc<DynDirInode> {
         &self.inode
     }
@@ -216,6 +772,14 @@ impl DEntryDir {
             // 根目录不许重命名
             return Err(errno::EBUSY);
         };
+        if let Some(source) = &self.bind_source {
+            let new_source = new_dir.bind_source.as_ref().ok_or(errno::EXDEV)?;
+            source.rename(new_source, new_name.clone())?;
+            old_dir.forget_bound(self.name(), new_dir, &new_name);
+            return Ok(0);
+        }
+        old_dir.may_delete(self.inode.meta())?;
+        new_dir.may_modify()?;
         // 按地址顺序加锁，防止死锁
         if old_dir.as_ptr().addr() < new_dir.as_ptr().addr() {
             old_children = old_dir.lock_children();
@@ -226,23 +790,31 @@ impl DEntryDir {
         } else {
             // TODO: 看看能不能优化
             let mut children = new_dir.lock_children();
+            old_dir.inode.rename(self.name(), &new_dir.inode, &new_name)?;
             children.remove(self.name());
-            let new_entry = Arc::new(DEntryDir::new(
-                Some(Arc::clone(new_dir)),
-                new_name.clone(),
-                Arc::clone(self.inode()),
-            ));
-            children.insert(new_name, DEntry::Dir(new_entry));
+            let new_entry = DEntryDir::new(Some(Arc::clone(new_dir)), new_name.clone(), Arc::clone(self.inode()));
+            let replaced = children.insert(new_name.clone(), DEntry::Dir(Arc::new(new_entry)));
+            unlink_replaced(replaced);
+            old_dir.remember_negative(self.name().clone());
+            new_dir.forget_negative(&new_name);
+            notify_move(old_dir, self.name(), new_dir, &new_name, self.inode.meta());
             return Ok(0);
         }
 
+        old_dir.inode.rename(self.name(), &new_dir.inode, &new_name)?;
         old_children.remove(self.name());
-        let new_entry = Arc::new(DEntryDir::new(
-            Some(Arc::clone(new_dir)),
-            new_name.clone(),
-            Arc::clone(self.inode()),
-        ));
-        new_children.insert(new_name, DEntry::Dir(new_entry));
+        let new_entry = DEntryDir::new(Some(Arc::clone(new_dir)), new_name.clone(), Arc::clone(self.inode()));
+        let replaced = new_children.insert(new_name.clone(), DEntry::Dir(Arc::new(new_entry)));
+        unlink_replaced(replaced);
+        // 被移动的目录的 `..` 改为指向新的父目录
+        old_dir
+            .inode
+            .meta()
+            .lock_inner_with(|inner| inner.nlink = inner.nlink.saturating_sub(1));
+        new_dir.inode.meta().lock_inner_with(|inner| inner.nlink += 1);
+        old_dir.remember_negative(self.name().clone());
+        new_dir.forget_negative(&new_name);
+        notify_move(old_dir, self.name(), new_dir, &new_name, self.inode.meta());
         Ok(0)
     }
 }
@@ -251,11 +823,17 @@ pub struct DEntryBytes {
     parent: Arc<DEntryDir>,
     name: EcoString,
     inode: Arc<DynBytesInode>,
+    cache: CacheState,
 }
 
 impl DEntryBytes {
     pub fn new(parent: Arc<DEntryDir>, name: EcoString, inode: Arc<DynBytesInode>) -> Self {
-        Self { parent, name, inode }
+        Self {
+            cache: CacheState::new(Some(&*parent)),
+            parent,
+            name,
+            inode,
+        }
     }
 
     pub fn parent(&self) -> &Arc<DEntryDir> {
@@ -270,7 +848,27 @@ impl DEntryBytes {
         &self.inode
     }
 
+    pub fn path(&self) -> EcoString {
+        let mut path = self.parent.path();
+        if path != "/" {
+            path.push('/');
+        }
+        path.push_str(&self.name);
+        path
+    }
+
     pub fn rename(self: &Arc<Self>, new_dir: &Arc<DEntryDir>, new_name: EcoString) -> KResult {
+        if let Some(source_dir) = &self.parent.bind_source {
+            let new_source = new_dir.bind_source.as_ref().ok_or(errno::EXDEV)?;
+            let Some(DEntry::Bytes(source)) = source_dir.lookup(self.name().clone()) else {
+                return Err(errno::ENOENT);
+            };
+            source.rename(new_source, new_name.clone())?;
+            self.parent.forget_bound(self.name(), new_dir, &new_name);
+            return Ok(0);
+        }
+        self.parent.may_delete(self.inode.meta())?;
+        new_dir.may_modify()?;
         let mut old_children;
         let mut new_children;
         // 按地址顺序加锁，防止死锁
@@ -283,23 +881,50 @@ impl DEntryBytes {
         } else {
             // TODO: 看看能不能优化
             let mut children = new_dir.lock_children();
+            self.parent.inode.rename(self.name(), &new_dir.inode, &new_name)?;
             children.remove(self.name());
-            let new_entry = Arc::new(DEntryBytes::new(
-                Arc::clone(new_dir),
-                new_name.clone(),
-                Arc::clone(self.inode()),
-            ));
-            children.insert(new_name, DEntry::Bytes(new_entry));
+            let new_entry = DEntryBytes::new(Arc::clone(new_dir), new_name.clone(), Arc::clone(self.inode()));
+            let replaced = children.insert(new_name.clone(), DEntry::Bytes(Arc::new(new_entry)));
+            unlink_replaced(replaced);
+            self.parent.remember_negative(self.name().clone());
+            new_dir.forget_negative(&new_name);
+            notify_move(&self.parent, self.name(), new_dir, &new_name, self.inode.meta());
             return Ok(0);
         }
 
+        self.parent.inode.rename(self.name(), &new_dir.inode, &new_name)?;
         old_children.remove(self.name());
-        let new_entry = Arc::new(DEntryBytes::new(
-            Arc::clone(new_dir),
-            new_name.clone(),
-            Arc::clone(self.inode()),
-        ));
-        new_children.insert(new_name, DEntry::Bytes(new_entry));
+        let new_entry = DEntryBytes::new(Arc::clone(new_dir), new_name.clone(), Arc::clone(self.inode()));
+        let replaced = new_children.insert(new_name.clone(), DEntry::Bytes(Arc::new(new_entry)));
+        unlink_replaced(replaced);
+        self.parent.remember_negative(self.name().clone());
+        new_dir.forget_negative(&new_name);
+        notify_move(&self.parent, self.name(), new_dir, &new_name, self.inode.meta());
         Ok(0)
     }
 }
+
+/// 重命名替换了已有的目录项 `replaced` 时，减少其链接数。其存储由后端在没有其他引用后释放
+fn unlink_replaced(replaced: Option<DEntry>) {
+    let Some(replaced) = replaced else {
+        return;
+    };
+    let curr_time = time::curr_time_spec();
+    replaced.meta().lock_inner_with(|inner| {
+        inner.nlink = inner.nlink.saturating_sub(1);
+        inner.change_time = curr_time;
+    });
+}
+
+/// 向 inotify 报告 `old_dir` 中的 `old_name` 被重命名为 `new_dir` 中的 `new_name`，`moved` 是被移动的 inode
+fn notify_move(old_dir: &DEntryDir, old_name: &str, new_dir: &DEntryDir, new_name: &str, moved: &InodeMeta) {
+    let is_dir = if moved.mode() == InodeMode::Dir {
+        InotifyMask::IN_ISDIR
+    } else {
+        InotifyMask::empty()
+    };
+    let cookie = inotify::new_cookie();
+    old_dir.notify_child(InotifyMask::IN_MOVED_FROM | is_dir, cookie, old_name);
+    new_dir.notify_child(InotifyMask::IN_MOVED_TO | is_dir, cookie, new_name);
+    moved.watches().notify(InotifyMask::IN_MOVE_SELF, 0, None);
+}
@@ -0,0 +1,339 @@
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    sync::{Arc as StdArc, Weak},
+    task::Wake,
+    vec::Vec,
+};
+use core::{
+    future::{self, Future},
+    pin::Pin,
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use defines::{
+    error::{errno, KResult},
+    fs::{EpollEvent, EpollEvents, PollEvents},
+};
+use event_listener::{Event, EventListener};
+use executor::time;
+use hashbrown::HashSet;
+use klocks::SpinMutex;
+use triomphe::Arc;
+
+use super::{file::File, inode::InodeMeta, poll::PollTable};
+use crate::hart::local_hart;
+
+/// epoll 最多嵌套的层数，同 linux
+const EP_MAX_NESTS: usize = 4;
+
+/// epoll 实例，类似于 linux 的 `struct eventpoll`。
+///
+/// 每个被监视的文件都有一个独立的 waker，文件状态变化时只会把对应的 fd 放入就绪列表，
+/// 因此等待时只需要检查就绪列表中的文件，空闲的文件没有任何开销
+pub struct Epoll {
+    /// epoll 没有对应的文件系统，使用单独的匿名 inode 元数据
+    meta: InodeMeta,
+    /// 兴趣列表，以 fd 为键
+    interests: SpinMutex<BTreeMap<usize, Interest>>,
+    ready: StdArc<ReadyList>,
+}
+
+/// 兴趣列表中的一项，类似于 linux 的 `struct epitem`
+struct Interest {
+    file: File,
+    /// 感兴趣的事件以及 `EPOLLET`、`EPOLLONESHOT` 等标志。`EPOLLONESHOT` 触发后会清除其中的事件
+    events: EpollEvents,
+    data: u64,
+    /// 文件状态变化时将该项放入就绪列表
+    waker: Waker,
+    /// 登记在文件上的监听者，需要保留到下一次轮询该文件
+    listeners: Vec<EventListener>,
+}
+
+/// 可能就绪的 fd。其中的 fd 不一定真的就绪，需要轮询文件后才能确定
+struct ReadyList {
+    inner: SpinMutex<ReadyListInner>,
+    /// 有 fd 被放入就绪列表时通知
+    event: Event,
+}
+
+#[derive(Default)]
+struct ReadyListInner {
+    queue: VecDeque<usize>,
+    /// 与 `queue` 中的 fd 相同，用于去重
+    set: HashSet<usize>,
+}
+
+impl ReadyList {
+    fn push(&self, fd: usize) {
+        {
+            let mut inner = self.inner.lock();
+            if inner.set.insert(fd) {
+                inner.queue.push_back(fd);
+            }
+        }
+        self.event.notify(usize::MAX);
+    }
+
+    fn pop(&self) -> Option<usize> {
+        let mut inner = self.inner.lock();
+        let fd = inner.queue.pop_front()?;
+        inner.set.remove(&fd);
+        Some(fd)
+    }
+
+    fn len(&self) -> usize {
+        self.inner.lock().queue.len()
+    }
+}
+
+/// 被监视的文件的 waker，被唤醒时将 fd 放入 epoll 的就绪列表。
+///
+/// 只持有就绪列表的弱引用，epoll 被释放后残留在文件上的 waker 什么也不做
+struct InterestWaker {
+    ready: Weak<ReadyList>,
+    fd: usize,
+}
+
+impl Wake for InterestWaker {
+    fn wake(self: StdArc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &StdArc<Self>) {
+        if let Some(ready) = self.ready.upgrade() {
+            ready.push(self.fd);
+        }
+    }
+}
+
+impl Epoll {
+    pub fn new() -> Self {
+        Self {
+            meta: InodeMeta::new_anonymous(),
+            interests: SpinMutex::new(BTreeMap::new()),
+            ready: StdArc::new(ReadyList {
+                inner: SpinMutex::new(ReadyListInner::default()),
+                event: Event::new(),
+            }),
+        }
+    }
+
+    pub fn meta(&self) -> &InodeMeta {
+        &self.meta
+    }
+
+    /// 将 `fd` 对应的 `file` 加入兴趣列表。
+    ///
+    /// 已经在兴趣列表中时返回 `EEXIST`；将 epoll 加入自身返回 `EINVAL`；形成环或嵌套过深返回 `ELOOP`
+    pub fn add(&self, fd: usize, file: File, events: EpollEvents, data: u64) -> KResult<()> {
+        if let File::Epoll(epoll) = &file {
+            if core::ptr::eq(self, &**epoll) {
+                return Err(errno::EINVAL);
+            }
+            self.check_nest(epoll, 1)?;
+        }
+        let waker = Waker::from(StdArc::new(InterestWaker {
+            ready: StdArc::downgrade(&self.ready),
+            fd,
+        }));
+        {
+            let mut interests = self.interests.lock();
+            if interests.contains_key(&fd) {
+                return Err(errno::EEXIST);
+            }
+            interests.insert(
+                fd,
+                Interest {
+                    file,
+                    events,
+                    data,
+                    waker,
+                    listeners: Vec::new(),
+                },
+            );
+        }
+        // 文件可能已经就绪了，放入就绪列表，下次检查时会轮询它并登记唤醒
+        self.ready.push(fd);
+        Ok(())
+    }
+
+    /// 修改 `fd` 感兴趣的事件和数据，同时会重新启用被 `EPOLLONESHOT` 禁用的项。不在兴趣列表中时返回 `ENOENT`
+    pub fn modify(&self, fd: usize, events: EpollEvents, data: u64) -> KResult<()> {
+        {
+            let mut interests = self.interests.lock();
+            let interest = interests.get_mut(&fd).ok_or(errno::ENOENT)?;
+            interest.events = events;
+            interest.data = data;
+        }
+        self.ready.push(fd);
+        Ok(())
+    }
+
+    /// 将 `fd` 移出兴趣列表。不在兴趣列表中时返回 `ENOENT`
+    pub fn delete(&self, fd: usize) -> KResult<()> {
+        // 监听者在这里被丢弃，文件不会再唤醒该项。就绪列表中残留的 fd 会在检查时被跳过
+        self.interests.lock().remove(&fd).map(drop).ok_or(errno::ENOENT)
+    }
+
+    /// 兴趣列表中有几项是 `file`
+    pub fn count_refs(&self, file: &File) -> usize {
+        self.interests
+            .lock()
+            .values()
+            .filter(|interest| interest.file.ptr_eq(file))
+            .count()
+    }
+
+    /// `fd` 被关闭时调用。只有兴趣列表中的项确实是 `file` 时才会将其移除
+    pub fn forget(&self, fd: usize, file: &File) {
+        let mut interests = self.interests.lock();
+        if interests.get(&fd).is_some_and(|interest| interest.file.ptr_eq(file)) {
+            interests.remove(&fd);
+        }
+    }
+
+    /// 检查将 `epoll` 加入本实例后，是否会形成环或者嵌套超过 [`EP_MAX_NESTS`] 层
+    fn check_nest(&self, epoll: &Epoll, depth: usize) -> KResult<()> {
+        if depth > EP_MAX_NESTS {
+            return Err(errno::ELOOP);
+        }
+        // 先收集再递归，避免同时持有多个 epoll 的锁
+        let children = epoll
+            .interests
+            .lock()
+            .values()
+            .filter_map(|interest| match &interest.file {
+                File::Epoll(child) => Some(Arc::clone(child)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        for child in children {
+            if core::ptr::eq(self, &*child) {
+                return Err(errno::ELOOP);
+            }
+            self.check_nest(&child, depth + 1)?;
+        }
+        Ok(())
+    }
+
+    /// 检查就绪列表中的文件，并重新登记唤醒。
+    ///
+    /// `events` 为 `None` 时只检查是否有文件就绪，不消耗任何事件，用于 [`Self::poll()`]；
+    /// 否则最多收集 `max_events` 个事件，并按照水平触发、边沿触发和 `EPOLLONESHOT` 的语义更新就绪列表。
+    ///
+    /// 返回是否有文件就绪
+    fn scan(&self, max_events: usize, mut events: Option<&mut Vec<EpollEvent>>) -> bool {
+        let mut found = false;
+        // 只检查开始时已经在就绪列表中的 fd，重新放回的 fd 留到下一次
+        for _ in 0..self.ready.len() {
+            if events.as_ref().is_some_and(|events| events.len() >= max_events) {
+                break;
+            }
+            let Some(fd) = self.ready.pop() else {
+                break;
+            };
+            let Some((file, waker, interest_events)) = self
+                .interests
+                .lock()
+                .get(&fd)
+                .map(|interest| (interest.file.clone(), interest.waker.clone(), interest.events))
+            else {
+                continue;
+            };
+
+            // 轮询文件时可能会唤醒 waker，从而对就绪列表加锁，因此不能持有任何锁
+            let mut listeners = Vec::new();
+            let mut cx = Context::from_waker(&waker);
+            let revents = file.poll(Some(&mut PollTable::new(&mut cx, &mut listeners)))
+                & (interest_events.to_poll() | PollEvents::POLLERR | PollEvents::POLLHUP);
+
+            let mut interests = self.interests.lock();
+            // 轮询期间该项可能被删除或替换
+            let Some(interest) = interests.get_mut(&fd).filter(|interest| interest.file.ptr_eq(&file)) else {
+                continue;
+            };
+            interest.listeners = listeners;
+            // 被 `EPOLLONESHOT` 禁用的项不报告事件，直到被重新启用
+            if revents.is_empty() || interest.events.to_poll().is_empty() {
+                continue;
+            }
+            found = true;
+            let Some(events) = &mut events else {
+                // 只检查时不消耗事件
+                drop(interests);
+                self.ready.push(fd);
+                continue;
+            };
+            events.push(EpollEvent {
+                events: EpollEvents::from_poll(revents).bits(),
+                data: interest.data,
+            });
+            if interest.events.contains(EpollEvents::EPOLLONESHOT) {
+                interest.events &= EpollEvents::EPOLLONESHOT | EpollEvents::EPOLLET;
+            } else if !interest.events.contains(EpollEvents::EPOLLET) {
+                // 水平触发的文件放回就绪列表，下次仍会检查
+                drop(interests);
+                self.ready.push(fd);
+            }
+        }
+        found
+    }
+
+    /// 等待兴趣列表中的文件就绪，最多返回 `max_events` 个事件。
+    ///
+    /// 超时后返回空；被未屏蔽的信号中断时返回 `EINTR`。`timeout` 为 `None` 表示无限等待
+    pub async fn wait(&self, max_events: usize, timeout: Option<Duration>) -> KResult<Vec<EpollEvent>> {
+        let thread = Arc::clone(&local_hart().curr_thread_arc());
+        let mut sleep = timeout.map(time::sleep);
+        loop {
+            // 先登记再检查，以免错过检查之后才到达的事件
+            let mut ready_listener = self.ready.event.listen();
+            let mut signal_listener = thread.signal_event().listen();
+            let mut events = Vec::new();
+            self.scan(max_events, Some(&mut events));
+            if !events.is_empty() {
+                return Ok(events);
+            }
+            if thread.has_unmasked_signal() {
+                return Err(errno::EINTR);
+            }
+            let timed_out = future::poll_fn(|cx| {
+                if Pin::new(&mut ready_listener).poll(cx).is_ready()
+                    || Pin::new(&mut signal_listener).poll(cx).is_ready()
+                {
+                    return Poll::Ready(false);
+                }
+                if let Some(sleep) = &mut sleep
+                    && Pin::new(sleep).poll(cx).is_ready()
+                {
+                    return Poll::Ready(true);
+                }
+                Poll::Pending
+            })
+            .await;
+            if timed_out {
+                return Ok(Vec::new());
+            }
+        }
+    }
+
+    /// epoll 本身的就绪状态，有文件就绪时可读，见 [`File::poll()`]
+    pub fn poll(&self, table: Option<&mut PollTable<'_, '_>>) -> PollEvents {
+        if let Some(table) = table {
+            table.listen(&self.ready.event);
+        }
+        if self.scan(usize::MAX, None) {
+            PollEvents::POLLIN
+        } else {
+            PollEvents::empty()
+        }
+    }
+}
+
+impl Default for Epoll {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,132 @@
+use defines::{
+    error::{errno, KResult},
+    fs::PollEvents,
+};
+use event_listener::{listener, Event};
+use klocks::SpinMutex;
+
+use super::{inode::InodeMeta, poll::PollTable};
+use crate::memory::{ReadBuffer, WriteBuffer};
+
+/// 计数器的最大值。写入后超过该值的写操作会阻塞
+const EVENTFD_MAX: u64 = u64::MAX - 1;
+
+/// 用于事件通知的计数器，类似于 linux 的 `struct eventfd_ctx`。
+///
+/// 读写都以 8 字节的 `u64` 为单位
+pub struct EventFd {
+    meta: InodeMeta,
+    counter: SpinMutex<u64>,
+    /// 为 `true` 时每次读取只减一，类似于信号量
+    semaphore: bool,
+    /// 计数器变为非零时通知
+    read_event: Event,
+    /// 计数器减小时通知
+    write_event: Event,
+}
+
+impl EventFd {
+    pub fn new(init_value: u64, semaphore: bool) -> Self {
+        Self {
+            meta: InodeMeta::new_anonymous(),
+            counter: SpinMutex::new(init_value),
+            semaphore,
+            read_event: Event::new(),
+            write_event: Event::new(),
+        }
+    }
+
+    pub fn meta(&self) -> &InodeMeta {
+        &self.meta
+    }
+
+    /// 读出计数器。普通模式下读出整个值并清零，信号量模式下读出 1 并减一。
+    ///
+    /// 计数器为零时会阻塞，`nonblock` 时返回 `EAGAIN`。缓冲区小于 8 字节时返回 `EINVAL`
+    pub async fn read(&self, mut buf: ReadBuffer<'_>, nonblock: bool) -> KResult<usize> {
+        if buf.len() < size_of::<u64>() {
+            return Err(errno::EINVAL);
+        }
+        loop {
+            listener!(self.read_event => listener);
+            {
+                // 预先检查用户缓冲区，以免取走了计数器后才发现无法写入
+                let mut user_buf;
+                let dst = match &mut buf {
+                    ReadBuffer::Kernel(buf) => &mut **buf,
+                    ReadBuffer::User(buf) => {
+                        user_buf = unsafe { buf.check_slice_mut()? };
+                        user_buf.as_bytes_mut()
+                    }
+                };
+                let mut counter = self.counter.lock();
+                if *counter > 0 {
+                    let value = if self.semaphore { 1 } else { *counter };
+                    *counter -= value;
+                    drop(counter);
+                    dst[..size_of::<u64>()].copy_from_slice(&value.to_ne_bytes());
+                    self.write_event.notify(usize::MAX);
+                    return Ok(size_of::<u64>());
+                }
+                if nonblock {
+                    return Err(errno::EAGAIN);
+                }
+            }
+            listener.await;
+        }
+    }
+
+    /// 将写入的值加到计数器上。
+    ///
+    /// 计数器将超过 [`EVENTFD_MAX`] 时会阻塞，`nonblock` 时返回 `EAGAIN`。
+    /// 缓冲区小于 8 字节或者写入 `u64::MAX` 时返回 `EINVAL`
+    pub async fn write(&self, buf: WriteBuffer<'_>, nonblock: bool) -> KResult<usize> {
+        let mut bytes = [0; size_of::<u64>()];
+        match &buf {
+            WriteBuffer::Kernel(buf) => bytes.copy_from_slice(buf.get(..size_of::<u64>()).ok_or(errno::EINVAL)?),
+            WriteBuffer::User(buf) => {
+                let buf = buf.slice(0..size_of::<u64>()).ok_or(errno::EINVAL)?;
+                bytes.copy_from_slice(&buf.check_slice()?);
+            }
+        }
+        let value = u64::from_ne_bytes(bytes);
+        if value == u64::MAX {
+            return Err(errno::EINVAL);
+        }
+        loop {
+            listener!(self.write_event => listener);
+            {
+                let mut counter = self.counter.lock();
+                if EVENTFD_MAX - *counter >= value {
+                    *counter += value;
+                    drop(counter);
+                    if value > 0 {
+                        self.read_event.notify(usize::MAX);
+                    }
+                    return Ok(size_of::<u64>());
+                }
+                if nonblock {
+                    return Err(errno::EAGAIN);
+                }
+            }
+            listener.await;
+        }
+    }
+
+    /// 计数器非零时可读，还能写入至少 1 时可写，见 [`File::poll()`](super::file::File::poll)
+    pub fn poll(&self, table: Option<&mut PollTable<'_, '_>>) -> PollEvents {
+        if let Some(table) = table {
+            table.listen(&self.read_event);
+            table.listen(&self.write_event);
+        }
+        let counter = *self.counter.lock();
+        let mut events = PollEvents::empty();
+        if counter > 0 {
+            events |= PollEvents::POLLIN;
+        }
+        if counter < EVENTFD_MAX {
+            events |= PollEvents::POLLOUT;
+        }
+        events
+    }
+}
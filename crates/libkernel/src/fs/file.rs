@@ -1,10 +1,15 @@
 use alloc::{collections::BTreeMap, vec::Vec};
-use core::{fmt::Debug, ops::Deref};
+use core::{fmt::Debug, mem, ops::Deref, ptr};
 
 use async_lock::Mutex as SleepMutex;
 use defines::{
     error::{errno, KResult},
-    fs::{Dirent64, OpenFlags, StatMode, MAX_FD_NUM, NAME_MAX},
+    fs::{
+        Dirent64, Flock, InotifyMask, OpenFlags, PollEvents, StatMode, F_RDLCK, F_UNLCK, F_WRLCK, MAX_FD_NUM, NAME_MAX,
+        SEEK_CUR, SEEK_END, SEEK_SET,
+    },
+    ioctl::{FIOASYNC, FIONBIO},
+    net::MsgFlags,
     resource::{RLimit, RLIM_INFINITY},
 };
 use klocks::SpinMutex;
@@ -13,18 +18,33 @@ use triomphe::Arc;
 use crate::{
     fs::{
         dentry::{DEntry, DEntryBytes, DEntryDir},
+        epoll::Epoll,
+        eventfd::EventFd,
         inode::{DynBytesInode, DynDirInode, InodeMeta, InodeMode},
+        inotify::{self, Inotify},
+        lock::{FileLocks, RecordLock, OFFSET_MAX},
         pipe::Pipe,
+        poll::PollTable,
+        signalfd::SignalFd,
+        timerfd::TimerFd,
     },
+    hart::local_hart,
     memory::{ReadBuffer, UserCheck, WriteBuffer},
+    net::Socket,
 };
 
 #[derive(Clone)]
 pub enum File {
-    Pipe(Pipe),
+    Pipe(Arc<Pipe>),
     Dir(Arc<DirFile>),
     Seekable(Arc<SeekableFile>),
     Stream(Arc<DEntryBytes>),
+    Epoll(Arc<Epoll>),
+    EventFd(Arc<EventFd>),
+    TimerFd(Arc<TimerFd>),
+    SignalFd(Arc<SignalFd>),
+    Inotify(Arc<Inotify>),
+    Socket(Arc<Socket>),
 }
 
 impl File {
@@ -34,6 +54,86 @@ impl File {
             File::Seekable(seekable) => seekable.inode().meta(),
             File::Pipe(pipe) => pipe.meta(),
             File::Stream(stream) => stream.inode().meta(),
+            File::Epoll(epoll) => epoll.meta(),
+            File::EventFd(eventfd) => eventfd.meta(),
+            File::TimerFd(timerfd) => timerfd.meta(),
+            File::SignalFd(signalfd) => signalfd.meta(),
+            File::Inotify(inotify) => inotify.meta(),
+            File::Socket(socket) => socket.meta(),
+        }
+    }
+
+    /// 文件对应的目录项，匿名管道、epoll、socket 等匿名文件没有目录项
+    pub fn dentry(&self) -> Option<DEntry> {
+        match self {
+            File::Dir(dir) => Some(DEntry::Dir(Arc::clone(dir.dentry()))),
+            File::Seekable(seekable) => Some(DEntry::Bytes(Arc::clone(seekable.dentry()))),
+            File::Stream(stream) => Some(DEntry::Bytes(Arc::clone(stream))),
+            File::Pipe(pipe) => pipe.dentry().map(|dentry| DEntry::Bytes(Arc::clone(dentry))),
+            File::Epoll(_)
+            | File::EventFd(_)
+            | File::TimerFd(_)
+            | File::SignalFd(_)
+            | File::Inotify(_)
+            | File::Socket(_) => None,
+        }
+    }
+
+    /// 是否是同一个打开的文件
+    pub fn ptr_eq(&self, other: &File) -> bool {
+        match (self, other) {
+            (File::Pipe(a), File::Pipe(b)) => Arc::ptr_eq(a, b),
+            (File::Dir(a), File::Dir(b)) => Arc::ptr_eq(a, b),
+            (File::Seekable(a), File::Seekable(b)) => Arc::ptr_eq(a, b),
+            (File::Stream(a), File::Stream(b)) => Arc::ptr_eq(a, b),
+            (File::Epoll(a), File::Epoll(b)) => Arc::ptr_eq(a, b),
+            (File::EventFd(a), File::EventFd(b)) => Arc::ptr_eq(a, b),
+            (File::TimerFd(a), File::TimerFd(b)) => Arc::ptr_eq(a, b),
+            (File::SignalFd(a), File::SignalFd(b)) => Arc::ptr_eq(a, b),
+            (File::Inotify(a), File::Inotify(b)) => Arc::ptr_eq(a, b),
+            (File::Socket(a), File::Socket(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
+    /// 打开的文件当前被引用的次数
+    pub fn ref_count(&self) -> usize {
+        match self {
+            File::Pipe(pipe) => Arc::count(pipe),
+            File::Dir(dir) => Arc::count(dir),
+            File::Seekable(seekable) => Arc::count(seekable),
+            File::Stream(stream) => Arc::count(stream),
+            File::Epoll(epoll) => Arc::count(epoll),
+            File::EventFd(eventfd) => Arc::count(eventfd),
+            File::TimerFd(timerfd) => Arc::count(timerfd),
+            File::SignalFd(signalfd) => Arc::count(signalfd),
+            File::Inotify(inotify) => Arc::count(inotify),
+            File::Socket(socket) => Arc::count(socket),
+        }
+    }
+
+    /// 文件当前的就绪状态，用于 `ppoll` 等。`table` 非空时同时登记唤醒，见 [`PollTable`]
+    pub fn poll(&self, table: Option<&mut PollTable<'_, '_>>) -> PollEvents {
+        match self {
+            File::Pipe(pipe) => pipe.poll(table),
+            File::Seekable(seekable) => seekable.inode().poll(table),
+            File::Stream(stream) => stream.inode().poll(table),
+            File::Epoll(epoll) => epoll.poll(table),
+            File::EventFd(eventfd) => eventfd.poll(table),
+            File::TimerFd(timerfd) => timerfd.poll(table),
+            File::SignalFd(signalfd) => signalfd.poll(table),
+            File::Inotify(inotify) => inotify.poll(table),
+            File::Socket(socket) => socket.poll(table),
+            // 目录没有合理的轮询语义，总是就绪
+            File::Dir(_) => PollEvents::POLLIN | PollEvents::POLLOUT,
+        }
+    }
+
+    /// 将文件在页缓存中的修改写回后备存储
+    pub async fn sync(&self) -> KResult<()> {
+        match self {
+            File::Seekable(seekable) => seekable.inode().sync().await,
+            _ => Ok(()),
         }
     }
 }
@@ -45,6 +145,7 @@ pub struct DirFile {
 
 impl DirFile {
     pub fn new(dentry: Arc<DEntryDir>) -> Self {
+        dentry.pin_children();
         Self {
             dentry,
             dirent_index: SpinMutex::new(0),
@@ -105,15 +206,15 @@ impl DirFile {
 
 pub struct SeekableFile {
     dentry: Arc<DEntryBytes>,
-    offset: SleepMutex<u64>,
 }
 
 impl SeekableFile {
     pub fn new(dentry: Arc<DEntryBytes>) -> Self {
-        Self {
-            dentry,
-            offset: SleepMutex::new(0),
-        }
+        Self { dentry }
+    }
+
+    pub fn dentry(&self) -> &Arc<DEntryBytes> {
+        &self.dentry
     }
 
     pub fn inode(&self) -> &Arc<DynBytesInode> {
@@ -121,6 +222,12 @@ impl SeekableFile {
     }
 }
 
+impl Drop for DirFile {
+    fn drop(&mut self) {
+        self.dentry.unpin_children();
+    }
+}
+
 #[derive(Clone)]
 pub struct FdTable {
     files: BTreeMap<usize, FileDescriptor>,
@@ -209,15 +316,73 @@ impl FdTable {
     }
 
     pub fn insert(&mut self, fd: usize, desc: FileDescriptor) -> Option<FileDescriptor> {
-        self.files.insert(fd, desc)
+        let old = self.files.insert(fd, desc)?;
+        self.forget_in_epolls(fd, &old);
+        Some(old)
     }
 
     pub fn remove(&mut self, fd: usize) -> Option<FileDescriptor> {
-        self.files.remove(&fd)
+        let desc = self.files.remove(&fd)?;
+        self.forget_in_epolls(fd, &desc);
+        Some(desc)
     }
 
-    pub fn close_on_exec(&mut self) {
-        self.files.retain(|_, file| !file.flags.contains(OpenFlags::CLOEXEC));
+    /// 按 fd 从小到大遍历
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &FileDescriptor)> {
+        self.files.iter().map(|(&fd, desc)| (fd, desc))
+    }
+
+    /// 关闭所有设置了 `CLOEXEC` 的 fd，返回被关闭的描述符
+    pub fn close_on_exec(&mut self) -> Vec<FileDescriptor> {
+        let mut closed = Vec::new();
+        self.files.retain(|&fd, desc| {
+            let close = desc.close_on_exec;
+            if close {
+                closed.push((fd, desc.clone()));
+            }
+            !close
+        });
+        closed
+            .into_iter()
+            .map(|(fd, desc)| {
+                self.forget_in_epolls(fd, &desc);
+                desc
+            })
+            .collect()
+    }
+
+    /// 关闭所有 fd，用于进程退出。返回被关闭的描述符
+    pub fn close_all(&mut self) -> Vec<FileDescriptor> {
+        mem::take(&mut self.files).into_values().collect()
+    }
+
+    /// `fd` 被关闭后，将其从本表中 epoll 的兴趣列表里移除。
+    ///
+    /// 与 linux 一样，只有该文件的所有 fd（包括其他进程中的）都关闭后才移除。
+    /// 打开的文件描述还被其他 fd 共享，或者文件除了 `closed` 本身和本表中 epoll 的兴趣列表之外还有其他引用时，
+    /// 认为文件仍被打开
+    fn forget_in_epolls(&self, fd: usize, closed: &FileDescriptor) {
+        if Arc::count(&closed.description) > 1 {
+            return;
+        }
+        let epolls = self
+            .files
+            .values()
+            .filter_map(|desc| match &**desc {
+                File::Epoll(epoll) => Some(epoll),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if epolls.is_empty() {
+            return;
+        }
+        let interest_refs: usize = epolls.iter().map(|epoll| epoll.count_refs(closed)).sum();
+        if closed.ref_count() > 1 + interest_refs {
+            return;
+        }
+        for epoll in epolls {
+            epoll.forget(fd, closed);
+        }
     }
 
     pub fn limit(&self) -> usize {
@@ -238,77 +403,170 @@ impl Debug for FdTable {
     }
 }
 
+/// 打开的文件描述（open file description），对应 linux 的 `struct file`。
+///
+/// 每次 `open()` 等都会新建一个，而 `dup()`、`fork()` 以及通过 `SCM_RIGHTS` 传递得到的 fd 与原 fd 共享同一个，
+/// 因此共享偏移量和文件状态标志
+struct FileDescription {
+    file: File,
+    /// 访问模式和文件状态标志，即 `F_GETFL` 的返回值
+    status: SpinMutex<OpenFlags>,
+    /// 读写偏移量，只对 [`File::Seekable`] 有意义
+    offset: SleepMutex<u64>,
+}
+
+impl FileDescription {
+    /// 作为 `flock()` 锁的持有者时的标识
+    fn lock_owner(&self) -> usize {
+        ptr::from_ref(self) as usize
+    }
+}
+
+impl Drop for FileDescription {
+    fn drop(&mut self) {
+        self.file.meta().locks().release_flock(self.lock_owner());
+        // 目前没有后台写回。进程退出、execve 关闭 CLOEXEC 的 fd、dup3 覆盖 fd 等路径都不经过 close()，
+        // 因此在最后一个引用消失时写回脏页
+        if let File::Seekable(seekable) = &self.file {
+            let inode = Arc::clone(seekable.inode());
+            let (runnable, task) = executor::spawn_with(
+                async move {
+                    if let Err(e) = inode.sync().await {
+                        warn!("sync failed on dropping file description: {e:?}");
+                    }
+                },
+                || {},
+            );
+            runnable.schedule();
+            task.detach();
+        }
+        let Some(dentry) = self.file.dentry() else {
+            return;
+        };
+        let mask = if self.status.get_mut().read_write().1 {
+            InotifyMask::IN_CLOSE_WRITE
+        } else {
+            InotifyMask::IN_CLOSE_NOWRITE
+        };
+        inotify::notify_dentry(&dentry, mask);
+    }
+}
+
 #[derive(Clone)]
 pub struct FileDescriptor {
-    file: File,
-    // FIXME: 实现有问题，要区分 file status flags 和 file descriptor flags
-    flags: OpenFlags,
+    description: Arc<FileDescription>,
+    /// 文件描述符标志，目前只有 `FD_CLOEXEC` 一种
+    close_on_exec: bool,
 }
 
 impl FileDescriptor {
+    /// 为 `file` 新建一个打开的文件描述。`flags` 中的 `CLOEXEC` 属于 fd 本身，其余的状态标志属于打开的文件描述
     pub fn new(file: File, flags: OpenFlags) -> Self {
-        Self { file, flags }
+        Self {
+            description: Arc::new(FileDescription {
+                file,
+                status: SpinMutex::new(flags.status_flags()),
+                offset: SleepMutex::new(0),
+            }),
+            close_on_exec: flags.contains(OpenFlags::CLOEXEC),
+        }
     }
 
     pub fn readable(&self) -> bool {
-        self.flags.read_write().0
+        self.status_flags().read_write().0
     }
 
     pub fn writable(&self) -> bool {
-        self.flags.read_write().1
+        self.status_flags().read_write().1
+    }
+
+    /// 打开的文件描述的访问模式和状态标志，被共享它的所有 fd 看到
+    pub fn status_flags(&self) -> OpenFlags {
+        *self.description.status.lock()
+    }
+
+    /// `fcntl()` 的 `F_SETFL`，只修改 [`OpenFlags::SETFL_MASK`] 中的标志，其余的位被忽略
+    pub fn set_status_flags(&self, flags: OpenFlags) {
+        let mut status = self.description.status.lock();
+        *status = status.difference(OpenFlags::SETFL_MASK) | flags.intersection(OpenFlags::SETFL_MASK);
+    }
+
+    fn nonblocking(&self) -> bool {
+        self.status_flags().contains(OpenFlags::NONBLOCK)
     }
 
     pub async fn read(&self, mut buf: ReadBuffer<'_>) -> KResult<usize> {
-        match &self.file {
+        let nonblock = self.nonblocking();
+        match &self.description.file {
             File::Dir(_) => Err(errno::EBADF),
-            File::Pipe(pipe) => pipe.read(buf).await,
+            File::Pipe(pipe) => pipe.read(buf, nonblock).await,
             File::Seekable(seekable) => {
                 let inode = seekable.inode();
-                let mut offset = seekable.offset.lock().await;
+                let mut offset = self.description.offset.lock().await;
                 let nread = inode.read_at(buf, *offset).await?;
                 *offset += nread as u64;
                 Ok(nread)
             }
-            File::Stream(stream) => stream.inode().read_at(buf, 0).await,
+            File::Stream(stream) => stream.inode().read_stream(buf, nonblock).await,
+            File::EventFd(eventfd) => eventfd.read(buf, nonblock).await,
+            File::TimerFd(timerfd) => timerfd.read(buf, nonblock).await,
+            File::SignalFd(signalfd) => signalfd.read(buf, nonblock).await,
+            File::Inotify(inotify) => inotify.read(buf, nonblock).await,
+            File::Socket(socket) => socket
+                .recv(buf, MsgFlags::empty(), nonblock)
+                .await
+                .map(|result| result.len),
+            File::Epoll(_) => Err(errno::EINVAL),
         }
     }
 
     pub async fn read_at(&self, mut buf: ReadBuffer<'_>, offset: u64) -> KResult<usize> {
-        match &self.file {
+        match &self.description.file {
             File::Seekable(seekable) => {
                 let inode = seekable.inode();
                 let nread = inode.read_at(buf, offset).await?;
                 Ok(nread)
             }
             File::Dir(_) => Err(errno::EBADF),
-            File::Stream(_) | File::Pipe(_) => Err(errno::ESPIPE),
+            _ => Err(errno::ESPIPE),
         }
     }
 
     pub async fn write(&self, buf: WriteBuffer<'_>) -> KResult<usize> {
-        match &self.file {
+        let status = self.status_flags();
+        let nonblock = status.contains(OpenFlags::NONBLOCK);
+        let nwrite = match &self.description.file {
             File::Seekable(seekable) => {
                 let inode = seekable.inode();
-                let mut offset = seekable.offset.lock().await;
-                if self.flags.contains(OpenFlags::APPEND) {
+                let mut offset = self.description.offset.lock().await;
+                if status.contains(OpenFlags::APPEND) {
                     *offset = inode.meta().lock_inner_with(|inner| inner.data_len);
                 }
                 let nwrite = inode.write_at(buf, *offset).await?;
                 *offset += nwrite as u64;
                 Ok(nwrite)
             }
-            File::Stream(stream) => stream.inode().write_at(buf, 0).await,
-            File::Pipe(pipe) => pipe.write(buf).await,
+            File::Stream(stream) => stream.inode().write_stream(buf, nonblock).await,
+            File::Pipe(pipe) => pipe.write(buf, nonblock).await,
+            File::EventFd(eventfd) => eventfd.write(buf, nonblock).await,
+            File::Socket(socket) => socket.send(buf, Vec::new(), None, MsgFlags::empty(), nonblock).await,
             File::Dir(_) => Err(errno::EBADF),
+            File::Epoll(_) | File::TimerFd(_) | File::SignalFd(_) | File::Inotify(_) => Err(errno::EINVAL),
+        }?;
+        if nwrite > 0
+            && let Some(dentry) = self.dentry()
+        {
+            inotify::notify_dentry(&dentry, InotifyMask::IN_MODIFY);
         }
+        Ok(nwrite)
     }
 
     pub async fn seek(&self, pos: SeekFrom) -> KResult<usize> {
-        match &self.file {
+        match &self.description.file {
             File::Seekable(seekable) => {
                 let ret = match pos {
                     SeekFrom::Start(pos) => {
-                        *seekable.offset.lock().await = pos;
+                        *self.description.offset.lock().await = pos;
                         pos as usize
                     }
                     SeekFrom::End(offset) => {
@@ -319,51 +577,191 @@ impl FileDescriptor {
                             .lock_inner_with(|inner| inner.data_len)
                             .checked_add_signed(offset)
                             .ok_or(errno::EOVERFLOW)?;
-                        *seekable.offset.lock().await = new_pos;
+                        *self.description.offset.lock().await = new_pos;
                         new_pos as usize
                     }
                     SeekFrom::Current(pos) => {
-                        let mut curr = seekable.offset.lock().await;
+                        let mut curr = self.description.offset.lock().await;
                         *curr = curr.checked_add_signed(pos).ok_or(errno::EOVERFLOW)?;
                         *curr as usize
                     }
                 };
                 Ok(ret)
             }
-            File::Stream(_) | File::Pipe(_) => Err(errno::ESPIPE),
             File::Dir(_) => todo!("[low] what does dir seek mean?"),
+            _ => Err(errno::ESPIPE),
         }
     }
 
     pub fn meta(&self) -> &InodeMeta {
-        self.file.meta()
+        self.description.file.meta()
     }
 
     pub fn ioctl(&self, request: usize, argp: usize) -> KResult {
-        // TODO: [low] 目前只支持字符设备，块设备不知道会不会用到
-        if !matches!(self.meta().mode(), InodeMode::CharDevice) {
-            return Err(errno::ENOTTY);
+        // 这两个命令修改的是打开的文件描述的状态标志，对所有文件都有效
+        if let FIONBIO | FIOASYNC = request {
+            let on = UserCheck::<i32>::new(argp as _)
+                .ok_or(errno::EFAULT)?
+                .check_ptr()?
+                .read()
+                != 0;
+            let flag = if request == FIONBIO {
+                OpenFlags::NONBLOCK
+            } else {
+                OpenFlags::ASYNC
+            };
+            self.description.status.lock().set(flag, on);
+            return Ok(0);
         }
-        match &self.file {
-            File::Stream(tty) => tty.inode().ioctl(request, argp),
+        match &self.description.file {
+            File::Pipe(pipe) => pipe.ioctl(request, argp),
+            File::Socket(socket) => socket.ioctl(request, argp),
+            File::Inotify(inotify) => inotify.ioctl(request, argp),
+            // 其他文件只有设备文件支持 ioctl
+            File::Stream(stream) if self.meta().mode() == InodeMode::CharDevice => stream.inode().ioctl(request, argp),
+            File::Seekable(seekable) if self.meta().mode() == InodeMode::BlockDevice => {
+                seekable.inode().ioctl(request, argp)
+            }
             _ => Err(errno::ENOTTY),
         }
     }
 
+    /// 加上或者释放 `flock()` 锁，`exclusive` 为 `None` 表示释放。
+    ///
+    /// 锁属于打开的文件描述，在其最后一个引用释放时自动释放
+    pub async fn flock(&self, exclusive: Option<bool>, nonblock: bool) -> KResult<()> {
+        let locks = self.meta().locks();
+        let owner = self.description.lock_owner();
+        match exclusive {
+            Some(exclusive) => {
+                let thread = Arc::clone(&local_hart().curr_thread_arc());
+                locks.flock(owner, exclusive, nonblock, &thread).await
+            }
+            None => {
+                locks.release_flock(owner);
+                Ok(())
+            }
+        }
+    }
+
+    /// `fcntl()` 的 `F_GETLK`。与 `flock` 描述的锁冲突的锁会被写回 `flock`，没有冲突时 `l_type` 被置为 `F_UNLCK`
+    pub async fn get_record_lock(&self, flock: &mut Flock, pid: usize) -> KResult<()> {
+        let exclusive = match flock.l_type {
+            F_RDLCK => false,
+            F_WRLCK => true,
+            _ => return Err(errno::EINVAL),
+        };
+        let (start, end) = self.record_range(flock).await?;
+        let lock = RecordLock {
+            pid,
+            start,
+            end,
+            exclusive,
+        };
+        let Some(held) = self.record_locks()?.test_record(&lock) else {
+            flock.l_type = F_UNLCK;
+            return Ok(());
+        };
+        *flock = Flock {
+            l_type: if held.exclusive { F_WRLCK } else { F_RDLCK },
+            l_whence: SEEK_SET as i16,
+            l_start: held.start as i64,
+            l_len: if held.end == OFFSET_MAX {
+                0
+            } else {
+                (held.end - held.start + 1) as i64
+            },
+            l_pid: held.pid as i32,
+        };
+        Ok(())
+    }
+
+    /// `fcntl()` 的 `F_SETLK` 和 `F_SETLKW`，后者 `wait` 为 `true`。
+    ///
+    /// 加读锁要求 fd 可读，加写锁要求 fd 可写，否则返回 `EBADF`
+    pub async fn set_record_lock(&self, flock: &Flock, pid: usize, wait: bool) -> KResult<()> {
+        let exclusive = match flock.l_type {
+            F_RDLCK if self.readable() => false,
+            F_WRLCK if self.writable() => true,
+            F_RDLCK | F_WRLCK => return Err(errno::EBADF),
+            F_UNLCK => {
+                let (start, end) = self.record_range(flock).await?;
+                self.record_locks()?.unlock_record(pid, start, end);
+                return Ok(());
+            }
+            _ => return Err(errno::EINVAL),
+        };
+        let (start, end) = self.record_range(flock).await?;
+        let lock = RecordLock {
+            pid,
+            start,
+            end,
+            exclusive,
+        };
+        let thread = Arc::clone(&local_hart().curr_thread_arc());
+        self.record_locks()?.set_record(lock, wait, &thread).await
+    }
+
+    /// 释放进程 `pid` 在该文件上的所有记录锁。同 POSIX，进程关闭文件的任意一个 fd 时都要调用
+    pub fn release_record_locks(&self, pid: usize) {
+        if let Ok(locks) = self.record_locks() {
+            locks.release_records(pid);
+        }
+    }
+
+    fn record_locks(&self) -> KResult<&FileLocks> {
+        match &self.description.file {
+            File::Seekable(seekable) => Ok(seekable.inode().meta().locks()),
+            File::Dir(dir) => Ok(dir.inode().meta().locks()),
+            // TODO: [low] 管道等其他文件也应当支持记录锁
+            _ => Err(errno::EINVAL),
+        }
+    }
+
+    /// 将 `flock` 描述的区间转换为 `(start, end)`，`end` 是最后一个字节的偏移
+    async fn record_range(&self, flock: &Flock) -> KResult<(u64, u64)> {
+        let base = match flock.l_whence as usize {
+            SEEK_SET => 0,
+            SEEK_CUR => match &self.description.file {
+                File::Seekable(_) => *self.description.offset.lock().await,
+                _ => 0,
+            },
+            SEEK_END => self.meta().lock_inner_with(|inner| inner.data_len),
+            _ => return Err(errno::EINVAL),
+        };
+        let start = (base as i64).checked_add(flock.l_start).ok_or(errno::EOVERFLOW)?;
+        let (start, end) = match flock.l_len {
+            0 => (start, OFFSET_MAX as i64),
+            len if len > 0 => (start, start.checked_add(len - 1).ok_or(errno::EOVERFLOW)?),
+            // 负数的长度表示 `start` 之前的区间，不包括 `start` 本身
+            len => (start.checked_add(len).ok_or(errno::EOVERFLOW)?, start - 1),
+        };
+        if start < 0 {
+            return Err(errno::EINVAL);
+        }
+        Ok((start as u64, end as u64))
+    }
+
     pub fn set_close_on_exec(&mut self, set: bool) {
-        self.flags.set(OpenFlags::CLOEXEC, set);
+        self.close_on_exec = set;
     }
 
-    pub fn flags(&self) -> OpenFlags {
-        self.flags
+    pub fn close_on_exec(&self) -> bool {
+        self.close_on_exec
     }
 
     pub fn debug_name(&self) -> &str {
-        match &self.file {
-            File::Pipe(_) => "<pipe>",
+        match &self.description.file {
+            File::Pipe(pipe) => pipe.dentry().map_or("<pipe>", |dentry| dentry.name()),
             File::Dir(dir) => dir.dentry.name(),
             File::Seekable(seekable) => seekable.dentry.name(),
             File::Stream(stream) => stream.name(),
+            File::Epoll(_) => "<epoll>",
+            File::EventFd(_) => "<eventfd>",
+            File::TimerFd(_) => "<timerfd>",
+            File::SignalFd(_) => "<signalfd>",
+            File::Inotify(_) => "<inotify>",
+            File::Socket(_) => "<socket>",
         }
     }
 }
@@ -372,7 +770,7 @@ impl Deref for FileDescriptor {
     type Target = File;
 
     fn deref(&self) -> &Self::Target {
-        &self.file
+        &self.description.file
     }
 }
 
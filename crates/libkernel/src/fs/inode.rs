@@ -1,18 +1,22 @@
+use alloc::vec::Vec;
 use core::sync::atomic::AtomicUsize;
 
 use atomic::Ordering;
 use common::config::{PAGE_OFFSET_MASK, PAGE_SIZE, PAGE_SIZE_BITS};
 use defines::{
     error::{errno, AKResult, KResult},
-    fs::StatMode,
+    fs::{FaccessatMode, PollEvents, StatMode},
     misc::TimeSpec,
 };
+use ecow::EcoString;
 use executor::time;
 use kernel_tracer::Instrument;
-use klocks::SpinMutex;
+use klocks::{Once, SpinMutex};
 use triomphe::Arc;
 
-use super::{dentry::DEntryDir, page_cache::PageCache};
+use super::{
+    dentry::DEntryDir, inotify::InodeWatches, lock::FileLocks, page_cache::PageCache, pipe::PipeBuffer, poll::PollTable,
+};
 use crate::{
     fs::page_cache::PageState,
     memory::{ReadBuffer, UserCheck, WriteBuffer},
@@ -32,6 +36,8 @@ pub enum InodeMode {
     Fifo,
     BlockDevice,
     CharDevice,
+    /// eventfd、epoll 等不对应文件系统中任何文件的 fd，同 linux 的 anon_inode
+    Anonymous,
 }
 
 impl From<InodeMode> for StatMode {
@@ -44,6 +50,8 @@ impl From<InodeMode> for StatMode {
             InodeMode::Fifo => StatMode::FIFO,
             InodeMode::BlockDevice => StatMode::BLOCK_DEVICE,
             InodeMode::CharDevice => StatMode::CHAR_DEVICE,
+            // 同 linux，没有文件类型位
+            InodeMode::Anonymous => StatMode::empty(),
         }
     }
 }
@@ -52,7 +60,15 @@ pub struct InodeMeta {
     /// inode number，在一个文件系统中唯一标识一个 Inode
     ino: usize,
     mode: InodeMode,
+    /// 设备文件对应的设备号，其他文件为 0
+    rdev: u64,
     page_cache: PageCache,
+    /// 命名管道的缓冲区，第一次打开时创建
+    fifo: Once<Arc<PipeBuffer>>,
+    /// `flock()` 锁和 POSIX 记录锁
+    locks: FileLocks,
+    /// inotify 监视
+    watches: InodeWatches,
     inner: SpinMutex<InodeMetaInner>,
 }
 
@@ -61,9 +77,22 @@ impl InodeMeta {
         Self {
             ino: INODE_NUMBER.fetch_add(1, Ordering::SeqCst),
             mode,
+            rdev: 0,
             page_cache: PageCache::new(),
+            fifo: Once::new(),
+            locks: FileLocks::default(),
+            watches: InodeWatches::default(),
             inner: SpinMutex::new(InodeMetaInner {
                 data_len: 0,
+                // 目录至少有父目录中的目录项和自身的 `.` 两个链接
+                nlink: if mode == InodeMode::Dir { 2 } else { 1 },
+                perm: match mode {
+                    InodeMode::Dir => StatMode::from_bits_truncate(0o755),
+                    InodeMode::SymbolLink => StatMode::from_bits_truncate(0o777),
+                    _ => StatMode::from_bits_truncate(0o644),
+                },
+                uid: 0,
+                gid: 0,
                 access_time: TimeSpec::default(),
                 modify_time: TimeSpec::default(),
                 change_time: TimeSpec::default(),
@@ -71,6 +100,18 @@ impl InodeMeta {
         }
     }
 
+    /// eventfd、epoll 等匿名 fd 的元数据，同 linux 只有所有者可读写
+    pub fn new_anonymous() -> Self {
+        let mut meta = Self::new(InodeMode::Anonymous);
+        let meta_inner = meta.get_inner_mut();
+        meta_inner.perm = StatMode::from_bits_truncate(0o600);
+        let curr_time = time::curr_time_spec();
+        meta_inner.access_time = curr_time;
+        meta_inner.change_time = curr_time;
+        meta_inner.modify_time = curr_time;
+        meta
+    }
+
     pub fn ino(&self) -> usize {
         self.ino
     }
@@ -79,10 +120,30 @@ impl InodeMeta {
         self.mode
     }
 
+    pub fn rdev(&self) -> u64 {
+        self.rdev
+    }
+
+    pub fn set_rdev(&mut self, rdev: u64) {
+        self.rdev = rdev;
+    }
+
     pub fn page_cache(&self) -> &PageCache {
         &self.page_cache
     }
 
+    pub fn locks(&self) -> &FileLocks {
+        &self.locks
+    }
+
+    pub fn watches(&self) -> &InodeWatches {
+        &self.watches
+    }
+
+    pub(super) fn fifo_buffer(&self) -> &Arc<PipeBuffer> {
+        self.fifo.call_once(|| Arc::new(PipeBuffer::new()))
+    }
+
     pub fn lock_inner_with<T>(&self, f: impl FnOnce(&mut InodeMetaInner) -> T) -> T {
         f(&mut self.inner.lock())
     }
@@ -90,11 +151,45 @@ impl InodeMeta {
     pub fn get_inner_mut(&mut self) -> &mut InodeMetaInner {
         self.inner.get_mut()
     }
+
+    /// 以用户 `uid`、组 `gid` 的身份检查是否拥有 `access` 指定的访问权限，没有则返回 `EACCES`
+    pub fn check_access(&self, uid: u32, gid: u32, access: FaccessatMode) -> KResult<()> {
+        let (perm, owner, group) = self.lock_inner_with(|inner| (inner.perm, inner.uid, inner.gid));
+        if uid == 0 {
+            // 特权用户可以读写任意文件，但执行非目录文件时要求至少有一个执行位
+            let any_exec = StatMode::S_IXUSR | StatMode::S_IXGRP | StatMode::S_IXOTH;
+            if access.contains(FaccessatMode::X_OK) && self.mode != InodeMode::Dir && !perm.intersects(any_exec) {
+                return Err(errno::EACCES);
+            }
+            return Ok(());
+        }
+        // 依次是所有者、组、其他用户的权限位
+        let shift = if uid == owner {
+            6
+        } else if gid == group {
+            3
+        } else {
+            0
+        };
+        let granted = (perm.bits() >> shift) & 0o7;
+        if access.bits() & !granted != 0 {
+            return Err(errno::EACCES);
+        }
+        Ok(())
+    }
 }
 
 pub struct InodeMetaInner {
     /// 对常规文件来说，是其文件内容大小；对目录来说，是它目录项列表占据的总共块空间；其他情况是 0
     pub data_len: u64,
+    /// 硬链接数。由 [`DEntryDir`] 在创建和删除目录项时维护
+    pub nlink: u32,
+    /// 权限位，包括 set-user-ID、set-group-ID 和 sticky 位，不含文件类型
+    pub perm: StatMode,
+    /// 所有者的用户 ID
+    pub uid: u32,
+    /// 所属的组 ID
+    pub gid: u32,
     /// 上一次访问时间
     pub access_time: TimeSpec,
     /// 上一次修改时间
@@ -112,25 +207,98 @@ pub trait DirInodeBackend: Send + Sync {
     fn lookup(&self, name: &str) -> Option<DynInode>;
     fn mkdir(&self, name: &str) -> KResult<Arc<DynDirInode>>;
     fn mknod(&self, name: &str, mode: InodeMode) -> KResult<Arc<DynBytesInode>>;
+    /// 创建名为 `name`、指向 `target` 的符号链接
+    fn symlink(&self, name: &str, target: &str) -> KResult<Arc<DynBytesInode>>;
+    /// 创建名为 `name`、指向 `target` 的硬链接。`target` 不会是目录
+    fn link(&self, name: &str, target: &Arc<DynBytesInode>) -> KResult<()>;
     fn unlink(&self, name: &str) -> KResult<()>;
+    /// 将本目录下名为 `old_name` 的文件移动到 `new_dir` 中并命名为 `new_name`，同名的目标会被替换。
+    ///
+    /// `new_dir` 与本目录属于同一个文件系统，也可能就是本目录
+    fn rename(&self, old_name: &str, new_dir: &Arc<DynDirInode>, new_name: &str) -> KResult<()>;
     fn read_dir(&self, parent: &Arc<DEntryDir>) -> KResult<()>;
     fn disk_space(&self) -> u64;
+    /// 已缓存的名为 `name` 的目录项是否仍然有效。
+    ///
+    /// 内容会自行变化的伪文件系统（如 procfs）需要实现
+    fn revalidate(&self, _name: &str) -> bool {
+        true
+    }
+    /// 本目录下未被引用的目录项能否被目录项缓存回收。
+    ///
+    /// 要求目录的修改对之后的 [`Self::lookup()`] 和 [`Self::read_dir()`] 可见，且仍被引用的 inode
+    /// 能被重新查找到。tmpfs 这类以目录项缓存本身作为存储的文件系统不能回收
+    fn dentry_reclaimable(&self) -> bool {
+        false
+    }
+    /// 本目录下查找失败的结果能否缓存为负目录项。
+    ///
+    /// 要求目录中的名字只会通过 dentry 层的创建、删除和重命名发生变化，且 [`Self::lookup()`] 区分大小写
+    fn cache_negative(&self) -> bool {
+        false
+    }
+    /// 目录项缓存回收了本目录下 inode 为 `child` 的子目录项后调用。
+    ///
+    /// 自行缓存 inode 的文件系统可以在 `child` 没有其他引用时将其移出缓存，以免回收目录项后内存仍然增长
+    fn evict_child(&self, _child: DynInode) {}
+    /// 将 `InodeMeta` 中的权限、所有者和时间写回后备存储。内存文件系统无需实现
+    fn write_meta(&self) -> KResult<()> {
+        Ok(())
+    }
 }
 
 pub trait BytesInodeBackend: Send + Sync + 'static {
     fn meta(&self) -> &InodeMeta;
     fn read_inode_at<'a>(&'a self, buf: ReadBuffer<'a>, _offset: u64) -> AKResult<'a, usize>;
     fn write_inode_at<'a>(&'a self, buf: WriteBuffer<'a>, offset: u64) -> AKResult<'a, usize>;
+    /// 字符设备等流式文件的读取。没有数据时，`nonblock` 为真则返回 `EAGAIN`，否则等待数据到来。
+    ///
+    /// 默认忽略 `nonblock`，直接转发给 [`Self::read_inode_at()`]，适用于读取从不阻塞的设备
+    fn read_stream<'a>(&'a self, buf: ReadBuffer<'a>, _nonblock: bool) -> AKResult<'a, usize> {
+        self.read_inode_at(buf, 0)
+    }
+    /// 同 [`Self::read_stream()`]，默认转发给 [`Self::write_inode_at()`]
+    fn write_stream<'a>(&'a self, buf: WriteBuffer<'a>, _nonblock: bool) -> AKResult<'a, usize> {
+        self.write_inode_at(buf, 0)
+    }
     fn ioctl(&self, request: usize, argp: usize) -> KResult {
         Err(errno::ENOTTY)
     }
+    /// 当前的就绪状态，用于 `ppoll` 等。`table` 非空且未就绪时，需要通过它登记唤醒。
+    ///
+    /// 默认总是可读写，适用于常规文件、块设备等没有合理轮询语义的文件
+    fn poll(&self, table: Option<&mut PollTable<'_, '_>>) -> PollEvents {
+        PollEvents::POLLIN | PollEvents::POLLOUT
+    }
     fn truncate(&self, len: u64) -> KResult<()> {
         Err(errno::EINVAL)
     }
+    /// 读取符号链接的目标路径，只对符号链接有意义
+    fn read_link(&self) -> KResult<EcoString> {
+        Err(errno::EINVAL)
+    }
+    /// 页缓存中的脏页是否需要通过 [`Self::write_inode_at()`] 写回。
+    ///
+    /// tmpfs 等内存文件系统以页缓存本身作为存储，不需要写回
+    fn need_writeback(&self) -> bool {
+        false
+    }
+    /// 读写是否经过页缓存。
+    ///
+    /// procfs 等伪文件系统中的常规文件，内容在每次读取时动态生成，不应该被缓存
+    fn use_page_cache(&self) -> bool {
+        true
+    }
+    /// mmap 时是否视为匿名映射而非映射文件内容，如 `/dev/zero`
+    fn anonymous_mmap(&self) -> bool {
+        false
+    }
+    /// 同 [`DirInodeBackend::write_meta()`]
+    fn write_meta(&self) -> KResult<()> {
+        Ok(())
+    }
 }
 
-// TODO: [low] 对于伪文件系统中的某些常规文件，比如 /proc/mounts，其实不需要也不应该走页缓存。另一方面，tmpfs 又是完全依据页缓存实现的
-
 impl dyn BytesInodeBackend {
     pub async fn read_at(&self, buf: ReadBuffer<'_>, offset: u64) -> KResult<usize> {
         self.read_at_impl(buf, offset)
@@ -139,6 +307,9 @@ impl dyn BytesInodeBackend {
     }
 
     async fn read_at_impl(&self, mut buf: ReadBuffer<'_>, offset: u64) -> KResult<usize> {
+        if !self.use_page_cache() {
+            return self.read_inode_at(buf, offset).await;
+        }
         let meta = self.meta();
         let data_len = meta.lock_inner_with(|inner| inner.data_len);
 
@@ -201,7 +372,7 @@ impl dyn BytesInodeBackend {
 
     async fn write_at_impl(&self, buf: WriteBuffer<'_>, offset: u64) -> KResult<usize> {
         let meta = self.meta();
-        if meta.mode() == InodeMode::Regular {
+        if meta.mode() == InodeMode::Regular && self.use_page_cache() {
             let curr_data_len = meta.lock_inner_with(|inner| inner.data_len);
             let curr_last_page_id = curr_data_len >> PAGE_SIZE_BITS;
 
@@ -239,6 +410,8 @@ impl dyn BytesInodeBackend {
                     WriteBuffer::User(buf) => &*buf.check_slice()?,
                 };
                 frame.as_page_bytes_mut()[page_offset..page_offset + copy_len].copy_from_slice(buf_slice);
+                // 写回时会先将页标记为 Synced，因此要在写入数据之后再标记为 Dirty
+                page.state.store(PageState::Dirty, Ordering::SeqCst);
                 nwrite += copy_len;
             }
             let curr_time = time::curr_time_spec();
@@ -257,6 +430,44 @@ impl dyn BytesInodeBackend {
         }
     }
 
+    /// 将页缓存中的脏页写回后备存储
+    pub async fn sync(&self) -> KResult<()> {
+        let meta = self.meta();
+        if meta.mode() != InodeMode::Regular || !self.need_writeback() {
+            return Ok(());
+        }
+        let dirty_pages = meta
+            .page_cache()
+            .lock_pages()
+            .iter()
+            .filter(|(_, page)| page.state.load(Ordering::SeqCst) == PageState::Dirty)
+            .map(|(&page_id, page)| (page_id, Arc::clone(page)))
+            .collect::<Vec<_>>();
+        for (page_id, page) in dirty_pages {
+            let _guard = page.state_guard.lock().await;
+            if page.state.load(Ordering::SeqCst) != PageState::Dirty {
+                continue;
+            }
+            let data_len = meta.lock_inner_with(|inner| inner.data_len);
+            let page_start = page_id << PAGE_SIZE_BITS;
+            // 先标记为 Synced，写回期间若页又被写入，会重新变为 Dirty
+            page.state.store(PageState::Synced, Ordering::SeqCst);
+            if page_start >= data_len {
+                continue;
+            }
+            let len = u64::min(PAGE_SIZE as u64, data_len - page_start) as usize;
+            let frame = page.inner.frame();
+            if let Err(e) = self
+                .write_inode_at(WriteBuffer::Kernel(&frame.as_page_bytes()[..len]), page_start)
+                .await
+            {
+                page.state.store(PageState::Dirty, Ordering::SeqCst);
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
     pub fn resize(&self, len: u64) -> KResult<()> {
         let meta = self.meta();
         assert_eq!(meta.mode, InodeMode::Regular);
@@ -270,6 +481,7 @@ impl dyn BytesInodeBackend {
     }
 }
 
+#[derive(Clone)]
 pub enum DynInode {
     Dir(Arc<DynDirInode>),
     Bytes(Arc<DynBytesInode>),
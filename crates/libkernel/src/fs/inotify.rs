@@ -0,0 +1,342 @@
+//! inotify 文件变化通知。
+//!
+//! 监视记录在 inode 上，见 [`InodeMeta::watches()`]，因此通过硬链接或者绑定挂载访问同一个文件也会产生事件。
+//! 事件由 [`DEntryDir`](super::dentry::DEntryDir) 的目录项操作和文件的写路径产生
+
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    vec::Vec,
+};
+use core::{
+    mem,
+    sync::atomic::{AtomicI32, AtomicU32, Ordering},
+};
+
+use defines::{
+    error::{errno, KResult},
+    fs::{InotifyEvent, InotifyMask, PollEvents},
+    ioctl::FIONREAD,
+};
+use ecow::EcoString;
+use event_listener::{listener, Event};
+use klocks::SpinMutex;
+use triomphe::Arc;
+
+use super::{dentry::DEntry, inode::InodeMeta, poll::PollTable};
+use crate::memory::{ReadBuffer, UserCheck};
+
+/// 每个 inotify 实例最多排队的事件数，同 linux 的 `/proc/sys/fs/inotify/max_queued_events`
+const MAX_QUEUED_EVENTS: usize = 16384;
+/// 每个 inotify 实例最多的监视数
+const MAX_WATCHES: usize = 8192;
+/// 读出的事件头的大小，文件名也按它对齐
+const EVENT_SIZE: usize = size_of::<InotifyEvent>();
+
+/// 关联同一次重命名的 `IN_MOVED_FROM` 和 `IN_MOVED_TO`
+static NEXT_COOKIE: AtomicU32 = AtomicU32::new(1);
+
+/// 为一次重命名分配 cookie
+pub fn new_cookie() -> u32 {
+    NEXT_COOKIE.fetch_add(1, Ordering::Relaxed)
+}
+
+/// 文件 `dentry` 本身产生了事件。
+///
+/// 同 linux，除了文件本身的监视，其所在目录的监视也会收到带有文件名的事件
+pub fn notify_dentry(dentry: &DEntry, mut mask: InotifyMask) {
+    let parent = match dentry {
+        DEntry::Dir(dir) => {
+            mask |= InotifyMask::IN_ISDIR;
+            dir.parent()
+        }
+        DEntry::Bytes(bytes) => Some(bytes.parent()),
+    };
+    dentry.meta().watches().notify(mask, 0, None);
+    if let Some(parent) = parent {
+        parent.inode().meta().watches().notify(mask, 0, Some(dentry.name()));
+    }
+}
+
+#[derive(PartialEq, Eq)]
+struct QueuedEvent {
+    wd: i32,
+    mask: InotifyMask,
+    cookie: u32,
+    name: Option<EcoString>,
+}
+
+impl QueuedEvent {
+    fn ignored(wd: i32) -> Self {
+        Self {
+            wd,
+            mask: InotifyMask::IN_IGNORED,
+            cookie: 0,
+            name: None,
+        }
+    }
+
+    /// 文件名加上至少一个 `\0` 后按 [`EVENT_SIZE`] 对齐的长度
+    fn name_len(&self) -> usize {
+        self.name
+            .as_ref()
+            .map_or(0, |name| (name.len() + 1).next_multiple_of(EVENT_SIZE))
+    }
+
+    /// 读出时占用的字节数
+    fn size(&self) -> usize {
+        EVENT_SIZE + self.name_len()
+    }
+
+    fn write_to(&self, dst: &mut [u8]) {
+        let header = InotifyEvent {
+            wd: self.wd,
+            mask: self.mask.bits(),
+            cookie: self.cookie,
+            len: self.name_len() as u32,
+        };
+        // SAFETY: `InotifyEvent` 是 `repr(C)` 且没有填充的
+        let bytes = unsafe { core::slice::from_raw_parts((&raw const header).cast::<u8>(), EVENT_SIZE) };
+        dst[..EVENT_SIZE].copy_from_slice(bytes);
+        let name_dst = &mut dst[EVENT_SIZE..self.size()];
+        name_dst.fill(0);
+        if let Some(name) = &self.name {
+            name_dst[..name.len()].copy_from_slice(name.as_bytes());
+        }
+    }
+}
+
+/// inotify 实例中被各个 inode 上的监视共享的部分，类似于 linux 的 `struct fsnotify_group`
+struct InotifyGroup {
+    queue: SpinMutex<VecDeque<QueuedEvent>>,
+    /// 监视描述符到被监视的文件。加锁顺序在 inode 的 [`InodeWatches`] 之后
+    watched: SpinMutex<BTreeMap<i32, DEntry>>,
+    /// 有新事件时通知
+    event: Event,
+}
+
+impl InotifyGroup {
+    fn push(&self, event: QueuedEvent) {
+        let mut queue = self.queue.lock();
+        // 同 linux，与队尾相同的事件会被合并
+        if queue.back() == Some(&event) {
+            return;
+        }
+        if queue.len() >= MAX_QUEUED_EVENTS {
+            // 队列满时丢弃新事件，只在队尾放置一个溢出事件
+            if queue.back().is_some_and(|last| last.mask == InotifyMask::IN_Q_OVERFLOW) {
+                return;
+            }
+            queue.push_back(QueuedEvent {
+                wd: -1,
+                mask: InotifyMask::IN_Q_OVERFLOW,
+                cookie: 0,
+                name: None,
+            });
+        } else {
+            queue.push_back(event);
+        }
+        drop(queue);
+        self.event.notify(usize::MAX);
+    }
+}
+
+struct Watch {
+    group: Arc<InotifyGroup>,
+    wd: i32,
+    /// 关心的事件，以及 `IN_ONESHOT` 等选项
+    mask: InotifyMask,
+}
+
+/// 一个 inode 上的所有 inotify 监视
+#[derive(Default)]
+pub struct InodeWatches {
+    watches: SpinMutex<Vec<Watch>>,
+}
+
+impl InodeWatches {
+    /// 向关心 `mask` 中事件的监视发送事件。
+    ///
+    /// `name` 是产生事件的目录项的名字，只用于目录的监视；事件的主体是本 inode 时为 `None`
+    pub fn notify(&self, mask: InotifyMask, cookie: u32, name: Option<&str>) {
+        let mut watches = self.watches.lock();
+        if watches.is_empty() {
+            return;
+        }
+        // 移除的 dentry 在释放锁之后再析构
+        let mut removed = Vec::new();
+        watches.retain(|watch| {
+            if !watch.mask.intersects(mask & InotifyMask::IN_ALL_EVENTS) {
+                return true;
+            }
+            watch.group.push(QueuedEvent {
+                wd: watch.wd,
+                mask,
+                cookie,
+                name: name.map(EcoString::from),
+            });
+            if !watch.mask.contains(InotifyMask::IN_ONESHOT) {
+                return true;
+            }
+            removed.extend(watch.group.watched.lock().remove(&watch.wd));
+            watch.group.push(QueuedEvent::ignored(watch.wd));
+            false
+        });
+        drop(watches);
+        drop(removed);
+    }
+
+    fn remove(&self, group: &Arc<InotifyGroup>) {
+        self.watches.lock().retain(|watch| !Arc::ptr_eq(&watch.group, group));
+    }
+}
+
+/// `inotify_init1()` 创建的 inotify 实例，类似于 linux 的 inotify `struct fsnotify_group`
+pub struct Inotify {
+    meta: InodeMeta,
+    group: Arc<InotifyGroup>,
+    next_wd: AtomicI32,
+}
+
+impl Inotify {
+    pub fn new() -> Self {
+        Self {
+            meta: InodeMeta::new_anonymous(),
+            group: Arc::new(InotifyGroup {
+                queue: SpinMutex::new(VecDeque::new()),
+                watched: SpinMutex::new(BTreeMap::new()),
+                event: Event::new(),
+            }),
+            next_wd: AtomicI32::new(1),
+        }
+    }
+
+    pub fn meta(&self) -> &InodeMeta {
+        &self.meta
+    }
+
+    /// 监视 `dentry` 上 `mask` 中的事件，返回监视描述符。
+    ///
+    /// 已经在监视该文件时返回原来的监视描述符，并按 `IN_MASK_ADD` 和 `IN_MASK_CREATE` 修改其掩码。
+    /// `IN_EXCL_UNLINK` 目前没有效果
+    pub fn add_watch(&self, dentry: DEntry, mask: InotifyMask) -> KResult<i32> {
+        if !mask.intersects(InotifyMask::IN_ALL_EVENTS)
+            || mask.contains(InotifyMask::IN_MASK_ADD | InotifyMask::IN_MASK_CREATE)
+        {
+            return Err(errno::EINVAL);
+        }
+        if mask.contains(InotifyMask::IN_ONLYDIR) && !dentry.is_dir() {
+            return Err(errno::ENOTDIR);
+        }
+        let new_mask = mask & (InotifyMask::IN_ALL_EVENTS | InotifyMask::IN_ONESHOT | InotifyMask::IN_EXCL_UNLINK);
+        let mut watches = dentry.meta().watches().watches.lock();
+        if let Some(watch) = watches.iter_mut().find(|watch| Arc::ptr_eq(&watch.group, &self.group)) {
+            if mask.contains(InotifyMask::IN_MASK_CREATE) {
+                return Err(errno::EEXIST);
+            }
+            if mask.contains(InotifyMask::IN_MASK_ADD) {
+                watch.mask |= new_mask;
+            } else {
+                watch.mask = new_mask;
+            }
+            return Ok(watch.wd);
+        }
+        let mut watched = self.group.watched.lock();
+        if watched.len() >= MAX_WATCHES {
+            return Err(errno::ENOSPC);
+        }
+        let wd = self.next_wd.fetch_add(1, Ordering::Relaxed);
+        watches.push(Watch {
+            group: Arc::clone(&self.group),
+            wd,
+            mask: new_mask,
+        });
+        drop(watches);
+        watched.insert(wd, dentry);
+        Ok(wd)
+    }
+
+    /// 移除监视描述符 `wd` 对应的监视，并产生一个 `IN_IGNORED` 事件
+    pub fn rm_watch(&self, wd: i32) -> KResult<()> {
+        let dentry = self.group.watched.lock().remove(&wd).ok_or(errno::EINVAL)?;
+        dentry.meta().watches().remove(&self.group);
+        self.group.push(QueuedEvent::ignored(wd));
+        Ok(())
+    }
+
+    /// 读出尽可能多的完整事件，返回读出的字节数。
+    ///
+    /// 没有事件时会阻塞，`nonblock` 时返回 `EAGAIN`。缓冲区放不下第一个事件时返回 `EINVAL`
+    pub async fn read(&self, mut buf: ReadBuffer<'_>, nonblock: bool) -> KResult<usize> {
+        loop {
+            listener!(self.group.event => listener);
+            {
+                let mut user_buf;
+                let dst = match &mut buf {
+                    ReadBuffer::Kernel(buf) => &mut **buf,
+                    ReadBuffer::User(buf) => {
+                        user_buf = unsafe { buf.check_slice_mut()? };
+                        user_buf.as_bytes_mut()
+                    }
+                };
+                let mut queue = self.group.queue.lock();
+                if let Some(first) = queue.front() {
+                    if first.size() > dst.len() {
+                        return Err(errno::EINVAL);
+                    }
+                    let mut nread = 0;
+                    while let Some(event) = queue.front()
+                        && nread + event.size() <= dst.len()
+                    {
+                        event.write_to(&mut dst[nread..]);
+                        nread += event.size();
+                        queue.pop_front();
+                    }
+                    return Ok(nread);
+                }
+                if nonblock {
+                    return Err(errno::EAGAIN);
+                }
+            }
+            listener.await;
+        }
+    }
+
+    /// 有事件时可读，见 [`File::poll()`](super::file::File::poll)
+    pub fn poll(&self, table: Option<&mut PollTable<'_, '_>>) -> PollEvents {
+        if let Some(table) = table {
+            table.listen(&self.group.event);
+        }
+        if self.group.queue.lock().is_empty() {
+            PollEvents::empty()
+        } else {
+            PollEvents::POLLIN
+        }
+    }
+
+    pub fn ioctl(&self, request: usize, argp: usize) -> KResult {
+        match request {
+            // 所有排队的事件的总字节数
+            FIONREAD => {
+                let len: usize = self.group.queue.lock().iter().map(QueuedEvent::size).sum();
+                unsafe { UserCheck::new(argp as *mut i32).ok_or(errno::EINVAL)?.check_ptr_mut()? }.write(len as i32);
+                Ok(0)
+            }
+            _ => Err(errno::ENOTTY),
+        }
+    }
+}
+
+impl Default for Inotify {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Inotify {
+    fn drop(&mut self) {
+        let watched = mem::take(&mut *self.group.watched.lock());
+        for dentry in watched.values() {
+            dentry.meta().watches().remove(&self.group);
+        }
+    }
+}
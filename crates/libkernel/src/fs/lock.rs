@@ -0,0 +1,254 @@
+//! 建议性文件锁，包括 `flock()` 的整文件锁和 `fcntl()` 的 POSIX 记录锁。
+//!
+//! 两种锁互不影响，都记录在 inode 上，见 [`InodeMeta::locks()`](super::inode::InodeMeta::locks)。
+//! `flock()` 锁属于打开的文件，引用它的所有 fd 都关闭后才释放；
+//! 记录锁属于进程，进程关闭该文件的任意一个 fd 或者退出时就会释放
+
+use alloc::{collections::BTreeMap, vec, vec::Vec};
+
+use defines::error::{errno, KResult};
+use event_listener::Event;
+use klocks::SpinMutex;
+use scopeguard::defer;
+
+use crate::thread::Thread;
+
+/// 记录锁能表示的最大偏移，即 `off_t` 的最大值。以此为结尾的锁覆盖到文件末尾之后
+pub const OFFSET_MAX: u64 = i64::MAX as u64;
+
+/// 因为记录锁而阻塞的线程，按 tid 索引，用于检测死锁
+static BLOCKED_ON: SpinMutex<BTreeMap<usize, BlockedThread>> = SpinMutex::new(BTreeMap::new());
+
+struct BlockedThread {
+    /// 线程所属进程的 pid，即想要加上的锁的持有者
+    pid: usize,
+    /// 等待的锁的持有者的 pid
+    blocker: usize,
+}
+
+/// 一个 POSIX 记录锁，覆盖 `start..=end`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RecordLock {
+    /// 持有者的 pid
+    pub pid: usize,
+    pub start: u64,
+    /// 最后一个字节的偏移，[`OFFSET_MAX`] 表示一直到文件末尾
+    pub end: u64,
+    /// 写锁（互斥锁）为 `true`，读锁（共享锁）为 `false`
+    pub exclusive: bool,
+}
+
+impl RecordLock {
+    fn overlaps(&self, start: u64, end: u64) -> bool {
+        self.start <= end && start <= self.end
+    }
+
+    fn conflicts_with(&self, other: &RecordLock) -> bool {
+        self.pid != other.pid && (self.exclusive || other.exclusive) && self.overlaps(other.start, other.end)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct FlockEntry {
+    /// 持有者，即打开的文件的地址
+    owner: usize,
+    exclusive: bool,
+}
+
+#[derive(Default)]
+struct LocksInner {
+    flocks: Vec<FlockEntry>,
+    records: Vec<RecordLock>,
+}
+
+/// 一个 inode 上的所有锁
+pub struct FileLocks {
+    inner: SpinMutex<LocksInner>,
+    /// 有锁被释放或降级时通知
+    event: Event,
+}
+
+impl Default for FileLocks {
+    fn default() -> Self {
+        Self {
+            inner: SpinMutex::new(LocksInner::default()),
+            event: Event::new(),
+        }
+    }
+}
+
+impl FileLocks {
+    /// 为 `owner` 加上 `flock()` 锁，锁被占用时阻塞，`nonblock` 时返回 `EAGAIN`。
+    ///
+    /// 同 linux，已持有另一种锁时会先释放它再重新加锁，因此锁的转换不是原子的
+    pub async fn flock(&self, owner: usize, exclusive: bool, nonblock: bool, thread: &Thread) -> KResult<()> {
+        {
+            let mut inner = self.inner.lock();
+            if let Some(index) = inner.flocks.iter().position(|entry| entry.owner == owner) {
+                if inner.flocks[index].exclusive == exclusive {
+                    return Ok(());
+                }
+                inner.flocks.swap_remove(index);
+                self.event.notify(usize::MAX);
+            }
+        }
+        loop {
+            let listener = self.event.listen();
+            {
+                let mut inner = self.inner.lock();
+                let conflicted = inner
+                    .flocks
+                    .iter()
+                    .any(|entry| entry.owner != owner && (exclusive || entry.exclusive));
+                if !conflicted {
+                    inner.flocks.push(FlockEntry { owner, exclusive });
+                    return Ok(());
+                }
+            }
+            if nonblock {
+                return Err(errno::EAGAIN);
+            }
+            thread.wait_interruptible(listener).await?;
+        }
+    }
+
+    /// 释放 `owner` 持有的 `flock()` 锁，没有持有时什么也不做
+    pub fn release_flock(&self, owner: usize) {
+        let mut inner = self.inner.lock();
+        let len = inner.flocks.len();
+        inner.flocks.retain(|entry| entry.owner != owner);
+        if inner.flocks.len() != len {
+            self.event.notify(usize::MAX);
+        }
+    }
+
+    /// 返回与 `lock` 冲突的第一个记录锁，即 `F_GETLK`
+    pub fn test_record(&self, lock: &RecordLock) -> Option<RecordLock> {
+        self.inner
+            .lock()
+            .records
+            .iter()
+            .find(|held| held.conflicts_with(lock))
+            .copied()
+    }
+
+    /// 加上记录锁 `lock`，与进程已持有的锁重叠的部分会被替换。即 `F_SETLK` 和 `F_SETLKW`。
+    ///
+    /// 与其他进程的锁冲突时，`wait` 为 `false` 则返回 `EAGAIN`，否则阻塞直到锁被释放。
+    /// 阻塞会造成死锁时返回 `EDEADLK`；被未屏蔽的信号中断时返回 `EINTR`
+    pub async fn set_record(&self, lock: RecordLock, wait: bool, thread: &Thread) -> KResult<()> {
+        loop {
+            let listener = self.event.listen();
+            let blocker = {
+                let mut inner = self.inner.lock();
+                match inner.records.iter().find(|held| held.conflicts_with(&lock)) {
+                    Some(held) => held.pid,
+                    None => {
+                        inner.insert_record(lock);
+                        // 原有的锁可能被降级或者缩小了
+                        self.event.notify(usize::MAX);
+                        return Ok(());
+                    }
+                }
+            };
+            if !wait {
+                return Err(errno::EAGAIN);
+            }
+            {
+                let mut blocked_on = BLOCKED_ON.lock();
+                if would_deadlock(&blocked_on, lock.pid, blocker) {
+                    return Err(errno::EDEADLK);
+                }
+                let blocked = BlockedThread { pid: lock.pid, blocker };
+                blocked_on.insert(thread.tid(), blocked);
+            }
+            defer! {
+                BLOCKED_ON.lock().remove(&thread.tid());
+            }
+            thread.wait_interruptible(listener).await?;
+        }
+    }
+
+    /// 释放进程 `pid` 在 `start..=end` 上的记录锁，即 `F_UNLCK`
+    pub fn unlock_record(&self, pid: usize, start: u64, end: u64) {
+        if self.inner.lock().punch_records(pid, start, end) {
+            self.event.notify(usize::MAX);
+        }
+    }
+
+    /// 释放进程 `pid` 持有的所有记录锁，用于关闭 fd 和进程退出
+    pub fn release_records(&self, pid: usize) {
+        self.unlock_record(pid, 0, OFFSET_MAX);
+    }
+}
+
+impl LocksInner {
+    /// 加入 `lock`，调用者应当已经检查过没有冲突。同一进程相邻或重叠的同类锁会被合并
+    fn insert_record(&mut self, mut lock: RecordLock) {
+        self.punch_records(lock.pid, lock.start, lock.end);
+        self.records.retain(|held| {
+            let adjacent = held.pid == lock.pid
+                && held.exclusive == lock.exclusive
+                && (held.end.checked_add(1) == Some(lock.start) || lock.end.checked_add(1) == Some(held.start));
+            if adjacent {
+                lock.start = lock.start.min(held.start);
+                lock.end = lock.end.max(held.end);
+            }
+            !adjacent
+        });
+        self.records.push(lock);
+    }
+
+    /// 从进程 `pid` 的记录锁中挖去 `start..=end`，部分重叠的锁会被截断或者分成两段。返回是否有锁发生了变化
+    fn punch_records(&mut self, pid: usize, start: u64, end: u64) -> bool {
+        let mut changed = false;
+        let mut split = Vec::new();
+        self.records.retain_mut(|held| {
+            if held.pid != pid || !held.overlaps(start, end) {
+                return true;
+            }
+            changed = true;
+            if held.start < start && held.end > end {
+                split.push(RecordLock {
+                    start: end + 1,
+                    ..*held
+                });
+                held.end = start - 1;
+                true
+            } else if held.start < start {
+                held.end = start - 1;
+                true
+            } else if held.end > end {
+                held.start = end + 1;
+                true
+            } else {
+                false
+            }
+        });
+        self.records.extend(split);
+        changed
+    }
+}
+
+/// 进程 `pid` 等待 `blocker` 持有的锁是否会形成环
+fn would_deadlock(blocked_on: &BTreeMap<usize, BlockedThread>, pid: usize, blocker: usize) -> bool {
+    // 同一进程的多个线程可能分别在等待不同进程的锁，因此要沿所有的等待关系搜索
+    let mut visited = Vec::new();
+    let mut pending = vec![blocker];
+    while let Some(curr) = pending.pop() {
+        if curr == pid {
+            return true;
+        }
+        if visited.contains(&curr) {
+            continue;
+        }
+        visited.push(curr);
+        pending.extend(
+            blocked_on
+                .values()
+                .filter(|blocked| blocked.pid == curr)
+                .map(|blocked| blocked.blocker),
+        );
+    }
+    false
+}
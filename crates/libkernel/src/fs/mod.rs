@@ -2,10 +2,17 @@
 #![allow(unused)]
 
 pub mod dentry;
+pub mod epoll;
+pub mod eventfd;
 pub mod file;
 pub mod inode;
+pub mod inotify;
+pub mod lock;
 mod page_cache;
 pub mod pipe;
+pub mod poll;
+pub mod signalfd;
+pub mod timerfd;
 
 use alloc::{string::String, vec::Vec};
 use core::{fmt::Write, str::FromStr};
@@ -14,7 +21,7 @@ use anstyle::{AnsiColor, Reset};
 use bitflags::Flags;
 use defines::{
     error::{errno, KResult},
-    fs::{MountFlags, Stat, StatFsFlags, StatMode, UnmountFlags, AT_FDCWD},
+    fs::{FaccessatMode, MountFlags, Stat, StatFsFlags, StatMode, UnmountFlags, AT_FDCWD},
 };
 use derive_more::Display;
 use ecow::EcoString;
@@ -25,16 +32,21 @@ use triomphe::Arc;
 
 use crate::{
     fs::{
-        dentry::{DEntry, DEntryDir},
+        dentry::{DEntry, DEntryBytes, DEntryDir},
         file::File,
-        inode::{DynBytesInode, InodeMeta},
+        inode::{DynBytesInode, InodeMeta, InodeMode},
     },
     hart::local_hart,
     memory::ReadBuffer,
+    process,
 };
 
 pub struct VirtFileSystem {
-    root_dir: Arc<DEntryDir>,
+    /// 当前的根目录。挂载到 `/` 上的文件系统会替换它
+    root_dir: SpinMutex<Arc<DEntryDir>>,
+    /// 以挂载的文件系统的根 dentry 为键，包括被覆盖的文件系统。
+    ///
+    /// 加锁顺序在 `root_dir` 之前
     mount_table: SpinMutex<HashMap<DEntry, FileSystem>>,
 }
 
@@ -43,7 +55,7 @@ static INSTANCE: Once<VirtFileSystem> = Once::new();
 impl VirtFileSystem {
     pub fn new(root_dir: Arc<DEntryDir>, mount_table: HashMap<DEntry, FileSystem>) -> Self {
         Self {
-            root_dir,
+            root_dir: SpinMutex::new(root_dir),
             mount_table: SpinMutex::new(mount_table),
         }
     }
@@ -57,15 +69,25 @@ impl VirtFileSystem {
         INSTANCE.get().unwrap()
     }
 
-    pub fn root_dir(&self) -> &Arc<DEntryDir> {
-        &self.root_dir
+    /// 初始化完成前返回 `None`
+    pub fn try_instance() -> Option<&'static Self> {
+        INSTANCE.get()
     }
 
+    pub fn root_dir(&self) -> Arc<DEntryDir> {
+        Arc::clone(&self.root_dir.lock())
+    }
+
+    /// 将 `create_fs` 创建的文件系统挂载到 `mount_point` 上。
+    ///
+    /// 挂载点上已有文件系统时，新的文件系统会覆盖它，卸载后恢复。挂载到 `/` 上时会替换根目录。
+    ///
+    /// `create_fs` 的参数依次为挂载点的父目录（挂载到 `/` 上时为 `None`）、挂载点的名字、设备路径和标志
     pub fn mount(
         &self,
         mount_point: &str,
         device_path: &str,
-        create_fs: impl FnOnce(Arc<DEntryDir>, EcoString, EcoString, StatFsFlags) -> KResult<FileSystem>,
+        create_fs: impl FnOnce(Option<Arc<DEntryDir>>, EcoString, EcoString, StatFsFlags) -> KResult<FileSystem>,
         flags: MountFlags,
     ) -> KResult<()> {
         let p2i = resolve_path_with_dir_fd(AT_FDCWD, mount_point)?;
@@ -73,86 +95,129 @@ impl VirtFileSystem {
         let statfs_flags = StatFsFlags::from_bits_truncate(flags.bits() & 0b1_1101_1111);
         let mut mount_table = self.mount_table.lock();
         // NOTE: linux 要求挂载必须发生在一个存在的目录上，但是我们的实现似乎不需要
-        let mounted_dentry;
-        let mut name = p2i.last_component.clone();
-
-        if let Some(dentry) = p2i.dir.lookup(&name) {
-            if mount_table.contains_key(&dentry) {
-                // 暂时不支持挂载到已有挂载的挂载点上
-                error!("cover mount fs not supported yet");
-                return Err(errno::EBUSY);
-            }
-            mounted_dentry = Some(dentry);
-            if name != "." && name != ".." {
-                name = mounted_dentry.as_ref().unwrap().name().clone();
-            }
-        } else {
-            mounted_dentry = None;
-        };
-        let parent = if let Some(mounted_dentry) = &mounted_dentry {
-            match mounted_dentry {
-                DEntry::Dir(dir) => match dir.parent() {
-                    Some(parent) => Arc::clone(parent),
-                    None => todo!("[low] mount under root dir"),
-                },
-                DEntry::Bytes(bytes) => Arc::clone(bytes.parent()),
-            }
-        } else {
-            p2i.dir
+        let mounted_dentry = p2i.dir.lookup(p2i.last_component.clone());
+        let (parent, name) = match &mounted_dentry {
+            // 用 dentry 自身的名字，以处理 `.` 和 `..`
+            Some(DEntry::Dir(dir)) => (dir.parent().cloned(), dir.name().clone()),
+            Some(DEntry::Bytes(bytes)) => (Some(Arc::clone(bytes.parent())), bytes.name().clone()),
+            None => (Some(p2i.dir), p2i.last_component),
         };
 
-        let mut fs = create_fs(
-            Arc::clone(&parent),
-            name.clone(),
-            EcoString::from(device_path),
-            statfs_flags,
-        )?;
-        {
-            let mut children = parent.lock_children();
-            children.insert(name, DEntry::Dir(Arc::clone(&fs.root_dentry)));
-        };
+        let mut fs = create_fs(parent, name, EcoString::from(device_path), statfs_flags)?;
+        let root_dentry = Arc::clone(&fs.root_dentry);
+        match root_dentry.parent() {
+            Some(parent) => {
+                parent
+                    .lock_children()
+                    .insert(root_dentry.name().clone(), DEntry::Dir(Arc::clone(&root_dentry)));
+            }
+            None => *self.root_dir.lock() = Arc::clone(&root_dentry),
+        }
         fs.mounted_dentry = mounted_dentry;
-        mount_table.insert(DEntry::Dir(Arc::clone(&fs.root_dentry)), fs);
+        mount_table.insert(DEntry::Dir(root_dentry), fs);
         Ok(())
     }
 
+    /// 将目录 `source` 绑定挂载到 `mount_point` 上，二者看到的是同一棵目录树
+    pub fn bind_mount(&self, mount_point: &str, source: Arc<DEntryDir>, flags: MountFlags) -> KResult<()> {
+        let (device_path, fs_type) = {
+            let mount_table = self.mount_table.lock();
+            let source_root = mounted_root_locked(&mount_table, DEntry::Dir(Arc::clone(&source)));
+            let source_fs = mount_table.get(&source_root).ok_or(errno::ENOENT)?;
+            (source_fs.device_path.clone(), source_fs.fs_type)
+        };
+        self.mount(
+            mount_point,
+            &device_path,
+            |parent, name, device_path, flags| {
+                let root_dentry = Arc::new(DEntryDir::new_bind(parent, name, source));
+                let mount_point = root_dentry.path();
+                Ok(FileSystem {
+                    root_dentry,
+                    device_path,
+                    fs_type,
+                    mounted_dentry: None,
+                    mount_point,
+                    flags,
+                })
+            },
+            flags,
+        )
+    }
+
+    /// 卸载 `mount_point` 上最后挂载的文件系统，恢复被它覆盖的 dentry。
+    ///
+    /// 其下还挂载有其他文件系统时返回 `EBUSY`，初始的根文件系统不可卸载
     pub fn unmount(&self, mount_point: &str, flags: UnmountFlags) -> KResult<()> {
-        debug!("mount {mount_point}, flags: {flags:?}");
+        debug!("unmount {mount_point}, flags: {flags:?}");
         let p2i = resolve_path_with_dir_fd(AT_FDCWD, mount_point)?;
         let dentry = p2i.dir.lookup(p2i.last_component).ok_or(errno::ENOENT)?;
 
-        let Some(fs) = self.mount_table.lock().remove(&dentry) else {
-            return Err(errno::EINVAL);
-        };
+        let mut mount_table = self.mount_table.lock();
+        let fs = mount_table.get(&dentry).ok_or(errno::EINVAL)?;
+        let root_dentry = Arc::clone(&fs.root_dentry);
+        if root_dentry.parent().is_none() && fs.mounted_dentry.is_none() {
+            return Err(errno::EBUSY);
+        }
+        // TODO: [low] 还没有检查是否有进程的工作目录或打开的文件位于该文件系统中
+        let is_busy = mount_table.values().any(|other| {
+            other
+                .root_dentry
+                .parent()
+                .is_some_and(|parent| mounted_root_locked(&mount_table, DEntry::Dir(Arc::clone(parent))) == dentry)
+        });
+        if is_busy {
+            return Err(errno::EBUSY);
+        }
 
-        if let Some(mounted_dentry) = fs.mounted_dentry {
-            let parent = match &dentry {
-                DEntry::Dir(dir) => match dir.parent() {
-                    Some(parent) => parent,
-                    None => todo!("[low] mount under root dir"),
-                },
-                DEntry::Bytes(bytes) => bytes.parent(),
-            };
-            let mut children = parent.lock_children();
-            let entry = children.get_mut(mounted_dentry.name()).unwrap();
-            *entry = mounted_dentry;
+        let fs = mount_table.remove(&dentry).expect("checked above");
+        match (root_dentry.parent(), fs.mounted_dentry) {
+            (Some(parent), Some(mounted_dentry)) => {
+                parent
+                    .lock_children()
+                    .insert(root_dentry.name().clone(), mounted_dentry);
+            }
+            (Some(parent), None) => {
+                parent.lock_children().remove(root_dentry.name());
+            }
+            (None, Some(DEntry::Dir(old_root))) => *self.root_dir.lock() = old_root,
+            (None, _) => unreachable!("root can only be covered by a directory"),
         }
 
         Ok(())
     }
 
+    /// `/proc/mounts` 的内容。
+    ///
+    /// 按挂载的层次输出，包括被覆盖的文件系统：父文件系统在前，同一位置上先挂载的在前
     pub fn mounts_info(&self) -> EcoString {
+        let mount_table = self.mount_table.lock();
+        // 每个文件系统挂载在哪个文件系统之上，初始的根文件系统没有
+        let mut children: HashMap<Option<DEntry>, Vec<&FileSystem>> = HashMap::new();
+        for fs in mount_table.values() {
+            let parent_fs = match (&fs.mounted_dentry, fs.root_dentry.parent()) {
+                (Some(mounted_dentry), _) if mount_table.contains_key(mounted_dentry) => Some(mounted_dentry.clone()),
+                (_, Some(parent)) => Some(mounted_root_locked(&mount_table, DEntry::Dir(Arc::clone(parent)))),
+                (_, None) => None,
+            };
+            children.entry(parent_fs).or_default().push(fs);
+        }
+
         let mut ret = EcoString::new();
-        let mounts = self.mount_table.lock();
-        let mut n_write = 0;
-        for (dentry, fs) in mounts.iter() {
+        let mut stack = children.remove(&None).unwrap_or_default();
+        stack.sort_unstable_by(|a, b| b.mount_point.cmp(&a.mount_point));
+        while let Some(fs) = stack.pop() {
             writeln!(
                 ret,
                 "{} {} {} {} 0 0",
                 fs.device_path, fs.mount_point, fs.fs_type, fs.flags
             )
             .expect("should not fail");
-            // TODO: [low]。是不是要考虑被覆盖挂载的文件系统？
+            if let Some(mut mounted) = children.remove(&Some(DEntry::Dir(Arc::clone(&fs.root_dentry)))) {
+                // 覆盖挂载的文件系统应当紧跟在被覆盖的之后
+                mounted.sort_unstable_by(|a, b| b.mount_point.cmp(&a.mount_point));
+                stack.extend(mounted);
+            }
         }
         ret
     }
@@ -167,19 +232,8 @@ impl VirtFileSystem {
         self.mount_table.lock().contains_key(dentry)
     }
 
-    pub fn mounted_root_of(&self, mut dentry: DEntry) -> DEntry {
-        let mount_table = self.mount_table.lock();
-        loop {
-            if mount_table.contains_key(&dentry) {
-                return dentry;
-            }
-            dentry = match dentry {
-                DEntry::Dir(dir) => DEntry::Dir(Arc::clone(
-                    dir.parent().expect("mount root should be reachable from dentry"),
-                )),
-                DEntry::Bytes(bytes) => DEntry::Dir(Arc::clone(bytes.parent())),
-            };
-        }
+    pub fn mounted_root_of(&self, dentry: DEntry) -> DEntry {
+        mounted_root_locked(&self.mount_table.lock(), dentry)
     }
 
     pub fn same_mounted_fs(&self, a: DEntry, b: DEntry) -> bool {
@@ -187,15 +241,37 @@ impl VirtFileSystem {
     }
 }
 
+/// 向上查找 `dentry` 所在的文件系统的根 dentry。
+///
+/// `dentry` 位于已卸载的根文件系统中时，返回那个文件系统的根
+fn mounted_root_locked(mount_table: &HashMap<DEntry, FileSystem>, mut dentry: DEntry) -> DEntry {
+    loop {
+        if mount_table.contains_key(&dentry) {
+            return dentry;
+        }
+        dentry = match dentry {
+            DEntry::Dir(dir) => match dir.parent() {
+                Some(parent) => DEntry::Dir(Arc::clone(parent)),
+                None => return DEntry::Dir(dir),
+            },
+            DEntry::Bytes(bytes) => DEntry::Dir(Arc::clone(bytes.parent())),
+        };
+    }
+}
+
 pub struct FileSystem {
     pub root_dentry: Arc<DEntryDir>,
     pub device_path: EcoString,
     pub fs_type: &'static str,
+    /// 被本文件系统覆盖的 dentry，卸载时恢复。可能是另一个文件系统的根
     pub mounted_dentry: Option<DEntry>,
     pub mount_point: EcoString,
     pub flags: StatFsFlags,
 }
 
+/// 一次路径解析中最多跟随的符号链接数，同 linux 的 `MAXSYMLINKS`
+const MAX_SYMLINK_FOLLOW: usize = 40;
+
 /// 类似于 linux 的 `struct nameidata`，存放 path walk 的结果。
 ///
 /// 也就是路径最后一个 component 和前面的其他部分解析得到的目录 dentry
@@ -203,6 +279,28 @@ pub struct PathToInode {
     pub dir: Arc<DEntryDir>,
     pub last_type: LastComponentType,
     pub last_component: EcoString,
+    /// 解析过程中已经跟随过的符号链接数
+    n_links: usize,
+}
+
+impl PathToInode {
+    /// 如果最后一个 component 是符号链接，则跟随它，直到它不再是符号链接或者不存在。
+    ///
+    /// 对应于 linux 的 `LOOKUP_FOLLOW`，不指定 `O_NOFOLLOW`、`AT_SYMLINK_NOFOLLOW` 等标志时使用
+    pub fn follow_last(mut self) -> KResult<Self> {
+        loop {
+            if self.last_type != LastComponentType::Normal {
+                return Ok(self);
+            }
+            let Some(DEntry::Bytes(bytes)) = self.dir.lookup(&self.last_component) else {
+                return Ok(self);
+            };
+            if bytes.inode().meta().mode() != InodeMode::SymbolLink {
+                return Ok(self);
+            }
+            self = follow_link(&self.dir, &bytes, self.n_links)?;
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -221,7 +319,7 @@ pub fn resolve_path_with_dir_fd(dir_fd: usize, path: &str) -> KResult<PathToInod
     let start_dir;
     // 绝对路径则忽视 fd
     if path.starts_with('/') {
-        start_dir = Arc::clone(VirtFileSystem::instance().root_dir());
+        start_dir = VirtFileSystem::instance().root_dir();
     } else {
         let process = local_hart().curr_process();
         let inner = process.lock_inner();
@@ -241,8 +339,14 @@ pub fn resolve_path_with_dir_fd(dir_fd: usize, path: &str) -> KResult<PathToInod
     path_walk(start_dir, path)
 }
 
-/// `path` 不应为空，否则返回 [`errno::ENOENT`]
+/// `path` 不应为空，否则返回 [`errno::ENOENT`]。
+///
+/// 中间的 component 如果是符号链接则会被跟随，最后一个 component 则不会，除非 `path` 以 `/` 结尾
 pub fn path_walk(start_dir: Arc<DEntryDir>, path: &str) -> KResult<PathToInode> {
+    path_walk_impl(start_dir, path, 0)
+}
+
+fn path_walk_impl(start_dir: Arc<DEntryDir>, path: &str, n_links: usize) -> KResult<PathToInode> {
     debug!("walk path: {path}, from {}", start_dir.name());
 
     // 边缘情况：
@@ -258,6 +362,7 @@ pub fn path_walk(start_dir: Arc<DEntryDir>, path: &str) -> KResult<PathToInode>
         dir: start_dir,
         last_type: LastComponentType::Normal,
         last_component: EcoString::from("."),
+        n_links,
     };
 
     let Some(mut curr_component) = split.next() else {
@@ -266,24 +371,64 @@ pub fn path_walk(start_dir: Arc<DEntryDir>, path: &str) -> KResult<PathToInode>
     };
 
     for next_component in split {
+        // 在目录中查找需要该目录的执行（搜索）权限
+        check_access(ret.dir.inode().meta(), FaccessatMode::X_OK)?;
         match ret.dir.lookup(curr_component) {
             Some(DEntry::Dir(next_dir)) => ret.dir = next_dir,
+            Some(DEntry::Bytes(bytes)) if bytes.inode().meta().mode() == InodeMode::SymbolLink => {
+                let link = follow_link(&ret.dir, &bytes, ret.n_links)?.follow_last()?;
+                match link.dir.lookup(link.last_component) {
+                    Some(DEntry::Dir(next_dir)) => ret.dir = next_dir,
+                    Some(_) => return Err(errno::ENOTDIR),
+                    None => return Err(errno::ENOENT),
+                }
+                ret.n_links = link.n_links;
+            }
             Some(_) => return Err(errno::ENOTDIR),
             None => return Err(errno::ENOENT),
         }
         curr_component = next_component;
     }
+    check_access(ret.dir.inode().meta(), FaccessatMode::X_OK)?;
     if curr_component == "." {
         ret.last_type = LastComponentType::Dot;
     } else if curr_component == ".." {
         ret.last_type = LastComponentType::DotDot;
     }
     ret.last_component = EcoString::from(curr_component);
+    // 以 `/` 结尾的路径要求最后一个 component 是目录，因此需要跟随符号链接
+    if path.ends_with('/') {
+        ret = ret.follow_last()?;
+    }
     Ok(ret)
 }
 
+/// 解析 `dir` 中的符号链接 `link`，`n_links` 是此前已经跟随过的符号链接数
+fn follow_link(dir: &Arc<DEntryDir>, link: &DEntryBytes, n_links: usize) -> KResult<PathToInode> {
+    if n_links >= MAX_SYMLINK_FOLLOW {
+        return Err(errno::ELOOP);
+    }
+    let target = link.inode().read_link()?;
+    if target.is_empty() {
+        return Err(errno::ENOENT);
+    }
+    let start_dir = if target.starts_with('/') {
+        VirtFileSystem::instance().root_dir()
+    } else {
+        Arc::clone(dir)
+    };
+    path_walk_impl(start_dir, &target, n_links + 1)
+}
+
+/// 以当前进程的有效身份检查对 `meta` 的访问权限，没有则返回 `EACCES`
+pub fn check_access(meta: &InodeMeta, access: FaccessatMode) -> KResult<()> {
+    let cred = process::curr_cred();
+    meta.check_access(cred.uid.effective, cred.gid.effective, access)
+}
+
+/// 查找 `path` 对应的文件，会跟随符号链接
 pub fn find_file(path: &str) -> KResult<DEntry> {
-    let p2i = resolve_path_with_dir_fd(AT_FDCWD, path)?;
+    let p2i = resolve_path_with_dir_fd(AT_FDCWD, path)?.follow_last()?;
     p2i.dir.lookup(p2i.last_component).ok_or(errno::ENOENT)
 }
 
@@ -303,16 +448,16 @@ pub fn stat_from_meta(meta: &InodeMeta) -> Stat {
     // TODO: fstat 的 device id 暂时是一个随意的数字
     stat.st_dev = 114514;
     stat.st_ino = meta.ino() as u64;
-    stat.st_mode = StatMode::from(meta.mode());
-    stat.st_nlink = 1;
-    stat.st_uid = 0;
-    stat.st_gid = 0;
-    stat.st_rdev = 0;
+    stat.st_rdev = meta.rdev();
     // TODO: 特殊文件也先填成 BLOCK_SIZE 吧
     stat.st_blksize = BLOCK_SIZE as u32;
     // TODO: 文件有空洞时，可能小于 st_size/512。而且可能实际占用的块数量会更多
     meta.lock_inner_with(|meta_inner| {
         stat.st_size = meta_inner.data_len;
+        stat.st_mode = StatMode::from(meta.mode()) | meta_inner.perm;
+        stat.st_nlink = meta_inner.nlink;
+        stat.st_uid = meta_inner.uid;
+        stat.st_gid = meta_inner.gid;
         stat.st_atime = meta_inner.access_time;
         stat.st_mtime = meta_inner.modify_time;
         stat.st_ctime = meta_inner.change_time;
@@ -2,7 +2,7 @@ use alloc::collections::BTreeMap;
 use core::ops::{Bound, RangeBounds};
 
 use async_lock::Mutex as SleepMutex;
-use atomic::Atomic;
+use atomic::{Atomic, Ordering};
 use klocks::{RwLock, RwLockReadGuard};
 use triomphe::Arc;
 
@@ -40,6 +40,14 @@ impl PageCache {
         self.get(page_id).unwrap_or_else(|| self.create(page_id))
     }
 
+    /// 是否有还没有写回的脏页
+    pub fn has_dirty(&self) -> bool {
+        self.pages
+            .read()
+            .values()
+            .any(|page| page.state.load(Ordering::SeqCst) == PageState::Dirty)
+    }
+
     pub fn lock_pages(&self) -> RwLockReadGuard<'_, BTreeMap<u64, Arc<BackedPage>>> {
         self.pages.read()
     }
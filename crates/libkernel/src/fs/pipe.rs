@@ -1,135 +1,451 @@
-use async_channel::{Receiver, Sender};
-use defines::error::{errno, KResult};
+use alloc::{vec, vec::Vec};
+
+use common::config::PAGE_SIZE;
+use defines::{
+    error::{errno, KResult},
+    fs::{OpenFlags, PollEvents},
+    ioctl::FIONREAD,
+};
+use event_listener::Event;
 use executor::time;
+use klocks::SpinMutex;
 use triomphe::Arc;
 
-use super::inode::InodeMeta;
+use super::{dentry::DEntryBytes, inode::InodeMeta, poll::PollTable};
 use crate::{
     fs::inode::InodeMode,
+    hart::local_hart,
     memory::{ReadBuffer, UserCheck, WriteBuffer},
+    process,
+    signal::KSignalSet,
 };
 
-// TODO: [low] pipe 的实现可以优化
+/// 管道的默认容量，同 linux 为 16 页
+const PIPE_DEFAULT_SIZE: usize = 16 * PAGE_SIZE;
+/// 非特权进程通过 `F_SETPIPE_SZ` 可以设置的最大容量，同 linux 的 `/proc/sys/fs/pipe-max-size`
+const PIPE_MAX_SIZE: usize = 1 << 20;
+/// 不超过该长度的写入是原子的，不会和其他写者的数据交错
+const PIPE_BUF: usize = PAGE_SIZE;
+
+/// 管道的缓冲区，由所有读端和写端共享，类似于 linux 的 `pipe_inode_info`。
+///
+/// 匿名管道的缓冲区只由两端持有；命名管道的缓冲区保存在 inode 中，所有打开者共享同一个缓冲区
+pub struct PipeBuffer {
+    inner: SpinMutex<PipeBufferInner>,
+    /// 有数据可读，或写端全部关闭时通知读者
+    read_event: Event,
+    /// 有空间可写，或读端全部关闭时通知写者
+    write_event: Event,
+    /// 有新的读端或写端打开时通知等待对端的命名管道打开者
+    open_event: Event,
+}
+
+struct PipeBufferInner {
+    ring: RingBuffer,
+    readers: usize,
+    writers: usize,
+    /// 读端被打开的总次数。等待对端的打开者据此判断对端是否打开过，即使对端随即又关闭了
+    reader_opens: usize,
+    writer_opens: usize,
+}
+
+impl PipeBuffer {
+    pub fn new() -> Self {
+        Self {
+            inner: SpinMutex::new(PipeBufferInner {
+                ring: RingBuffer::new(PIPE_DEFAULT_SIZE),
+                readers: 0,
+                writers: 0,
+                reader_opens: 0,
+                writer_opens: 0,
+            }),
+            read_event: Event::new(),
+            write_event: Event::new(),
+            open_event: Event::new(),
+        }
+    }
+}
+
+impl Default for PipeBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 定长的环形缓冲区。
+///
+/// 存储空间在第一次写入时才分配，这样打开后未使用的命名管道不会占用内存
+struct RingBuffer {
+    data: Vec<u8>,
+    capacity: usize,
+    /// 第一个有效字节的位置
+    head: usize,
+    len: usize,
+}
+
+impl RingBuffer {
+    fn new(capacity: usize) -> Self {
+        Self {
+            data: Vec::new(),
+            capacity,
+            head: 0,
+            len: 0,
+        }
+    }
+
+    fn free_space(&self) -> usize {
+        self.capacity - self.len
+    }
+
+    /// 取出至多 `dst.len()` 字节到 `dst` 中，返回取出的长度
+    fn pop(&mut self, dst: &mut [u8]) -> usize {
+        let len = usize::min(dst.len(), self.len);
+        let first = usize::min(len, self.capacity - self.head);
+        dst[..first].copy_from_slice(&self.data[self.head..self.head + first]);
+        dst[first..len].copy_from_slice(&self.data[..len - first]);
+        self.head = (self.head + len) % self.capacity;
+        self.len -= len;
+        if self.len == 0 {
+            self.head = 0;
+        }
+        len
+    }
+
+    /// 写入 `src` 中尽可能多的字节，返回写入的长度
+    fn push(&mut self, src: &[u8]) -> usize {
+        if self.data.is_empty() {
+            self.data = vec![0; self.capacity];
+        }
+        let len = usize::min(src.len(), self.free_space());
+        let tail = (self.head + self.len) % self.capacity;
+        let first = usize::min(len, self.capacity - tail);
+        self.data[tail..tail + first].copy_from_slice(&src[..first]);
+        self.data[..len - first].copy_from_slice(&src[first..len]);
+        self.len += len;
+        len
+    }
+
+    /// 修改容量，已有的数据会保留。容量小于已有数据的长度时返回 `EBUSY`
+    fn resize(&mut self, capacity: usize) -> KResult<()> {
+        if capacity < self.len {
+            return Err(errno::EBUSY);
+        }
+        if !self.data.is_empty() {
+            let len = self.len;
+            let mut data = vec![0; capacity];
+            self.pop(&mut data[..len]);
+            self.data = data;
+            self.len = len;
+        }
+        self.capacity = capacity;
+        Ok(())
+    }
 
-const PIPE_CAPACITY: usize = 16384;
+    /// 丢弃所有数据并释放存储空间
+    fn clear(&mut self) {
+        self.data = Vec::new();
+        self.head = 0;
+        self.len = 0;
+    }
+}
 
-#[derive(Clone)]
+/// 管道的一端。命名管道以读写模式打开时，同时是读端和写端
 pub struct Pipe {
-    meta: Arc<InodeMeta>,
-    inner: PipeInner,
+    buffer: Arc<PipeBuffer>,
+    source: PipeSource,
+    readable: bool,
+    writable: bool,
+}
+
+enum PipeSource {
+    /// 匿名管道没有目录项，两端共享一个单独的 inode 元数据
+    Anonymous(Arc<InodeMeta>),
+    /// 命名管道（FIFO）
+    Named(Arc<DEntryBytes>),
 }
 
 impl Pipe {
-    pub async fn read(&self, mut buf: ReadBuffer<'_>) -> KResult<usize> {
-        let PipeInner::ReadEnd(receiver) = &self.inner else {
-            return Err(errno::EBADF);
-        };
-        let len = buf.len();
-        let mut n_read = 0;
-
-        while n_read < len {
-            let Ok(byte) = receiver.recv().await else {
-                break;
-            };
-            match &mut buf {
-                ReadBuffer::Kernel(buf) => buf[n_read] = byte,
-                ReadBuffer::User(buf) => unsafe {
-                    buf.as_user_check()
-                        .add(n_read)
-                        .ok_or(errno::EINVAL)?
-                        .check_ptr_mut()?
-                        .write(byte);
-                },
+    fn new(buffer: Arc<PipeBuffer>, source: PipeSource, readable: bool, writable: bool) -> Self {
+        {
+            let mut inner = buffer.inner.lock();
+            if readable {
+                inner.readers += 1;
+                inner.reader_opens += 1;
+            }
+            if writable {
+                inner.writers += 1;
+                inner.writer_opens += 1;
             }
-            n_read += 1;
         }
-        let curr_time = time::curr_time_spec();
-        self.meta.lock_inner_with(|inner| inner.access_time = curr_time);
-        Ok(n_read)
+        buffer.open_event.notify(usize::MAX);
+        Self {
+            buffer,
+            source,
+            readable,
+            writable,
+        }
     }
 
-    pub async fn write(&self, mut buf: WriteBuffer<'_>) -> KResult<usize> {
-        let PipeInner::WriteEnd(sender) = &self.inner else {
+    /// 读取管道中的数据。
+    ///
+    /// 管道为空时会阻塞，直到有数据写入或写端全部关闭，后者返回 0。
+    /// `nonblock` 时不阻塞，而是返回 `EAGAIN`。阻塞时被信号打断返回 `EINTR`
+    pub async fn read(&self, mut buf: ReadBuffer<'_>, nonblock: bool) -> KResult<usize> {
+        if !self.readable {
             return Err(errno::EBADF);
+        }
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let thread = Arc::clone(&local_hart().curr_thread_arc());
+        let nread = loop {
+            let listener = self.buffer.read_event.listen();
+            {
+                // 预先检查用户缓冲区，这样持有锁时可以直接拷贝，而不需要经过中转。
+                // 访问用户内存的权限不能跨越 await，因此每次都需要重新检查
+                let mut user_buf;
+                let dst = match &mut buf {
+                    ReadBuffer::Kernel(buf) => &mut **buf,
+                    ReadBuffer::User(buf) => {
+                        user_buf = unsafe { buf.check_slice_mut()? };
+                        user_buf.as_bytes_mut()
+                    }
+                };
+                let mut inner = self.buffer.inner.lock();
+                let len = inner.ring.pop(dst);
+                if len > 0 {
+                    drop(inner);
+                    self.buffer.write_event.notify(usize::MAX);
+                    break len;
+                }
+                if inner.writers == 0 {
+                    return Ok(0);
+                }
+                if nonblock {
+                    return Err(errno::EAGAIN);
+                }
+            }
+            thread.wait_interruptible(listener).await?;
         };
-        let len = buf.len();
-        let mut n_write = 0;
 
-        'out: loop {
-            let mut this_n_write = 0;
-            let last_byte = {
-                let slice = match &buf {
+        let curr_time = time::curr_time_spec();
+        self.meta().lock_inner_with(|inner| inner.access_time = curr_time);
+        Ok(nread)
+    }
+
+    /// 向管道写入数据。缓冲区满时会阻塞，直到全部写入。
+    ///
+    /// 不超过 [`PIPE_BUF`] 的写入是原子的，会等到缓冲区有足够的空间再一次性写入。
+    /// `nonblock` 时不阻塞，若一个字节都写不进去则返回 `EAGAIN`。阻塞时被信号打断同理，返回已写入的长度或者 `EINTR`。
+    ///
+    /// 读端全部关闭时会向当前线程发送 `SIGPIPE`，若已经写入了部分数据则返回已写入的长度，否则返回 `EPIPE`
+    pub async fn write(&self, buf: WriteBuffer<'_>, nonblock: bool) -> KResult<usize> {
+        if !self.writable {
+            return Err(errno::EBADF);
+        }
+        let len = buf.len();
+        if len == 0 {
+            return Ok(0);
+        }
+        let atomic = len <= PIPE_BUF;
+        let thread = Arc::clone(&local_hart().curr_thread_arc());
+        let mut nwrite = 0;
+        while nwrite < len {
+            let listener = self.buffer.write_event.listen();
+            {
+                let rest = buf.slice(nwrite..len).expect("in bound");
+                let user_buf;
+                let src = match &rest {
                     WriteBuffer::Kernel(buf) => *buf,
-                    WriteBuffer::User(buf) => &buf.check_slice()?,
-                };
-                for &byte in slice.iter() {
-                    if let Err(err) = sender.try_send(byte) {
-                        this_n_write += 1;
-                        n_write += this_n_write;
-                        break 'out;
+                    WriteBuffer::User(buf) => {
+                        user_buf = buf.check_slice()?;
+                        &*user_buf
                     }
-                    this_n_write += 1;
+                };
+                let mut inner = self.buffer.inner.lock();
+                if inner.readers == 0 {
+                    drop(inner);
+                    thread.receive_signal(KSignalSet::SIGPIPE);
+                    return if nwrite > 0 { Ok(nwrite) } else { Err(errno::EPIPE) };
+                }
+                let free_space = inner.ring.free_space();
+                if free_space >= src.len() || (!atomic && free_space > 0) {
+                    nwrite += inner.ring.push(src);
+                    drop(inner);
+                    self.buffer.read_event.notify(usize::MAX);
+                    continue;
                 }
-                if this_n_write < slice.len() {
-                    slice[this_n_write]
-                } else {
-                    n_write += this_n_write;
-                    break 'out;
+                if nonblock {
+                    return if nwrite > 0 { Ok(nwrite) } else { Err(errno::EAGAIN) };
                 }
-            };
-            sender.send(last_byte).await;
-            this_n_write += 1;
-            n_write += this_n_write;
-            buf = buf.slice(this_n_write..buf.len()).expect("this_n_write <= buf.len()");
+            }
+            if let Err(e) = thread.wait_interruptible(listener).await {
+                return if nwrite > 0 { Ok(nwrite) } else { Err(e) };
+            }
         }
 
         let curr_time = time::curr_time_spec();
-        self.meta.lock_inner_with(|inner| inner.modify_time = curr_time);
-        Ok(n_write)
+        self.meta().lock_inner_with(|inner| inner.modify_time = curr_time);
+        Ok(nwrite)
+    }
+
+    /// 管道当前的就绪状态，见 [`File::poll()`](super::file::File::poll)
+    pub fn poll(&self, mut table: Option<&mut PollTable<'_, '_>>) -> PollEvents {
+        if let Some(table) = &mut table {
+            if self.readable {
+                table.listen(&self.buffer.read_event);
+            }
+            if self.writable {
+                table.listen(&self.buffer.write_event);
+            }
+        }
+        let inner = self.buffer.inner.lock();
+        let mut events = PollEvents::empty();
+        if self.readable {
+            if inner.ring.len > 0 {
+                events |= PollEvents::POLLIN;
+            }
+            // 写端全部关闭后，缓冲区中剩余的数据仍然可读
+            if inner.writers == 0 {
+                events |= PollEvents::POLLHUP;
+            }
+        }
+        if self.writable {
+            // 同 linux，至少能原子地写入 `PIPE_BUF` 字节时才算可写
+            if inner.ring.free_space() >= PIPE_BUF {
+                events |= PollEvents::POLLOUT;
+            }
+            if inner.readers == 0 {
+                events |= PollEvents::POLLERR;
+            }
+        }
+        events
+    }
+
+    pub fn ioctl(&self, request: usize, argp: usize) -> KResult {
+        match request {
+            FIONREAD => {
+                let len = self.buffer.inner.lock().ring.len;
+                unsafe { UserCheck::new(argp as *mut i32).ok_or(errno::EINVAL)?.check_ptr_mut()? }.write(len as i32);
+                Ok(0)
+            }
+            _ => Err(errno::ENOTTY),
+        }
+    }
+
+    /// 管道的容量，即 `F_GETPIPE_SZ`
+    pub fn capacity(&self) -> usize {
+        self.buffer.inner.lock().ring.capacity
+    }
+
+    /// 设置管道的容量，即 `F_SETPIPE_SZ`，返回实际设置的容量。
+    ///
+    /// 同 linux，容量会向上取整为 2 的幂个页。非特权进程不能超过 [`PIPE_MAX_SIZE`]
+    pub fn set_capacity(&self, size: usize) -> KResult<usize> {
+        if size > 1 << 31 {
+            return Err(errno::EINVAL);
+        }
+        let capacity = size.max(PAGE_SIZE).next_power_of_two();
+        if capacity > PIPE_MAX_SIZE && !process::curr_cred().is_privileged() {
+            return Err(errno::EPERM);
+        }
+        self.buffer.inner.lock().ring.resize(capacity)?;
+        // 容量变大后可能有等待空间的写者可以继续了
+        self.buffer.write_event.notify(usize::MAX);
+        Ok(capacity)
     }
 
     pub fn meta(&self) -> &InodeMeta {
-        &self.meta
+        match &self.source {
+            PipeSource::Anonymous(meta) => meta,
+            PipeSource::Named(dentry) => dentry.inode().meta(),
+        }
+    }
+
+    /// 命名管道对应的目录项，匿名管道没有目录项
+    pub fn dentry(&self) -> Option<&Arc<DEntryBytes>> {
+        match &self.source {
+            PipeSource::Anonymous(_) => None,
+            PipeSource::Named(dentry) => Some(dentry),
+        }
     }
 }
 
-#[derive(Clone)]
-enum PipeInner {
-    ReadEnd(Receiver<u8>),
-    WriteEnd(Sender<u8>),
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        let mut inner = self.buffer.inner.lock();
+        if self.readable {
+            inner.readers -= 1;
+            if inner.readers == 0 {
+                self.buffer.write_event.notify(usize::MAX);
+            }
+        }
+        if self.writable {
+            inner.writers -= 1;
+            if inner.writers == 0 {
+                self.buffer.read_event.notify(usize::MAX);
+            }
+        }
+        // 同 linux，所有端都关闭后丢弃命名管道中残留的数据
+        if inner.readers == 0 && inner.writers == 0 {
+            inner.ring.clear();
+        }
+    }
 }
 
 /// 返回 (`read_end`, `write_end`)
 pub fn make_pipe() -> (Pipe, Pipe) {
-    let (sender, receiver) = async_channel::bounded(PIPE_CAPACITY);
+    let buffer = Arc::new(PipeBuffer::new());
     let meta = Arc::new(InodeMeta::new(InodeMode::Fifo));
     let curr_time = time::curr_time_spec();
     meta.lock_inner_with(|inner| {
-        inner.data_len = PIPE_CAPACITY as u64;
+        inner.data_len = PIPE_DEFAULT_SIZE as u64;
         inner.change_time = curr_time;
     });
     (
-        Pipe {
-            meta: Arc::clone(&meta),
-            inner: PipeInner::ReadEnd(receiver),
-        },
-        Pipe {
-            meta,
-            inner: PipeInner::WriteEnd(sender),
-        },
+        Pipe::new(
+            Arc::clone(&buffer),
+            PipeSource::Anonymous(Arc::clone(&meta)),
+            true,
+            false,
+        ),
+        Pipe::new(buffer, PipeSource::Anonymous(meta), false, true),
     )
 }
 
-//     fn fstat(&self) -> Stat {
-//         Stat {
-//             st_mode: StatMode::S_IFIFO | StatMode::S_IRWXU | StatMode::S_IRWXG | StatMode::S_IRWXO,
-//             st_size: RING_BUFFER_SIZE as u64,
-//             st_blksize: BLOCK_SIZE,
-//             ..Default::default()
-//         }
-//     }
-
-//     fn remove(&self, _name: &str) {
-//         panic!("pipe cannot remove");
-//     }
-// }
+/// 打开命名管道 `dentry`。
+///
+/// 只读或只写打开时会阻塞到对端被打开为止，除非指定了 `O_NONBLOCK`。等待时被信号打断返回 `EINTR`。
+/// 以 `O_NONBLOCK` 只写打开而没有读端时返回 `ENXIO`。读写打开总是立即返回
+pub async fn open_fifo(dentry: Arc<DEntryBytes>, flags: OpenFlags) -> KResult<Pipe> {
+    let buffer = Arc::clone(dentry.inode().meta().fifo_buffer());
+    let (readable, writable) = flags.read_write();
+    let nonblock = flags.contains(OpenFlags::NONBLOCK);
+    if writable && !readable && nonblock && buffer.inner.lock().readers == 0 {
+        return Err(errno::ENXIO);
+    }
+    let pipe = Pipe::new(Arc::clone(&buffer), PipeSource::Named(dentry), readable, writable);
+    if nonblock || (readable && writable) {
+        return Ok(pipe);
+    }
+
+    let peer_opens = |inner: &PipeBufferInner| {
+        if readable {
+            (inner.writers, inner.writer_opens)
+        } else {
+            (inner.readers, inner.reader_opens)
+        }
+    };
+    let (peers, opens_before) = peer_opens(&buffer.inner.lock());
+    if peers > 0 {
+        return Ok(pipe);
+    }
+    let thread = Arc::clone(&local_hart().curr_thread_arc());
+    loop {
+        let listener = buffer.open_event.listen();
+        if peer_opens(&buffer.inner.lock()).1 != opens_before {
+            return Ok(pipe);
+        }
+        thread.wait_interruptible(listener).await?;
+    }
+}
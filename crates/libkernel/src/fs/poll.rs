@@ -0,0 +1,39 @@
+use alloc::vec::Vec;
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Waker},
+};
+
+use event_listener::{Event, EventListener};
+
+/// 轮询文件时用于登记唤醒的上下文，类似于 linux 的 `poll_table`。
+///
+/// 文件在报告当前就绪状态之前，应当先通过 [`Self::listen()`] 或 [`Self::waker()`] 登记，以免错过状态变化
+pub struct PollTable<'a, 'cx> {
+    cx: &'a mut Context<'cx>,
+    listeners: &'a mut Vec<EventListener>,
+}
+
+impl<'a, 'cx> PollTable<'a, 'cx> {
+    /// `listeners` 保存登记的监听者，需要存活到轮询者被唤醒为止
+    pub fn new(cx: &'a mut Context<'cx>, listeners: &'a mut Vec<EventListener>) -> Self {
+        Self { cx, listeners }
+    }
+
+    /// 在 `event` 被通知时唤醒轮询者
+    pub fn listen(&mut self, event: &Event) {
+        let mut listener = event.listen();
+        // 轮询一次以登记 waker
+        if Pin::new(&mut listener).poll(self.cx).is_ready() {
+            self.cx.waker().wake_by_ref();
+        } else {
+            self.listeners.push(listener);
+        }
+    }
+
+    /// 轮询者的 waker，供不使用 [`Event`] 的文件（如 tty）登记
+    pub fn waker(&self) -> &Waker {
+        self.cx.waker()
+    }
+}
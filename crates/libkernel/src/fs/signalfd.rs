@@ -0,0 +1,110 @@
+use defines::{
+    error::{errno, KResult},
+    fs::PollEvents,
+    signal::SignalfdSiginfo,
+};
+use klocks::SpinMutex;
+use triomphe::Arc;
+
+use super::{inode::InodeMeta, poll::PollTable};
+use crate::{hart::local_hart, memory::ReadBuffer, signal::KSignalSet};
+
+/// 以文件的形式接收信号，类似于 linux 的 `struct signalfd_ctx`。
+///
+/// 读取时从**当前线程**的待处理信号中取走 `mask` 中的信号。
+/// 通常需要先用 `sigprocmask` 屏蔽这些信号，否则它们会先被正常递送
+pub struct SignalFd {
+    meta: InodeMeta,
+    mask: SpinMutex<KSignalSet>,
+}
+
+impl SignalFd {
+    pub fn new(mask: KSignalSet) -> Self {
+        Self {
+            meta: InodeMeta::new_anonymous(),
+            mask: SpinMutex::new(Self::valid_mask(mask)),
+        }
+    }
+
+    pub fn meta(&self) -> &InodeMeta {
+        &self.meta
+    }
+
+    /// 替换关心的信号集
+    pub fn set_mask(&self, mask: KSignalSet) {
+        *self.mask.lock() = Self::valid_mask(mask);
+    }
+
+    /// 同 linux，`SIGKILL` 和 `SIGSTOP` 无法通过 signalfd 接收，会被静默忽略
+    fn valid_mask(mask: KSignalSet) -> KSignalSet {
+        mask.difference(KSignalSet::SIGKILL | KSignalSet::SIGSTOP)
+    }
+
+    /// 取走尽可能多的信号，每个信号写入一个 [`SignalfdSiginfo`]，返回写入的字节数。
+    ///
+    /// 没有信号时会阻塞，`nonblock` 时返回 `EAGAIN`；阻塞期间收到未屏蔽的其他信号则返回 `EINTR`。
+    /// 缓冲区放不下一个 [`SignalfdSiginfo`] 时返回 `EINVAL`
+    pub async fn read(&self, mut buf: ReadBuffer<'_>, nonblock: bool) -> KResult<usize> {
+        const INFO_SIZE: usize = size_of::<SignalfdSiginfo>();
+        let max_signals = buf.len() / INFO_SIZE;
+        if max_signals == 0 {
+            return Err(errno::EINVAL);
+        }
+        let thread = Arc::clone(&local_hart().curr_thread_arc());
+        loop {
+            let listener = thread.signal_event().listen();
+            {
+                let mut user_buf;
+                let dst = match &mut buf {
+                    ReadBuffer::Kernel(buf) => &mut **buf,
+                    ReadBuffer::User(buf) => {
+                        user_buf = unsafe { buf.check_slice_mut()? };
+                        user_buf.as_bytes_mut()
+                    }
+                };
+                let mask = *self.mask.lock();
+                let mut nread = 0;
+                {
+                    let mut inner = thread.lock_inner();
+                    while nread < max_signals
+                        && let Some(signal) = (inner.pending_signal & mask).first_pending()
+                    {
+                        inner.pending_signal.remove(KSignalSet::from(signal));
+                        let info = SignalfdSiginfo {
+                            ssi_signo: u32::from(signal.to_user()),
+                            ..SignalfdSiginfo::default()
+                        };
+                        // SAFETY: `SignalfdSiginfo` 是 `repr(C)` 且没有填充的
+                        let bytes = unsafe { core::slice::from_raw_parts((&raw const info).cast::<u8>(), INFO_SIZE) };
+                        dst[nread * INFO_SIZE..(nread + 1) * INFO_SIZE].copy_from_slice(bytes);
+                        nread += 1;
+                    }
+                }
+                if nread > 0 {
+                    return Ok(nread * INFO_SIZE);
+                }
+                if nonblock {
+                    return Err(errno::EAGAIN);
+                }
+                if thread.has_unmasked_signal() {
+                    return Err(errno::EINTR);
+                }
+            }
+            listener.await;
+        }
+    }
+
+    /// 当前线程有 `mask` 中的待处理信号时可读，见 [`File::poll()`](super::file::File::poll)
+    pub fn poll(&self, table: Option<&mut PollTable<'_, '_>>) -> PollEvents {
+        let thread = local_hart().curr_thread();
+        if let Some(table) = table {
+            table.listen(thread.signal_event());
+        }
+        let mask = *self.mask.lock();
+        if thread.lock_inner_with(|inner| inner.pending_signal.intersects(mask)) {
+            PollEvents::POLLIN
+        } else {
+            PollEvents::empty()
+        }
+    }
+}
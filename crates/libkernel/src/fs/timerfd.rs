@@ -0,0 +1,191 @@
+use core::{
+    future::{self, Future},
+    pin::Pin,
+    task::Poll,
+    time::Duration,
+};
+
+use defines::{
+    error::{errno, KResult},
+    fs::PollEvents,
+};
+use event_listener::Event;
+use executor::time;
+use klocks::SpinMutex;
+
+use super::{inode::InodeMeta, poll::PollTable};
+use crate::memory::ReadBuffer;
+
+/// 定时器文件，类似于 linux 的 `struct timerfd_ctx`。
+///
+/// 到期次数是在读取或轮询时根据当前时间惰性计算的，等待者通过执行器的定时器堆唤醒
+pub struct TimerFd {
+    meta: InodeMeta,
+    /// 是否基于挂钟 (`CLOCK_REALTIME`)。内部的时刻总是相对于开机，只是绝对时间的换算不同
+    realtime: bool,
+    inner: SpinMutex<TimerFdInner>,
+    /// 定时器被重新设置时通知
+    set_event: Event,
+}
+
+struct TimerFdInner {
+    /// 下一次到期的时刻，相对于开机。为 `None` 表示定时器未启动
+    deadline: Option<Duration>,
+    /// 周期定时器的间隔，为 0 表示只触发一次
+    interval: Duration,
+    /// 尚未被读取的到期次数
+    expirations: u64,
+}
+
+impl TimerFdInner {
+    /// 根据当前时间累计到期次数，并推进下一次到期的时刻
+    fn update(&mut self, now: Duration) {
+        let Some(deadline) = self.deadline else {
+            return;
+        };
+        if now < deadline {
+            return;
+        }
+        if self.interval.is_zero() {
+            self.expirations += 1;
+            self.deadline = None;
+        } else {
+            let interval_ns = self.interval.as_nanos();
+            let overruns = (now - deadline).as_nanos() / interval_ns + 1;
+            self.expirations = self.expirations.saturating_add(overruns as u64);
+            self.deadline = Some(deadline + Duration::from_nanos((overruns * interval_ns) as u64));
+        }
+    }
+}
+
+impl TimerFd {
+    pub fn new(realtime: bool) -> Self {
+        Self {
+            meta: InodeMeta::new_anonymous(),
+            realtime,
+            inner: SpinMutex::new(TimerFdInner {
+                deadline: None,
+                interval: Duration::ZERO,
+                expirations: 0,
+            }),
+            set_event: Event::new(),
+        }
+    }
+
+    pub fn meta(&self) -> &InodeMeta {
+        &self.meta
+    }
+
+    /// 返回 (距离下一次到期的时间, 间隔)。定时器未启动时前者为 0
+    pub fn get(&self) -> (Duration, Duration) {
+        let now = time::curr_time();
+        let mut inner = self.inner.lock();
+        inner.update(now);
+        let remaining = inner.deadline.map_or(Duration::ZERO, |deadline| deadline - now);
+        (remaining, inner.interval)
+    }
+
+    /// 重新设置定时器，返回旧的设置，格式同 [`Self::get()`]。未读取的到期次数会被清零。
+    ///
+    /// `value` 为 0 表示停止定时器。`absolute` 时 `value` 是定时器所用时钟上的绝对时刻，否则是相对于当前的时间
+    pub fn set(&self, value: Duration, interval: Duration, absolute: bool) -> (Duration, Duration) {
+        let now = time::curr_time();
+        let deadline = if value.is_zero() {
+            None
+        } else if !absolute {
+            Some(now + value)
+        } else if self.realtime {
+            // 换算为相对于开机的时刻
+            Some(value.saturating_sub(time::real_time() - now))
+        } else {
+            Some(value)
+        };
+
+        let old = {
+            let mut inner = self.inner.lock();
+            inner.update(now);
+            let old_remaining = inner.deadline.map_or(Duration::ZERO, |deadline| deadline - now);
+            let old = (old_remaining, inner.interval);
+            inner.deadline = deadline;
+            inner.interval = interval;
+            inner.expirations = 0;
+            old
+        };
+        self.set_event.notify(usize::MAX);
+        old
+    }
+
+    /// 读出到期次数并清零，以 `u64` 写入。
+    ///
+    /// 还没有到期时会阻塞，`nonblock` 时返回 `EAGAIN`。缓冲区小于 8 字节时返回 `EINVAL`
+    pub async fn read(&self, mut buf: ReadBuffer<'_>, nonblock: bool) -> KResult<usize> {
+        if buf.len() < size_of::<u64>() {
+            return Err(errno::EINVAL);
+        }
+        loop {
+            let mut set_listener = self.set_event.listen();
+            let deadline = {
+                let mut user_buf;
+                let dst = match &mut buf {
+                    ReadBuffer::Kernel(buf) => &mut **buf,
+                    ReadBuffer::User(buf) => {
+                        user_buf = unsafe { buf.check_slice_mut()? };
+                        user_buf.as_bytes_mut()
+                    }
+                };
+                let mut inner = self.inner.lock();
+                inner.update(time::curr_time());
+                if inner.expirations > 0 {
+                    let expirations = core::mem::take(&mut inner.expirations);
+                    drop(inner);
+                    dst[..size_of::<u64>()].copy_from_slice(&expirations.to_ne_bytes());
+                    return Ok(size_of::<u64>());
+                }
+                if nonblock {
+                    return Err(errno::EAGAIN);
+                }
+                inner.deadline
+            };
+            // 等到下一次到期或者定时器被重新设置
+            let mut timer_registered = false;
+            future::poll_fn(|cx| {
+                if Pin::new(&mut set_listener).poll(cx).is_ready() {
+                    return Poll::Ready(());
+                }
+                if let Some(deadline) = deadline {
+                    if time::curr_time() >= deadline {
+                        return Poll::Ready(());
+                    }
+                    if !timer_registered {
+                        time::wake_at(deadline, cx.waker().clone());
+                        timer_registered = true;
+                    }
+                }
+                Poll::Pending
+            })
+            .await;
+        }
+    }
+
+    /// 有未读取的到期次数时可读，见 [`File::poll()`](super::file::File::poll)
+    pub fn poll(&self, mut table: Option<&mut PollTable<'_, '_>>) -> PollEvents {
+        if let Some(table) = &mut table {
+            table.listen(&self.set_event);
+        }
+        let (deadline, expired) = {
+            let mut inner = self.inner.lock();
+            inner.update(time::curr_time());
+            (inner.deadline, inner.expirations > 0)
+        };
+        if expired {
+            return PollEvents::POLLIN;
+        }
+        // 在下一次到期时唤醒。其间定时器若被重新设置，上面登记的监听者会唤醒轮询者
+        if let Some(table) = table
+            && let Some(deadline) = deadline
+        {
+            time::wake_at(deadline, table.waker().clone());
+        }
+        PollEvents::empty()
+    }
+}
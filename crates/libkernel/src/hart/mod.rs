@@ -1,3 +1,5 @@
+mod stat;
+
 use alloc::vec::Vec;
 use core::{
     arch::asm,
@@ -10,6 +12,7 @@ use crossbeam_utils::CachePadded;
 use kernel_tracer::SpanId;
 use triomphe::Arc;
 
+pub use self::stat::{cpu_info, init_cpu_info, irq_names, register_irq, CpuInfo, HartStat};
 use crate::{process::Process, thread::Thread, trap::TrapContext};
 
 // `CachePadded` 可以保证 per-cpu 的结构位于不同的 cache line 中
@@ -31,6 +34,7 @@ pub struct Hart {
     thread: RefCell<Option<Arc<Thread>>>,
     pub span_stack: RefCell<Vec<SpanId>>,
     pub panicked: Cell<bool>,
+    pub stat: HartStat,
 }
 
 impl Hart {
@@ -41,6 +45,7 @@ impl Hart {
             thread: RefCell::new(None),
             span_stack: RefCell::new(Vec::new()),
             panicked: Cell::new(false),
+            stat: HartStat::new(),
         }
     }
 
@@ -57,6 +62,10 @@ impl Hart {
         Ref::map(self.thread.borrow(), |t| t.as_ref().unwrap().as_ref())
     }
 
+    pub fn curr_thread_arc(&self) -> Ref<'_, Arc<Thread>> {
+        Ref::map(self.thread.borrow(), |t| t.as_ref().unwrap())
+    }
+
     /// 辅助方法，相当于 `curr_thread()` 并从中取出 trap context
     pub fn curr_trap_context(&self) -> NonNull<TrapContext> {
         NonNull::from(unsafe { &mut self.curr_thread().get_owned().as_mut().trap_context })
@@ -69,6 +78,11 @@ impl Hart {
     pub fn curr_process_arc(&self) -> Ref<'_, Arc<Process>> {
         Ref::map(self.curr_thread(), |t| &t.process)
     }
+
+    /// 同 [`Self::curr_process()`]，但当前 hart 上没有运行用户线程（如内核初始化阶段）时返回 `None`
+    pub fn try_curr_process(&self) -> Option<Ref<'_, Process>> {
+        Ref::filter_map(self.thread.borrow(), |t| t.as_ref().map(|t| t.process.as_ref())).ok()
+    }
 }
 
 /// 设置当前 hart 的 `Hart` 结构，将 `tp` 设置为其地址
@@ -80,6 +94,7 @@ pub unsafe fn set_local_hart(hart_id: usize) {
     unsafe {
         let hart_ptr = HARTS[hart_id].get();
         (&mut (*hart_ptr)).hart_id = hart_id;
+        (*hart_ptr).stat.set_online();
         asm!("mv tp, {}", in(reg) hart_ptr as usize);
     }
 }
@@ -91,3 +106,11 @@ pub fn local_hart<'a>() -> &'a Hart {
         &*(tp as *const Hart)
     }
 }
+
+/// 所有已启动的 hart。注意其他 hart 的 `Hart` 只应当访问其中的 [`HartStat`]
+pub fn online_harts() -> impl Iterator<Item = &'static Hart> {
+    HARTS
+        .iter()
+        .map(|hart| unsafe { &**hart.get() })
+        .filter(|hart| hart.stat.is_online())
+}
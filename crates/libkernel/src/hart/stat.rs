@@ -0,0 +1,117 @@
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use common::config::MAX_IRQ_NUM;
+use ecow::EcoString;
+use klocks::{Once, SpinMutex};
+
+/// hart 的运行统计，时间的单位均为时钟周期。
+///
+/// 只由对应的 hart 更新，但可能被其他 hart 读取（如 `/proc/stat`），因此使用原子变量
+pub struct HartStat {
+    online: AtomicBool,
+    /// hart 开始运行的时间
+    start_time: AtomicUsize,
+    /// 处于用户态的时间
+    user_time: AtomicUsize,
+    /// 空闲（没有可运行的任务而挂起）的时间
+    idle_time: AtomicUsize,
+    /// 时钟中断次数
+    timer_irqs: AtomicUsize,
+    /// 各个外部中断源的中断次数，以 PLIC 中断号为下标
+    external_irqs: [AtomicUsize; MAX_IRQ_NUM],
+}
+
+impl HartStat {
+    pub(super) const fn new() -> Self {
+        Self {
+            online: AtomicBool::new(false),
+            start_time: AtomicUsize::new(0),
+            user_time: AtomicUsize::new(0),
+            idle_time: AtomicUsize::new(0),
+            timer_irqs: AtomicUsize::new(0),
+            external_irqs: [const { AtomicUsize::new(0) }; MAX_IRQ_NUM],
+        }
+    }
+
+    pub(super) fn set_online(&self) {
+        self.start_time.store(riscv_time::get_time(), Ordering::Relaxed);
+        self.online.store(true, Ordering::Release);
+    }
+
+    pub fn is_online(&self) -> bool {
+        self.online.load(Ordering::Acquire)
+    }
+
+    pub fn add_user_time(&self, time: usize) {
+        self.user_time.fetch_add(time, Ordering::Relaxed);
+    }
+
+    pub fn add_idle_time(&self, time: usize) {
+        self.idle_time.fetch_add(time, Ordering::Relaxed);
+    }
+
+    pub fn count_timer_irq(&self) {
+        self.timer_irqs.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn count_external_irq(&self, irq: usize) {
+        if let Some(count) = self.external_irqs.get(irq) {
+            count.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn user_time(&self) -> usize {
+        self.user_time.load(Ordering::Relaxed)
+    }
+
+    pub fn idle_time(&self) -> usize {
+        self.idle_time.load(Ordering::Relaxed)
+    }
+
+    /// 处于内核态且不空闲的时间，即运行时间减去用户态和空闲时间
+    pub fn system_time(&self) -> usize {
+        let total = riscv_time::get_time().saturating_sub(self.start_time.load(Ordering::Relaxed));
+        total.saturating_sub(self.user_time() + self.idle_time())
+    }
+
+    pub fn timer_irqs(&self) -> usize {
+        self.timer_irqs.load(Ordering::Relaxed)
+    }
+
+    pub fn external_irqs(&self, irq: usize) -> usize {
+        self.external_irqs
+            .get(irq)
+            .map_or(0, |count| count.load(Ordering::Relaxed))
+    }
+}
+
+/// 启动时从设备树中发现的 hart 信息，即 `/proc/cpuinfo` 的内容
+pub struct CpuInfo {
+    pub hart_id: usize,
+    /// 如 `rv64imafdc`
+    pub isa: EcoString,
+    /// 如 `sv39`
+    pub mmu: EcoString,
+}
+
+static CPU_INFO: Once<Vec<CpuInfo>> = Once::new();
+
+pub fn init_cpu_info(cpus: Vec<CpuInfo>) {
+    CPU_INFO.call_once(|| cpus);
+}
+
+pub fn cpu_info() -> &'static [CpuInfo] {
+    CPU_INFO.get().map_or(&[], Vec::as_slice)
+}
+
+/// 已启用的外部中断源的名字，以 PLIC 中断号为键
+static IRQ_NAMES: SpinMutex<BTreeMap<usize, &'static str>> = SpinMutex::new(BTreeMap::new());
+
+pub fn register_irq(irq: usize, name: &'static str) {
+    IRQ_NAMES.lock().insert(irq, name);
+}
+
+pub fn irq_names() -> Vec<(usize, &'static str)> {
+    IRQ_NAMES.lock().iter().map(|(&irq, &name)| (irq, name)).collect()
+}
@@ -20,6 +20,7 @@ pub mod extern_symbols;
 pub mod fs;
 pub mod hart;
 pub mod memory;
+pub mod net;
 pub mod process;
 pub mod signal;
 pub mod thread;
@@ -100,6 +100,11 @@ impl MemorySpace {
         memory_set
     }
 
+    /// 按地址从低到高遍历用户地址空间中的区域
+    pub fn user_areas(&self) -> impl Iterator<Item = &FramedVmArea> {
+        self.user_areas.values()
+    }
+
     pub fn empty_user() -> Self {
         let mut ret = Self::new_bare();
         ret.map_kernel_areas();
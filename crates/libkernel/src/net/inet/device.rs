@@ -0,0 +1,113 @@
+//! 协议栈使用的以太网设备。
+//!
+//! 回环和网卡共用同一个 smoltcp 接口：发往自己 MAC 地址的帧直接放回接收队列，其他帧交给网卡。
+//! 这样路由完全由 smoltcp 根据目的地址决定，发往本机地址（包括 127.0.0.1 和网卡地址）的报文不会被发到网卡上
+
+use alloc::{collections::VecDeque, vec, vec::Vec};
+
+use hal::net_device::NetDevice;
+use smoltcp::{
+    phy::{self, DeviceCapabilities, Medium},
+    time::Instant,
+    wire::{EthernetAddress, EthernetFrame},
+};
+
+/// 没有网卡时使用的 MAC 地址，是一个本地管理的单播地址
+const LOOPBACK_MAC: EthernetAddress = EthernetAddress([0x02, 0, 0, 0, 0, 0x01]);
+/// 以太网帧头的长度
+const ETHERNET_HEADER_LEN: usize = 14;
+/// 没有网卡时的 MTU，同 linux 回环网卡
+const LOOPBACK_MTU: usize = 65536;
+/// 有网卡时的 MTU，即以太网的标准 MTU
+const ETHERNET_MTU: usize = 1500;
+
+pub(super) struct EthDevice {
+    mac: EthernetAddress,
+    nic: Option<&'static dyn NetDevice>,
+    /// 发给自己的帧
+    looped: VecDeque<Vec<u8>>,
+}
+
+impl EthDevice {
+    pub fn new(nic: Option<&'static dyn NetDevice>) -> Self {
+        Self {
+            mac: nic.map_or(LOOPBACK_MAC, |nic| EthernetAddress(nic.mac_address())),
+            nic,
+            looped: VecDeque::new(),
+        }
+    }
+
+    pub fn mac(&self) -> EthernetAddress {
+        self.mac
+    }
+
+    /// 是否还有发给自己、尚未被处理的帧
+    pub fn has_looped(&self) -> bool {
+        !self.looped.is_empty()
+    }
+
+    fn transmit_frame(&mut self, frame: Vec<u8>) {
+        let Ok(dst) = EthernetFrame::new_checked(&frame).map(|frame| frame.dst_addr()) else {
+            return;
+        };
+        if dst == self.mac {
+            self.looped.push_back(frame);
+            return;
+        }
+        if let Some(nic) = self.nic {
+            nic.send(&frame);
+        }
+        // 广播帧（如查询本机地址的 ARP 请求）自己也要收到
+        if !dst.is_unicast() {
+            self.looped.push_back(frame);
+        }
+    }
+}
+
+impl phy::Device for EthDevice {
+    type RxToken<'a> = RxToken;
+    type TxToken<'a> = TxToken<'a>;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let frame = match self.looped.pop_front() {
+            Some(frame) => frame,
+            None => self.nic?.receive()?,
+        };
+        Some((RxToken(frame), TxToken(self)))
+    }
+
+    fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        if self.nic.is_some_and(|nic| !nic.can_send()) {
+            return None;
+        }
+        Some(TxToken(self))
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.medium = Medium::Ethernet;
+        // TODO: [low] 有网卡时，回环的报文也受限于以太网的 MTU
+        let mtu = if self.nic.is_some() { ETHERNET_MTU } else { LOOPBACK_MTU };
+        caps.max_transmission_unit = mtu + ETHERNET_HEADER_LEN;
+        caps
+    }
+}
+
+pub(super) struct RxToken(Vec<u8>);
+
+impl phy::RxToken for RxToken {
+    fn consume<R, F: FnOnce(&[u8]) -> R>(self, f: F) -> R {
+        f(&self.0)
+    }
+}
+
+pub(super) struct TxToken<'a>(&'a mut EthDevice);
+
+impl phy::TxToken for TxToken<'_> {
+    fn consume<R, F: FnOnce(&mut [u8]) -> R>(self, len: usize, f: F) -> R {
+        let mut frame = vec![0; len];
+        let ret = f(&mut frame);
+        self.0.transmit_frame(frame);
+        ret
+    }
+}
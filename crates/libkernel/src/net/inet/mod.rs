@@ -0,0 +1,336 @@
+//! IPv4 socket，基于 smoltcp 协议栈。
+//!
+//! 协议栈只有一个以太网接口，除了回环地址 127.0.0.1 以外，探测到网卡时还会配置上 QEMU 用户态网络的地址
+//! [`NIC_ADDR`]，见 [`device`]。所有的 smoltcp socket 都放在全局的 [`NetStack`] 中，
+//! 每次操作 socket 之后都会驱动一次协议栈，回环的报文因此会被同步地处理完。
+//! 协议栈的状态发生变化时（包括网卡收到报文的中断）会通知同一个 [`Event`]，所有等待 IPv4 socket 的任务都在其上等待
+
+mod device;
+mod tcp;
+mod udp;
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    vec::Vec,
+};
+use core::{
+    net::{IpAddr, Ipv4Addr, SocketAddrV4},
+    ops::RangeInclusive,
+    time::Duration,
+};
+
+use defines::error::{errno, KResult};
+use event_listener::{Event, EventListener};
+use executor::time;
+use hal::net_device;
+use klocks::{Lazy, SpinMutex};
+use smoltcp::{
+    iface::{Config, Interface, PollResult, SocketHandle, SocketSet},
+    socket::tcp::{self as smol_tcp, State},
+    time::Instant,
+    wire::{HardwareAddress, IpAddress, IpCidr, IpEndpoint, IpListenEndpoint},
+};
+pub use tcp::TcpSocket;
+pub use udp::UdpSocket;
+
+use self::device::EthDevice;
+use super::Waiting;
+use crate::thread::Thread;
+
+/// 临时端口的范围，同 linux 的 `/proc/sys/net/ipv4/ip_local_port_range`
+const EPHEMERAL_PORTS: RangeInclusive<u16> = 32768..=60999;
+
+// TODO: [low] 通过 DHCP 获取地址
+/// 网卡的地址，即 QEMU 用户态网络分配给客户机的默认地址
+const NIC_ADDR: Ipv4Addr = Ipv4Addr::new(10, 0, 2, 15);
+const NIC_PREFIX_LEN: u8 = 24;
+/// 默认网关，即 QEMU 用户态网络中的主机
+const GATEWAY_ADDR: Ipv4Addr = Ipv4Addr::new(10, 0, 2, 2);
+
+/// 全局的协议栈
+struct Net {
+    stack: SpinMutex<NetStack>,
+    /// 协议栈的状态发生变化时通知
+    event: Event,
+}
+
+static NET: Lazy<Net> = Lazy::new(|| Net {
+    stack: SpinMutex::new(NetStack::new()),
+    event: Event::new(),
+});
+
+struct NetStack {
+    iface: Interface,
+    device: EthDevice,
+    sockets: SocketSet<'static>,
+    /// 被 TCP socket 占用的端口。`accept()` 得到的连接与监听者共用端口，不单独占用
+    tcp_ports: BTreeSet<u16>,
+    /// 监听中的 TCP 端口
+    listeners: BTreeMap<u16, Listener>,
+    /// 绑定了的 UDP 端口
+    udp_ports: BTreeMap<u16, udp::UdpPort>,
+    /// 已经被关闭、还在进行挥手的 TCP 连接，以及其占用的端口。结束后才释放
+    closing: Vec<(SocketHandle, Option<u16>)>,
+    /// 下一次分配临时端口时开始查找的位置
+    next_ephemeral: u16,
+}
+
+/// 一个监听中的 TCP 端口。
+///
+/// smoltcp 的一个 socket 只能接受一个连接，因此始终保持一个处于 `LISTEN` 状态的 socket，
+/// 它接受连接后被移入 `pending`，并创建新的 socket 继续监听，直到 `pending` 达到 backlog 的上限
+struct Listener {
+    local: SocketAddrV4,
+    listening: Option<SocketHandle>,
+    /// 已经建立、等待 `accept()` 的连接
+    pending: VecDeque<SocketHandle>,
+    backlog: usize,
+    /// 新建的连接的接收和发送缓冲区大小
+    buf_size: (usize, usize),
+}
+
+impl NetStack {
+    fn new() -> Self {
+        let nic = net_device::instance();
+        let mut device = EthDevice::new(nic);
+        let config = Config::new(HardwareAddress::Ethernet(device.mac()));
+        let mut iface = Interface::new(config, &mut device, now());
+        iface.update_ip_addrs(|addrs| {
+            // 未指定本地地址时，smoltcp 对不在同一子网的远端使用第一个地址，因此网卡地址要放在前面
+            if nic.is_some() {
+                addrs
+                    .push(IpCidr::new(IpAddress::Ipv4(NIC_ADDR), NIC_PREFIX_LEN))
+                    .expect("the first address");
+            }
+            addrs
+                .push(IpCidr::new(IpAddress::Ipv4(Ipv4Addr::LOCALHOST), 8))
+                .expect("at most the second address");
+        });
+        if nic.is_some() {
+            iface
+                .routes_mut()
+                .add_default_ipv4_route(GATEWAY_ADDR)
+                .expect("the first route");
+        }
+        Self {
+            iface,
+            device,
+            sockets: SocketSet::new(Vec::new()),
+            tcp_ports: BTreeSet::new(),
+            listeners: BTreeMap::new(),
+            udp_ports: BTreeMap::new(),
+            closing: Vec::new(),
+            next_ephemeral: *EPHEMERAL_PORTS.start(),
+        }
+    }
+
+    /// 驱动协议栈，处理完所有能处理的报文。返回 socket 的状态是否可能发生了变化
+    fn poll(&mut self) -> bool {
+        let mut changed = false;
+        // 发给自己的报文会立即被收到，需要反复处理直到没有新的报文。
+        // 只有 ARP 报文时 smoltcp 不认为 socket 的状态发生了变化，因此还要检查是否有发给自己的帧
+        loop {
+            let result = self.iface.poll(now(), &mut self.device, &mut self.sockets);
+            let state_changed = matches!(result, PollResult::SocketStateChanged);
+            changed |= state_changed;
+            if !state_changed && !self.device.has_looped() {
+                break;
+            }
+        }
+        self.refill_listeners();
+        self.reap_closing();
+        changed | self.dispatch_udp()
+    }
+
+    fn tcp_socket(&mut self, handle: SocketHandle) -> &mut smol_tcp::Socket<'static> {
+        self.sockets.get_mut(handle)
+    }
+
+    /// 协议栈下一次需要被驱动的时刻（如重传），相对于开机
+    fn poll_at(&mut self) -> Option<Duration> {
+        self.iface
+            .poll_at(now(), &self.sockets)
+            .map(|instant| Duration::from_micros(instant.total_micros().max(0) as u64))
+    }
+
+    /// 将已经接受了连接的监听 socket 移入等待队列，并补充新的监听 socket
+    fn refill_listeners(&mut self) {
+        for listener in self.listeners.values_mut() {
+            let Some(handle) = listener.listening else {
+                continue;
+            };
+            let socket = self.sockets.get_mut::<smol_tcp::Socket<'static>>(handle);
+            match socket.state() {
+                State::Listen => {}
+                // 握手时被对端重置了，继续监听即可
+                State::Closed => {
+                    socket
+                        .listen(to_listen_endpoint(listener.local))
+                        .expect("closed socket can listen");
+                }
+                _ => {
+                    listener.pending.push_back(handle);
+                    listener.listening = None;
+                    listener.refill(&mut self.sockets);
+                }
+            }
+        }
+    }
+
+    /// 回收挥手结束的连接。同 linux，关闭后还收到数据的连接会被重置
+    fn reap_closing(&mut self) {
+        self.closing.retain(|&(handle, port)| {
+            let socket = self.sockets.get_mut::<smol_tcp::Socket<'static>>(handle);
+            if socket.can_recv() {
+                socket.abort();
+            }
+            // TODO: [low] TIME-WAIT 是为了应对丢包，回环上不需要，但经过网卡的连接应当等待其结束
+            if matches!(socket.state(), State::Closed | State::TimeWait) {
+                self.sockets.remove(handle);
+                if let Some(port) = port {
+                    self.tcp_ports.remove(&port);
+                }
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    /// 分配一个 TCP 端口，`port` 为 0 时分配临时端口
+    fn alloc_tcp_port(&mut self, port: u16) -> KResult<u16> {
+        if port != 0 {
+            return if self.tcp_ports.insert(port) {
+                Ok(port)
+            } else {
+                Err(errno::EADDRINUSE)
+            };
+        }
+        let port = self.find_ephemeral(|stack, port| !stack.tcp_ports.contains(&port))?;
+        self.tcp_ports.insert(port);
+        Ok(port)
+    }
+
+    /// 查找一个满足 `is_free` 的临时端口，全部被占用时返回 `EADDRNOTAVAIL`
+    fn find_ephemeral(&mut self, is_free: impl Fn(&Self, u16) -> bool) -> KResult<u16> {
+        let start = self.next_ephemeral;
+        let mut port = start;
+        loop {
+            port = if port == *EPHEMERAL_PORTS.end() {
+                *EPHEMERAL_PORTS.start()
+            } else {
+                port + 1
+            };
+            if is_free(self, port) {
+                self.next_ephemeral = port;
+                return Ok(port);
+            }
+            if port == start {
+                return Err(errno::EADDRNOTAVAIL);
+            }
+        }
+    }
+}
+
+impl Listener {
+    /// 没有监听中的 socket 并且 backlog 没满时，创建一个新的监听 socket
+    fn refill(&mut self, sockets: &mut SocketSet<'static>) {
+        if self.listening.is_some() || self.pending.len() >= self.backlog {
+            return;
+        }
+        let mut socket = tcp::new_smol_socket(self.buf_size);
+        socket.listen(to_listen_endpoint(self.local)).expect("valid endpoint");
+        self.listening = Some(sockets.add(socket));
+    }
+}
+
+/// smoltcp 的当前时间
+fn now() -> Instant {
+    Instant::from_micros(time::curr_time().as_micros() as i64)
+}
+
+/// 持有协议栈的锁执行 `f`，前后各驱动一次协议栈，状态发生变化时唤醒等待者
+fn with_stack<T>(f: impl FnOnce(&mut NetStack) -> T) -> T {
+    let (ret, changed) = {
+        let mut stack = NET.stack.lock();
+        let mut changed = stack.poll();
+        let ret = f(&mut stack);
+        changed |= stack.poll();
+        (ret, changed)
+    };
+    if changed {
+        NET.event.notify(usize::MAX);
+    }
+    ret
+}
+
+/// 唤醒所有等待者，用于协议栈以外的状态变化，如 `shutdown()`
+fn notify_net() {
+    NET.event.notify(usize::MAX);
+}
+
+/// 在检查等待条件前调用，返回的 listener 会在协议栈状态变化时被通知
+fn listen_net() -> EventListener {
+    NET.event.listen()
+}
+
+/// 等待协议栈的状态发生变化。协议栈有定时任务（如重传）时，到期后也会返回，以便调用者驱动协议栈
+async fn wait_net(wait: &Waiting, thread: &Thread, listener: EventListener) -> KResult<()> {
+    let poll_at = NET.stack.lock().poll_at();
+    wait.wait_until(thread, listener, poll_at).await
+}
+
+/// 网卡收到报文或者发送完成时调用，唤醒等待者去驱动协议栈。
+///
+/// 中断处理时可能打断了持有协议栈的锁的任务，因此这里不能驱动协议栈
+pub fn handle_irq() {
+    if let Some(nic) = net_device::instance() {
+        nic.ack_interrupt();
+    }
+    notify_net();
+}
+
+/// 检查本地地址，只能是 `INADDR_ANY` 或者本机的地址
+pub(super) fn check_local_addr(addr: &SocketAddrV4) -> KResult<()> {
+    let ip = *addr.ip();
+    if ip.is_unspecified() || ip == Ipv4Addr::LOCALHOST || (ip == NIC_ADDR && net_device::instance().is_some()) {
+        Ok(())
+    } else {
+        Err(errno::EADDRNOTAVAIL)
+    }
+}
+
+/// 检查并规范化远端地址。同 linux，`INADDR_ANY` 视为本机；没有网卡时，回环以外的地址都不可达
+fn resolve_remote_addr(addr: SocketAddrV4) -> KResult<SocketAddrV4> {
+    if addr.port() == 0 {
+        return Err(errno::ECONNREFUSED);
+    }
+    if addr.ip().is_unspecified() || addr.ip().is_loopback() {
+        Ok(SocketAddrV4::new(Ipv4Addr::LOCALHOST, addr.port()))
+    } else if net_device::instance().is_some() {
+        Ok(addr)
+    } else {
+        Err(errno::ENETUNREACH)
+    }
+}
+
+fn to_endpoint(addr: SocketAddrV4) -> IpEndpoint {
+    IpEndpoint::new(IpAddress::Ipv4(*addr.ip()), addr.port())
+}
+
+fn to_listen_endpoint(addr: SocketAddrV4) -> IpListenEndpoint {
+    IpListenEndpoint {
+        addr: (!addr.ip().is_unspecified()).then_some(IpAddress::Ipv4(*addr.ip())),
+        port: addr.port(),
+    }
+}
+
+fn from_endpoint(endpoint: IpEndpoint) -> SocketAddrV4 {
+    match IpAddr::from(endpoint.addr) {
+        IpAddr::V4(addr) => SocketAddrV4::new(addr, endpoint.port),
+        IpAddr::V6(_) => unreachable!("only IPv4 is enabled"),
+    }
+}
+
+/// 未绑定的 socket 的地址，即 `0.0.0.0:0`
+const UNSPECIFIED_ADDR: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0);
@@ -0,0 +1,580 @@
+//! TCP socket。连接的状态机由 smoltcp 实现，这里只负责把 smoltcp socket 包装成 linux 的语义
+
+use alloc::{collections::VecDeque, vec, vec::Vec};
+use core::net::SocketAddrV4;
+
+use defines::{
+    error::{errno, Error, KResult},
+    fs::PollEvents,
+    ioctl::FIONREAD,
+    net::{MsgFlags, IPPROTO_TCP, SOMAXCONN, TCP_MAXSEG, TCP_NODELAY},
+};
+use klocks::SpinMutex;
+use smoltcp::{
+    iface::SocketHandle,
+    socket::tcp::{Socket as SmolSocket, SocketBuffer, State},
+};
+use triomphe::Arc;
+
+use super::{
+    from_endpoint, listen_net, notify_net, resolve_remote_addr, to_endpoint, wait_net, with_stack, Listener, NetStack,
+    NET, UNSPECIFIED_ADDR,
+};
+use crate::{
+    fs::poll::PollTable,
+    hart::local_hart,
+    memory::{ReadBuffer, UserCheck, WriteBuffer},
+    net::{RecvResult, Waiting},
+    signal::KSignalSet,
+};
+
+/// 回环网卡的 MSS，即 MTU 减去 IP 和 TCP 头部
+const LOOPBACK_MSS: usize = 65535 - 40;
+
+/// 创建一个 smoltcp 的 TCP socket，`buf_size` 是接收和发送缓冲区的大小
+pub(super) fn new_smol_socket((recv_buf, send_buf): (usize, usize)) -> SmolSocket<'static> {
+    let mut socket = SmolSocket::new(
+        SocketBuffer::new(vec![0; recv_buf]),
+        SocketBuffer::new(vec![0; send_buf]),
+    );
+    // 回环网卡不会丢包，延迟确认只会在没有人驱动协议栈时拖慢传输
+    socket.set_ack_delay(None);
+    socket
+}
+
+pub struct TcpSocket {
+    inner: SpinMutex<TcpInner>,
+}
+
+struct TcpInner {
+    state: TcpState,
+    /// `bind()` 或者 `listen()` 时绑定的地址
+    local: Option<SocketAddrV4>,
+    /// 该 socket 占用的端口，关闭后释放。`accept()` 得到的连接不占用端口
+    owned_port: Option<u16>,
+    nodelay: bool,
+    shut_read: bool,
+    /// 已经发起连接，但还不知道结果
+    connecting: bool,
+    /// 异步发生的错误，如非阻塞连接失败。`SO_ERROR` 读取后清除
+    error: Option<Error>,
+}
+
+#[derive(Clone, Copy)]
+enum TcpState {
+    /// 新建或者仅绑定了地址
+    Closed,
+    /// 监听中，值为监听的端口
+    Listening(u16),
+    /// 正在连接或者已经建立了连接
+    Connected(SocketHandle),
+}
+
+/// 读取一次接收缓冲区的结果
+enum RecvState {
+    Data(usize),
+    Eof,
+    Pending,
+}
+
+impl TcpSocket {
+    pub(in crate::net) fn new() -> Self {
+        Self::with_state(TcpState::Closed, false)
+    }
+
+    fn with_state(state: TcpState, nodelay: bool) -> Self {
+        Self {
+            inner: SpinMutex::new(TcpInner {
+                state,
+                local: None,
+                owned_port: None,
+                nodelay,
+                shut_read: false,
+                connecting: false,
+                error: None,
+            }),
+        }
+    }
+
+    pub fn is_listening(&self) -> bool {
+        matches!(self.inner.lock().state, TcpState::Listening(_))
+    }
+
+    pub fn local_addr(&self) -> SocketAddrV4 {
+        let inner = self.inner.lock();
+        match (inner.state, inner.local) {
+            (TcpState::Connected(handle), _) => with_stack(|stack| {
+                stack
+                    .tcp_socket(handle)
+                    .local_endpoint()
+                    .map_or(UNSPECIFIED_ADDR, from_endpoint)
+            }),
+            (_, Some(local)) => local,
+            (_, None) => UNSPECIFIED_ADDR,
+        }
+    }
+
+    pub fn peer_addr(&self) -> KResult<SocketAddrV4> {
+        let TcpState::Connected(handle) = self.inner.lock().state else {
+            return Err(errno::ENOTCONN);
+        };
+        with_stack(|stack| stack.tcp_socket(handle).remote_endpoint().map(from_endpoint)).ok_or(errno::ENOTCONN)
+    }
+
+    /// 读取并清除异步发生的错误，即 `SO_ERROR`
+    pub fn take_error(&self) -> Option<Error> {
+        let mut inner = self.inner.lock();
+        with_stack(|stack| inner.update_connecting(stack));
+        inner.error.take()
+    }
+
+    pub fn bind(&self, addr: SocketAddrV4) -> KResult<()> {
+        let mut inner = self.inner.lock();
+        if inner.local.is_some() || !matches!(inner.state, TcpState::Closed) {
+            return Err(errno::EINVAL);
+        }
+        let port = with_stack(|stack| stack.alloc_tcp_port(addr.port()))?;
+        inner.owned_port = Some(port);
+        inner.local = Some(SocketAddrV4::new(*addr.ip(), port));
+        Ok(())
+    }
+
+    /// 开始监听。`buf_size` 是之后建立的连接的接收和发送缓冲区大小
+    pub fn listen(&self, backlog: usize, buf_size: (usize, usize)) -> KResult<()> {
+        // 同 linux，backlog 为 0 时也能接受一个连接
+        let backlog = backlog.clamp(1, SOMAXCONN);
+        let mut inner = self.inner.lock();
+        match inner.state {
+            TcpState::Connected(_) => Err(errno::EINVAL),
+            TcpState::Listening(port) => {
+                with_stack(|stack| {
+                    let NetStack { listeners, sockets, .. } = stack;
+                    let listener = listeners.get_mut(&port).expect("listening port");
+                    listener.backlog = backlog;
+                    listener.refill(sockets);
+                });
+                Ok(())
+            }
+            TcpState::Closed => {
+                let inner = &mut *inner;
+                with_stack(|stack| -> KResult<()> {
+                    // 未绑定时自动绑定一个临时端口
+                    let local = match inner.local {
+                        Some(local) => local,
+                        None => {
+                            let port = stack.alloc_tcp_port(0)?;
+                            inner.owned_port = Some(port);
+                            SocketAddrV4::new(*UNSPECIFIED_ADDR.ip(), port)
+                        }
+                    };
+                    let mut listener = Listener {
+                        local,
+                        listening: None,
+                        pending: VecDeque::new(),
+                        backlog,
+                        buf_size,
+                    };
+                    listener.refill(&mut stack.sockets);
+                    stack.listeners.insert(local.port(), listener);
+                    inner.local = Some(local);
+                    inner.state = TcpState::Listening(local.port());
+                    Ok(())
+                })
+            }
+        }
+    }
+
+    pub(in crate::net) async fn accept(&self, wait: Waiting) -> KResult<TcpSocket> {
+        let thread = Arc::clone(&local_hart().curr_thread_arc());
+        loop {
+            let listener = listen_net();
+            let (accepted, nodelay) = {
+                let inner = self.inner.lock();
+                let TcpState::Listening(port) = inner.state else {
+                    return Err(errno::EINVAL);
+                };
+                let accepted = with_stack(|stack| {
+                    let NetStack { listeners, sockets, .. } = stack;
+                    let listener = listeners.get_mut(&port).expect("listening port");
+                    let handle = listener.pending.pop_front()?;
+                    listener.refill(sockets);
+                    // 同 linux，`TCP_NODELAY` 会被继承
+                    sockets
+                        .get_mut::<SmolSocket<'static>>(handle)
+                        .set_nagle_enabled(!inner.nodelay);
+                    Some(handle)
+                });
+                (accepted, inner.nodelay)
+            };
+            if let Some(handle) = accepted {
+                return Ok(Self::with_state(TcpState::Connected(handle), nodelay));
+            }
+            wait_net(&wait, &thread, listener).await?;
+        }
+    }
+
+    /// 连接到 `addr`。非阻塞时连接在后台进行，返回 `EINPROGRESS`
+    pub(in crate::net) async fn connect(
+        &self,
+        addr: SocketAddrV4,
+        wait: Waiting,
+        buf_size: (usize, usize),
+    ) -> KResult<()> {
+        let remote = resolve_remote_addr(addr)?;
+        {
+            let mut inner = self.inner.lock();
+            let inner = &mut *inner;
+            match inner.state {
+                TcpState::Listening(_) => return Err(errno::EISCONN),
+                TcpState::Connected(_) => {
+                    with_stack(|stack| inner.update_connecting(stack));
+                    if inner.connecting {
+                        return Err(errno::EALREADY);
+                    }
+                    if matches!(inner.state, TcpState::Connected(_)) {
+                        return Err(errno::EISCONN);
+                    }
+                }
+                TcpState::Closed => {}
+            }
+            with_stack(|stack| -> KResult<()> {
+                let port = match inner.local {
+                    Some(local) => local.port(),
+                    None => match inner.owned_port {
+                        Some(port) => port,
+                        None => {
+                            let port = stack.alloc_tcp_port(0)?;
+                            inner.owned_port = Some(port);
+                            port
+                        }
+                    },
+                };
+                let mut socket = new_smol_socket(buf_size);
+                socket.set_nagle_enabled(!inner.nodelay);
+                socket
+                    .connect(stack.iface.context(), to_endpoint(remote), port)
+                    .map_err(|_| errno::EADDRNOTAVAIL)?;
+                inner.state = TcpState::Connected(stack.sockets.add(socket));
+                inner.connecting = true;
+                Ok(())
+            })?;
+        }
+
+        let thread = Arc::clone(&local_hart().curr_thread_arc());
+        loop {
+            let listener = listen_net();
+            {
+                let mut inner = self.inner.lock();
+                with_stack(|stack| inner.update_connecting(stack));
+                if !inner.connecting {
+                    return inner.error.take().map_or(Ok(()), Err);
+                }
+            }
+            if let Err(e) = wait_net(&wait, &thread, listener).await {
+                // 连接会在后台继续进行
+                return Err(if e == errno::EAGAIN { errno::EINPROGRESS } else { e });
+            }
+        }
+    }
+
+    fn connected_handle(&self) -> KResult<SocketHandle> {
+        match self.inner.lock().state {
+            TcpState::Connected(handle) => Ok(handle),
+            TcpState::Closed | TcpState::Listening(_) => Err(errno::ENOTCONN),
+        }
+    }
+
+    pub(in crate::net) async fn send(&self, buf: WriteBuffer<'_>, flags: MsgFlags, wait: Waiting) -> KResult<usize> {
+        let handle = self.connected_handle()?;
+        let len = buf.len();
+        if len == 0 {
+            return Ok(0);
+        }
+        let thread = Arc::clone(&local_hart().curr_thread_arc());
+        let mut nwrite = 0;
+        loop {
+            let listener = listen_net();
+            {
+                let rest = buf.slice(nwrite..len).expect("in bound");
+                let user_buf;
+                let src = match &rest {
+                    WriteBuffer::Kernel(buf) => *buf,
+                    WriteBuffer::User(buf) => {
+                        user_buf = buf.check_slice()?;
+                        &*user_buf
+                    }
+                };
+                let sent = with_stack(|stack| {
+                    let socket = stack.tcp_socket(handle);
+                    if matches!(socket.state(), State::SynSent | State::SynReceived) {
+                        return Ok(0);
+                    }
+                    if !socket.may_send() {
+                        return Err(errno::EPIPE);
+                    }
+                    Ok(socket.send_slice(src).unwrap_or(0))
+                });
+                match sent {
+                    Ok(0) => {}
+                    Ok(n) => {
+                        nwrite += n;
+                        if nwrite == len {
+                            return Ok(nwrite);
+                        }
+                        continue;
+                    }
+                    Err(e) => {
+                        if !flags.contains(MsgFlags::NOSIGNAL) {
+                            thread.receive_signal(KSignalSet::SIGPIPE);
+                        }
+                        return if nwrite > 0 { Ok(nwrite) } else { Err(e) };
+                    }
+                }
+            }
+            if let Err(e) = wait_net(&wait, &thread, listener).await {
+                return if nwrite > 0 { Ok(nwrite) } else { Err(e) };
+            }
+        }
+    }
+
+    pub(in crate::net) async fn recv(
+        &self,
+        mut buf: ReadBuffer<'_>,
+        flags: MsgFlags,
+        wait: Waiting,
+    ) -> KResult<RecvResult> {
+        let handle = self.connected_handle()?;
+        let peek = flags.contains(MsgFlags::PEEK);
+        let wait_all = flags.contains(MsgFlags::WAITALL) && !peek;
+        let thread = Arc::clone(&local_hart().curr_thread_arc());
+        let result = |len| RecvResult {
+            len,
+            truncated: false,
+            from: None,
+            rights: Vec::new(),
+        };
+        if buf.is_empty() {
+            return Ok(result(0));
+        }
+        let mut nread = 0;
+        loop {
+            let listener = listen_net();
+            {
+                let mut user_buf;
+                let dst = match &mut buf {
+                    ReadBuffer::Kernel(buf) => &mut **buf,
+                    ReadBuffer::User(buf) => {
+                        user_buf = unsafe { buf.check_slice_mut()? };
+                        user_buf.as_bytes_mut()
+                    }
+                };
+                let shut_read = self.inner.lock().shut_read;
+                let state = with_stack(|stack| {
+                    let socket = stack.tcp_socket(handle);
+                    if socket.can_recv() {
+                        let len = if peek {
+                            socket.peek_slice(&mut dst[nread..])
+                        } else {
+                            socket.recv_slice(&mut dst[nread..])
+                        };
+                        return RecvState::Data(len.unwrap_or(0));
+                    }
+                    if shut_read {
+                        return RecvState::Eof;
+                    }
+                    match socket.state() {
+                        State::SynSent | State::SynReceived => RecvState::Pending,
+                        _ if !socket.may_recv() => RecvState::Eof,
+                        _ => RecvState::Pending,
+                    }
+                });
+                match state {
+                    RecvState::Data(len) => {
+                        nread += len;
+                        if !wait_all || nread == dst.len() {
+                            return Ok(result(nread));
+                        }
+                        continue;
+                    }
+                    RecvState::Eof => return Ok(result(nread)),
+                    RecvState::Pending => {}
+                }
+            }
+            if let Err(e) = wait_net(&wait, &thread, listener).await {
+                return if nread > 0 { Ok(result(nread)) } else { Err(e) };
+            }
+        }
+    }
+
+    /// 关闭读方向和（或）写方向。关闭写方向会向对端发送 FIN
+    pub fn shutdown(&self, read: bool, write: bool) -> KResult<()> {
+        let mut inner = self.inner.lock();
+        let TcpState::Connected(handle) = inner.state else {
+            return Err(errno::ENOTCONN);
+        };
+        inner.shut_read |= read;
+        if write {
+            with_stack(|stack| stack.tcp_socket(handle).close());
+        }
+        drop(inner);
+        notify_net();
+        Ok(())
+    }
+
+    /// 就绪状态，同 linux 的 `tcp_poll()`
+    pub fn poll(&self, table: Option<&mut PollTable<'_, '_>>) -> PollEvents {
+        if let Some(table) = table {
+            table.listen(&NET.event);
+        }
+        let mut inner = self.inner.lock();
+        let inner = &mut *inner;
+        let mut events = with_stack(|stack| {
+            inner.update_connecting(stack);
+            match inner.state {
+                TcpState::Closed => PollEvents::POLLOUT | PollEvents::POLLHUP,
+                TcpState::Listening(port) => {
+                    if stack.listeners[&port].pending.is_empty() {
+                        PollEvents::empty()
+                    } else {
+                        PollEvents::POLLIN
+                    }
+                }
+                TcpState::Connected(_) if inner.connecting => PollEvents::empty(),
+                TcpState::Connected(handle) => {
+                    let socket = stack.tcp_socket(handle);
+                    let mut events = PollEvents::empty();
+                    if socket.can_recv() || inner.shut_read {
+                        events |= PollEvents::POLLIN;
+                    }
+                    if !socket.may_recv() {
+                        events |= PollEvents::POLLIN | PollEvents::POLLRDHUP;
+                    }
+                    // 写方向关闭时也算可写，写入会立即返回 `EPIPE`
+                    if socket.can_send() || !socket.may_send() {
+                        events |= PollEvents::POLLOUT;
+                    }
+                    if !socket.may_recv() && !socket.may_send() {
+                        events |= PollEvents::POLLHUP;
+                    }
+                    events
+                }
+            }
+        });
+        if inner.error.is_some() {
+            events |= PollEvents::POLLERR;
+        }
+        events
+    }
+
+    pub fn ioctl(&self, request: usize, argp: usize) -> KResult {
+        match request {
+            FIONREAD => {
+                let len = match self.inner.lock().state {
+                    TcpState::Listening(_) => return Err(errno::EINVAL),
+                    TcpState::Closed => 0,
+                    TcpState::Connected(handle) => with_stack(|stack| stack.tcp_socket(handle).recv_queue()),
+                };
+                unsafe { UserCheck::new(argp as *mut i32).ok_or(errno::EINVAL)?.check_ptr_mut()? }.write(len as i32);
+                Ok(0)
+            }
+            _ => Err(errno::ENOTTY),
+        }
+    }
+
+    /// `IPPROTO_TCP` 层的选项
+    pub fn get_option(&self, level: i32, name: i32) -> KResult<Vec<u8>> {
+        if level != IPPROTO_TCP {
+            return Err(errno::ENOPROTOOPT);
+        }
+        let value = match name {
+            TCP_NODELAY => i32::from(self.inner.lock().nodelay),
+            TCP_MAXSEG => LOOPBACK_MSS as i32,
+            _ => return Err(errno::ENOPROTOOPT),
+        };
+        Ok(Vec::from(value.to_ne_bytes()))
+    }
+
+    pub fn set_option(&self, level: i32, name: i32, value: i32) -> KResult<()> {
+        if level != IPPROTO_TCP {
+            return Err(errno::ENOPROTOOPT);
+        }
+        match name {
+            TCP_NODELAY => {
+                let mut inner = self.inner.lock();
+                inner.nodelay = value != 0;
+                if let TcpState::Connected(handle) = inner.state {
+                    with_stack(|stack| stack.tcp_socket(handle).set_nagle_enabled(value == 0));
+                }
+            }
+            // 同 linux，只检查范围。回环网卡的 MSS 由 MTU 决定
+            TCP_MAXSEG => {
+                if !(88..=32767).contains(&value) {
+                    return Err(errno::EINVAL);
+                }
+            }
+            _ => return Err(errno::ENOPROTOOPT),
+        }
+        Ok(())
+    }
+}
+
+impl TcpInner {
+    /// 正在连接时，根据 smoltcp socket 的状态更新连接的结果。
+    ///
+    /// 连接被拒绝时 socket 回到未连接的状态，可以再次连接
+    fn update_connecting(&mut self, stack: &mut NetStack) {
+        let TcpState::Connected(handle) = self.state else {
+            return;
+        };
+        if !self.connecting {
+            return;
+        }
+        match stack.tcp_socket(handle).state() {
+            State::SynSent | State::SynReceived => {}
+            State::Closed => {
+                stack.sockets.remove(handle);
+                if self.local.is_none()
+                    && let Some(port) = self.owned_port.take()
+                {
+                    stack.tcp_ports.remove(&port);
+                }
+                self.state = TcpState::Closed;
+                self.connecting = false;
+                self.error = Some(errno::ECONNREFUSED);
+            }
+            _ => self.connecting = false,
+        }
+    }
+}
+
+impl Drop for TcpSocket {
+    fn drop(&mut self) {
+        let inner = self.inner.get_mut();
+        let (state, owned_port) = (inner.state, inner.owned_port);
+        with_stack(|stack| {
+            match state {
+                TcpState::Closed => {
+                    if let Some(port) = owned_port {
+                        stack.tcp_ports.remove(&port);
+                    }
+                }
+                TcpState::Listening(port) => {
+                    let listener = stack.listeners.remove(&port).expect("listening port");
+                    // 还没有被接受的连接直接重置
+                    for handle in listener.listening.into_iter().chain(listener.pending) {
+                        stack.tcp_socket(handle).abort();
+                        stack.closing.push((handle, None));
+                    }
+                    if let Some(port) = owned_port {
+                        stack.tcp_ports.remove(&port);
+                    }
+                }
+                // 挥手在后台进行，结束后才释放端口
+                TcpState::Connected(handle) => {
+                    stack.tcp_socket(handle).close();
+                    stack.closing.push((handle, owned_port));
+                }
+            }
+        });
+    }
+}
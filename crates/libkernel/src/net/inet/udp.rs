@@ -0,0 +1,353 @@
+//! UDP socket。
+//!
+//! 同一个端口可以被多个设置了 `SO_REUSEADDR` 的 socket 绑定（如 iperf3 的服务端），而 smoltcp 只会把数据报交给第一个匹配的 socket。
+//! 因此每个端口只有一个 smoltcp socket，收到的数据报由 [`NetStack`] 分发到各个 socket 自己的接收队列：
+//! 优先分发给连接到了发送者的 socket，其次是最后绑定的未连接的 socket
+
+use alloc::{collections::VecDeque, vec, vec::Vec};
+use core::net::SocketAddrV4;
+
+use defines::{
+    error::{errno, KResult},
+    fs::PollEvents,
+    ioctl::FIONREAD,
+    net::MsgFlags,
+};
+use klocks::SpinMutex;
+use smoltcp::{
+    iface::SocketHandle,
+    socket::udp::{PacketBuffer, PacketMetadata, SendError, Socket as SmolSocket},
+};
+use triomphe::Arc;
+
+use super::{
+    from_endpoint, listen_net, notify_net, resolve_remote_addr, to_endpoint, wait_net, with_stack, NetStack, NET,
+    UNSPECIFIED_ADDR,
+};
+use crate::{
+    fs::poll::PollTable,
+    hart::local_hart,
+    memory::{ReadBuffer, UserCheck, WriteBuffer},
+    net::{RecvResult, SocketAddr, Waiting, SOCK_BUF_DEFAULT},
+};
+
+/// 一个 UDP 数据报最大的长度，即 IP 报文的最大长度减去 IP 和 UDP 头部
+const UDP_MAX_PAYLOAD: usize = 65535 - 20 - 8;
+/// smoltcp socket 的缓冲区中最多的数据报个数
+const SMOL_PACKETS: usize = 256;
+/// smoltcp socket 的缓冲区大小，至少要能放下一个最大的数据报
+const SMOL_BUF_SIZE: usize = 256 * 1024;
+
+/// 一个被绑定了的 UDP 端口
+pub(super) struct UdpPort {
+    handle: SocketHandle,
+    /// 绑定了该端口的 socket 的接收队列，以及是否设置了 `SO_REUSEADDR`
+    members: Vec<(Arc<UdpRx>, bool)>,
+}
+
+struct UdpRx {
+    inner: SpinMutex<RxInner>,
+}
+
+struct RxInner {
+    /// `connect()` 指定的对端，只接收来自它的数据报
+    peer: Option<SocketAddrV4>,
+    /// 数据报及其发送者
+    datagrams: VecDeque<(SocketAddrV4, Vec<u8>)>,
+    /// 队列中所有数据报的总长度
+    len: usize,
+    capacity: usize,
+    shut_read: bool,
+}
+
+pub struct UdpSocket {
+    rx: Arc<UdpRx>,
+    inner: SpinMutex<UdpInner>,
+}
+
+struct UdpInner {
+    local: Option<SocketAddrV4>,
+    shut_write: bool,
+}
+
+impl NetStack {
+    /// 将端口的 smoltcp socket 收到的数据报分发到各个 socket。返回是否分发了数据报
+    pub(super) fn dispatch_udp(&mut self) -> bool {
+        let mut delivered = false;
+        for port in self.udp_ports.values() {
+            let socket = self.sockets.get_mut::<SmolSocket<'static>>(port.handle);
+            while let Ok((data, meta)) = socket.recv() {
+                let from = from_endpoint(meta.endpoint);
+                let target = port
+                    .members
+                    .iter()
+                    .find(|(rx, _)| rx.inner.lock().peer == Some(from))
+                    .or_else(|| port.members.iter().rev().find(|(rx, _)| rx.inner.lock().peer.is_none()));
+                let Some((rx, _)) = target else {
+                    continue;
+                };
+                let mut rx = rx.inner.lock();
+                // 同 linux，接收队列满了就丢弃
+                if !rx.shut_read && rx.len + data.len() <= rx.capacity {
+                    rx.len += data.len();
+                    rx.datagrams.push_back((from, data.to_vec()));
+                    delivered = true;
+                }
+            }
+        }
+        delivered
+    }
+
+    /// 绑定 UDP 端口，`port` 为 0 时分配临时端口。
+    ///
+    /// 已经被绑定的端口，只有双方都设置了 `SO_REUSEADDR` 时才能再次绑定
+    fn bind_udp(&mut self, port: u16, rx: &Arc<UdpRx>, reuse: bool) -> KResult<u16> {
+        let port = if port == 0 {
+            self.find_ephemeral(|stack, port| !stack.udp_ports.contains_key(&port))?
+        } else {
+            port
+        };
+        if let Some(udp_port) = self.udp_ports.get_mut(&port) {
+            if !reuse || !udp_port.members.iter().all(|&(_, reuse)| reuse) {
+                return Err(errno::EADDRINUSE);
+            }
+            udp_port.members.push((Arc::clone(rx), reuse));
+            return Ok(port);
+        }
+        let mut socket = SmolSocket::new(
+            PacketBuffer::new(vec![PacketMetadata::EMPTY; SMOL_PACKETS], vec![0; SMOL_BUF_SIZE]),
+            PacketBuffer::new(vec![PacketMetadata::EMPTY; SMOL_PACKETS], vec![0; SMOL_BUF_SIZE]),
+        );
+        socket.bind(port).map_err(|_| errno::EINVAL)?;
+        let handle = self.sockets.add(socket);
+        self.udp_ports.insert(
+            port,
+            UdpPort {
+                handle,
+                members: vec![(Arc::clone(rx), reuse)],
+            },
+        );
+        Ok(port)
+    }
+
+    fn unbind_udp(&mut self, port: u16, rx: &Arc<UdpRx>) {
+        let udp_port = self.udp_ports.get_mut(&port).expect("bound port");
+        udp_port.members.retain(|(member, _)| !Arc::ptr_eq(member, rx));
+        if udp_port.members.is_empty() {
+            let udp_port = self.udp_ports.remove(&port).expect("bound port");
+            self.sockets.remove(udp_port.handle);
+        }
+    }
+}
+
+impl UdpSocket {
+    pub(in crate::net) fn new() -> Self {
+        Self {
+            rx: Arc::new(UdpRx {
+                inner: SpinMutex::new(RxInner {
+                    peer: None,
+                    datagrams: VecDeque::new(),
+                    len: 0,
+                    capacity: SOCK_BUF_DEFAULT,
+                    shut_read: false,
+                }),
+            }),
+            inner: SpinMutex::new(UdpInner {
+                local: None,
+                shut_write: false,
+            }),
+        }
+    }
+
+    pub fn local_addr(&self) -> SocketAddrV4 {
+        self.inner.lock().local.unwrap_or(UNSPECIFIED_ADDR)
+    }
+
+    pub fn peer_addr(&self) -> KResult<SocketAddrV4> {
+        self.rx.inner.lock().peer.ok_or(errno::ENOTCONN)
+    }
+
+    /// 绑定地址，`reuse` 即 `SO_REUSEADDR`
+    pub fn bind(&self, addr: SocketAddrV4, reuse: bool) -> KResult<()> {
+        let mut inner = self.inner.lock();
+        if inner.local.is_some() {
+            return Err(errno::EINVAL);
+        }
+        let port = with_stack(|stack| stack.bind_udp(addr.port(), &self.rx, reuse))?;
+        inner.local = Some(SocketAddrV4::new(*addr.ip(), port));
+        Ok(())
+    }
+
+    /// 未绑定时自动绑定一个临时端口，返回本地端口
+    fn autobind(&self) -> KResult<u16> {
+        if let Some(local) = self.inner.lock().local {
+            return Ok(local.port());
+        }
+        self.bind(UNSPECIFIED_ADDR, false)?;
+        Ok(self.local_addr().port())
+    }
+
+    /// 设置默认的对端，之后只接收来自它的数据报
+    pub fn connect(&self, addr: SocketAddrV4) -> KResult<()> {
+        let remote = resolve_remote_addr(addr)?;
+        self.autobind()?;
+        self.rx.inner.lock().peer = Some(remote);
+        Ok(())
+    }
+
+    /// 发送一个数据报，`to` 为 `None` 时发送给 `connect()` 指定的对端
+    pub(in crate::net) async fn send(
+        &self,
+        buf: WriteBuffer<'_>,
+        to: Option<SocketAddrV4>,
+        wait: Waiting,
+    ) -> KResult<usize> {
+        let dest = match to {
+            Some(to) => resolve_remote_addr(to)?,
+            None => self.rx.inner.lock().peer.ok_or(errno::EDESTADDRREQ)?,
+        };
+        if buf.len() > UDP_MAX_PAYLOAD {
+            return Err(errno::EMSGSIZE);
+        }
+        if self.inner.lock().shut_write {
+            return Err(errno::EPIPE);
+        }
+        let port = self.autobind()?;
+        let thread = Arc::clone(&local_hart().curr_thread_arc());
+        loop {
+            let listener = listen_net();
+            {
+                let user_buf;
+                let src = match &buf {
+                    WriteBuffer::Kernel(buf) => *buf,
+                    WriteBuffer::User(buf) => {
+                        user_buf = buf.check_slice()?;
+                        &*user_buf
+                    }
+                };
+                let sent = with_stack(|stack| {
+                    let handle = stack.udp_ports[&port].handle;
+                    match stack
+                        .sockets
+                        .get_mut::<SmolSocket<'static>>(handle)
+                        .send_slice(src, to_endpoint(dest))
+                    {
+                        Ok(()) => Ok(true),
+                        Err(SendError::BufferFull) => Ok(false),
+                        Err(SendError::Unaddressable) => Err(errno::EINVAL),
+                    }
+                });
+                if sent? {
+                    return Ok(src.len());
+                }
+            }
+            wait_net(&wait, &thread, listener).await?;
+        }
+    }
+
+    pub(in crate::net) async fn recv(
+        &self,
+        mut buf: ReadBuffer<'_>,
+        flags: MsgFlags,
+        wait: Waiting,
+    ) -> KResult<RecvResult> {
+        let peek = flags.contains(MsgFlags::PEEK);
+        let thread = Arc::clone(&local_hart().curr_thread_arc());
+        loop {
+            let listener = listen_net();
+            // 驱动协议栈，把已经到达的数据报分发到接收队列
+            with_stack(|_| ());
+            {
+                let mut user_buf;
+                let dst = match &mut buf {
+                    ReadBuffer::Kernel(buf) => &mut **buf,
+                    ReadBuffer::User(buf) => {
+                        user_buf = unsafe { buf.check_slice_mut()? };
+                        user_buf.as_bytes_mut()
+                    }
+                };
+                let mut rx = self.rx.inner.lock();
+                if let Some((from, data)) = rx.datagrams.front() {
+                    let full_len = data.len();
+                    let len = usize::min(full_len, dst.len());
+                    dst[..len].copy_from_slice(&data[..len]);
+                    let from = *from;
+                    if !peek {
+                        rx.datagrams.pop_front();
+                        rx.len -= full_len;
+                    }
+                    return Ok(RecvResult {
+                        len: if flags.contains(MsgFlags::TRUNC) { full_len } else { len },
+                        truncated: len < full_len,
+                        from: Some(SocketAddr::Inet(from)),
+                        rights: Vec::new(),
+                    });
+                }
+                if rx.shut_read {
+                    return Ok(RecvResult {
+                        len: 0,
+                        truncated: false,
+                        from: None,
+                        rights: Vec::new(),
+                    });
+                }
+            }
+            wait_net(&wait, &thread, listener).await?;
+        }
+    }
+
+    /// 同 linux，未连接的 UDP socket 不能 `shutdown()`
+    pub fn shutdown(&self, read: bool, write: bool) -> KResult<()> {
+        let mut rx = self.rx.inner.lock();
+        if rx.peer.is_none() {
+            return Err(errno::ENOTCONN);
+        }
+        rx.shut_read |= read;
+        drop(rx);
+        self.inner.lock().shut_write |= write;
+        notify_net();
+        Ok(())
+    }
+
+    /// 就绪状态，同 linux 的 `udp_poll()`
+    pub fn poll(&self, table: Option<&mut PollTable<'_, '_>>) -> PollEvents {
+        if let Some(table) = table {
+            table.listen(&NET.event);
+        }
+        with_stack(|_| ());
+        let shut_write = self.inner.lock().shut_write;
+        let rx = self.rx.inner.lock();
+        // 发送缓冲区满时只会短暂地阻塞，总是视为可写
+        let mut events = PollEvents::POLLOUT;
+        if !rx.datagrams.is_empty() {
+            events |= PollEvents::POLLIN;
+        }
+        if rx.shut_read {
+            events |= PollEvents::POLLIN | PollEvents::POLLRDHUP;
+        }
+        if rx.shut_read && shut_write {
+            events |= PollEvents::POLLHUP;
+        }
+        events
+    }
+
+    pub fn ioctl(&self, request: usize, argp: usize) -> KResult {
+        match request {
+            FIONREAD => {
+                // 返回下一个数据报的长度
+                let len = self.rx.inner.lock().datagrams.front().map_or(0, |(_, data)| data.len());
+                unsafe { UserCheck::new(argp as *mut i32).ok_or(errno::EINVAL)?.check_ptr_mut()? }.write(len as i32);
+                Ok(0)
+            }
+            _ => Err(errno::ENOTTY),
+        }
+    }
+}
+
+impl Drop for UdpSocket {
+    fn drop(&mut self) {
+        if let Some(local) = self.inner.get_mut().local {
+            with_stack(|stack| stack.unbind_udp(local.port(), &self.rx));
+        }
+    }
+}
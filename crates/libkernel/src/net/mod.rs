@@ -0,0 +1,588 @@
+//! socket 层。支持 unix domain socket（见 [`unix`]）和基于 smoltcp 的 IPv4 socket（见 [`inet`]）
+
+mod inet;
+mod unix;
+
+use alloc::vec::Vec;
+use core::{
+    future::{self, Future},
+    net::{Ipv4Addr, SocketAddrV4},
+    pin::Pin,
+    task::Poll,
+    time::Duration,
+};
+
+use defines::{
+    error::{errno, KResult},
+    fs::PollEvents,
+    misc::TimeVal,
+    net::{
+        MsgFlags, SockaddrIn, AF_INET, AF_UNIX, IPPROTO_TCP, IPPROTO_UDP, SOCK_DGRAM, SOCK_STREAM, SOL_SOCKET,
+        SO_ACCEPTCONN, SO_BROADCAST, SO_DOMAIN, SO_ERROR, SO_KEEPALIVE, SO_LINGER, SO_PASSCRED, SO_PEERCRED,
+        SO_PROTOCOL, SO_RCVBUF, SO_RCVTIMEO, SO_REUSEADDR, SO_SNDBUF, SO_SNDTIMEO, SO_TYPE,
+    },
+};
+use event_listener::EventListener;
+use executor::time;
+pub use inet::{handle_irq, TcpSocket, UdpSocket};
+use klocks::SpinMutex;
+pub use unix::{UnixAddr, UnixSocket};
+
+use crate::{
+    fs::{
+        file::FileDescriptor,
+        inode::{InodeMeta, InodeMode},
+        poll::PollTable,
+    },
+    memory::{ReadBuffer, WriteBuffer},
+    thread::Thread,
+};
+
+/// socket 缓冲区的默认大小，同 linux 的 `/proc/sys/net/core/wmem_default`
+const SOCK_BUF_DEFAULT: usize = 212_992;
+/// `SO_SNDBUF` 和 `SO_RCVBUF` 可以设置的最大值，同 linux 的 `/proc/sys/net/core/wmem_max`
+const SOCK_BUF_MAX: usize = 212_992;
+/// `SO_SNDBUF` 和 `SO_RCVBUF` 的最小值，同 linux 的 `SOCK_MIN_RCVBUF`
+const SOCK_BUF_MIN: usize = 2304;
+
+/// 一个 socket，类似于 linux 的 `struct socket`。
+///
+/// 通用的部分（inode 元数据和 `SOL_SOCKET` 层的选项）放在这里，协议相关的部分见 [`SocketKind`]
+pub struct Socket {
+    /// socket 没有对应的文件系统，使用单独的匿名 inode 元数据
+    meta: InodeMeta,
+    options: SpinMutex<SocketOptions>,
+    kind: SocketKind,
+}
+
+pub enum SocketKind {
+    Unix(UnixSocket),
+    Tcp(TcpSocket),
+    Udp(UdpSocket),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SocketType {
+    /// `SOCK_STREAM`
+    Stream,
+    /// `SOCK_DGRAM`
+    Dgram,
+}
+
+impl SocketType {
+    pub fn from_user(socket_type: u32) -> Option<Self> {
+        match socket_type {
+            SOCK_STREAM => Some(Self::Stream),
+            SOCK_DGRAM => Some(Self::Dgram),
+            _ => None,
+        }
+    }
+
+    pub fn to_user(self) -> u32 {
+        match self {
+            Self::Stream => SOCK_STREAM,
+            Self::Dgram => SOCK_DGRAM,
+        }
+    }
+}
+
+/// socket 地址，即用户态的 `struct sockaddr`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SocketAddr {
+    Unix(UnixAddr),
+    Inet(SocketAddrV4),
+}
+
+impl SocketAddr {
+    /// 从用户传入的 `struct sockaddr` 解析地址，`bytes` 的长度即 `addrlen`。
+    ///
+    /// 长度不足以包含地址族时返回 `EINVAL`，不支持的地址族返回 `EAFNOSUPPORT`
+    pub fn from_bytes(bytes: &[u8]) -> KResult<Self> {
+        let family = bytes.get(..size_of::<u16>()).ok_or(errno::EINVAL)?;
+        match u16::from_ne_bytes([family[0], family[1]]) {
+            AF_UNIX => Ok(Self::Unix(UnixAddr::from_path_bytes(&bytes[size_of::<u16>()..])?)),
+            AF_INET => {
+                let bytes = bytes.get(..size_of::<SockaddrIn>()).ok_or(errno::EINVAL)?;
+                let port = u16::from_be_bytes([bytes[2], bytes[3]]);
+                let addr = Ipv4Addr::new(bytes[4], bytes[5], bytes[6], bytes[7]);
+                Ok(Self::Inet(SocketAddrV4::new(addr, port)))
+            }
+            _ => Err(errno::EAFNOSUPPORT),
+        }
+    }
+
+    /// 转换为用户态的 `struct sockaddr`，返回值的长度即地址的实际长度
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Unix(addr) => {
+                let mut bytes = Vec::from(AF_UNIX.to_ne_bytes());
+                addr.write_path_bytes(&mut bytes);
+                bytes
+            }
+            Self::Inet(addr) => {
+                let mut bytes = Vec::with_capacity(size_of::<SockaddrIn>());
+                bytes.extend_from_slice(&AF_INET.to_ne_bytes());
+                bytes.extend_from_slice(&addr.port().to_be_bytes());
+                bytes.extend_from_slice(&addr.ip().octets());
+                bytes.resize(size_of::<SockaddrIn>(), 0);
+                bytes
+            }
+        }
+    }
+}
+
+/// `recv` 系列操作的结果
+pub struct RecvResult {
+    /// 读取的字节数。数据报指定了 `MSG_TRUNC` 时为数据报的实际长度
+    pub len: usize,
+    /// 数据报因缓冲区太小被截断
+    pub truncated: bool,
+    /// 发送者的地址，流式 socket 为 `None`
+    pub from: Option<SocketAddr>,
+    /// 通过 `SCM_RIGHTS` 收到的文件
+    pub rights: Vec<FileDescriptor>,
+}
+
+/// `SOL_SOCKET` 层的选项
+struct SocketOptions {
+    reuse_addr: bool,
+    keep_alive: bool,
+    broadcast: bool,
+    pass_cred: bool,
+    // TODO: [low] 缓冲区大小只影响之后建立的 TCP 连接，对 unix socket 和 UDP 只是记录下来
+    send_buf: usize,
+    recv_buf: usize,
+    /// 阻塞接收的超时时间，`None` 表示无限等待
+    recv_timeout: Option<Duration>,
+    send_timeout: Option<Duration>,
+    /// `struct linger`，只是记录下来，关闭时并不会等待
+    linger: (i32, i32),
+}
+
+impl Socket {
+    /// 创建一个 socket，即 `socket()`。
+    ///
+    /// 不支持的地址族返回 `EAFNOSUPPORT`，不支持的类型返回 `ESOCKTNOSUPPORT`，不支持的协议返回 `EPROTONOSUPPORT`
+    pub fn new(domain: u16, socket_type: u32, protocol: i32) -> KResult<Self> {
+        let kind = match domain {
+            AF_UNIX => SocketKind::Unix(UnixSocket::new(unix_socket_type(socket_type, protocol)?)),
+            AF_INET => match SocketType::from_user(socket_type).ok_or(errno::ESOCKTNOSUPPORT)? {
+                SocketType::Stream if protocol == 0 || protocol == IPPROTO_TCP => SocketKind::Tcp(TcpSocket::new()),
+                SocketType::Dgram if protocol == 0 || protocol == IPPROTO_UDP => SocketKind::Udp(UdpSocket::new()),
+                _ => return Err(errno::EPROTONOSUPPORT),
+            },
+            _ => return Err(errno::EAFNOSUPPORT),
+        };
+        Ok(Self::with_kind(kind))
+    }
+
+    /// 创建一对相互连接的 socket，即 `socketpair()`。只有 unix socket 支持
+    pub fn new_pair(domain: u16, socket_type: u32, protocol: i32) -> KResult<(Self, Self)> {
+        match domain {
+            AF_UNIX => {
+                let (a, b) = UnixSocket::new_pair(unix_socket_type(socket_type, protocol)?);
+                Ok((
+                    Self::with_kind(SocketKind::Unix(a)),
+                    Self::with_kind(SocketKind::Unix(b)),
+                ))
+            }
+            _ => Err(errno::EOPNOTSUPP),
+        }
+    }
+
+    fn with_kind(kind: SocketKind) -> Self {
+        let meta = InodeMeta::new(InodeMode::Socket);
+        let curr_time = time::curr_time_spec();
+        meta.lock_inner_with(|inner| inner.change_time = curr_time);
+        Self {
+            meta,
+            options: SpinMutex::new(SocketOptions {
+                reuse_addr: false,
+                keep_alive: false,
+                broadcast: false,
+                pass_cred: false,
+                send_buf: SOCK_BUF_DEFAULT,
+                recv_buf: SOCK_BUF_DEFAULT,
+                recv_timeout: None,
+                send_timeout: None,
+                linger: (0, 0),
+            }),
+            kind,
+        }
+    }
+
+    pub fn meta(&self) -> &InodeMeta {
+        &self.meta
+    }
+
+    pub fn kind(&self) -> &SocketKind {
+        &self.kind
+    }
+
+    pub fn socket_type(&self) -> SocketType {
+        match &self.kind {
+            SocketKind::Unix(unix) => unix.socket_type(),
+            SocketKind::Tcp(_) => SocketType::Stream,
+            SocketKind::Udp(_) => SocketType::Dgram,
+        }
+    }
+
+    /// 绑定地址，即 `bind()`
+    pub fn bind(&self, addr: SocketAddr) -> KResult<()> {
+        match (&self.kind, addr) {
+            (SocketKind::Unix(unix), SocketAddr::Unix(addr)) => unix.bind(addr),
+            (SocketKind::Unix(_), _) => Err(errno::EINVAL),
+            (SocketKind::Tcp(tcp), SocketAddr::Inet(addr)) => {
+                inet::check_local_addr(&addr)?;
+                tcp.bind(addr)
+            }
+            (SocketKind::Udp(udp), SocketAddr::Inet(addr)) => {
+                inet::check_local_addr(&addr)?;
+                udp.bind(addr, self.options.lock().reuse_addr)
+            }
+            (SocketKind::Tcp(_) | SocketKind::Udp(_), _) => Err(errno::EAFNOSUPPORT),
+        }
+    }
+
+    /// 开始监听连接，即 `listen()`
+    pub fn listen(&self, backlog: usize) -> KResult<()> {
+        match &self.kind {
+            SocketKind::Unix(unix) => unix.listen(backlog),
+            SocketKind::Tcp(tcp) => tcp.listen(backlog, self.buf_size()),
+            SocketKind::Udp(_) => Err(errno::EOPNOTSUPP),
+        }
+    }
+
+    /// 取出一个已经建立的连接，即 `accept()`。没有连接时会阻塞，`nonblock` 时返回 `EAGAIN`
+    pub async fn accept(&self, nonblock: bool) -> KResult<Socket> {
+        let wait = self.waiting(nonblock, false);
+        match &self.kind {
+            SocketKind::Unix(unix) => {
+                let accepted = unix.accept(wait).await?;
+                Ok(Self::with_kind(SocketKind::Unix(accepted)))
+            }
+            SocketKind::Tcp(tcp) => {
+                let accepted = tcp.accept(wait).await?;
+                Ok(Self::with_kind(SocketKind::Tcp(accepted)))
+            }
+            SocketKind::Udp(_) => Err(errno::EOPNOTSUPP),
+        }
+    }
+
+    /// 连接到 `addr`，即 `connect()`
+    pub async fn connect(&self, addr: SocketAddr, nonblock: bool) -> KResult<()> {
+        let wait = self.waiting(nonblock, true);
+        match (&self.kind, addr) {
+            (SocketKind::Unix(unix), SocketAddr::Unix(addr)) => unix.connect(addr, wait).await,
+            (SocketKind::Unix(_), _) => Err(errno::EINVAL),
+            (SocketKind::Tcp(tcp), SocketAddr::Inet(addr)) => tcp.connect(addr, wait, self.buf_size()).await,
+            (SocketKind::Udp(udp), SocketAddr::Inet(addr)) => udp.connect(addr),
+            (SocketKind::Tcp(_) | SocketKind::Udp(_), _) => Err(errno::EAFNOSUPPORT),
+        }
+    }
+
+    /// 发送数据，`rights` 是通过 `SCM_RIGHTS` 一同发送的文件。`to` 为 `None` 时发送给连接的对端。
+    ///
+    /// 返回发送的字节数
+    pub async fn send(
+        &self,
+        buf: WriteBuffer<'_>,
+        rights: Vec<FileDescriptor>,
+        to: Option<SocketAddr>,
+        flags: MsgFlags,
+        nonblock: bool,
+    ) -> KResult<usize> {
+        let wait = self.waiting(nonblock || flags.contains(MsgFlags::DONTWAIT), true);
+        match &self.kind {
+            SocketKind::Unix(unix) => {
+                let to = match to {
+                    Some(SocketAddr::Unix(addr)) => Some(addr),
+                    Some(_) => return Err(errno::EINVAL),
+                    None => None,
+                };
+                unix.send(buf, rights, to, flags, wait).await
+            }
+            // 同 linux，流式 socket 忽略目的地址
+            SocketKind::Tcp(tcp) => tcp.send(buf, flags, wait).await,
+            SocketKind::Udp(udp) => {
+                let to = match to {
+                    Some(SocketAddr::Inet(addr)) => Some(addr),
+                    Some(_) => return Err(errno::EAFNOSUPPORT),
+                    None => None,
+                };
+                udp.send(buf, to, wait).await
+            }
+        }
+    }
+
+    /// 接收数据
+    pub async fn recv(&self, buf: ReadBuffer<'_>, flags: MsgFlags, nonblock: bool) -> KResult<RecvResult> {
+        let wait = self.waiting(nonblock || flags.contains(MsgFlags::DONTWAIT), false);
+        match &self.kind {
+            SocketKind::Unix(unix) => unix.recv(buf, flags, wait).await,
+            SocketKind::Tcp(tcp) => tcp.recv(buf, flags, wait).await,
+            SocketKind::Udp(udp) => udp.recv(buf, flags, wait).await,
+        }
+    }
+
+    /// 一次接收最多能得到的字节数，即接收缓冲区的容量。UDP 为数据报的最大长度
+    pub fn recv_capacity(&self) -> usize {
+        match &self.kind {
+            SocketKind::Unix(_) => SOCK_BUF_DEFAULT,
+            SocketKind::Tcp(_) => self.options.lock().recv_buf,
+            SocketKind::Udp(_) => u16::MAX as usize,
+        }
+    }
+
+    /// 关闭连接的读方向、写方向或者全部，即 `shutdown()`
+    pub fn shutdown(&self, read: bool, write: bool) -> KResult<()> {
+        match &self.kind {
+            SocketKind::Unix(unix) => {
+                unix.shutdown(read, write);
+                Ok(())
+            }
+            SocketKind::Tcp(tcp) => tcp.shutdown(read, write),
+            SocketKind::Udp(udp) => udp.shutdown(read, write),
+        }
+    }
+
+    /// 本地地址，即 `getsockname()`
+    pub fn local_addr(&self) -> SocketAddr {
+        match &self.kind {
+            SocketKind::Unix(unix) => SocketAddr::Unix(unix.local_addr()),
+            SocketKind::Tcp(tcp) => SocketAddr::Inet(tcp.local_addr()),
+            SocketKind::Udp(udp) => SocketAddr::Inet(udp.local_addr()),
+        }
+    }
+
+    /// 对端地址，即 `getpeername()`。未连接时返回 `ENOTCONN`
+    pub fn peer_addr(&self) -> KResult<SocketAddr> {
+        match &self.kind {
+            SocketKind::Unix(unix) => unix.peer_addr().map(SocketAddr::Unix),
+            SocketKind::Tcp(tcp) => tcp.peer_addr().map(SocketAddr::Inet),
+            SocketKind::Udp(udp) => udp.peer_addr().map(SocketAddr::Inet),
+        }
+    }
+
+    /// 见 [`File::poll()`](crate::fs::file::File::poll)
+    pub fn poll(&self, table: Option<&mut PollTable<'_, '_>>) -> PollEvents {
+        match &self.kind {
+            SocketKind::Unix(unix) => unix.poll(table),
+            SocketKind::Tcp(tcp) => tcp.poll(table),
+            SocketKind::Udp(udp) => udp.poll(table),
+        }
+    }
+
+    pub fn ioctl(&self, request: usize, argp: usize) -> KResult {
+        match &self.kind {
+            SocketKind::Unix(unix) => unix.ioctl(request, argp),
+            SocketKind::Tcp(tcp) => tcp.ioctl(request, argp),
+            SocketKind::Udp(udp) => udp.ioctl(request, argp),
+        }
+    }
+
+    /// 读取选项，即 `getsockopt()`，返回选项值的字节表示。不支持的选项返回 `ENOPROTOOPT`
+    pub fn get_option(&self, level: i32, name: i32) -> KResult<Vec<u8>> {
+        if level != SOL_SOCKET {
+            return match &self.kind {
+                SocketKind::Tcp(tcp) => tcp.get_option(level, name),
+                SocketKind::Unix(_) | SocketKind::Udp(_) => Err(errno::ENOPROTOOPT),
+            };
+        }
+        let int = |value: i32| Vec::from(value.to_ne_bytes());
+        let options = self.options.lock();
+        let value = match name {
+            SO_TYPE => int(self.socket_type().to_user() as i32),
+            SO_DOMAIN => match &self.kind {
+                SocketKind::Unix(_) => int(i32::from(AF_UNIX)),
+                SocketKind::Tcp(_) | SocketKind::Udp(_) => int(i32::from(AF_INET)),
+            },
+            SO_PROTOCOL => match &self.kind {
+                SocketKind::Unix(_) => int(0),
+                SocketKind::Tcp(_) => int(IPPROTO_TCP),
+                SocketKind::Udp(_) => int(IPPROTO_UDP),
+            },
+            // 返回正的错误码
+            SO_ERROR => match &self.kind {
+                SocketKind::Tcp(tcp) => int(tcp.take_error().map_or(0, |e| -e.as_isize() as i32)),
+                SocketKind::Unix(_) | SocketKind::Udp(_) => int(0),
+            },
+            SO_ACCEPTCONN => int(match &self.kind {
+                SocketKind::Unix(unix) => i32::from(unix.is_listening()),
+                SocketKind::Tcp(tcp) => i32::from(tcp.is_listening()),
+                SocketKind::Udp(_) => 0,
+            }),
+            SO_REUSEADDR => int(i32::from(options.reuse_addr)),
+            SO_KEEPALIVE => int(i32::from(options.keep_alive)),
+            SO_BROADCAST => int(i32::from(options.broadcast)),
+            SO_PASSCRED => int(i32::from(options.pass_cred)),
+            SO_SNDBUF => int(options.send_buf as i32),
+            SO_RCVBUF => int(options.recv_buf as i32),
+            SO_RCVTIMEO => timeval_bytes(options.recv_timeout),
+            SO_SNDTIMEO => timeval_bytes(options.send_timeout),
+            SO_LINGER => [options.linger.0.to_ne_bytes(), options.linger.1.to_ne_bytes()].concat(),
+            SO_PEERCRED => match &self.kind {
+                SocketKind::Unix(unix) => {
+                    let cred = unix.peer_cred();
+                    [cred.pid.to_ne_bytes(), cred.uid.to_ne_bytes(), cred.gid.to_ne_bytes()].concat()
+                }
+                SocketKind::Tcp(_) | SocketKind::Udp(_) => return Err(errno::ENOPROTOOPT),
+            },
+            _ => return Err(errno::ENOPROTOOPT),
+        };
+        Ok(value)
+    }
+
+    /// 设置选项，即 `setsockopt()`，`value` 是选项值的字节表示。
+    ///
+    /// 不支持的选项返回 `ENOPROTOOPT`，`value` 长度不足返回 `EINVAL`
+    pub fn set_option(&self, level: i32, name: i32, value: &[u8]) -> KResult<()> {
+        let int = || -> KResult<i32> {
+            let bytes = value.get(..size_of::<i32>()).ok_or(errno::EINVAL)?;
+            Ok(i32::from_ne_bytes(bytes.try_into().unwrap()))
+        };
+        if level != SOL_SOCKET {
+            return match &self.kind {
+                SocketKind::Tcp(tcp) => tcp.set_option(level, name, int()?),
+                SocketKind::Unix(_) | SocketKind::Udp(_) => Err(errno::ENOPROTOOPT),
+            };
+        }
+        let mut options = self.options.lock();
+        match name {
+            SO_REUSEADDR => options.reuse_addr = int()? != 0,
+            SO_KEEPALIVE => options.keep_alive = int()? != 0,
+            SO_BROADCAST => options.broadcast = int()? != 0,
+            SO_PASSCRED => options.pass_cred = int()? != 0,
+            // 同 linux，设置的值会被加倍，以包含簿记的开销
+            SO_SNDBUF => options.send_buf = (int()?.max(0) as usize).min(SOCK_BUF_MAX).max(SOCK_BUF_MIN / 2) * 2,
+            SO_RCVBUF => options.recv_buf = (int()?.max(0) as usize).min(SOCK_BUF_MAX).max(SOCK_BUF_MIN / 2) * 2,
+            SO_RCVTIMEO => options.recv_timeout = timeval_from_bytes(value)?,
+            SO_SNDTIMEO => options.send_timeout = timeval_from_bytes(value)?,
+            SO_LINGER => {
+                let bytes = value.get(..2 * size_of::<i32>()).ok_or(errno::EINVAL)?;
+                options.linger = (
+                    i32::from_ne_bytes(bytes[..4].try_into().unwrap()),
+                    i32::from_ne_bytes(bytes[4..].try_into().unwrap()),
+                );
+            }
+            _ => return Err(errno::ENOPROTOOPT),
+        }
+        Ok(())
+    }
+
+    /// IPv4 连接的接收和发送缓冲区大小
+    fn buf_size(&self) -> (usize, usize) {
+        let options = self.options.lock();
+        (options.recv_buf, options.send_buf)
+    }
+
+    /// 根据 `nonblock` 和超时选项决定阻塞操作的等待方式。`send` 表示是发送方向的操作
+    fn waiting(&self, nonblock: bool, send: bool) -> Waiting {
+        let options = self.options.lock();
+        let timeout = if send {
+            options.send_timeout
+        } else {
+            options.recv_timeout
+        };
+        Waiting {
+            nonblock,
+            deadline: timeout.map(|timeout| time::curr_time() + timeout),
+        }
+    }
+}
+
+/// 检查 unix socket 的类型和协议
+fn unix_socket_type(socket_type: u32, protocol: i32) -> KResult<SocketType> {
+    let socket_type = SocketType::from_user(socket_type).ok_or(errno::ESOCKTNOSUPPORT)?;
+    // 同 linux，unix socket 的协议只能是 0 或者 `PF_UNIX`
+    if protocol != 0 && protocol != i32::from(AF_UNIX) {
+        return Err(errno::EPROTONOSUPPORT);
+    }
+    Ok(socket_type)
+}
+
+/// 将超时时间转换为 `struct timeval`，`None` 表示无限等待，即全为 0
+fn timeval_bytes(timeout: Option<Duration>) -> Vec<u8> {
+    let timeout = timeout.unwrap_or_default();
+    let timeval = TimeVal {
+        sec: timeout.as_secs() as usize,
+        usec: timeout.subsec_micros() as usize,
+    };
+    [timeval.sec.to_ne_bytes(), timeval.usec.to_ne_bytes()].concat()
+}
+
+/// 从 `struct timeval` 解析超时时间，全为 0 表示无限等待
+fn timeval_from_bytes(bytes: &[u8]) -> KResult<Option<Duration>> {
+    let bytes = bytes.get(..size_of::<TimeVal>()).ok_or(errno::EINVAL)?;
+    let sec = i64::from_ne_bytes(bytes[..8].try_into().unwrap());
+    let usec = i64::from_ne_bytes(bytes[8..].try_into().unwrap());
+    if !(0..1_000_000).contains(&usec) {
+        return Err(errno::EINVAL);
+    }
+    // 同 linux，负数的超时时间视为立即超时
+    let timeout = if sec < 0 {
+        Duration::from_micros(1)
+    } else {
+        Duration::new(sec as u64, usec as u32 * 1000)
+    };
+    Ok((!timeout.is_zero()).then_some(timeout))
+}
+
+/// 阻塞的 socket 操作如何等待
+#[derive(Clone, Copy, Debug)]
+struct Waiting {
+    nonblock: bool,
+    /// 等待的截止时刻，相对于开机。来自 `SO_RCVTIMEO` 或 `SO_SNDTIMEO`
+    deadline: Option<Duration>,
+}
+
+impl Waiting {
+    /// 等待 `listener` 被通知，调用前应该已经检查过等待的条件。
+    ///
+    /// `nonblock` 或者超时时返回 `EAGAIN`；有未屏蔽的信号时返回 `EINTR`
+    async fn wait(&self, thread: &Thread, listener: EventListener) -> KResult<()> {
+        self.wait_until(thread, listener, None).await
+    }
+
+    /// 同 [`Waiting::wait()`]，但到达 `wake_at` 时也会返回，以便调用者做一些定时的工作
+    async fn wait_until(&self, thread: &Thread, mut listener: EventListener, wake_at: Option<Duration>) -> KResult<()> {
+        if self.nonblock {
+            return Err(errno::EAGAIN);
+        }
+        let mut signal_listener = thread.signal_event().listen();
+        if thread.has_unmasked_signal() {
+            return Err(errno::EINTR);
+        }
+        let mut timer_registered = false;
+        let mut wake_registered = false;
+        let timed_out = future::poll_fn(|cx| {
+            if Pin::new(&mut listener).poll(cx).is_ready() || Pin::new(&mut signal_listener).poll(cx).is_ready() {
+                return Poll::Ready(false);
+            }
+            if let Some(wake_at) = wake_at {
+                if time::curr_time() >= wake_at {
+                    return Poll::Ready(false);
+                }
+                if !wake_registered {
+                    time::wake_at(wake_at, cx.waker().clone());
+                    wake_registered = true;
+                }
+            }
+            if let Some(deadline) = self.deadline {
+                if time::curr_time() >= deadline {
+                    return Poll::Ready(true);
+                }
+                if !timer_registered {
+                    time::wake_at(deadline, cx.waker().clone());
+                    timer_registered = true;
+                }
+            }
+            Poll::Pending
+        })
+        .await;
+        if timed_out {
+            return Err(errno::EAGAIN);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,873 @@
+//! unix domain socket，类似于 linux 的 `net/unix/af_unix.c`。
+//!
+//! 每个 socket 有一个接收队列，发送者直接把消息放进对端的接收队列中。
+//! 绑定了地址的 socket 会把接收队列登记在全局的名字表中，以便其他 socket 连接或者发送数据报
+
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    format,
+    vec::Vec,
+};
+use core::{
+    mem,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use defines::{
+    error::{errno, KResult},
+    fs::{FaccessatMode, PollEvents, StatMode, AT_FDCWD},
+    ioctl::FIONREAD,
+    net::{MsgFlags, UCred, SOMAXCONN, UNIX_PATH_MAX},
+};
+use ecow::EcoString;
+use event_listener::Event;
+use klocks::SpinMutex;
+use triomphe::Arc;
+
+use super::{RecvResult, SocketAddr, SocketType, Waiting, SOCK_BUF_DEFAULT};
+use crate::{
+    fs::{self, file::FileDescriptor, inode::InodeMode, poll::PollTable},
+    hart::local_hart,
+    memory::{ReadBuffer, UserCheck, WriteBuffer},
+    process,
+    signal::KSignalSet,
+};
+
+/// unix socket 的地址
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UnixAddr {
+    /// 未绑定地址
+    Unnamed,
+    /// 文件系统中的路径
+    Path(EcoString),
+    /// 抽象地址，不包含开头的 0。不在文件系统中，名字可以包含任意字节
+    Abstract(Vec<u8>),
+}
+
+impl UnixAddr {
+    /// 从 `sun_path` 解析地址，`path` 的长度由用户传入的地址长度决定
+    pub fn from_path_bytes(path: &[u8]) -> KResult<Self> {
+        if path.len() > UNIX_PATH_MAX {
+            return Err(errno::EINVAL);
+        }
+        match path {
+            [] => Ok(Self::Unnamed),
+            [0, name @ ..] => Ok(Self::Abstract(name.to_vec())),
+            _ => {
+                // 路径在第一个 0 处结束，没有 0 时是整个 `sun_path`
+                let len = path.iter().position(|&b| b == 0).unwrap_or(path.len());
+                let path = core::str::from_utf8(&path[..len]).map_err(|_| errno::EINVAL)?;
+                Ok(Self::Path(EcoString::from(path)))
+            }
+        }
+    }
+
+    /// 将 `sun_path` 追加到 `bytes` 中，长度同 linux 的 `getsockname()`
+    pub fn write_path_bytes(&self, bytes: &mut Vec<u8>) {
+        match self {
+            Self::Unnamed => {}
+            Self::Path(path) => {
+                bytes.extend_from_slice(path.as_bytes());
+                bytes.push(0);
+            }
+            Self::Abstract(name) => {
+                bytes.push(0);
+                bytes.extend_from_slice(name);
+            }
+        }
+    }
+}
+
+/// 名字表的键。路径地址以 socket 文件的 inode 编号区分，因此通过不同的路径（如符号链接）也能找到同一个 socket
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum NameKey {
+    Inode(usize),
+    Abstract(Vec<u8>),
+}
+
+/// 名字表中的一项，即一个绑定了地址的 socket
+struct Binding {
+    socket_type: SocketType,
+    addr: UnixAddr,
+    queue: Arc<UnixQueue>,
+}
+
+/// 所有绑定了地址的 unix socket。socket 被释放时移除
+static NAME_TABLE: SpinMutex<BTreeMap<NameKey, Binding>> = SpinMutex::new(BTreeMap::new());
+
+/// 自动绑定时分配的抽象地址，同 linux 为 5 位十六进制数
+static NEXT_AUTOBIND: AtomicUsize = AtomicUsize::new(0);
+
+/// 找到 `addr` 对应的名字表的键，不检查是否真的被绑定了。
+///
+/// 路径地址要求文件存在且可写，不是 socket 文件时返回 `ECONNREFUSED`
+fn lookup_key(addr: &UnixAddr) -> KResult<NameKey> {
+    match addr {
+        UnixAddr::Unnamed => Err(errno::EINVAL),
+        UnixAddr::Abstract(name) => Ok(NameKey::Abstract(name.clone())),
+        UnixAddr::Path(path) => {
+            let p2i = fs::resolve_path_with_dir_fd(AT_FDCWD, path)?.follow_last()?;
+            let dentry = p2i.dir.lookup(p2i.last_component).ok_or(errno::ENOENT)?;
+            fs::check_access(dentry.meta(), FaccessatMode::W_OK)?;
+            if dentry.meta().mode() != InodeMode::Socket {
+                return Err(errno::ECONNREFUSED);
+            }
+            Ok(NameKey::Inode(dentry.meta().ino()))
+        }
+    }
+}
+
+/// 找到 `addr` 上绑定的 socket。没有 socket 绑定时返回 `ECONNREFUSED`，类型不同时返回 `EPROTOTYPE`
+fn lookup_binding(addr: &UnixAddr, socket_type: SocketType) -> KResult<(UnixAddr, Arc<UnixQueue>)> {
+    let key = lookup_key(addr)?;
+    let table = NAME_TABLE.lock();
+    let binding = table.get(&key).ok_or(errno::ECONNREFUSED)?;
+    if binding.socket_type != socket_type {
+        return Err(errno::EPROTOTYPE);
+    }
+    Ok((binding.addr.clone(), Arc::clone(&binding.queue)))
+}
+
+/// 当前进程的凭证，用于 `SO_PEERCRED`
+fn curr_ucred() -> UCred {
+    let cred = process::curr_cred();
+    UCred {
+        pid: local_hart().curr_process().pid() as i32,
+        uid: cred.uid.effective,
+        gid: cred.gid.effective,
+    }
+}
+
+/// 一个 socket 的接收队列，由 socket 自身和向它发送数据的对端共享
+struct UnixQueue {
+    inner: SpinMutex<QueueInner>,
+    /// 队列中的消息、连接请求或者关闭状态变化时通知，读者和写者都在此等待
+    event: Event,
+}
+
+struct QueueInner {
+    messages: VecDeque<Message>,
+    /// 队列中尚未读取的字节数
+    len: usize,
+    capacity: usize,
+    /// 接收者已被释放，或者流式 socket 关闭了读方向。此后发送者会得到 `EPIPE` 或 `ECONNREFUSED`
+    reader_closed: bool,
+    /// 流式连接的对端已被释放或者关闭了写方向。队列中的数据读完后会读到 EOF
+    writer_closed: bool,
+    /// 只有监听中的 socket 才有
+    listening: Option<Listening>,
+}
+
+struct Listening {
+    /// 等待 `accept()` 的连接数的上限
+    limit: usize,
+    /// 调用 `listen()` 的进程的凭证，作为连接者的 `SO_PEERCRED`
+    cred: UCred,
+    /// 已经建立、等待 `accept()` 的连接，是服务端一侧的 socket
+    backlog: VecDeque<UnixSocket>,
+}
+
+struct Message {
+    data: Vec<u8>,
+    /// 流式 socket 中已经被读取的字节数
+    offset: usize,
+    /// 发送者的地址，只有数据报使用
+    from: UnixAddr,
+    /// 通过 `SCM_RIGHTS` 传递的文件
+    rights: Vec<FileDescriptor>,
+}
+
+impl UnixQueue {
+    fn new() -> Self {
+        Self {
+            inner: SpinMutex::new(QueueInner {
+                messages: VecDeque::new(),
+                len: 0,
+                capacity: SOCK_BUF_DEFAULT,
+                reader_closed: false,
+                writer_closed: false,
+                listening: None,
+            }),
+            event: Event::new(),
+        }
+    }
+}
+
+impl QueueInner {
+    fn free_space(&self) -> usize {
+        self.capacity.saturating_sub(self.len)
+    }
+
+    fn push(&mut self, message: Message) {
+        self.len += message.data.len() - message.offset;
+        self.messages.push_back(message);
+    }
+
+    /// 按字节流读取，返回读取的长度和收到的文件。
+    ///
+    /// 同 linux，读到携带文件的消息后就停止，以免不同消息的文件混在一起
+    fn read_stream(&mut self, dst: &mut [u8], peek: bool) -> (usize, Vec<FileDescriptor>) {
+        let mut nread = 0;
+        let mut index = 0;
+        while nread < dst.len() {
+            let Some(message) = self.messages.get_mut(index) else {
+                break;
+            };
+            let src = &message.data[message.offset..];
+            let len = usize::min(src.len(), dst.len() - nread);
+            dst[nread..nread + len].copy_from_slice(&src[..len]);
+            nread += len;
+            let rights = if peek {
+                message.rights.clone()
+            } else {
+                mem::take(&mut message.rights)
+            };
+            if peek {
+                index += 1;
+            } else {
+                message.offset += len;
+                self.len -= len;
+                if message.offset == message.data.len() {
+                    self.messages.pop_front();
+                }
+            }
+            if !rights.is_empty() {
+                return (nread, rights);
+            }
+        }
+        (nread, Vec::new())
+    }
+}
+
+/// 连接的对端
+struct Peer {
+    /// 对端的接收队列
+    queue: Arc<UnixQueue>,
+    addr: UnixAddr,
+    /// 建立连接时对端的凭证。数据报 socket 通过 `connect()` 指定的对端没有
+    cred: Option<UCred>,
+}
+
+pub struct UnixSocket {
+    socket_type: SocketType,
+    rx: Arc<UnixQueue>,
+    inner: SpinMutex<UnixSocketInner>,
+}
+
+struct UnixSocketInner {
+    local: UnixAddr,
+    /// 绑定地址后在名字表中的键，释放时据此移除
+    name_key: Option<NameKey>,
+    peer: Option<Peer>,
+    /// 调用过 `shutdown()` 关闭读方向
+    shut_read: bool,
+    /// 调用过 `shutdown()` 关闭写方向
+    shut_write: bool,
+}
+
+impl UnixSocket {
+    pub fn new(socket_type: SocketType) -> Self {
+        Self {
+            socket_type,
+            rx: Arc::new(UnixQueue::new()),
+            inner: SpinMutex::new(UnixSocketInner {
+                local: UnixAddr::Unnamed,
+                name_key: None,
+                peer: None,
+                shut_read: false,
+                shut_write: false,
+            }),
+        }
+    }
+
+    /// 创建一对相互连接的未命名 socket，见 `socketpair()`
+    pub fn new_pair(socket_type: SocketType) -> (Self, Self) {
+        let mut a = Self::new(socket_type);
+        let mut b = Self::new(socket_type);
+        let cred = curr_ucred();
+        a.inner.get_mut().peer = Some(Peer {
+            queue: Arc::clone(&b.rx),
+            addr: UnixAddr::Unnamed,
+            cred: Some(cred),
+        });
+        b.inner.get_mut().peer = Some(Peer {
+            queue: Arc::clone(&a.rx),
+            addr: UnixAddr::Unnamed,
+            cred: Some(cred),
+        });
+        (a, b)
+    }
+
+    pub fn socket_type(&self) -> SocketType {
+        self.socket_type
+    }
+
+    pub fn local_addr(&self) -> UnixAddr {
+        self.inner.lock().local.clone()
+    }
+
+    /// 未连接时返回 `ENOTCONN`
+    pub fn peer_addr(&self) -> KResult<UnixAddr> {
+        self.inner
+            .lock()
+            .peer
+            .as_ref()
+            .map(|peer| peer.addr.clone())
+            .ok_or(errno::ENOTCONN)
+    }
+
+    /// 对端的凭证。同 linux，没有时 pid 为 0，uid 和 gid 为 -1
+    pub fn peer_cred(&self) -> UCred {
+        self.inner
+            .lock()
+            .peer
+            .as_ref()
+            .and_then(|peer| peer.cred)
+            .unwrap_or(UCred {
+                pid: 0,
+                uid: u32::MAX,
+                gid: u32::MAX,
+            })
+    }
+
+    pub fn is_listening(&self) -> bool {
+        self.rx.inner.lock().listening.is_some()
+    }
+
+    /// 绑定地址。地址为 [`UnixAddr::Unnamed`] 时自动绑定一个抽象地址。
+    ///
+    /// 已经绑定过时返回 `EINVAL`，地址已被使用时返回 `EADDRINUSE`。
+    /// 路径地址会在文件系统中创建 socket 文件，路径已存在时返回 `EADDRINUSE`
+    pub fn bind(&self, addr: UnixAddr) -> KResult<()> {
+        if self.inner.lock().name_key.is_some() {
+            return Err(errno::EINVAL);
+        }
+        let key = match &addr {
+            UnixAddr::Unnamed => None,
+            UnixAddr::Abstract(name) => Some(NameKey::Abstract(name.clone())),
+            UnixAddr::Path(path) => {
+                let p2i = fs::resolve_path_with_dir_fd(AT_FDCWD, path)?;
+                if p2i.dir.lookup(p2i.last_component.clone()).is_some() {
+                    return Err(errno::EADDRINUSE);
+                }
+                let umask = local_hart().curr_process().lock_inner_with(|inner| inner.umask);
+                let dentry = p2i
+                    .dir
+                    .mknod(
+                        p2i.last_component,
+                        InodeMode::Socket,
+                        StatMode::from_bits_truncate(0o777 & !umask),
+                    )
+                    .map_err(|e| if e == errno::EEXIST { errno::EADDRINUSE } else { e })?;
+                Some(NameKey::Inode(dentry.inode().meta().ino()))
+            }
+        };
+
+        let mut table = NAME_TABLE.lock();
+        let mut inner = self.inner.lock();
+        if inner.name_key.is_some() {
+            return Err(errno::EINVAL);
+        }
+        let (key, addr) = match key {
+            Some(key) => {
+                if table.contains_key(&key) {
+                    return Err(errno::EADDRINUSE);
+                }
+                (key, addr)
+            }
+            None => loop {
+                let name = format!("{:05x}", NEXT_AUTOBIND.fetch_add(1, Ordering::Relaxed) & 0xfffff).into_bytes();
+                let key = NameKey::Abstract(name.clone());
+                if !table.contains_key(&key) {
+                    break (key, UnixAddr::Abstract(name));
+                }
+            },
+        };
+        table.insert(
+            key.clone(),
+            Binding {
+                socket_type: self.socket_type,
+                addr: addr.clone(),
+                queue: Arc::clone(&self.rx),
+            },
+        );
+        inner.local = addr;
+        inner.name_key = Some(key);
+        Ok(())
+    }
+
+    /// 开始监听。只有流式 socket 支持，且需要已经绑定了地址、没有连接
+    pub fn listen(&self, backlog: usize) -> KResult<()> {
+        if self.socket_type != SocketType::Stream {
+            return Err(errno::EOPNOTSUPP);
+        }
+        let inner = self.inner.lock();
+        if inner.peer.is_some() || inner.name_key.is_none() {
+            return Err(errno::EINVAL);
+        }
+        let limit = backlog.min(SOMAXCONN);
+        {
+            let mut rx = self.rx.inner.lock();
+            // 重复调用只修改上限
+            match &mut rx.listening {
+                Some(listening) => listening.limit = limit,
+                None => {
+                    rx.listening = Some(Listening {
+                        limit,
+                        cred: curr_ucred(),
+                        backlog: VecDeque::new(),
+                    });
+                }
+            }
+        }
+        // 上限变大后，等待中的连接者可能可以继续了
+        self.rx.event.notify(usize::MAX);
+        Ok(())
+    }
+
+    pub(super) async fn accept(&self, wait: Waiting) -> KResult<UnixSocket> {
+        if self.socket_type != SocketType::Stream {
+            return Err(errno::EOPNOTSUPP);
+        }
+        let thread = Arc::clone(&local_hart().curr_thread_arc());
+        loop {
+            let listener = self.rx.event.listen();
+            {
+                let mut rx = self.rx.inner.lock();
+                let listening = rx.listening.as_mut().ok_or(errno::EINVAL)?;
+                if let Some(socket) = listening.backlog.pop_front() {
+                    drop(rx);
+                    self.rx.event.notify(usize::MAX);
+                    return Ok(socket);
+                }
+            }
+            wait.wait(&thread, listener).await?;
+        }
+    }
+
+    pub(super) async fn connect(&self, addr: UnixAddr, wait: Waiting) -> KResult<()> {
+        match self.socket_type {
+            SocketType::Stream => self.connect_stream(addr, wait).await,
+            SocketType::Dgram => {
+                // 数据报的连接只是记录默认的对端
+                let (addr, queue) = lookup_binding(&addr, SocketType::Dgram)?;
+                self.inner.lock().peer = Some(Peer {
+                    queue,
+                    addr,
+                    cred: None,
+                });
+                Ok(())
+            }
+        }
+    }
+
+    /// 连接到监听中的 socket。会创建一个服务端一侧的 socket 放入其 backlog，`accept()` 时取出。
+    ///
+    /// backlog 满时会阻塞
+    async fn connect_stream(&self, addr: UnixAddr, wait: Waiting) -> KResult<()> {
+        let thread = Arc::clone(&local_hart().curr_thread_arc());
+        loop {
+            let (server_addr, queue) = lookup_binding(&addr, SocketType::Stream)?;
+            let listener = queue.event.listen();
+            {
+                let mut inner = self.inner.lock();
+                if inner.peer.is_some() {
+                    return Err(errno::EISCONN);
+                }
+                if self.rx.inner.lock().listening.is_some() {
+                    return Err(errno::EINVAL);
+                }
+                let mut server_queue = queue.inner.lock();
+                if server_queue.reader_closed {
+                    return Err(errno::ECONNREFUSED);
+                }
+                let Some(listening) = &mut server_queue.listening else {
+                    return Err(errno::ECONNREFUSED);
+                };
+                // 同 linux，backlog 中的连接数超过上限才算满
+                if listening.backlog.len() <= listening.limit {
+                    let server = UnixSocket::new(SocketType::Stream);
+                    {
+                        let mut server_inner = server.inner.lock();
+                        server_inner.local = server_addr.clone();
+                        server_inner.peer = Some(Peer {
+                            queue: Arc::clone(&self.rx),
+                            addr: inner.local.clone(),
+                            cred: Some(curr_ucred()),
+                        });
+                    }
+                    inner.peer = Some(Peer {
+                        queue: Arc::clone(&server.rx),
+                        addr: server_addr,
+                        cred: Some(listening.cred),
+                    });
+                    listening.backlog.push_back(server);
+                    drop(server_queue);
+                    queue.event.notify(usize::MAX);
+                    return Ok(());
+                }
+            }
+            wait.wait(&thread, listener).await?;
+        }
+    }
+
+    pub(super) async fn send(
+        &self,
+        buf: WriteBuffer<'_>,
+        rights: Vec<FileDescriptor>,
+        to: Option<UnixAddr>,
+        flags: MsgFlags,
+        wait: Waiting,
+    ) -> KResult<usize> {
+        match self.socket_type {
+            SocketType::Stream => {
+                if to.is_some() {
+                    return Err(if self.inner.lock().peer.is_some() {
+                        errno::EISCONN
+                    } else {
+                        errno::EOPNOTSUPP
+                    });
+                }
+                self.send_stream(buf, rights, flags, wait).await
+            }
+            SocketType::Dgram => self.send_dgram(buf, rights, to, flags, wait).await,
+        }
+    }
+
+    /// 发送到流式连接的对端。缓冲区满时会阻塞，直到全部发送，被中断时返回已发送的长度。
+    ///
+    /// 对端已关闭或者本端关闭了写方向时，除非指定了 `MSG_NOSIGNAL`，会向当前线程发送 `SIGPIPE`，并返回 `EPIPE`
+    async fn send_stream(
+        &self,
+        buf: WriteBuffer<'_>,
+        rights: Vec<FileDescriptor>,
+        flags: MsgFlags,
+        wait: Waiting,
+    ) -> KResult<usize> {
+        let queue = {
+            let inner = self.inner.lock();
+            let peer = inner.peer.as_ref().ok_or(errno::ENOTCONN)?;
+            Arc::clone(&peer.queue)
+        };
+        let len = buf.len();
+        if len == 0 {
+            return Ok(0);
+        }
+        let thread = Arc::clone(&local_hart().curr_thread_arc());
+        // 文件随第一段数据一起发送
+        let mut rights = Some(rights);
+        let mut nwrite = 0;
+        loop {
+            let listener = queue.event.listen();
+            {
+                let rest = buf.slice(nwrite..len).expect("in bound");
+                let user_buf;
+                let src = match &rest {
+                    WriteBuffer::Kernel(buf) => *buf,
+                    WriteBuffer::User(buf) => {
+                        user_buf = buf.check_slice()?;
+                        &*user_buf
+                    }
+                };
+                let mut peer_queue = queue.inner.lock();
+                if peer_queue.reader_closed || peer_queue.writer_closed {
+                    drop(peer_queue);
+                    if !flags.contains(MsgFlags::NOSIGNAL) {
+                        thread.receive_signal(KSignalSet::SIGPIPE);
+                    }
+                    return if nwrite > 0 { Ok(nwrite) } else { Err(errno::EPIPE) };
+                }
+                let free_space = peer_queue.free_space();
+                if free_space > 0 {
+                    let chunk = usize::min(free_space, src.len());
+                    peer_queue.push(Message {
+                        data: src[..chunk].to_vec(),
+                        offset: 0,
+                        from: UnixAddr::Unnamed,
+                        rights: rights.take().unwrap_or_default(),
+                    });
+                    drop(peer_queue);
+                    queue.event.notify(usize::MAX);
+                    nwrite += chunk;
+                    if nwrite == len {
+                        return Ok(nwrite);
+                    }
+                    continue;
+                }
+            }
+            if let Err(e) = wait.wait(&thread, listener).await {
+                return if nwrite > 0 { Ok(nwrite) } else { Err(e) };
+            }
+        }
+    }
+
+    /// 发送一个数据报，`to` 为 `None` 时发送给 `connect()` 指定的对端。
+    ///
+    /// 数据报不会被拆分，接收队列放不下时会阻塞；比队列的容量还大时返回 `EMSGSIZE`
+    async fn send_dgram(
+        &self,
+        buf: WriteBuffer<'_>,
+        rights: Vec<FileDescriptor>,
+        to: Option<UnixAddr>,
+        flags: MsgFlags,
+        wait: Waiting,
+    ) -> KResult<usize> {
+        let (peer_queue, from, shut_write) = {
+            let inner = self.inner.lock();
+            let peer_queue = inner.peer.as_ref().map(|peer| Arc::clone(&peer.queue));
+            (peer_queue, inner.local.clone(), inner.shut_write)
+        };
+        let queue = match to {
+            Some(to) => lookup_binding(&to, SocketType::Dgram)?.1,
+            None => peer_queue.ok_or(errno::ENOTCONN)?,
+        };
+        let thread = Arc::clone(&local_hart().curr_thread_arc());
+        if shut_write {
+            if !flags.contains(MsgFlags::NOSIGNAL) {
+                thread.receive_signal(KSignalSet::SIGPIPE);
+            }
+            return Err(errno::EPIPE);
+        }
+        let data = match &buf {
+            WriteBuffer::Kernel(buf) => buf.to_vec(),
+            WriteBuffer::User(buf) => buf.check_slice()?.to_vec(),
+        };
+        let len = data.len();
+        let mut message = Some(Message {
+            data,
+            offset: 0,
+            from,
+            rights,
+        });
+        loop {
+            let listener = queue.event.listen();
+            {
+                let mut peer_queue = queue.inner.lock();
+                if peer_queue.reader_closed {
+                    return Err(errno::ECONNREFUSED);
+                }
+                if len > peer_queue.capacity {
+                    return Err(errno::EMSGSIZE);
+                }
+                if peer_queue.free_space() >= len {
+                    peer_queue.push(message.take().expect("sent only once"));
+                    drop(peer_queue);
+                    queue.event.notify(usize::MAX);
+                    return Ok(len);
+                }
+            }
+            wait.wait(&thread, listener).await?;
+        }
+    }
+
+    pub(super) async fn recv(&self, mut buf: ReadBuffer<'_>, flags: MsgFlags, wait: Waiting) -> KResult<RecvResult> {
+        let (connected, shut_read) = {
+            let inner = self.inner.lock();
+            (inner.peer.is_some(), inner.shut_read)
+        };
+        let stream = self.socket_type == SocketType::Stream;
+        // 同 linux，未连接的流式 socket 不能读取
+        if stream && !connected {
+            return Err(errno::EINVAL);
+        }
+        let peek = flags.contains(MsgFlags::PEEK);
+        let wait_all = stream && flags.contains(MsgFlags::WAITALL) && !peek;
+        let thread = Arc::clone(&local_hart().curr_thread_arc());
+        let mut nread = 0;
+        loop {
+            let listener = self.rx.event.listen();
+            {
+                // 同管道，预先检查用户缓冲区，持有锁时直接拷贝
+                let mut user_buf;
+                let dst = match &mut buf {
+                    ReadBuffer::Kernel(buf) => &mut **buf,
+                    ReadBuffer::User(buf) => {
+                        user_buf = unsafe { buf.check_slice_mut()? };
+                        user_buf.as_bytes_mut()
+                    }
+                };
+                let mut rx = self.rx.inner.lock();
+                if stream {
+                    let (len, rights) = rx.read_stream(&mut dst[nread..], peek);
+                    if len > 0 {
+                        drop(rx);
+                        if !peek {
+                            self.rx.event.notify(usize::MAX);
+                        }
+                        nread += len;
+                        if !wait_all || nread == dst.len() || !rights.is_empty() {
+                            return Ok(RecvResult {
+                                len: nread,
+                                truncated: false,
+                                from: None,
+                                rights,
+                            });
+                        }
+                        continue;
+                    }
+                } else if let Some(message) = rx.messages.front() {
+                    let full_len = message.data.len();
+                    let len = usize::min(full_len, dst.len());
+                    dst[..len].copy_from_slice(&message.data[..len]);
+                    let from = message.from.clone();
+                    let rights = if peek {
+                        message.rights.clone()
+                    } else {
+                        let message = rx.messages.pop_front().expect("checked above");
+                        rx.len -= full_len;
+                        drop(rx);
+                        self.rx.event.notify(usize::MAX);
+                        message.rights
+                    };
+                    return Ok(RecvResult {
+                        len: if flags.contains(MsgFlags::TRUNC) { full_len } else { len },
+                        truncated: len < full_len,
+                        from: Some(SocketAddr::Unix(from)),
+                        rights,
+                    });
+                }
+                // 没有数据可读了，关闭了读方向或者对端关闭了写方向时读到 EOF
+                if shut_read || rx.reader_closed || (stream && rx.writer_closed) {
+                    return Ok(RecvResult {
+                        len: nread,
+                        truncated: false,
+                        from: None,
+                        rights: Vec::new(),
+                    });
+                }
+            }
+            if let Err(e) = wait.wait(&thread, listener).await {
+                if nread > 0 {
+                    return Ok(RecvResult {
+                        len: nread,
+                        truncated: false,
+                        from: None,
+                        rights: Vec::new(),
+                    });
+                }
+                return Err(e);
+            }
+        }
+    }
+
+    /// 关闭读方向和（或）写方向。
+    ///
+    /// 流式 socket 的关闭会反映到对端：关闭读方向后对端写入会得到 `EPIPE`，关闭写方向后对端读完数据后读到 EOF
+    pub fn shutdown(&self, read: bool, write: bool) {
+        let peer_queue = {
+            let mut inner = self.inner.lock();
+            inner.shut_read |= read;
+            inner.shut_write |= write;
+            inner.peer.as_ref().map(|peer| Arc::clone(&peer.queue))
+        };
+        if self.socket_type == SocketType::Stream {
+            if read {
+                self.rx.inner.lock().reader_closed = true;
+            }
+            if write && let Some(peer_queue) = &peer_queue {
+                peer_queue.inner.lock().writer_closed = true;
+                peer_queue.event.notify(usize::MAX);
+            }
+        }
+        self.rx.event.notify(usize::MAX);
+    }
+
+    /// 就绪状态，同 linux 的 `unix_poll()` 和 `unix_dgram_poll()`
+    pub fn poll(&self, mut table: Option<&mut PollTable<'_, '_>>) -> PollEvents {
+        let (peer_queue, shut_read, shut_write) = {
+            let inner = self.inner.lock();
+            let peer_queue = inner.peer.as_ref().map(|peer| Arc::clone(&peer.queue));
+            (peer_queue, inner.shut_read, inner.shut_write)
+        };
+        if let Some(table) = &mut table {
+            table.listen(&self.rx.event);
+            if let Some(peer_queue) = &peer_queue {
+                table.listen(&peer_queue.event);
+            }
+        }
+        let stream = self.socket_type == SocketType::Stream;
+        let mut events = PollEvents::empty();
+        let read_closed = {
+            let rx = self.rx.inner.lock();
+            if let Some(listening) = &rx.listening {
+                return if listening.backlog.is_empty() {
+                    PollEvents::empty()
+                } else {
+                    PollEvents::POLLIN
+                };
+            }
+            if !rx.messages.is_empty() {
+                events |= PollEvents::POLLIN;
+            }
+            shut_read || (stream && rx.writer_closed)
+        };
+        let (write_closed, writable) = match &peer_queue {
+            Some(peer_queue) => {
+                let peer_queue = peer_queue.inner.lock();
+                let closed = stream && peer_queue.reader_closed;
+                (shut_write || closed, peer_queue.free_space() > 0)
+            }
+            None => (shut_write, !stream),
+        };
+        if read_closed {
+            events |= PollEvents::POLLIN | PollEvents::POLLRDHUP;
+        }
+        if read_closed && write_closed {
+            events |= PollEvents::POLLHUP;
+        }
+        if stream && peer_queue.is_none() {
+            // 未连接的流式 socket
+            events |= PollEvents::POLLOUT | PollEvents::POLLHUP;
+        } else if writable || write_closed {
+            // 写方向关闭时也算可写，写入会立即返回 `EPIPE`
+            events |= PollEvents::POLLOUT;
+        }
+        events
+    }
+
+    pub fn ioctl(&self, request: usize, argp: usize) -> KResult {
+        match request {
+            FIONREAD => {
+                let len = {
+                    let rx = self.rx.inner.lock();
+                    if rx.listening.is_some() {
+                        return Err(errno::EINVAL);
+                    }
+                    match self.socket_type {
+                        SocketType::Stream => rx.len,
+                        // 数据报返回下一个数据报的长度
+                        SocketType::Dgram => rx.messages.front().map_or(0, |message| message.data.len()),
+                    }
+                };
+                unsafe { UserCheck::new(argp as *mut i32).ok_or(errno::EINVAL)?.check_ptr_mut()? }.write(len as i32);
+                Ok(0)
+            }
+            _ => Err(errno::ENOTTY),
+        }
+    }
+}
+
+impl Drop for UnixSocket {
+    fn drop(&mut self) {
+        let inner = self.inner.get_mut();
+        if let Some(key) = &inner.name_key {
+            NAME_TABLE.lock().remove(key);
+        }
+        // 队列中的消息和未被接受的连接可能持有其他 socket，在锁外释放
+        let (messages, listening) = {
+            let mut rx = self.rx.inner.lock();
+            rx.reader_closed = true;
+            rx.len = 0;
+            (mem::take(&mut rx.messages), rx.listening.take())
+        };
+        self.rx.event.notify(usize::MAX);
+        if self.socket_type == SocketType::Stream
+            && let Some(peer) = &inner.peer
+        {
+            peer.queue.inner.lock().writer_closed = true;
+            peer.queue.event.notify(usize::MAX);
+        }
+        drop(messages);
+        drop(listening);
+    }
+}
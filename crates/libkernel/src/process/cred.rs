@@ -0,0 +1,155 @@
+use defines::{
+    error::{errno, KResult},
+    fs::StatMode,
+};
+
+/// 一组真实、有效、保存的 ID，用户 ID 和组 ID 各有一组
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IdSet {
+    pub real: u32,
+    pub effective: u32,
+    pub saved: u32,
+}
+
+impl IdSet {
+    const fn new(id: u32) -> Self {
+        Self {
+            real: id,
+            effective: id,
+            saved: id,
+        }
+    }
+
+    fn contains(&self, id: u32) -> bool {
+        id == self.real || id == self.effective || id == self.saved
+    }
+
+    /// `setuid()`/`setgid()`。特权进程同时修改三个 ID，否则只能将有效 ID 改为真实 ID 或保存的 ID
+    fn set(&mut self, id: u32, privileged: bool) -> KResult<()> {
+        if privileged {
+            *self = Self::new(id);
+        } else if id == self.real || id == self.saved {
+            self.effective = id;
+        } else {
+            return Err(errno::EPERM);
+        }
+        Ok(())
+    }
+
+    /// `setreuid()`/`setregid()`，`None` 表示不修改
+    fn set_re(&mut self, real: Option<u32>, effective: Option<u32>, privileged: bool) -> KResult<()> {
+        if !privileged {
+            if real.is_some_and(|id| id != self.real && id != self.effective) {
+                return Err(errno::EPERM);
+            }
+            if effective.is_some_and(|id| !self.contains(id)) {
+                return Err(errno::EPERM);
+            }
+        }
+        let old_real = self.real;
+        if let Some(id) = real {
+            self.real = id;
+        }
+        if let Some(id) = effective {
+            self.effective = id;
+        }
+        // 修改了真实 ID，或者有效 ID 被设置为与原真实 ID 不同的值时，保存的 ID 跟随新的有效 ID
+        if real.is_some() || effective.is_some_and(|id| id != old_real) {
+            self.saved = self.effective;
+        }
+        Ok(())
+    }
+
+    /// `setresuid()`/`setresgid()`，`None` 表示不修改。非特权进程只能设置为三个 ID 中的某一个
+    fn set_res(
+        &mut self,
+        real: Option<u32>,
+        effective: Option<u32>,
+        saved: Option<u32>,
+        privileged: bool,
+    ) -> KResult<()> {
+        if !privileged
+            && [real, effective, saved]
+                .into_iter()
+                .flatten()
+                .any(|id| !self.contains(id))
+        {
+            return Err(errno::EPERM);
+        }
+        if let Some(id) = real {
+            self.real = id;
+        }
+        if let Some(id) = effective {
+            self.effective = id;
+        }
+        if let Some(id) = saved {
+            self.saved = id;
+        }
+        Ok(())
+    }
+}
+
+/// 进程的身份，类似于 linux 的 `struct cred`。
+///
+/// 文件系统的权限检查使用有效 ID
+///
+/// TODO: [low] 暂不支持附加组（supplementary groups）和 capabilities，有效用户 ID 为 0 即视为特权用户
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Credentials {
+    pub uid: IdSet,
+    pub gid: IdSet,
+}
+
+impl Credentials {
+    pub const ROOT: Self = Self {
+        uid: IdSet::new(0),
+        gid: IdSet::new(0),
+    };
+
+    pub fn is_privileged(&self) -> bool {
+        self.uid.effective == 0
+    }
+
+    pub fn setuid(&mut self, uid: u32) -> KResult<()> {
+        let privileged = self.is_privileged();
+        self.uid.set(uid, privileged)
+    }
+
+    pub fn setgid(&mut self, gid: u32) -> KResult<()> {
+        let privileged = self.is_privileged();
+        self.gid.set(gid, privileged)
+    }
+
+    pub fn setreuid(&mut self, ruid: Option<u32>, euid: Option<u32>) -> KResult<()> {
+        let privileged = self.is_privileged();
+        self.uid.set_re(ruid, euid, privileged)
+    }
+
+    pub fn setregid(&mut self, rgid: Option<u32>, egid: Option<u32>) -> KResult<()> {
+        let privileged = self.is_privileged();
+        self.gid.set_re(rgid, egid, privileged)
+    }
+
+    pub fn setresuid(&mut self, ruid: Option<u32>, euid: Option<u32>, suid: Option<u32>) -> KResult<()> {
+        let privileged = self.is_privileged();
+        self.uid.set_res(ruid, euid, suid, privileged)
+    }
+
+    pub fn setresgid(&mut self, rgid: Option<u32>, egid: Option<u32>, sgid: Option<u32>) -> KResult<()> {
+        let privileged = self.is_privileged();
+        self.gid.set_res(rgid, egid, sgid, privileged)
+    }
+
+    /// 执行新程序时，根据可执行文件的 set-user-ID 和 set-group-ID 位切换有效 ID，并更新保存的 ID
+    pub fn exec(&mut self, perm: StatMode, owner: u32, group: u32) {
+        if perm.contains(StatMode::S_ISUID) {
+            self.uid.effective = owner;
+        }
+        // 没有组执行权限时，set-group-ID 位表示强制锁而非切换组
+        if perm.contains(StatMode::S_ISGID | StatMode::S_IXGRP) {
+            self.gid.effective = group;
+        }
+        self.uid.saved = self.uid.effective;
+        self.gid.saved = self.gid.effective;
+    }
+}
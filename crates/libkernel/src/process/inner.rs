@@ -2,6 +2,7 @@ use alloc::vec::Vec;
 use core::ops::Range;
 
 use common::config::LOW_ADDRESS_END;
+use ecow::EcoString;
 use hashbrown::HashMap;
 use idallocator::RecycleAllocator;
 use memory::{MemorySpace, VirtAddr};
@@ -9,7 +10,10 @@ use triomphe::Arc;
 
 use super::Process;
 use crate::{
-    fs::{dentry::DEntryDir, file::FdTable},
+    fs::{
+        dentry::{DEntryBytes, DEntryDir},
+        file::FdTable,
+    },
     memory,
     signal::{KSignalSet, Signal, SignalHandlers},
     thread::Thread,
@@ -34,10 +38,16 @@ pub struct ProcessInner {
     pub children: Vec<Arc<Process>>,
     /// 当前工作目录
     pub cwd: Arc<DEntryDir>,
+    /// 正在执行的可执行文件，进程退出后为 `None`
+    pub exe: Option<Arc<DEntryBytes>>,
+    /// 执行时的命令行参数，即 `/proc/[pid]/cmdline`
+    pub cmdline: Vec<EcoString>,
 
     // 文件
     /// 文件描述符表
     pub fd_table: FdTable,
+    /// 创建文件时从 mode 中去除的权限位
+    pub umask: u32,
 
     // 信号
     /// 信号处理函数
@@ -77,10 +87,11 @@ impl ProcessInner {
     pub fn receive_signal(&mut self, signal: Signal) {
         let signal = KSignalSet::from(signal);
         for thread in self.threads.values() {
-            let mut inner = thread.lock_inner();
-            if !inner.signal_mask.contains(signal) && !inner.pending_signal.contains(signal) {
+            let acceptable = thread
+                .lock_inner_with(|inner| !inner.signal_mask.contains(signal) && !inner.pending_signal.contains(signal));
+            if acceptable {
                 debug!("thread {} receive signal {signal:?}", thread.tid());
-                inner.pending_signal.insert(signal);
+                thread.receive_signal(signal);
                 break;
             }
         }
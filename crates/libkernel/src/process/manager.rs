@@ -1,34 +1,55 @@
 use alloc::collections::BTreeMap;
+use core::sync::atomic::{AtomicUsize, Ordering};
 
 use klocks::{SpinMutex, SpinMutexGuard};
 use triomphe::Arc;
 
 use super::Process;
 
-pub struct ProcessManager(SpinMutex<BTreeMap<usize, Arc<Process>>>);
+pub struct ProcessManager {
+    processes: SpinMutex<BTreeMap<usize, Arc<Process>>>,
+    /// 自启动以来创建的进程总数
+    created: AtomicUsize,
+    /// 最近创建的进程的 pid
+    last_pid: AtomicUsize,
+}
 
 impl ProcessManager {
     pub const fn new() -> Self {
-        Self(SpinMutex::new(BTreeMap::new()))
+        Self {
+            processes: SpinMutex::new(BTreeMap::new()),
+            created: AtomicUsize::new(0),
+            last_pid: AtomicUsize::new(0),
+        }
     }
 
     pub fn add(&self, pid: usize, process: Arc<Process>) {
-        self.0.lock().insert(pid, process);
+        self.processes.lock().insert(pid, process);
+        self.created.fetch_add(1, Ordering::Relaxed);
+        self.last_pid.store(pid, Ordering::Relaxed);
     }
 
     pub fn remove(&self, pid: usize) {
-        self.0.lock().remove(&pid);
+        self.processes.lock().remove(&pid);
     }
 
     pub fn get(&self, pid: usize) -> Option<Arc<Process>> {
-        self.0.lock().get(&pid).cloned()
+        self.processes.lock().get(&pid).cloned()
     }
 
     pub fn init_proc(&self) -> Arc<Process> {
-        Arc::clone(self.0.lock().get(&1).expect("initproc should never die"))
+        Arc::clone(self.processes.lock().get(&1).expect("initproc should never die"))
     }
 
     pub fn lock_all(&self) -> SpinMutexGuard<'_, BTreeMap<usize, Arc<Process>>> {
-        self.0.lock()
+        self.processes.lock()
+    }
+
+    pub fn created(&self) -> usize {
+        self.created.load(Ordering::Relaxed)
+    }
+
+    pub fn last_pid(&self) -> usize {
+        self.last_pid.load(Ordering::Relaxed)
     }
 }
@@ -1,3 +1,4 @@
+mod cred;
 mod inner;
 mod manager;
 
@@ -16,9 +17,16 @@ use manager::ProcessManager;
 use memory::MemorySpace;
 use triomphe::Arc;
 
+pub use self::cred::{Credentials, IdSet};
 use self::inner::ProcessInner;
 use crate::{
-    fs::{self, dentry::DEntry, file::FdTable, VirtFileSystem},
+    fs::{
+        self,
+        dentry::{DEntry, DEntryBytes},
+        file::FdTable,
+        VirtFileSystem,
+    },
+    hart::local_hart,
     memory,
     signal::{KSignalSet, Signal, SignalHandlers},
     thread::Thread,
@@ -27,6 +35,8 @@ use crate::{
 
 pub static PROCESS_MANAGER: ProcessManager = ProcessManager::new();
 pub const INITPROC_PID: usize = 1;
+/// 新进程默认的 umask
+const DEFAULT_UMASK: u32 = 0o022;
 
 pub async fn init() {
     let init_proc = Process::from_path("/initproc", vec![EcoString::from("/initproc")])
@@ -44,6 +54,8 @@ pub struct Process {
     pub status: Atomic<ProcessStatus>,
     /// 退出时向父进程发送的信号
     pub exit_signal: Option<Signal>,
+    /// 进程的用户和组身份。文件系统权限检查时会频繁读取，因此不放在 `inner` 中
+    cred: SpinMutex<Credentials>,
     inner: SpinMutex<ProcessInner>,
 }
 
@@ -68,11 +80,11 @@ impl Process {
         let _enter = info_span!("spawn process", path = path, args = args).entered();
 
         let mut memory_space;
+        let DEntry::Bytes(exe) = fs::find_file(path)? else {
+            return Err(errno::EISDIR);
+        };
         let (elf_end, auxv, elf_entry) = {
-            let DEntry::Bytes(bytes) = fs::find_file(path)? else {
-                return Err(errno::EISDIR);
-            };
-            let elf_data = fs::read_file(bytes.inode()).await?;
+            let elf_data = fs::read_file(exe.inode()).await?;
             let elf = Elf::parse(&elf_data).map_err(|e| {
                 warn!("parse elf error {e}");
                 errno::ENOEXEC
@@ -85,6 +97,7 @@ impl Process {
         // 在用户栈上推入参数、环境变量、辅助向量等
         let process_name = Self::process_name_from_args(Some(path), &args).expect("path_fallback guarantees name");
         let argc = args.len();
+        let cmdline = args.clone();
         let (user_sp, argv_base) = memory_space.init_stack(0, args, Vec::new(), auxv);
 
         let brk = elf_end.vpn_ceil().page_start();
@@ -101,13 +114,17 @@ impl Process {
             wait4_event: Event::new(),
             status: Atomic::new(ProcessStatus::normal()),
             exit_signal: None,
+            cred: SpinMutex::new(Credentials::ROOT),
             inner: SpinMutex::new(ProcessInner {
                 memory_space,
                 heap_range: brk..brk,
                 parent: None,
                 children: Vec::new(),
-                cwd: Arc::clone(VirtFileSystem::instance().root_dir()),
+                cwd: VirtFileSystem::instance().root_dir(),
+                exe: Some(exe),
+                cmdline,
                 fd_table: FdTable::with_stdio(),
+                umask: DEFAULT_UMASK,
                 signal_handlers: SignalHandlers::new(),
                 tid_allocator,
                 threads: HashMap::new(),
@@ -149,13 +166,17 @@ impl Process {
                 wait4_event: Event::new(),
                 status: Atomic::new(self.status.load(Ordering::SeqCst)),
                 exit_signal,
+                cred: SpinMutex::new(self.cred()),
                 inner: SpinMutex::new(ProcessInner {
                     memory_space: MemorySpace::from_other(&inner.memory_space),
                     heap_range: inner.heap_range.clone(),
                     parent: Some(Arc::clone(self)),
                     children: Vec::new(),
                     cwd: Arc::clone(&inner.cwd),
+                    exe: inner.exe.clone(),
+                    cmdline: inner.cmdline.clone(),
                     fd_table: inner.fd_table.clone(),
+                    umask: inner.umask,
                     signal_handlers: inner.signal_handlers.clone(),
                     tid_allocator: inner.tid_allocator.clone(),
                     threads: HashMap::new(),
@@ -180,10 +201,16 @@ impl Process {
         })
     }
 
-    /// 根据 `elf_data` 加载一个新的 ELF 文件并执行。
+    /// 根据 `elf_data` 加载一个新的 ELF 文件并执行，`exe` 是该 ELF 文件。
     ///
     /// 目前要求原进程仅有一个线程并且没有子进程
-    pub fn exec(&self, elf_data: &[u8], args: Vec<EcoString>, envs: Vec<EcoString>) -> KResult<()> {
+    pub fn exec(
+        &self,
+        exe: Arc<DEntryBytes>,
+        elf_data: &[u8],
+        args: Vec<EcoString>,
+        envs: Vec<EcoString>,
+    ) -> KResult<()> {
         // let bytes = {
         //     let DEntry::Bytes(bytes) =
         //         fs::find_file(self.lock_inner_with(|inner| Arc::clone(&inner.cwd)), &path)?
@@ -260,10 +287,14 @@ impl Process {
                 let brk = elf_end.vpn_ceil().page_start();
                 brk..brk
             };
-            inner.fd_table.close_on_exec();
+            for desc in inner.fd_table.close_on_exec() {
+                desc.release_record_locks(self.pid());
+            }
             debug!("fd table: {:?}", inner.fd_table);
             inner.signal_handlers = SignalHandlers::new();
 
+            inner.exe = Some(exe);
+            inner.cmdline = args.clone();
             let argc = args.len();
             let (user_sp, argv_base) = inner.memory_space.init_stack(0, args, envs, auxv);
             memory::flush_tlb(None);
@@ -299,6 +330,14 @@ impl Process {
         self.pid
     }
 
+    pub fn cred(&self) -> Credentials {
+        *self.cred.lock()
+    }
+
+    pub fn lock_cred_with<T>(&self, f: impl FnOnce(&mut Credentials) -> T) -> T {
+        f(&mut self.cred.lock())
+    }
+
     pub fn name(&self) -> EcoString {
         self.name.lock().clone()
     }
@@ -334,6 +373,13 @@ impl Drop for Process {
     }
 }
 
+/// 当前进程的身份。内核初始化阶段还没有用户进程，此时视为特权用户
+pub fn curr_cred() -> Credentials {
+    local_hart()
+        .try_curr_process()
+        .map_or(Credentials::ROOT, |process| process.cred())
+}
+
 static PID_ALLOCATOR: SpinMutex<RecycleAllocator> = SpinMutex::new(RecycleAllocator::begin_with(1));
 
 /// 退出进程，终止其所有线程。
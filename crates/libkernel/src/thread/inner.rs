@@ -6,6 +6,10 @@ pub struct ThreadInner {
     pub signal_mask: KSignalSet,
     /// 待处理信号队列
     pub pending_signal: KSignalSet,
+    /// `ppoll` 等系统调用临时替换信号掩码前的掩码。
+    ///
+    /// 临时掩码一直生效到返回用户态。若返回前处理了信号，则在信号处理函数返回后恢复，否则返回前立即恢复
+    pub saved_signal_mask: Option<KSignalSet>,
 }
 
 /// 线程拥有的值，只会由线程自己访问的值，因此可以包裹在 [`UnsafeCell`] 中
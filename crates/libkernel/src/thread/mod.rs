@@ -1,9 +1,11 @@
 mod inner;
 
-use core::{cell::SyncUnsafeCell, mem, ops::Range, ptr::NonNull};
+use core::{cell::SyncUnsafeCell, future, mem, ops::Range, pin::Pin, ptr::NonNull, task::Poll};
 
 use atomic::{Atomic, Ordering};
 use common::config::{LOW_ADDRESS_END, PAGE_SIZE, USER_STACK_SIZE};
+use defines::error::{errno, KResult};
+use event_listener::{Event, EventListener};
 use hashbrown::HashMap;
 use klocks::{SpinMutex, SpinMutexGuard};
 use triomphe::Arc;
@@ -30,6 +32,8 @@ pub struct Thread {
     pub process: Arc<Process>,
     /// 可能被并发访问的可变结构
     inner: SpinMutex<ThreadInner>,
+    /// 线程收到信号时通知，用于打断可被信号中断的等待
+    signal_event: Event,
     /// 不应被并发访问的可变结构
     owned: SyncUnsafeCell<ThreadOwned>,
 }
@@ -44,7 +48,9 @@ impl Thread {
             inner: SpinMutex::new(ThreadInner {
                 signal_mask,
                 pending_signal: KSignalSet::empty(),
+                saved_signal_mask: None,
             }),
+            signal_event: Event::new(),
             owned: SyncUnsafeCell::new(ThreadOwned {
                 trap_context,
                 clear_child_tid: 0,
@@ -65,6 +71,50 @@ impl Thread {
         f(&mut self.inner.lock())
     }
 
+    /// 向线程投递信号，并唤醒其可被信号中断的等待
+    pub fn receive_signal(&self, signal: KSignalSet) {
+        self.inner.lock().pending_signal.insert(signal);
+        self.signal_event.notify(usize::MAX);
+    }
+
+    /// 是否有未被屏蔽的待处理信号。可被信号中断的等待据此返回 `EINTR`
+    pub fn has_unmasked_signal(&self) -> bool {
+        let inner = self.inner.lock();
+        inner.pending_signal.intersects(!inner.signal_mask)
+    }
+
+    /// 临时替换信号掩码直到返回用户态，用于 `ppoll` 等系统调用。`SIGKILL` 和 `SIGSTOP` 不能被屏蔽
+    pub fn set_temporary_signal_mask(&self, mask: KSignalSet) {
+        let mut inner = self.inner.lock();
+        let old_mask = inner.signal_mask;
+        inner.saved_signal_mask.get_or_insert(old_mask);
+        inner.signal_mask = mask.difference(KSignalSet::SIGKILL | KSignalSet::SIGSTOP);
+    }
+
+    /// 线程收到信号时通知的事件。
+    ///
+    /// 等待者应当先监听该事件，再检查 [`Self::has_unmasked_signal()`]，以免错过信号
+    pub fn signal_event(&self) -> &Event {
+        &self.signal_event
+    }
+
+    /// 等待 `listener` 被通知，有未屏蔽的信号时返回 `EINTR`
+    pub async fn wait_interruptible(&self, mut listener: EventListener) -> KResult<()> {
+        let mut signal_listener = self.signal_event.listen();
+        if self.has_unmasked_signal() {
+            return Err(errno::EINTR);
+        }
+        future::poll_fn(|cx| {
+            if Pin::new(&mut listener).poll(cx).is_ready() || Pin::new(&mut signal_listener).poll(cx).is_ready() {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+        Ok(())
+    }
+
     /// 获取线程私有的值，只应由当前运行该线程的 hart 访问
     pub fn get_owned(&self) -> NonNull<ThreadOwned> {
         unsafe { NonNull::new_unchecked(self.owned.get()) }
@@ -133,14 +183,21 @@ impl Thread {
         if process_inner.threads.is_empty() {
             info!("all threads exit");
             // 不太想让 `cwd` 加个 `Option`，但是也最好不要保持原来的引用了，所以引到根目录去得了
-            process_inner.cwd = Arc::clone(VirtFileSystem::instance().root_dir());
+            process_inner.cwd = VirtFileSystem::instance().root_dir();
+            process_inner.exe = None;
             process_inner.memory_space.recycle_user_pages();
             process_inner.threads = HashMap::new();
             process_inner.tid_allocator.release();
             let children = mem::take(&mut process_inner.children);
             let parent = process_inner.parent.take();
+            let files = process_inner.fd_table.close_all();
             drop(process_inner);
 
+            // 关闭所有文件，释放记录锁。`flock()` 锁会在打开的文件的最后一个引用消失时释放
+            for desc in files {
+                desc.release_record_locks(process.pid());
+            }
+
             // 如果进程已标记为退出（即已调用 `exit_process()`），则标记为僵尸并使用已有的退出码
             // 否则使用线程的退出码
             let exit_code = self.exit_code.load(Ordering::SeqCst);
@@ -17,14 +17,25 @@ use crate::{
 
 /// 如果进程因为信号被终止了，则返回 true
 pub fn check_signal(thread: &Thread) -> bool {
-    let first_pending = {
+    let (first_pending, saved_mask) = {
         let mut inner = thread.lock_inner();
+        // 临时信号掩码只在本次返回用户态之前生效
+        let saved_mask = inner.saved_signal_mask.take();
         let pendings = inner.pending_signal.intersection(!inner.signal_mask);
         let Some(first_pending) = pendings.first_pending() else {
+            if let Some(saved_mask) = saved_mask {
+                inner.signal_mask = saved_mask;
+            }
             return false;
         };
         inner.pending_signal.remove(KSignalSet::from(first_pending));
-        first_pending
+        (first_pending, saved_mask)
+    };
+    // 信号被忽略时，同样要恢复临时信号掩码
+    let restore_mask = || {
+        if let Some(saved_mask) = saved_mask {
+            thread.lock_inner_with(|inner| inner.signal_mask = saved_mask);
+        }
     };
 
     debug!("handle signal {first_pending:?}");
@@ -47,18 +58,25 @@ pub fn check_signal(thread: &Thread) -> bool {
                 // TODO:[low] 要处理 CoreDump
                 return true;
             }
-            DefaultHandler::Ignore => return false,
+            DefaultHandler::Ignore => {
+                restore_mask();
+                return false;
+            }
             DefaultHandler::Stop | DefaultHandler::Continue => {
                 // 被信号 stop 或者 continue 都要通知 `sys_wait4()`
                 todo!("[low] default handler Stop and Continue")
             }
         },
-        SIG_IGN => return false,
+        SIG_IGN => {
+            restore_mask();
+            return false;
+        }
         handler => handler,
     };
 
     let old_mask = thread.lock_inner_with(|inner| {
-        let old_mask = inner.signal_mask;
+        // 信号处理函数返回后恢复的是临时掩码生效前的掩码
+        let old_mask = saved_mask.unwrap_or(inner.signal_mask);
         inner.signal_mask.insert(action.kmask());
         if !action.flags.contains(SignalActionFlags::SA_NODEFER) {
             inner.signal_mask.set(KSignalSet::from(first_pending), true);
@@ -50,6 +50,8 @@ pub const MMIO: &[(usize, usize)] = &[
 pub const QEMU_UART_ADDR: usize = 0x1000_0000;
 pub const QEMU_PLIC_ADDR: usize = 0xc00_0000;
 pub const QEMU_VIRTIO0: usize = 0x1000_1000;
+/// PLIC 中断源数量上限，同 qemu 中的 `VIRT_IRQCHIP_NUM_SOURCES`
+pub const MAX_IRQ_NUM: usize = 96;
 
 /// 内核线程的数量（核心数）
 pub const MAX_HART_NUM: usize = 8;
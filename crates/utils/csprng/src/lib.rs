@@ -0,0 +1,89 @@
+//! 基于 ChaCha20（RFC 8439）的密码学安全伪随机数生成器
+
+#![no_std]
+
+const CONSTANTS: [u32; 4] = [0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574];
+
+pub const KEY_SIZE: usize = 32;
+pub const BLOCK_SIZE: usize = 64;
+
+/// `ChaCha20` 的块函数，生成 `counter` 对应的 64 字节密钥流
+pub fn chacha20_block(key: &[u8; KEY_SIZE], counter: u32, nonce: &[u8; 12]) -> [u8; BLOCK_SIZE] {
+    let mut state = [0u32; 16];
+    state[..4].copy_from_slice(&CONSTANTS);
+    for (word, bytes) in state[4..12].iter_mut().zip(key.chunks_exact(4)) {
+        *word = u32::from_le_bytes(bytes.try_into().unwrap());
+    }
+    state[12] = counter;
+    for (word, bytes) in state[13..].iter_mut().zip(nonce.chunks_exact(4)) {
+        *word = u32::from_le_bytes(bytes.try_into().unwrap());
+    }
+
+    let mut working = state;
+    for _ in 0..10 {
+        // 列轮
+        quarter_round(&mut working, 0, 4, 8, 12);
+        quarter_round(&mut working, 1, 5, 9, 13);
+        quarter_round(&mut working, 2, 6, 10, 14);
+        quarter_round(&mut working, 3, 7, 11, 15);
+        // 对角轮
+        quarter_round(&mut working, 0, 5, 10, 15);
+        quarter_round(&mut working, 1, 6, 11, 12);
+        quarter_round(&mut working, 2, 7, 8, 13);
+        quarter_round(&mut working, 3, 4, 9, 14);
+    }
+
+    let mut ret = [0; BLOCK_SIZE];
+    for ((bytes, word), init) in ret.chunks_exact_mut(4).zip(working).zip(state) {
+        bytes.copy_from_slice(&word.wrapping_add(init).to_le_bytes());
+    }
+    ret
+}
+
+fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(12);
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(7);
+}
+
+/// 以 `ChaCha20` 密钥流作为输出的随机数生成器。
+///
+/// 每次生成之后都会用新的密钥流替换密钥（fast key erasure），因此即使当前状态泄露，也无法推出之前的输出
+pub struct ChaChaRng {
+    key: [u8; KEY_SIZE],
+}
+
+impl ChaChaRng {
+    pub const fn from_seed(seed: [u8; KEY_SIZE]) -> Self {
+        Self { key: seed }
+    }
+
+    /// 将新的熵混入密钥
+    pub fn mix(&mut self, entropy: &[u8]) {
+        for chunk in entropy.chunks(KEY_SIZE) {
+            for (key, byte) in self.key.iter_mut().zip(chunk) {
+                *key ^= byte;
+            }
+            self.rekey();
+        }
+    }
+
+    pub fn fill_bytes(&mut self, dest: &mut [u8]) {
+        // 计数 0 的块用于更换密钥，因此输出从 1 开始
+        for (counter, chunk) in (1..).zip(dest.chunks_mut(BLOCK_SIZE)) {
+            let block = chacha20_block(&self.key, counter, &[0; 12]);
+            chunk.copy_from_slice(&block[..chunk.len()]);
+        }
+        self.rekey();
+    }
+
+    fn rekey(&mut self) {
+        let block = chacha20_block(&self.key, 0, &[0; 12]);
+        self.key.copy_from_slice(&block[..KEY_SIZE]);
+    }
+}
@@ -0,0 +1,58 @@
+use csprng::{chacha20_block, ChaChaRng};
+
+/// RFC 8439 2.3.2 中的块函数测试向量
+#[test]
+fn chacha20_block_rfc8439() {
+    let key: [u8; 32] = core::array::from_fn(|i| i as u8);
+    let nonce = [0, 0, 0, 0x09, 0, 0, 0, 0x4a, 0, 0, 0, 0];
+    let expected = [
+        0x10, 0xf1, 0xe7, 0xe4, 0xd1, 0x3b, 0x59, 0x15, 0x50, 0x0f, 0xdd, 0x1f, 0xa3, 0x20, 0x71, 0xc4, 0xc7, 0xd1,
+        0xf4, 0xc7, 0x33, 0xc0, 0x68, 0x03, 0x04, 0x22, 0xaa, 0x9a, 0xc3, 0xd4, 0x6c, 0x4e, 0xd2, 0x82, 0x64, 0x46,
+        0x07, 0x9f, 0xaa, 0x09, 0x14, 0xc2, 0xd7, 0x05, 0xd9, 0x8b, 0x02, 0xa2, 0xb5, 0x12, 0x9c, 0xd1, 0xde, 0x16,
+        0x4e, 0xb9, 0xcb, 0xd0, 0x83, 0xe8, 0xa2, 0x50, 0x3c, 0x4e,
+    ];
+    assert_eq!(chacha20_block(&key, 1, &nonce), expected);
+}
+
+/// RFC 8439 A.1 中全零密钥的测试向量
+#[test]
+fn chacha20_block_zero_key() {
+    let expected = [
+        0x76, 0xb8, 0xe0, 0xad, 0xa0, 0xf1, 0x3d, 0x90, 0x40, 0x5d, 0x6a, 0xe5, 0x53, 0x86, 0xbd, 0x28, 0xbd, 0xd2,
+        0x19, 0xb8, 0xa0, 0x8d, 0xed, 0x1a, 0xa8, 0x36, 0xef, 0xcc, 0x8b, 0x77, 0x0d, 0xc7, 0xda, 0x41, 0x59, 0x7c,
+        0x51, 0x57, 0x48, 0x8d, 0x77, 0x24, 0xe0, 0x3f, 0xb8, 0xd8, 0x4a, 0x37, 0x6a, 0x43, 0xb8, 0xf4, 0x15, 0x18,
+        0xa1, 0x1c, 0xc3, 0x87, 0xb6, 0x69, 0xb2, 0xee, 0x65, 0x86,
+    ];
+    assert_eq!(chacha20_block(&[0; 32], 0, &[0; 12]), expected);
+}
+
+#[test]
+fn rng_never_repeats_output() {
+    let mut rng = ChaChaRng::from_seed([7; 32]);
+    let mut first = [0; 100];
+    let mut second = [0; 100];
+    rng.fill_bytes(&mut first);
+    rng.fill_bytes(&mut second);
+    assert_ne!(first, second);
+    // 不同的块之间也不应相同
+    assert_ne!(first[..64], second[..64]);
+    assert_ne!(first[..36], first[64..]);
+}
+
+#[test]
+fn rng_is_deterministic_for_same_seed_and_entropy() {
+    let mut a = ChaChaRng::from_seed([1; 32]);
+    let mut b = ChaChaRng::from_seed([1; 32]);
+    a.mix(b"jitter");
+    b.mix(b"jitter");
+    let (mut out_a, mut out_b) = ([0; 48], [0; 48]);
+    a.fill_bytes(&mut out_a);
+    b.fill_bytes(&mut out_b);
+    assert_eq!(out_a, out_b);
+
+    let mut c = ChaChaRng::from_seed([1; 32]);
+    c.mix(b"jitteR");
+    let mut out_c = [0; 48];
+    c.fill_bytes(&mut out_c);
+    assert_ne!(out_a, out_c);
+}
@@ -46,6 +46,7 @@ pub mod errno {
         ENOMEM,         -12,    "Out of memory",
         EACCES,         -13,    "EACCES",
         EFAULT,         -14,    "Bad address.",
+        ENOTBLK,        -15,    "Block device required.",
         EBUSY,          -16,    "Device or resource busy.",
         EEXIST,         -17,    "File exists.",
         EXDEV,          -18,    "Cross-device link.",
@@ -55,11 +56,36 @@ pub mod errno {
         EINVAL,         -22,    "Invalid argument.",
         EMFILE,         -24,    "Too many open files.",
         ENOTTY,         -25,    "Not a tty.",
+        EFBIG,          -27,    "File too large.",
         ENOSPC,         -28,    "No space left on device",
         ESPIPE,         -29,    "Illegal seek.",
+        EMLINK,         -31,    "Too many links.",
+        EPIPE,          -32,    "Broken pipe.",
         ERANGE,         -34,    "Exceed range.",
+        EDEADLK,        -35,    "Resource deadlock would occur.",
+        ENOTEMPTY,      -39,    "Directory not empty",
+        ELOOP,          -40,    "Too many symbolic links encountered.",
         EOVERFLOW,      -75,    "Value too large for data type",
         ENAMETOOLONG,   -78,    "Filename too long",
-        ENOTEMPTY,      -93,    "Directory not empty",
+        ENOTSOCK,       -88,    "Socket operation on non-socket.",
+        EDESTADDRREQ,   -89,    "Destination address required.",
+        EMSGSIZE,       -90,    "Message too long.",
+        EPROTOTYPE,     -91,    "Protocol wrong type for socket.",
+        ENOPROTOOPT,    -92,    "Protocol not available.",
+        EPROTONOSUPPORT, -93,   "Protocol not supported.",
+        ESOCKTNOSUPPORT, -94,   "Socket type not supported.",
+        EOPNOTSUPP,     -95,    "Operation not supported on transport endpoint.",
+        EAFNOSUPPORT,   -97,    "Address family not supported by protocol.",
+        EADDRINUSE,     -98,    "Address already in use.",
+        EADDRNOTAVAIL,  -99,    "Cannot assign requested address.",
+        ENETUNREACH,    -101,   "Network is unreachable.",
+        ECONNABORTED,   -103,   "Software caused connection abort.",
+        ECONNRESET,     -104,   "Connection reset by peer.",
+        EISCONN,        -106,   "Transport endpoint is already connected.",
+        ENOTCONN,       -107,   "Transport endpoint is not connected.",
+        ETIMEDOUT,      -110,   "Connection timed out.",
+        ECONNREFUSED,   -111,   "Connection refused.",
+        EALREADY,       -114,   "Operation already in progress.",
+        EINPROGRESS,    -115,   "Operation now in progress.",
     );
 }
@@ -34,25 +34,25 @@ bitflags! {
         /// 是 FIFO
         const FIFO          = (1 << 12);
 
-        // /// 是否设置 uid/gid/sticky
-        // // const S_ISUID = 1 << 11;
-        // // const S_ISGID = 1 << 10;
-        // // const S_ISVTX = 1 << 9;
-        // /// 所有者权限
-        // const S_IRWXU = Self::S_IRUSR.bits() | Self::S_IWUSR.bits() | Self::S_IXUSR.bits();
-        // const S_IRUSR = 1 << 8;
-        // const S_IWUSR = 1 << 7;
-        // const S_IXUSR = 1 << 6;
-        // /// 用户组权限
-        // const S_IRWXG = Self::S_IRGRP.bits() | Self::S_IWGRP.bits() | Self::S_IXGRP.bits();
-        // const S_IRGRP = 1 << 5;
-        // const S_IWGRP = 1 << 4;
-        // const S_IXGRP = 1 << 3;
-        // /// 其他用户权限
-        // const S_IRWXO = Self::S_IROTH.bits() | Self::S_IWOTH.bits() | Self::S_IXOTH.bits();
-        // const S_IROTH = 1 << 2;
-        // const S_IWOTH = 1 << 1;
-        // const S_IXOTH = 1 << 0;
+        /// 是否设置 uid/gid/sticky
+        const S_ISUID = 1 << 11;
+        const S_ISGID = 1 << 10;
+        const S_ISVTX = 1 << 9;
+        /// 所有者权限
+        const S_IRWXU = Self::S_IRUSR.bits() | Self::S_IWUSR.bits() | Self::S_IXUSR.bits();
+        const S_IRUSR = 1 << 8;
+        const S_IWUSR = 1 << 7;
+        const S_IXUSR = 1 << 6;
+        /// 用户组权限
+        const S_IRWXG = Self::S_IRGRP.bits() | Self::S_IWGRP.bits() | Self::S_IXGRP.bits();
+        const S_IRGRP = 1 << 5;
+        const S_IWGRP = 1 << 4;
+        const S_IXGRP = 1 << 3;
+        /// 其他用户权限
+        const S_IRWXO = Self::S_IROTH.bits() | Self::S_IWOTH.bits() | Self::S_IXOTH.bits();
+        const S_IROTH = 1 << 2;
+        const S_IWOTH = 1 << 1;
+        const S_IXOTH = 1 << 0;
     }
 
     #[derive(Debug)]
@@ -73,44 +73,46 @@ bitflags! {
 
     #[derive(Debug)]
     pub struct MountFlags : u32 {
-        // const MS_RDONLY         = 1 <<  0;
-        // const MS_NOSUID         = 1 <<  1;
-        // const MS_NODEV          = 1 <<  2;
-        // const MS_NOEXEC         = 1 <<  3;
-        // const MS_SYNCHRONOUS    = 1 <<  4;
-        // const MS_REMOUNT        = 1 <<  5;
-        // const MS_MANDLOCK       = 1 <<  6;
-        // const MS_DIRSYNC        = 1 <<  7;
-        // const MS_NOSYMFOLLOW    = 1 <<  8;
-        // const MS_NOATIME        = 1 <<  9;
-        // const MS_NODIRATIME     = 1 << 10;
-        // const MS_BIND           = 1 << 11;
-        // const MS_MOVE           = 1 << 12;
-        // const MS_REC            = 1 << 13;
-        // const MS_SILENT         = 1 << 14;
-        // const MS_POSIXACL       = 1 << 16;
-        // const MS_UNBINDABLE     = 1 << 17;
-        // const MS_PRIVATE        = 1 << 18;
-        // const MS_SLAVE          = 1 << 19;
-        // const MS_SHARED         = 1 << 20;
-        // const MS_RELATIME       = 1 << 21;
-        // const MS_KERNMOUNT      = 1 << 22;
-        // const MS_I_VERSION      = 1 << 23;
-        // const MS_STRICTATIME    = 1 << 24;
-        // const MS_LAZYTIME       = 1 << 25;
-        // const MS_NOREMOTELOCK   = 1 << 27;
-        // const MS_NOSEC          = 1 << 28;
-        // const MS_BORN           = 1 << 29;
-        // const MS_ACTIVE         = 1 << 30;
-        // const MS_NOUSER         = 1 << 31;
+        const MS_RDONLY         = 1 <<  0;
+        const MS_NOSUID         = 1 <<  1;
+        const MS_NODEV          = 1 <<  2;
+        const MS_NOEXEC         = 1 <<  3;
+        const MS_SYNCHRONOUS    = 1 <<  4;
+        const MS_REMOUNT        = 1 <<  5;
+        const MS_MANDLOCK       = 1 <<  6;
+        const MS_DIRSYNC        = 1 <<  7;
+        const MS_NOSYMFOLLOW    = 1 <<  8;
+        const MS_NOATIME        = 1 <<  9;
+        const MS_NODIRATIME     = 1 << 10;
+        /// 将一个目录树绑定到另一个位置
+        const MS_BIND           = 1 << 11;
+        const MS_MOVE           = 1 << 12;
+        /// 与 `MS_BIND` 一同使用时，递归地绑定其下的挂载
+        const MS_REC            = 1 << 13;
+        const MS_SILENT         = 1 << 14;
+        const MS_POSIXACL       = 1 << 16;
+        const MS_UNBINDABLE     = 1 << 17;
+        const MS_PRIVATE        = 1 << 18;
+        const MS_SLAVE          = 1 << 19;
+        const MS_SHARED         = 1 << 20;
+        const MS_RELATIME       = 1 << 21;
+        const MS_KERNMOUNT      = 1 << 22;
+        const MS_I_VERSION      = 1 << 23;
+        const MS_STRICTATIME    = 1 << 24;
+        const MS_LAZYTIME       = 1 << 25;
+        const MS_NOREMOTELOCK   = 1 << 27;
+        const MS_NOSEC          = 1 << 28;
+        const MS_BORN           = 1 << 29;
+        const MS_ACTIVE         = 1 << 30;
+        const MS_NOUSER         = 1 << 31;
     }
 
     #[derive(Debug)]
     pub struct UnmountFlags : u32 {
-        // const MNT_FORCE         =   1 << 0;
-        // const MNT_DETACH        =   1 << 1;
-        // const MNT_EXPIRE        =   1 << 2;
-        // const UMOUNT_NOFOLLOW   =   1 << 3;
+        const MNT_FORCE         =   1 << 0;
+        const MNT_DETACH        =   1 << 1;
+        const MNT_EXPIRE        =   1 << 2;
+        const UMOUNT_NOFOLLOW   =   1 << 3;
     }
 
     #[derive(Clone, Copy)]
@@ -159,8 +161,8 @@ bitflags! {
         const LARGEFILE = 1 << 15;
         /// 如果打开的文件不是目录，那么就返回失败
         const DIRECTORY = 1 << 16;
-        // /// 如果路径的 basename 是一个符号链接，则打开失败并返回 `ELOOP`，目前不支持
-        // const O_NOFOLLOW    = 1 << 17;
+        /// 如果路径的 basename 是一个符号链接，则打开失败并返回 `ELOOP`
+        const NOFOLLOW  = 1 << 17;
         // /// 读文件时不更新文件的 last access time，暂不支持
         // const O_NOATIME     = 1 << 18;
         /// 设置打开的文件描述符的 close-on-exec 标志
@@ -177,6 +179,16 @@ bitflags! {
         const R_OK = 1 << 2;
     }
 
+    #[derive(Debug)]
+    pub struct FaccessatFlags: u32 {
+        /// 使用有效用户 ID 和有效组 ID 而不是真实 ID 进行检查
+        const AT_EACCESS            = 1 << 9;
+        /// 同 [`FstatFlags::AT_SYMLINK_NOFOLLOW`]
+        const AT_SYMLINK_NOFOLLOW   = 1 << 8;
+        /// 同 [`FstatFlags::AT_EMPTY_PATH`]
+        const AT_EMPTY_PATH         = 1 << 12;
+    }
+
     #[derive(Debug)]
     pub struct Renameat2Flags: u32 {
         /// Don't overwrite newpath of the rename. Return an error if newpath already exists.
@@ -195,6 +207,16 @@ impl Display for StatFsFlags {
 }
 
 impl OpenFlags {
+    /// `fcntl()` 的 `F_SETFL` 能修改的文件状态标志
+    pub const SETFL_MASK: Self = Self::APPEND.union(Self::NONBLOCK).union(Self::ASYNC);
+
+    /// 打开文件后保存在打开的文件描述中的标志，即访问模式和文件状态标志。
+    ///
+    /// 创建标志只在打开时起作用，`CLOEXEC` 则属于文件描述符本身
+    pub fn status_flags(self) -> Self {
+        self.difference(Self::CREATE | Self::EXCL | Self::NOCTTY | Self::TRUNCATE | Self::CLOEXEC)
+    }
+
     pub fn with_read_only(self) -> Self {
         self.difference(Self::WRONLY | Self::RDWR)
     }
@@ -267,6 +289,22 @@ pub struct Stat {
     pub st_ctime: TimeSpec,
 }
 
+/// 由主、次设备号构造设备号，编码方式同 glibc 的 `makedev`
+pub const fn makedev(major: u32, minor: u32) -> u64 {
+    let (major, minor) = (major as u64, minor as u64);
+    ((major & 0xffff_f000) << 32) | ((major & 0x0000_0fff) << 8) | ((minor & 0xffff_ff00) << 12) | (minor & 0x0000_00ff)
+}
+
+/// 设备号中的主设备号
+pub const fn major(dev: u64) -> u32 {
+    (((dev >> 32) & 0xffff_f000) | ((dev >> 8) & 0x0000_0fff)) as u32
+}
+
+/// 设备号中的次设备号
+pub const fn minor(dev: u64) -> u32 {
+    (((dev >> 12) & 0xffff_ff00) | (dev & 0x0000_00ff)) as u32
+}
+
 #[repr(C)]
 #[derive(Default)]
 pub struct FsStat {
@@ -318,7 +356,7 @@ pub struct PollFd {
 }
 
 bitflags! {
-    #[derive(Debug)]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
     pub struct PollEvents: i16 {
         /// 有数据可读
         const POLLIN = 1 << 0;
@@ -341,5 +379,218 @@ bitflags! {
         const POLLHUP = 1 << 4;
         /// 无效请求，fd 未打开（仅在 `revents` 返回，在 `events` 中会被忽略）
         const POLLNVAL = 1 << 5;
+        /// 流式 socket 的对端关闭了连接，或者关闭了写方向
+        const POLLRDHUP = 1 << 13;
     }
 }
+
+/// `fd_set` 能容纳的 fd 数量，`select()` 系列系统调用只能监视小于它的 fd
+pub const FD_SETSIZE: usize = 1024;
+/// `fd_set` 由若干个 `u64` 组成，每个 `u64` 表示 64 个 fd
+pub const NFDBITS: usize = u64::BITS as usize;
+
+/// `pselect6` 的第六个参数。因为系统调用的参数个数有限，信号掩码和其大小被打包在一起
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct PselectSigMask {
+    /// 指向信号掩码的指针，可以为 `NULL`
+    pub ss: usize,
+    pub ss_len: usize,
+}
+
+bitflags! {
+    /// `struct epoll_event` 中的事件。低位与 [`PollEvents`] 相同
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct EpollEvents: u32 {
+        const EPOLLIN = 1 << 0;
+        const EPOLLPRI = 1 << 1;
+        const EPOLLOUT = 1 << 2;
+        const EPOLLERR = 1 << 3;
+        const EPOLLHUP = 1 << 4;
+        const EPOLLNVAL = 1 << 5;
+        const EPOLLRDNORM = 1 << 6;
+        const EPOLLRDBAND = 1 << 7;
+        const EPOLLWRNORM = 1 << 8;
+        const EPOLLWRBAND = 1 << 9;
+        const EPOLLMSG = 1 << 10;
+        /// 流式 socket 的对端关闭了连接或者关闭了写端
+        const EPOLLRDHUP = 1 << 13;
+        /// 多个 epoll 监视同一文件时，事件只唤醒其中之一
+        const EPOLLEXCLUSIVE = 1 << 28;
+        /// 与系统休眠相关，会被忽略
+        const EPOLLWAKEUP = 1 << 29;
+        /// 报告一次事件后即禁用该文件，直到通过 `EPOLL_CTL_MOD` 重新启用
+        const EPOLLONESHOT = 1 << 30;
+        /// 边沿触发，只在文件状态变化后报告一次事件
+        const EPOLLET = 1 << 31;
+    }
+}
+
+impl EpollEvents {
+    pub fn from_poll(events: PollEvents) -> Self {
+        Self::from_bits_truncate(events.bits() as u16 as u32)
+    }
+
+    pub fn to_poll(self) -> PollEvents {
+        PollEvents::from_bits_truncate(self.bits() as i16)
+    }
+}
+
+/// `epoll_wait()` 返回的事件。
+///
+/// 注意 linux 只在 `x86_64` 上将其声明为 packed，riscv64 上是自然对齐的
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct EpollEvent {
+    pub events: u32,
+    /// 用户在 `epoll_ctl()` 时指定的数据，内核原样返回
+    pub data: u64,
+}
+
+bitflags! {
+    /// `eventfd2()` 的 flags
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct EventFdFlags: u32 {
+        /// 读取时计数器只减一并返回 1，类似于信号量
+        const SEMAPHORE = 1;
+        const NONBLOCK = OpenFlags::NONBLOCK.bits();
+        const CLOEXEC = OpenFlags::CLOEXEC.bits();
+    }
+}
+
+bitflags! {
+    /// `timerfd_settime()` 的 flags
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct TimerFdSetFlags: u32 {
+        /// `it_value` 是绝对时间，否则是相对于当前时间
+        const ABSTIME = 1 << 0;
+        /// 挂钟被修改时取消定时器，仅对 `CLOCK_REALTIME` 的绝对时间定时器有效
+        const CANCEL_ON_SET = 1 << 1;
+    }
+}
+
+/// 将 fd 加入 epoll 的兴趣列表
+pub const EPOLL_CTL_ADD: usize = 1;
+/// 将 fd 移出 epoll 的兴趣列表
+pub const EPOLL_CTL_DEL: usize = 2;
+/// 修改 fd 在 epoll 中的事件和数据
+pub const EPOLL_CTL_MOD: usize = 3;
+
+/// `fcntl()` 的记录锁类型：读锁（共享锁）
+pub const F_RDLCK: i16 = 0;
+/// `fcntl()` 的记录锁类型：写锁（互斥锁）
+pub const F_WRLCK: i16 = 1;
+/// `fcntl()` 的记录锁类型：解锁
+pub const F_UNLCK: i16 = 2;
+
+/// `fcntl()` 的 `F_GETLK`、`F_SETLK` 和 `F_SETLKW` 命令使用的记录锁描述
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Flock {
+    /// 锁的类型，`F_RDLCK`、`F_WRLCK` 或 `F_UNLCK`
+    pub l_type: i16,
+    /// `l_start` 的基准，`SEEK_SET`、`SEEK_CUR` 或 `SEEK_END`
+    pub l_whence: i16,
+    /// 锁的起始偏移
+    pub l_start: i64,
+    /// 锁的长度。为 0 表示一直到文件末尾（包括之后增长的部分），为负数表示 `l_start` 之前的区间
+    pub l_len: i64,
+    /// `F_GETLK` 返回的持有冲突锁的进程
+    pub l_pid: i32,
+}
+
+bitflags! {
+    /// `flock()` 的操作
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct FlockOp: u32 {
+        /// 共享锁
+        const LOCK_SH = 1;
+        /// 互斥锁
+        const LOCK_EX = 2;
+        /// 不阻塞，锁被占用时返回 `EWOULDBLOCK`
+        const LOCK_NB = 4;
+        /// 解锁
+        const LOCK_UN = 8;
+    }
+}
+
+bitflags! {
+    /// inotify 的事件类型，以及 `inotify_add_watch()` 的选项
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct InotifyMask: u32 {
+        /// 文件被读取
+        const IN_ACCESS         = 0x0000_0001;
+        /// 文件被写入
+        const IN_MODIFY         = 0x0000_0002;
+        /// 元数据被修改
+        const IN_ATTRIB         = 0x0000_0004;
+        /// 以可写方式打开的文件被关闭
+        const IN_CLOSE_WRITE    = 0x0000_0008;
+        /// 以只读方式打开的文件被关闭
+        const IN_CLOSE_NOWRITE  = 0x0000_0010;
+        /// 文件被打开
+        const IN_OPEN           = 0x0000_0020;
+        /// 文件被移出被监视的目录
+        const IN_MOVED_FROM     = 0x0000_0040;
+        /// 文件被移入被监视的目录
+        const IN_MOVED_TO       = 0x0000_0080;
+        /// 被监视的目录中创建了文件
+        const IN_CREATE         = 0x0000_0100;
+        /// 被监视的目录中删除了文件
+        const IN_DELETE         = 0x0000_0200;
+        /// 被监视的文件本身被删除
+        const IN_DELETE_SELF    = 0x0000_0400;
+        /// 被监视的文件本身被移动
+        const IN_MOVE_SELF      = 0x0000_0800;
+
+        /// 文件系统被卸载，只出现在读出的事件中
+        const IN_UNMOUNT        = 0x0000_2000;
+        /// 事件队列溢出，只出现在读出的事件中，`wd` 为 -1
+        const IN_Q_OVERFLOW     = 0x0000_4000;
+        /// 监视被移除，只出现在读出的事件中
+        const IN_IGNORED        = 0x0000_8000;
+
+        /// 路径不是目录时添加失败
+        const IN_ONLYDIR        = 0x0100_0000;
+        /// 不跟随路径最后的符号链接
+        const IN_DONT_FOLLOW    = 0x0200_0000;
+        /// 文件从被监视的目录中删除后不再产生事件
+        const IN_EXCL_UNLINK    = 0x0400_0000;
+        /// 已经在监视时返回 `EEXIST`，而不是修改原来的监视
+        const IN_MASK_CREATE    = 0x1000_0000;
+        /// 已经在监视时将事件加入原来的掩码，而不是替换
+        const IN_MASK_ADD       = 0x2000_0000;
+        /// 事件的主体是目录，只出现在读出的事件中
+        const IN_ISDIR          = 0x4000_0000;
+        /// 只产生一个事件，之后移除监视
+        const IN_ONESHOT        = 0x8000_0000;
+
+        const IN_CLOSE = Self::IN_CLOSE_WRITE.bits() | Self::IN_CLOSE_NOWRITE.bits();
+        const IN_MOVE = Self::IN_MOVED_FROM.bits() | Self::IN_MOVED_TO.bits();
+        /// 所有可以被监视的事件
+        const IN_ALL_EVENTS = 0x0000_0fff;
+    }
+}
+
+bitflags! {
+    /// `inotify_init1()` 的 flags
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct InotifyInitFlags: u32 {
+        const IN_NONBLOCK = OpenFlags::NONBLOCK.bits();
+        const IN_CLOEXEC = OpenFlags::CLOEXEC.bits();
+    }
+}
+
+/// 从 inotify 中读出的一个事件，之后紧跟 `len` 字节以 `\0` 填充的文件名
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct InotifyEvent {
+    /// 产生事件的监视
+    pub wd: i32,
+    /// 事件类型，见 [`InotifyMask`]
+    pub mask: u32,
+    /// 关联同一次重命名的 `IN_MOVED_FROM` 和 `IN_MOVED_TO`，其他事件为 0
+    pub cookie: u32,
+    /// 文件名的长度，包括填充的 `\0`。事件的主体是被监视的文件本身时为 0
+    pub len: u32,
+}
@@ -304,3 +304,118 @@ pub const PENDIN: u32 = 0o040000;
 pub const EXTPROC: u32 = 0o200000;
 
 pub const XTABS: u32 = 0o014000;
+
+// 以下和 rtc 相关，参考 linux include/uapi/linux/rtc.h
+
+/// 读取 RTC 时间，`_IOR('p', 0x09, struct rtc_time)`
+pub const RTC_RD_TIME: usize = 0x8024_7009;
+/// 设置 RTC 时间，`_IOW('p', 0x0a, struct rtc_time)`
+pub const RTC_SET_TIME: usize = 0x4024_700a;
+
+/// 同 linux 的 `struct rtc_time`，即 `struct tm` 的前 9 个字段。时区总是 UTC
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RtcTime {
+    pub tm_sec: i32,
+    pub tm_min: i32,
+    pub tm_hour: i32,
+    /// 1 到 31
+    pub tm_mday: i32,
+    /// 0 到 11
+    pub tm_mon: i32,
+    /// 自 1900 年以来的年数
+    pub tm_year: i32,
+    /// 0 为周日
+    pub tm_wday: i32,
+    /// 0 到 365
+    pub tm_yday: i32,
+    pub tm_isdst: i32,
+}
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+fn is_leap_year(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        1 => 28 + u32::from(is_leap_year(year)),
+        3 | 5 | 8 | 10 => 30,
+        _ => 31,
+    }
+}
+
+/// 1970-01-01 到 `year`-`month`-`day` 的天数，`month` 从 1 开始。算法来自 Howard Hinnant 的 `days_from_civil`
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+impl RtcTime {
+    /// 将自 Epoch 以来的秒数转换为 UTC 时间
+    pub fn from_unix_secs(secs: u64) -> Self {
+        let days = (secs / SECS_PER_DAY) as i64;
+        let secs_of_day = (secs % SECS_PER_DAY) as i32;
+
+        // Howard Hinnant 的 `civil_from_days`
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let day_of_era = z - era * 146097;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let mday = day_of_year - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+        Self {
+            tm_sec: secs_of_day % 60,
+            tm_min: secs_of_day / 60 % 60,
+            tm_hour: secs_of_day / 3600,
+            tm_mday: mday as i32,
+            tm_mon: (month - 1) as i32,
+            tm_year: (year - 1900) as i32,
+            // 1970-01-01 是周四
+            tm_wday: ((days + 4) % 7) as i32,
+            tm_yday: (days - days_from_civil(year, 1, 1)) as i32,
+            tm_isdst: 0,
+        }
+    }
+
+    /// 转换为自 Epoch 以来的秒数。各字段超出范围或早于 Epoch 时返回 `None`，同 linux 的 `rtc_valid_tm()`
+    ///
+    /// `tm_wday`、`tm_yday` 和 `tm_isdst` 会被忽略
+    pub fn to_unix_secs(&self) -> Option<u64> {
+        let year = i64::from(self.tm_year) + 1900;
+        let month = u32::try_from(self.tm_mon).ok().filter(|&month| month < 12)?;
+        let valid = year >= 1970
+            && self.tm_mday >= 1
+            && self.tm_mday as u32 <= days_in_month(year, month)
+            && (0..24).contains(&self.tm_hour)
+            && (0..60).contains(&self.tm_min)
+            && (0..60).contains(&self.tm_sec);
+        if !valid {
+            return None;
+        }
+        let days = days_from_civil(year, i64::from(month) + 1, i64::from(self.tm_mday));
+        let secs = days * SECS_PER_DAY as i64
+            + i64::from(self.tm_hour) * 3600
+            + i64::from(self.tm_min) * 60
+            + i64::from(self.tm_sec);
+        Some(secs as u64)
+    }
+}
+
+// 以下和块设备相关，参考 linux include/uapi/linux/fs.h
+
+/// 以 512 字节扇区为单位的设备大小，`_IO(0x12, 96)`，结果为 `unsigned long`
+pub const BLKGETSIZE: usize = 0x1260;
+/// 逻辑扇区大小，`_IO(0x12, 104)`，结果为 `int`
+pub const BLKSSZGET: usize = 0x1268;
+/// 以字节为单位的设备大小，`_IOR(0x12, 114, size_t)`，结果为 `u64`
+pub const BLKGETSIZE64: usize = 0x8008_1272;
@@ -8,6 +8,7 @@ pub mod error;
 pub mod fs;
 pub mod ioctl;
 pub mod misc;
+pub mod net;
 pub mod resource;
 pub mod signal;
 pub mod syscall;
@@ -84,6 +84,26 @@ impl TryFrom<TimeSpec> for Duration {
     }
 }
 
+/// 定时器的设置，用于 `timerfd_settime()` 等
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct ITimerSpec {
+    /// 周期定时器的间隔，为 0 表示只触发一次
+    pub interval: TimeSpec,
+    /// 距离下一次触发的时间，为 0 表示定时器未启动
+    pub value: TimeSpec,
+}
+
+/// 全局时钟，或者说挂钟
+pub const CLOCK_REALTIME: usize = 0;
+/// 自开机以来单调递增的时钟
+pub const CLOCK_MONOTONIC: usize = 1;
+pub const CLOCK_MONOTONIC_RAW: usize = 4;
+pub const CLOCK_REALTIME_COARSE: usize = 5;
+pub const CLOCK_MONOTONIC_COARSE: usize = 6;
+/// 同 `CLOCK_MONOTONIC`，但包括系统挂起的时间。目前不支持挂起，因此二者一样
+pub const CLOCK_BOOTTIME: usize = 7;
+
 #[repr(C)]
 #[derive(Debug)]
 pub struct TimeVal {
@@ -0,0 +1,161 @@
+use bitflags::bitflags;
+
+use crate::fs::{IoVec, OpenFlags};
+
+/// 本地通信，即 unix domain socket
+pub const AF_UNIX: u16 = 1;
+/// IPv4
+pub const AF_INET: u16 = 2;
+
+/// 面向连接的字节流
+pub const SOCK_STREAM: u32 = 1;
+/// 无连接、保留消息边界的数据报
+pub const SOCK_DGRAM: u32 = 2;
+/// `socket()` 的 `type` 参数中表示类型的部分，其余的位是 [`SocketFlags`]
+pub const SOCK_TYPE_MASK: u32 = 0xf;
+
+bitflags! {
+    /// `socket()`、`socketpair()` 的 `type` 参数以及 `accept4()` 的 `flags` 中的标志
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct SocketFlags: u32 {
+        const NONBLOCK = OpenFlags::NONBLOCK.bits();
+        const CLOEXEC = OpenFlags::CLOEXEC.bits();
+    }
+}
+
+/// `listen()` 的 backlog 的上限，同 linux 的 `/proc/sys/net/core/somaxconn`
+pub const SOMAXCONN: usize = 4096;
+
+/// `getsockopt()` 等的 `level`，表示 socket 层本身的选项
+pub const SOL_SOCKET: i32 = 1;
+
+pub const SO_REUSEADDR: i32 = 2;
+pub const SO_TYPE: i32 = 3;
+pub const SO_ERROR: i32 = 4;
+pub const SO_BROADCAST: i32 = 6;
+pub const SO_SNDBUF: i32 = 7;
+pub const SO_RCVBUF: i32 = 8;
+pub const SO_KEEPALIVE: i32 = 9;
+pub const SO_LINGER: i32 = 13;
+pub const SO_PASSCRED: i32 = 16;
+pub const SO_PEERCRED: i32 = 17;
+pub const SO_RCVTIMEO: i32 = 20;
+pub const SO_SNDTIMEO: i32 = 21;
+pub const SO_ACCEPTCONN: i32 = 30;
+pub const SO_PROTOCOL: i32 = 38;
+pub const SO_DOMAIN: i32 = 39;
+
+/// `socket()` 的 `protocol` 参数，也是 `getsockopt()` 等的 `level`
+pub const IPPROTO_IP: i32 = 0;
+pub const IPPROTO_TCP: i32 = 6;
+pub const IPPROTO_UDP: i32 = 17;
+
+/// `IPPROTO_TCP` 层的选项
+pub const TCP_NODELAY: i32 = 1;
+pub const TCP_MAXSEG: i32 = 2;
+
+/// 关闭读方向
+pub const SHUT_RD: i32 = 0;
+/// 关闭写方向
+pub const SHUT_WR: i32 = 1;
+/// 关闭读写两个方向
+pub const SHUT_RDWR: i32 = 2;
+
+bitflags! {
+    /// `sendmsg()`、`recvmsg()` 等的 flags，以及 `msghdr` 的 `msg_flags`
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct MsgFlags: u32 {
+        /// 读取数据但不将其从接收队列中取走
+        const PEEK = 0x2;
+        /// 输出，控制消息因缓冲区太小被截断
+        const CTRUNC = 0x8;
+        /// 输出时表示数据报被截断；输入时表示返回数据报的实际长度
+        const TRUNC = 0x20;
+        /// 本次操作不阻塞，相当于临时设置了 `O_NONBLOCK`
+        const DONTWAIT = 0x40;
+        /// 流式 socket 等到读满缓冲区再返回，除非遇到信号、错误或对端关闭
+        const WAITALL = 0x100;
+        /// 对端关闭时不发送 `SIGPIPE`，只返回 `EPIPE`
+        const NOSIGNAL = 0x4000;
+        /// 通过 `SCM_RIGHTS` 收到的 fd 设置 close-on-exec
+        const CMSG_CLOEXEC = 0x4000_0000;
+    }
+}
+
+/// 控制消息的类型，传递文件描述符
+pub const SCM_RIGHTS: i32 = 1;
+/// 控制消息的类型，传递进程凭证 [`UCred`]
+pub const SCM_CREDENTIALS: i32 = 2;
+
+/// `sockaddr_un` 中 `sun_path` 的长度
+pub const UNIX_PATH_MAX: usize = 108;
+
+/// unix domain socket 的地址。
+///
+/// `sun_path` 以 0 开头时为抽象地址，名字是其后的所有字节（由地址长度决定，可以包含 0）；
+/// 否则是以 0 结尾的文件系统路径。地址长度只包含 `sun_family` 时表示未命名
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SockaddrUn {
+    pub sun_family: u16,
+    pub sun_path: [u8; UNIX_PATH_MAX],
+}
+
+/// IPv4 的 socket 地址。端口和地址都是网络字节序（大端）
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct SockaddrIn {
+    pub sin_family: u16,
+    pub sin_port: u16,
+    pub sin_addr: [u8; 4],
+    pub sin_zero: [u8; 8],
+}
+
+/// `sendmsg()` 和 `recvmsg()` 使用的消息头
+#[repr(C)]
+pub struct MsgHdr {
+    /// 对端地址，可以为空
+    pub msg_name: *mut u8,
+    pub msg_namelen: u32,
+    pub msg_iov: *mut IoVec,
+    pub msg_iovlen: usize,
+    /// 控制消息（辅助数据）的缓冲区，其中是若干个按 [`cmsg_align()`] 对齐的 [`CmsgHdr`] 及其数据
+    pub msg_control: *mut u8,
+    pub msg_controllen: usize,
+    /// 仅在 `recvmsg()` 时作为输出，见 [`MsgFlags`]
+    pub msg_flags: u32,
+}
+
+/// 控制消息的头部，其后紧跟着数据
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct CmsgHdr {
+    /// 包括头部在内的长度，即 [`cmsg_len()`]
+    pub cmsg_len: usize,
+    pub cmsg_level: i32,
+    pub cmsg_type: i32,
+}
+
+/// 进程凭证，用于 `SO_PEERCRED` 和 `SCM_CREDENTIALS`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UCred {
+    pub pid: i32,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+/// 控制消息的对齐，即 glibc 的 `CMSG_ALIGN`
+pub const fn cmsg_align(len: usize) -> usize {
+    len.next_multiple_of(size_of::<usize>())
+}
+
+/// 数据长度为 `data_len` 的控制消息的 `cmsg_len`，即 `CMSG_LEN`
+pub const fn cmsg_len(data_len: usize) -> usize {
+    cmsg_align(size_of::<CmsgHdr>()) + data_len
+}
+
+/// 数据长度为 `data_len` 的控制消息在缓冲区中占据的空间，即 `CMSG_SPACE`
+pub const fn cmsg_space(data_len: usize) -> usize {
+    cmsg_align(size_of::<CmsgHdr>()) + cmsg_align(data_len)
+}
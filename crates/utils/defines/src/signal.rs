@@ -80,3 +80,35 @@ pub const SIGWINCH: u8 = 28;
 pub const SIGIO: u8 = 29;
 pub const SIGPWR: u8 = 30;
 pub const SIGSYS: u8 = 31;
+
+/// 从 signalfd 读出的信号信息，同 linux 的 `struct signalfd_siginfo`，共 128 字节
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SignalfdSiginfo {
+    /// 信号编号，从 1 开始
+    pub ssi_signo: u32,
+    pub ssi_errno: i32,
+    /// 信号来源，如 `SI_USER`
+    pub ssi_code: i32,
+    /// 发送者的 pid
+    pub ssi_pid: u32,
+    /// 发送者的真实 uid
+    pub ssi_uid: u32,
+    pub ssi_fd: i32,
+    pub ssi_tid: u32,
+    pub ssi_band: u32,
+    pub ssi_overrun: u32,
+    pub ssi_trapno: u32,
+    pub ssi_status: i32,
+    pub ssi_int: i32,
+    pub ssi_ptr: u64,
+    pub ssi_utime: u64,
+    pub ssi_stime: u64,
+    pub ssi_addr: u64,
+    pub ssi_addr_lsb: u16,
+    pub _pad2: u16,
+    pub ssi_syscall: i32,
+    pub ssi_call_addr: u64,
+    pub ssi_arch: u32,
+    pub _pad: [u8; 28],
+}
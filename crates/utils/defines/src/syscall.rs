@@ -13,18 +13,32 @@ macro_rules! declare_syscall_id {
 #[rustfmt::skip]
 declare_syscall_id!(
     GETCWD,             17,
+    EVENTFD2,           19,
+    EPOLL_CREATE1,      20,
+    EPOLL_CTL,          21,
+    EPOLL_PWAIT,        22,
     DUP,                23,
     DUP3,               24,
     FCNTL64,            25,
+    INOTIFY_INIT1,      26,
+    INOTIFY_ADD_WATCH,  27,
+    INOTIFY_RM_WATCH,   28,
     IOCTL,              29,
+    FLOCK,              32,
+    MKNODAT,            33,
     MKDIRAT,            34,
     UNLINKAT,           35,
-    // LINKAT,             37,
+    SYMLINKAT,          36,
+    LINKAT,             37,
     UMOUNT,             39,
     MOUNT,              40,
     STATFS64,           43,
     FACCESSAT,          48,
     CHDIR,              49,
+    FCHMOD,             52,
+    FCHMODAT,           53,
+    FCHOWNAT,           54,
+    FCHOWN,             55,
     OPENAT,             56,
     CLOSE,              57,
     PIPE2,              59,
@@ -35,14 +49,22 @@ declare_syscall_id!(
     READV,              65,
     WRITEV,             66,
     SENDFILE64,         71,
+    PSELECT6,           72,
     PPOLL,              73,
+    SIGNALFD4,          74,
+    READLINKAT,         78,
     NEWFSTATAT,         79,
     NEWFSTAT,           80,
+    FSYNC,              82,
+    TIMERFD_CREATE,     85,
+    TIMERFD_SETTIME,    86,
+    TIMERFD_GETTIME,    87,
     UTIMENSAT,          88,
     EXIT,               93,
     EXIT_GROUP,         94,
     SET_TID_ADDRESS,    96,
     NANOSLEEP,          101,
+    CLOCK_SETTIME,      112,
     CLOCK_GETTIME,      113,
     SYSLOG,             116,
     SCHED_YIELD,        124,
@@ -51,11 +73,21 @@ declare_syscall_id!(
     RT_SIGPROCMASK,     135,
     RT_SIGRETURN,       139,
     SETPRIORITY,        140,
+    SETREGID,           143,
+    SETGID,             144,
+    SETREUID,           145,
+    SETUID,             146,
+    SETRESUID,          147,
+    GETRESUID,          148,
+    SETRESGID,          149,
+    GETRESGID,          150,
     TIMES,              153,
     SETPGID,            154,
     GETPGID,            155,
     UNAME,              160,
+    UMASK,              166,
     GET_TIME_OF_DAY,    169,
+    SETTIMEOFDAY,       170,
     GETPID,             172,
     GETPPID,            173,
     GETUID,             174,
@@ -64,11 +96,28 @@ declare_syscall_id!(
     GETEGID,            177,
     GETTID,             178,
     SYSINFO,            179,
+    SOCKET,             198,
+    SOCKETPAIR,         199,
+    BIND,               200,
+    LISTEN,             201,
+    ACCEPT,             202,
+    CONNECT,            203,
+    GETSOCKNAME,        204,
+    GETPEERNAME,        205,
+    SENDTO,             206,
+    RECVFROM,           207,
+    SETSOCKOPT,         208,
+    GETSOCKOPT,         209,
+    SHUTDOWN,           210,
+    SENDMSG,            211,
+    RECVMSG,            212,
     BRK,                214,
     MUNMAP,             215,
     CLONE,              220,
     EXECVE,             221,
     MMAP,               222,
+    ACCEPT4,            242,
     WAIT4,              260,
     RENAMEAT2,          276,
+    FACCESSAT2,         439,
 );
@@ -0,0 +1,131 @@
+use defines::{
+    fs::{major, makedev, minor, Flock, InotifyEvent, OpenFlags},
+    ioctl::RtcTime,
+    misc::ITimerSpec,
+    net::{cmsg_len, cmsg_space, CmsgHdr, MsgHdr, SockaddrIn, SockaddrUn, UCred},
+    signal::SignalfdSiginfo,
+};
+
+fn rtc_time(year: i32, mon: i32, mday: i32, hour: i32, min: i32, sec: i32) -> RtcTime {
+    RtcTime {
+        tm_sec: sec,
+        tm_min: min,
+        tm_hour: hour,
+        tm_mday: mday,
+        tm_mon: mon - 1,
+        tm_year: year - 1900,
+        ..RtcTime::default()
+    }
+}
+
+#[test]
+fn rtc_time_epoch() {
+    let epoch = RtcTime::from_unix_secs(0);
+    assert_eq!(
+        epoch,
+        RtcTime {
+            tm_wday: 4,
+            ..rtc_time(1970, 1, 1, 0, 0, 0)
+        }
+    );
+    assert_eq!(epoch.to_unix_secs(), Some(0));
+}
+
+#[test]
+fn rtc_time_known_dates() {
+    // 2000-02-29 12:34:56 UTC，闰日
+    let leap_day = RtcTime::from_unix_secs(951_827_696);
+    assert_eq!(
+        leap_day,
+        RtcTime {
+            tm_wday: 2,
+            tm_yday: 59,
+            ..rtc_time(2000, 2, 29, 12, 34, 56)
+        }
+    );
+    // 2038-01-19 03:14:08 UTC，32 位 time_t 溢出之后的一秒
+    let y2038 = RtcTime::from_unix_secs(1 << 31);
+    assert_eq!(
+        y2038,
+        RtcTime {
+            tm_wday: 2,
+            tm_yday: 18,
+            ..rtc_time(2038, 1, 19, 3, 14, 8)
+        }
+    );
+    // 2024-12-31 23:59:59 UTC，闰年的最后一天
+    let new_year_eve = RtcTime::from_unix_secs(1_735_689_599);
+    assert_eq!(new_year_eve.tm_yday, 365);
+    assert_eq!(new_year_eve.to_unix_secs(), Some(1_735_689_599));
+}
+
+#[test]
+fn rtc_time_roundtrip() {
+    for secs in (0..5_000_000_000u64).step_by(86_399 * 37 + 13) {
+        assert_eq!(RtcTime::from_unix_secs(secs).to_unix_secs(), Some(secs));
+    }
+}
+
+#[test]
+fn rtc_time_rejects_invalid_fields() {
+    assert_eq!(rtc_time(1969, 12, 31, 23, 59, 59).to_unix_secs(), None);
+    assert_eq!(rtc_time(2023, 2, 29, 0, 0, 0).to_unix_secs(), None);
+    assert_eq!(rtc_time(2100, 2, 29, 0, 0, 0).to_unix_secs(), None);
+    assert_eq!(rtc_time(2024, 13, 1, 0, 0, 0).to_unix_secs(), None);
+    assert_eq!(rtc_time(2024, 4, 31, 0, 0, 0).to_unix_secs(), None);
+    assert_eq!(rtc_time(2024, 1, 1, 24, 0, 0).to_unix_secs(), None);
+    assert_eq!(rtc_time(2024, 1, 0, 0, 0, 0).to_unix_secs(), None);
+    assert!(rtc_time(2024, 2, 29, 0, 0, 0).to_unix_secs().is_some());
+}
+
+#[test]
+fn device_number_round_trip() {
+    // 与 glibc 的 `makedev` 结果一致
+    assert_eq!(makedev(8, 1), 0x801);
+    assert_eq!(makedev(254, 16), 0xfe10);
+    assert_eq!(makedev(0x0001_2345, 0x0067_89ab), 0x0001_2006_7893_45ab);
+    for (ma, mi) in [(0, 0), (1, 3), (254, 17), (0xfff, 0xff), (0xffff_ffff, 0xffff_ffff)] {
+        let dev = makedev(ma, mi);
+        assert_eq!((major(dev), minor(dev)), (ma, mi));
+    }
+}
+
+#[test]
+fn user_struct_layouts() {
+    // 与 linux riscv64 的 ABI 一致
+    assert_eq!(size_of::<SignalfdSiginfo>(), 128);
+    assert_eq!(core::mem::offset_of!(SignalfdSiginfo, ssi_ptr), 48);
+    assert_eq!(core::mem::offset_of!(SignalfdSiginfo, ssi_call_addr), 88);
+    assert_eq!(size_of::<ITimerSpec>(), 32);
+    assert_eq!(size_of::<SockaddrUn>(), 110);
+    assert_eq!(size_of::<SockaddrIn>(), 16);
+    assert_eq!(core::mem::offset_of!(SockaddrIn, sin_addr), 4);
+    assert_eq!(size_of::<MsgHdr>(), 56);
+    assert_eq!(core::mem::offset_of!(MsgHdr, msg_control), 32);
+    assert_eq!(size_of::<CmsgHdr>(), 16);
+    assert_eq!(size_of::<UCred>(), 12);
+    assert_eq!(size_of::<Flock>(), 32);
+    assert_eq!(core::mem::offset_of!(Flock, l_start), 8);
+    assert_eq!(size_of::<InotifyEvent>(), 16);
+}
+
+#[test]
+fn cmsg_sizes() {
+    // 与 glibc 的 `CMSG_LEN` 和 `CMSG_SPACE` 一致
+    assert_eq!(cmsg_len(0), 16);
+    assert_eq!(cmsg_len(size_of::<i32>()), 20);
+    assert_eq!(cmsg_space(size_of::<i32>()), 24);
+    assert_eq!(cmsg_space(3 * size_of::<i32>()), 32);
+    assert_eq!(cmsg_space(size_of::<UCred>()), 32);
+}
+
+#[test]
+fn open_status_flags() {
+    let flags = OpenFlags::RDWR | OpenFlags::CREATE | OpenFlags::TRUNCATE | OpenFlags::APPEND | OpenFlags::CLOEXEC;
+    assert_eq!(
+        flags.status_flags().bits(),
+        (OpenFlags::RDWR | OpenFlags::APPEND).bits()
+    );
+    // `F_SETFL` 不能改变访问模式
+    assert!(!OpenFlags::SETFL_MASK.intersects(OpenFlags::WRONLY | OpenFlags::RDWR));
+}
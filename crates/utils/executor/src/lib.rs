@@ -4,12 +4,13 @@
 extern crate kernel_tracer;
 extern crate alloc;
 
+pub mod load_avg;
 pub mod time;
 mod yield_now;
 
 use core::{
     future::Future,
-    sync::atomic::{AtomicBool, Ordering},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
     task::Poll,
 };
 
@@ -22,6 +23,10 @@ pub use yield_now::yield_now;
 pub static SHUTDOWN: AtomicBool = AtomicBool::new(false);
 
 static TASK_QUEUE: Lazy<TaskQueue> = Lazy::new(TaskQueue::new);
+/// 正在各个 hart 上运行的任务数
+static RUNNING: AtomicUsize = AtomicUsize::new(0);
+/// 自启动以来任务被调度运行的总次数，近似于上下文切换次数
+static SWITCHES: AtomicUsize = AtomicUsize::new(0);
 
 /// NOTE: 目前的实现中，并发的任务量是有硬上限 (`TASK_LIMIT`) 的，超过会直接 panic
 struct TaskQueue {
@@ -42,6 +47,10 @@ impl TaskQueue {
     fn fetch_task(&self) -> Option<Runnable> {
         self.queue.pop()
     }
+
+    fn len(&self) -> usize {
+        self.queue.len()
+    }
 }
 
 pub fn spawn_with<F, A>(future: F, action: A) -> (Runnable, Task<F::Output>)
@@ -61,7 +70,10 @@ pub fn run_until_shutdown(mut suspend: impl FnMut()) {
     loop {
         while let Some(task) = TASK_QUEUE.fetch_task() {
             trace!("Schedule new task");
+            RUNNING.fetch_add(1, Ordering::Relaxed);
+            SWITCHES.fetch_add(1, Ordering::Relaxed);
             task.run();
+            RUNNING.fetch_sub(1, Ordering::Relaxed);
         }
         if SHUTDOWN.load(Ordering::SeqCst) {
             break;
@@ -70,6 +82,15 @@ pub fn run_until_shutdown(mut suspend: impl FnMut()) {
     }
 }
 
+/// 可运行的任务数，包括正在运行和在队列中等待的
+pub fn nr_runnable() -> usize {
+    RUNNING.load(Ordering::Relaxed) + TASK_QUEUE.len()
+}
+
+pub fn nr_switches() -> usize {
+    SWITCHES.load(Ordering::Relaxed)
+}
+
 pub fn block_on<T>(fut: impl Future<Output = T>) -> T {
     let waker = futures::task::noop_waker_ref();
     let mut cx = core::task::Context::from_waker(waker);
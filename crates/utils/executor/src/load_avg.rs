@@ -0,0 +1,48 @@
+//! 平均负载，算法同 linux：每 `LOAD_FREQ_MS` 对可运行的任务数采样一次，计算 1、5、15 分钟的指数移动平均
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// 定点数的小数位数
+pub const FSHIFT: usize = 11;
+/// 定点数的 1.0
+pub const FIXED_1: usize = 1 << FSHIFT;
+
+const LOAD_FREQ_MS: usize = 5000;
+/// 即 `FIXED_1 / exp(5s / 1min)` 等
+const EXP: [usize; 3] = [1884, 2014, 2037];
+
+static LOAD_AVG: [AtomicUsize; 3] = [const { AtomicUsize::new(0) }; 3];
+static NEXT_SAMPLE_MS: AtomicUsize = AtomicUsize::new(LOAD_FREQ_MS);
+
+/// 在时钟中断中调用，到达采样时间则更新平均负载。多个 hart 同时调用时只有一个会进行更新
+pub fn update_load_avg() {
+    let curr_ms = riscv_time::get_time_ms();
+    let next_sample = NEXT_SAMPLE_MS.load(Ordering::Relaxed);
+    if curr_ms < next_sample
+        || NEXT_SAMPLE_MS
+            .compare_exchange(
+                next_sample,
+                curr_ms + LOAD_FREQ_MS,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            )
+            .is_err()
+    {
+        return;
+    }
+    let active = crate::nr_runnable() * FIXED_1;
+    for (load, exp) in LOAD_AVG.iter().zip(EXP) {
+        let old = load.load(Ordering::Relaxed);
+        let mut new = old * exp + active * (FIXED_1 - exp);
+        // 负载上升时向上取整，同 linux 的 `calc_load()`
+        if active >= old {
+            new += FIXED_1 - 1;
+        }
+        load.store(new / FIXED_1, Ordering::Relaxed);
+    }
+}
+
+/// 1、5、15 分钟的平均负载，为 [`FSHIFT`] 位小数的定点数
+pub fn load_avg() -> [usize; 3] {
+    LOAD_AVG.each_ref().map(|load| load.load(Ordering::Relaxed))
+}
@@ -1,9 +1,15 @@
 mod timer;
 
-use core::time::Duration;
+use core::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
 
 use defines::misc::TimeSpec;
-pub use timer::{check_timer, sleep};
+pub use timer::{check_timer, sleep, wake_at};
+
+/// 开机时刻自 Epoch 以来的纳秒数。由 RTC 驱动初始化，`clock_settime()` 等也会修改
+static BOOT_TIME_NS: AtomicU64 = AtomicU64::new(0);
 
 /// 目前是返回自开机以来的 [`Duration`]
 pub fn curr_time() -> Duration {
@@ -11,6 +17,18 @@ pub fn curr_time() -> Duration {
     Duration::from_nanos(curr_ns as u64)
 }
 
+/// 挂钟时间，即自 Epoch 以来的 [`Duration`]
+pub fn real_time() -> Duration {
+    curr_time() + Duration::from_nanos(BOOT_TIME_NS.load(Ordering::Relaxed))
+}
+
+/// 设置挂钟时间
+pub fn set_real_time(now: Duration) {
+    let boot_time = now.saturating_sub(curr_time());
+    BOOT_TIME_NS.store(boot_time.as_nanos() as u64, Ordering::Relaxed);
+}
+
+/// 当前的挂钟时间，一般用于文件的时间戳
 pub fn curr_time_spec() -> TimeSpec {
-    TimeSpec::from(curr_time())
+    TimeSpec::from(real_time())
 }
@@ -73,6 +73,19 @@ pub fn check_timer() {
     }
 }
 
+/// 在开机后的 `deadline` 时刻唤醒 `waker`，已经过了该时刻则立即唤醒。
+///
+/// 适用于没有一个一直存活的 future 可以轮询的情况，比如登记 timerfd 的到期
+pub fn wake_at(deadline: Duration, waker: Waker) {
+    // 向上取整，以免在到期之前被唤醒
+    let expire_ms = deadline.as_nanos().div_ceil(1_000_000) as usize;
+    if expire_ms <= riscv_time::get_time_ms() {
+        waker.wake();
+    } else {
+        TIMERS.lock().push(Reverse(Timer { expire_ms, waker }));
+    }
+}
+
 pub fn sleep(time: Duration) -> impl Future<Output = ()> {
     let curr_ms = riscv_time::get_time_ms();
     let expire_ms = curr_ms + time.as_millis() as usize;
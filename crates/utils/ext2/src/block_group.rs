@@ -0,0 +1,42 @@
+use crate::{read_u16, read_u32, write_u16, write_u32};
+
+pub const GROUP_DESC_SIZE: usize = 32;
+
+/// 块组描述符，块组描述符表紧跟在超级块所在的块之后
+///
+/// 参考 <https://www.nongnu.org/ext2-doc/ext2.html#block-group-descriptor-table>
+#[derive(Clone, Copy, Debug)]
+pub struct BlockGroupDesc {
+    /// 块位图所在的块号
+    pub block_bitmap: u32,
+    /// inode 位图所在的块号
+    pub inode_bitmap: u32,
+    /// inode 表的起始块号
+    pub inode_table: u32,
+    pub free_blocks_count: u16,
+    pub free_inodes_count: u16,
+    /// 块组中目录的数量
+    pub used_dirs_count: u16,
+}
+
+impl BlockGroupDesc {
+    pub fn new(src: &[u8; GROUP_DESC_SIZE]) -> Self {
+        Self {
+            block_bitmap: read_u32(src, 0),
+            inode_bitmap: read_u32(src, 4),
+            inode_table: read_u32(src, 8),
+            free_blocks_count: read_u16(src, 12),
+            free_inodes_count: read_u16(src, 14),
+            used_dirs_count: read_u16(src, 16),
+        }
+    }
+
+    pub fn write_to(&self, dst: &mut [u8; GROUP_DESC_SIZE]) {
+        write_u32(dst, 0, self.block_bitmap);
+        write_u32(dst, 4, self.inode_bitmap);
+        write_u32(dst, 8, self.inode_table);
+        write_u16(dst, 12, self.free_blocks_count);
+        write_u16(dst, 14, self.free_inodes_count);
+        write_u16(dst, 16, self.used_dirs_count);
+    }
+}
@@ -0,0 +1,104 @@
+use defines::error::{errno, KResult};
+use ecow::EcoString;
+
+use crate::{inode::FileType, read_u16, read_u32, write_u16, write_u32};
+
+/// 目录项中，文件名之前的固定部分的大小
+pub const DIR_ENTRY_HEADER_SIZE: usize = 8;
+
+/// 解析后的目录项
+#[derive(Clone, Debug)]
+pub struct DirEntry {
+    pub ino: u32,
+    pub name: EcoString,
+    /// 未启用 `FILETYPE` 特性时，目录项中不记录文件类型，需要读 inode 才能知道
+    pub file_type: Option<FileType>,
+}
+
+/// 目录块中的一个原始目录项，`ino` 为 0 表示该目录项未被使用
+pub(crate) struct RawDirEntry {
+    /// 在目录块中的偏移
+    pub offset: usize,
+    pub ino: u32,
+    /// 目录项总长度，包括其后的空闲空间
+    pub rec_len: usize,
+    pub name_len: usize,
+    pub file_type: u8,
+}
+
+impl RawDirEntry {
+    pub fn name<'a>(&self, block: &'a [u8]) -> &'a [u8] {
+        let start = self.offset + DIR_ENTRY_HEADER_SIZE;
+        &block[start..start + self.name_len]
+    }
+
+    /// 该目录项实际需要的长度，剩下的部分可以用于存放新的目录项
+    pub fn actual_len(&self) -> usize {
+        if self.ino == 0 {
+            0
+        } else {
+            rec_len_for(self.name_len)
+        }
+    }
+
+    pub fn to_dir_entry(&self, block: &[u8], has_file_type: bool) -> KResult<DirEntry> {
+        let name = core::str::from_utf8(self.name(block)).map_err(|e| {
+            warn!("non-utf8 ext2 dir entry name: {e}");
+            errno::EIO
+        })?;
+        Ok(DirEntry {
+            ino: self.ino,
+            name: EcoString::from(name),
+            file_type: if has_file_type {
+                FileType::from_dir_entry_type(self.file_type)
+            } else {
+                None
+            },
+        })
+    }
+}
+
+/// 存放名字长度为 `name_len` 的目录项至少需要的长度，需要 4 字节对齐
+pub(crate) fn rec_len_for(name_len: usize) -> usize {
+    (DIR_ENTRY_HEADER_SIZE + name_len).next_multiple_of(4)
+}
+
+/// 遍历一个目录块中的所有原始目录项。目录块损坏时会产生一个 `EIO` 然后结束
+pub(crate) fn raw_entries(block: &[u8]) -> impl Iterator<Item = KResult<RawDirEntry>> + '_ {
+    let mut offset = 0;
+    core::iter::from_fn(move || {
+        if offset + DIR_ENTRY_HEADER_SIZE > block.len() {
+            return None;
+        }
+        let ino = read_u32(block, offset);
+        let rec_len = read_u16(block, offset + 4) as usize;
+        let name_len = block[offset + 6] as usize;
+        let file_type = block[offset + 7];
+        if rec_len < DIR_ENTRY_HEADER_SIZE
+            || !rec_len.is_multiple_of(4)
+            || offset + rec_len > block.len()
+            || (ino != 0 && DIR_ENTRY_HEADER_SIZE + name_len > rec_len)
+        {
+            warn!("corrupted ext2 dir entry at {offset}, rec_len: {rec_len}, name_len: {name_len}");
+            offset = block.len();
+            return Some(Err(errno::EIO));
+        }
+        let entry = RawDirEntry {
+            offset,
+            ino,
+            rec_len,
+            name_len,
+            file_type,
+        };
+        offset += rec_len;
+        Some(Ok(entry))
+    })
+}
+
+pub(crate) fn write_entry(block: &mut [u8], offset: usize, ino: u32, rec_len: usize, name: &[u8], file_type: u8) {
+    write_u32(block, offset, ino);
+    write_u16(block, offset + 4, rec_len as u16);
+    block[offset + 6] = name.len() as u8;
+    block[offset + 7] = file_type;
+    block[offset + DIR_ENTRY_HEADER_SIZE..offset + DIR_ENTRY_HEADER_SIZE + name.len()].copy_from_slice(name);
+}
@@ -0,0 +1,1066 @@
+use alloc::{vec, vec::Vec};
+
+use defines::error::{errno, KResult};
+use hal::block_device::BlockDevice;
+use klocks::SpinMutex;
+
+use crate::{
+    block_group::{BlockGroupDesc, GROUP_DESC_SIZE},
+    dir_entry::{self, DirEntry},
+    inode::{
+        DiskInode, FileType, BLOCK_POINTER_COUNT, DIRECT_BLOCK_COUNT, DISK_INODE_SIZE, DOUBLE_INDIRECT_BLOCK_INDEX,
+        INDEX_FL, INDIRECT_BLOCK_INDEX, TRIPLE_INDIRECT_BLOCK_INDEX,
+    },
+    superblock::{IncompatFeatures, RoCompatFeatures, SuperBlock},
+    write_u16, write_u32, NAME_MAX, SECTOR_SIZE, SUPERBLOCK_OFFSET, SUPERBLOCK_SIZE,
+};
+
+pub struct Ext2FileSystem {
+    block_device: &'static dyn BlockDevice,
+    block_size: usize,
+    inode_size: usize,
+    blocks_count: u32,
+    inodes_count: u32,
+    blocks_per_group: u32,
+    inodes_per_group: u32,
+    first_data_block: u32,
+    first_ino: u32,
+    /// 目录项中是否记录了文件类型
+    has_file_type: bool,
+    /// 常规文件大小是否可以超过 4GiB
+    large_file: bool,
+    alloc_meta: SpinMutex<AllocMeta>,
+}
+
+/// 分配与释放块、inode 时需要一同修改的元数据
+struct AllocMeta {
+    superblock: SuperBlock,
+    groups: Vec<BlockGroupDesc>,
+}
+
+impl Ext2FileSystem {
+    pub fn new(block_device: &'static dyn BlockDevice) -> KResult<Self> {
+        let _enter = debug_span!("ext2_init").entered();
+        let mut buf = [0; SUPERBLOCK_SIZE];
+        read_device_bytes(block_device, SUPERBLOCK_OFFSET as u64, &mut buf, false);
+        let superblock = SuperBlock::new(&buf)?;
+        let block_size = superblock.block_size();
+
+        // 块组描述符表位于超级块所在块的下一个块
+        let gdt_offset = (superblock.first_data_block as u64 + 1) * block_size as u64;
+        let group_count = superblock.group_count();
+        let mut groups = Vec::with_capacity(group_count as usize);
+        for group_id in 0..group_count {
+            let mut buf = [0; GROUP_DESC_SIZE];
+            read_device_bytes(
+                block_device,
+                gdt_offset + group_id as u64 * GROUP_DESC_SIZE as u64,
+                &mut buf,
+                false,
+            );
+            let group = BlockGroupDesc::new(&buf);
+            let inode_table_blocks =
+                (superblock.inodes_per_group as usize * superblock.inode_size as usize).div_ceil(block_size) as u32;
+            if group.block_bitmap >= superblock.blocks_count
+                || group.inode_bitmap >= superblock.blocks_count
+                || group.inode_table.saturating_add(inode_table_blocks) > superblock.blocks_count
+            {
+                warn!("invalid ext2 block group {group_id}: {group:?}");
+                return Err(errno::EINVAL);
+            }
+            groups.push(group);
+        }
+
+        Ok(Self {
+            block_device,
+            block_size,
+            inode_size: superblock.inode_size as usize,
+            blocks_count: superblock.blocks_count,
+            inodes_count: superblock.inodes_count,
+            blocks_per_group: superblock.blocks_per_group,
+            inodes_per_group: superblock.inodes_per_group,
+            first_data_block: superblock.first_data_block,
+            first_ino: superblock.first_ino,
+            has_file_type: superblock.feature_incompat.contains(IncompatFeatures::FILETYPE),
+            large_file: superblock.feature_ro_compat.contains(RoCompatFeatures::LARGE_FILE),
+            alloc_meta: SpinMutex::new(AllocMeta { superblock, groups }),
+        })
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    pub fn block_device(&self) -> &'static dyn BlockDevice {
+        self.block_device
+    }
+
+    /// 返回 `(空闲块数, 空闲 inode 数)`
+    pub fn free_counts(&self) -> (u32, u32) {
+        let meta = self.alloc_meta.lock();
+        (meta.superblock.free_blocks_count, meta.superblock.free_inodes_count)
+    }
+
+    fn read_block(&self, block_id: u32, buf: &mut [u8], cached: bool) {
+        debug_assert_eq!(buf.len(), self.block_size);
+        read_device_bytes(self.block_device, self.block_offset(block_id), buf, cached);
+    }
+
+    fn write_block(&self, block_id: u32, buf: &[u8]) {
+        debug_assert_eq!(buf.len(), self.block_size);
+        write_device_bytes(self.block_device, self.block_offset(block_id), buf);
+    }
+
+    fn block_offset(&self, block_id: u32) -> u64 {
+        block_id as u64 * self.block_size as u64
+    }
+
+    fn inode_offset(&self, ino: u32) -> KResult<u64> {
+        if ino == 0 || ino > self.inodes_count {
+            warn!("invalid ext2 inode number {ino}");
+            return Err(errno::EIO);
+        }
+        let group_id = (ino - 1) / self.inodes_per_group;
+        let index = (ino - 1) % self.inodes_per_group;
+        let inode_table = self.alloc_meta.lock().groups[group_id as usize].inode_table;
+        Ok(self.block_offset(inode_table) + index as u64 * self.inode_size as u64)
+    }
+
+    pub fn read_inode(&self, ino: u32) -> KResult<DiskInode> {
+        let mut buf = [0; DISK_INODE_SIZE];
+        read_device_bytes(self.block_device, self.inode_offset(ino)?, &mut buf, true);
+        Ok(DiskInode::new(&buf))
+    }
+
+    pub fn write_inode(&self, ino: u32, inode: &DiskInode) -> KResult<()> {
+        let mut buf = [0; DISK_INODE_SIZE];
+        inode.write_to(&mut buf);
+        write_device_bytes(self.block_device, self.inode_offset(ino)?, &buf);
+        Ok(())
+    }
+
+    /// 分配一个新块，新块的内容会被清零
+    pub fn alloc_block(&self) -> KResult<u32> {
+        let block_id = {
+            let mut meta = self.alloc_meta.lock();
+            let mut found = None;
+            for group_id in 0..meta.groups.len() {
+                let group = &meta.groups[group_id];
+                if group.free_blocks_count == 0 {
+                    continue;
+                }
+                if let Some(bit) = self.alloc_in_bitmap(group.block_bitmap, 0, self.blocks_in_group(group_id as u32)) {
+                    found = Some((group_id, bit));
+                    break;
+                }
+                warn!("ext2 block group {group_id} free_blocks_count is inconsistent with bitmap");
+            }
+            let (group_id, bit) = found.ok_or(errno::ENOSPC)?;
+            meta.groups[group_id].free_blocks_count -= 1;
+            meta.superblock.free_blocks_count = meta.superblock.free_blocks_count.saturating_sub(1);
+            self.sync_alloc_meta(&meta, group_id);
+            self.first_data_block + group_id as u32 * self.blocks_per_group + bit
+        };
+        self.write_block(block_id, &vec![0; self.block_size]);
+        Ok(block_id)
+    }
+
+    pub fn free_block(&self, block_id: u32) -> KResult<()> {
+        if block_id < self.first_data_block || block_id >= self.blocks_count {
+            warn!("free invalid ext2 block {block_id}");
+            return Err(errno::EIO);
+        }
+        let group_id = ((block_id - self.first_data_block) / self.blocks_per_group) as usize;
+        let bit = (block_id - self.first_data_block) % self.blocks_per_group;
+        let mut meta = self.alloc_meta.lock();
+        if !self.free_in_bitmap(meta.groups[group_id].block_bitmap, bit) {
+            warn!("double free ext2 block {block_id}");
+            return Ok(());
+        }
+        meta.groups[group_id].free_blocks_count += 1;
+        meta.superblock.free_blocks_count += 1;
+        self.sync_alloc_meta(&meta, group_id);
+        Ok(())
+    }
+
+    pub fn alloc_inode(&self, is_dir: bool) -> KResult<u32> {
+        let mut meta = self.alloc_meta.lock();
+        let mut found = None;
+        for group_id in 0..meta.groups.len() {
+            let group = &meta.groups[group_id];
+            if group.free_inodes_count == 0 {
+                continue;
+            }
+            // 跳过保留的 inode
+            let start = if group_id == 0 { self.first_ino - 1 } else { 0 };
+            if let Some(bit) = self.alloc_in_bitmap(group.inode_bitmap, start, self.inodes_per_group) {
+                found = Some((group_id, bit));
+                break;
+            }
+            warn!("ext2 block group {group_id} free_inodes_count is inconsistent with bitmap");
+        }
+        let (group_id, bit) = found.ok_or(errno::ENOSPC)?;
+        let group = &mut meta.groups[group_id];
+        group.free_inodes_count -= 1;
+        if is_dir {
+            group.used_dirs_count += 1;
+        }
+        meta.superblock.free_inodes_count = meta.superblock.free_inodes_count.saturating_sub(1);
+        self.sync_alloc_meta(&meta, group_id);
+        Ok(group_id as u32 * self.inodes_per_group + bit + 1)
+    }
+
+    pub fn free_inode(&self, ino: u32, is_dir: bool) -> KResult<()> {
+        if ino < self.first_ino || ino > self.inodes_count {
+            warn!("free invalid ext2 inode {ino}");
+            return Err(errno::EIO);
+        }
+        let group_id = ((ino - 1) / self.inodes_per_group) as usize;
+        let bit = (ino - 1) % self.inodes_per_group;
+        let mut meta = self.alloc_meta.lock();
+        if !self.free_in_bitmap(meta.groups[group_id].inode_bitmap, bit) {
+            warn!("double free ext2 inode {ino}");
+            return Ok(());
+        }
+        let group = &mut meta.groups[group_id];
+        group.free_inodes_count += 1;
+        if is_dir {
+            group.used_dirs_count = group.used_dirs_count.saturating_sub(1);
+        }
+        meta.superblock.free_inodes_count += 1;
+        self.sync_alloc_meta(&meta, group_id);
+        Ok(())
+    }
+
+    /// 最后一个块组的块数可能不足 `blocks_per_group`
+    fn blocks_in_group(&self, group_id: u32) -> u32 {
+        let group_start = self.first_data_block + group_id * self.blocks_per_group;
+        u32::min(self.blocks_per_group, self.blocks_count - group_start)
+    }
+
+    /// 在位图的 `start..end` 范围内找到第一个空闲位并置位
+    fn alloc_in_bitmap(&self, bitmap_block: u32, start: u32, end: u32) -> Option<u32> {
+        let mut bitmap = vec![0; self.block_size];
+        self.read_block(bitmap_block, &mut bitmap, true);
+        let bit = (start..end).find(|&bit| bitmap[bit as usize / 8] & (1 << (bit % 8)) == 0)?;
+        bitmap[bit as usize / 8] |= 1 << (bit % 8);
+        self.write_block(bitmap_block, &bitmap);
+        Some(bit)
+    }
+
+    /// 清除位图中的某一位，返回该位原本是否被置位
+    fn free_in_bitmap(&self, bitmap_block: u32, bit: u32) -> bool {
+        let mut bitmap = vec![0; self.block_size];
+        self.read_block(bitmap_block, &mut bitmap, true);
+        let mask = 1 << (bit % 8);
+        if bitmap[bit as usize / 8] & mask == 0 {
+            return false;
+        }
+        bitmap[bit as usize / 8] &= !mask;
+        self.write_block(bitmap_block, &bitmap);
+        true
+    }
+
+    /// 将块组描述符和超级块中的空闲计数写回磁盘
+    ///
+    /// TODO: [low] 目前只更新主超级块和主块组描述符表，备份没有同步
+    fn sync_alloc_meta(&self, meta: &AllocMeta, group_id: usize) {
+        let mut buf = [0; GROUP_DESC_SIZE];
+        let gdt_offset = self.block_offset(self.first_data_block + 1);
+        meta.groups[group_id].write_to(&mut buf);
+        write_device_bytes(
+            self.block_device,
+            gdt_offset + (group_id * GROUP_DESC_SIZE) as u64,
+            &buf,
+        );
+
+        let mut buf = [0; SUPERBLOCK_SIZE];
+        read_device_bytes(self.block_device, SUPERBLOCK_OFFSET as u64, &mut buf, false);
+        meta.superblock.write_to(&mut buf);
+        write_device_bytes(self.block_device, SUPERBLOCK_OFFSET as u64, &buf);
+    }
+
+    fn ptrs_per_block(&self) -> u64 {
+        (self.block_size / core::mem::size_of::<u32>()) as u64
+    }
+
+    /// 逻辑块号在块指针树中的路径。
+    ///
+    /// 返回 `i_block` 中的下标、各级间接块中的下标以及间接的层数
+    fn block_path(&self, logical: u64) -> KResult<(usize, [u32; 3], usize)> {
+        let ptrs = self.ptrs_per_block();
+        let mut logical = logical;
+        if logical < DIRECT_BLOCK_COUNT as u64 {
+            return Ok((logical as usize, [0; 3], 0));
+        }
+        logical -= DIRECT_BLOCK_COUNT as u64;
+        if logical < ptrs {
+            return Ok((INDIRECT_BLOCK_INDEX, [logical as u32, 0, 0], 1));
+        }
+        logical -= ptrs;
+        if logical < ptrs * ptrs {
+            return Ok((
+                DOUBLE_INDIRECT_BLOCK_INDEX,
+                [(logical / ptrs) as u32, (logical % ptrs) as u32, 0],
+                2,
+            ));
+        }
+        logical -= ptrs * ptrs;
+        if logical < ptrs * ptrs * ptrs {
+            return Ok((
+                TRIPLE_INDIRECT_BLOCK_INDEX,
+                [
+                    (logical / (ptrs * ptrs)) as u32,
+                    (logical / ptrs % ptrs) as u32,
+                    (logical % ptrs) as u32,
+                ],
+                3,
+            ));
+        }
+        Err(errno::EFBIG)
+    }
+
+    fn read_block_ptr(&self, block_id: u32, index: u32) -> KResult<u32> {
+        let mut buf = [0; 4];
+        read_device_bytes(
+            self.block_device,
+            self.block_offset(block_id) + index as u64 * 4,
+            &mut buf,
+            true,
+        );
+        let ptr = u32::from_le_bytes(buf);
+        if ptr >= self.blocks_count {
+            warn!("invalid ext2 block pointer {ptr} in block {block_id}");
+            return Err(errno::EIO);
+        }
+        Ok(ptr)
+    }
+
+    fn write_block_ptr(&self, block_id: u32, index: u32, ptr: u32) {
+        write_device_bytes(
+            self.block_device,
+            self.block_offset(block_id) + index as u64 * 4,
+            &ptr.to_le_bytes(),
+        );
+    }
+
+    /// 查找文件中第 `logical` 个块对应的物理块号，空洞返回 `None`
+    pub fn lookup_block(&self, inode: &DiskInode, logical: u64) -> KResult<Option<u32>> {
+        let (root, path, depth) = self.block_path(logical)?;
+        let mut block_id = inode.block[root];
+        for &index in &path[..depth] {
+            if block_id == 0 {
+                return Ok(None);
+            }
+            block_id = self.read_block_ptr(block_id, index)?;
+        }
+        Ok((block_id != 0).then_some(block_id))
+    }
+
+    /// 查找文件中第 `logical` 个块对应的物理块号，遇到空洞时会分配新块（包括所需的间接块）
+    pub fn get_or_alloc_block(&self, inode: &mut DiskInode, logical: u64) -> KResult<u32> {
+        let (root, path, depth) = self.block_path(logical)?;
+        let sectors_per_block = (self.block_size / SECTOR_SIZE) as u32;
+        if inode.block[root] == 0 {
+            inode.block[root] = self.alloc_block()?;
+            inode.blocks += sectors_per_block;
+        }
+        let mut block_id = inode.block[root];
+        for &index in &path[..depth] {
+            let mut next = self.read_block_ptr(block_id, index)?;
+            if next == 0 {
+                next = self.alloc_block()?;
+                inode.blocks += sectors_per_block;
+                self.write_block_ptr(block_id, index, next);
+            }
+            block_id = next;
+        }
+        Ok(block_id)
+    }
+
+    /// 从文件的 `offset` 处读取数据，空洞部分读出 0
+    pub fn read_data(&self, inode: &DiskInode, offset: u64, buf: &mut [u8]) -> KResult<usize> {
+        if offset >= inode.size {
+            return Ok(0);
+        }
+        let len = u64::min(buf.len() as u64, inode.size - offset) as usize;
+        let block_size = self.block_size as u64;
+        let mut nread = 0;
+        while nread < len {
+            let pos = offset + nread as u64;
+            let block_offset = (pos % block_size) as usize;
+            let n = usize::min(len - nread, self.block_size - block_offset);
+            let dst = &mut buf[nread..nread + n];
+            match self.lookup_block(inode, pos / block_size)? {
+                Some(block_id) => read_device_bytes(
+                    self.block_device,
+                    self.block_offset(block_id) + block_offset as u64,
+                    dst,
+                    false,
+                ),
+                None => dst.fill(0),
+            }
+            nread += n;
+        }
+        Ok(nread)
+    }
+
+    /// 向文件的 `offset` 处写入数据，按需分配新块并扩展文件大小。
+    ///
+    /// 只修改 `inode`，需要调用者将其写回磁盘。空间不足时返回已写入的字节数
+    pub fn write_data(&self, inode: &mut DiskInode, offset: u64, buf: &[u8]) -> KResult<usize> {
+        let max_size = if self.large_file { u64::MAX } else { u32::MAX as u64 };
+        if offset.checked_add(buf.len() as u64).is_none_or(|end| end > max_size) {
+            return Err(errno::EFBIG);
+        }
+        let block_size = self.block_size as u64;
+        let mut nwrite = 0;
+        while nwrite < buf.len() {
+            let pos = offset + nwrite as u64;
+            let block_offset = (pos % block_size) as usize;
+            let n = usize::min(buf.len() - nwrite, self.block_size - block_offset);
+            let block_id = match self.get_or_alloc_block(inode, pos / block_size) {
+                Ok(block_id) => block_id,
+                Err(e) if nwrite == 0 => return Err(e),
+                Err(_) => break,
+            };
+            write_device_bytes(
+                self.block_device,
+                self.block_offset(block_id) + block_offset as u64,
+                &buf[nwrite..nwrite + n],
+            );
+            nwrite += n;
+        }
+        inode.size = u64::max(inode.size, offset + nwrite as u64);
+        Ok(nwrite)
+    }
+
+    /// 修改文件大小。缩小时释放多余的块，扩大时只修改大小（即产生空洞）。
+    ///
+    /// 只修改 `inode`，需要调用者将其写回磁盘
+    pub fn truncate(&self, inode: &mut DiskInode, new_size: u64) -> KResult<()> {
+        if new_size < inode.size && !is_fast_symlink(inode) {
+            let block_size = self.block_size as u64;
+            self.free_blocks_from(inode, new_size.div_ceil(block_size))?;
+            // 清零最后一个块中新末尾之后的部分，以保证之后扩展文件时读到的是 0
+            let tail = (new_size % block_size) as usize;
+            if tail != 0
+                && let Some(block_id) = self.lookup_block(inode, new_size / block_size)?
+            {
+                write_device_bytes(
+                    self.block_device,
+                    self.block_offset(block_id) + tail as u64,
+                    &vec![0; self.block_size - tail],
+                );
+            }
+        }
+        inode.size = new_size;
+        Ok(())
+    }
+
+    /// 释放文件中第 `first_logical` 个块及之后的所有块，以及不再需要的间接块
+    fn free_blocks_from(&self, inode: &mut DiskInode, first_logical: u64) -> KResult<()> {
+        let mut freed = 0;
+        for block in inode
+            .block
+            .iter_mut()
+            .take(DIRECT_BLOCK_COUNT)
+            .skip(first_logical as usize)
+        {
+            if *block != 0 {
+                self.free_block(*block)?;
+                *block = 0;
+                freed += 1;
+            }
+        }
+
+        let ptrs = self.ptrs_per_block();
+        // 各级间接块所覆盖的逻辑块范围为 `base..base + span`
+        let mut base = DIRECT_BLOCK_COUNT as u64;
+        let mut span = ptrs;
+        for (root, depth) in [
+            (INDIRECT_BLOCK_INDEX, 1),
+            (DOUBLE_INDIRECT_BLOCK_INDEX, 2),
+            (TRIPLE_INDIRECT_BLOCK_INDEX, 3),
+        ] {
+            if first_logical < base + span {
+                let start = first_logical.saturating_sub(base);
+                self.truncate_tree(&mut inode.block[root], depth, start, span, &mut freed)?;
+            }
+            base += span;
+            span *= ptrs;
+        }
+
+        let sectors_per_block = (self.block_size / SECTOR_SIZE) as u32;
+        inode.blocks = inode.blocks.saturating_sub(freed * sectors_per_block);
+        Ok(())
+    }
+
+    /// 释放以 `*ptr` 为根、覆盖 `span` 个逻辑块的子树中，第 `start` 个逻辑块及之后的块。
+    ///
+    /// 若整棵子树都被释放，则 `*ptr` 会被置为 0
+    fn truncate_tree(&self, ptr: &mut u32, depth: u32, start: u64, span: u64, freed: &mut u32) -> KResult<()> {
+        if *ptr == 0 {
+            return Ok(());
+        }
+        if depth == 0 {
+            self.free_block(*ptr)?;
+            *ptr = 0;
+            *freed += 1;
+            return Ok(());
+        }
+
+        let child_span = span / self.ptrs_per_block();
+        let mut buf = vec![0; self.block_size];
+        self.read_block(*ptr, &mut buf, true);
+        let first_child = (start / child_span) as usize;
+        let mut dirty = false;
+        for (i, entry) in buf.chunks_exact_mut(4).enumerate().skip(first_child) {
+            let mut child = u32::from_le_bytes((&*entry).try_into().unwrap());
+            if child == 0 {
+                continue;
+            }
+            if child >= self.blocks_count {
+                warn!("invalid ext2 block pointer {child} in block {}", *ptr);
+                return Err(errno::EIO);
+            }
+            let child_start = if i == first_child { start % child_span } else { 0 };
+            self.truncate_tree(&mut child, depth - 1, child_start, child_span, freed)?;
+            entry.copy_from_slice(&child.to_le_bytes());
+            dirty = true;
+        }
+
+        if start == 0 {
+            self.free_block(*ptr)?;
+            *ptr = 0;
+            *freed += 1;
+        } else if dirty {
+            self.write_block(*ptr, &buf);
+        }
+        Ok(())
+    }
+
+    /// 读出目录中所有使用中的目录项，包括 `.` 和 `..`
+    pub fn read_dir(&self, dir: &DiskInode) -> KResult<Vec<DirEntry>> {
+        let mut ret = Vec::new();
+        let mut block = vec![0; self.block_size];
+        for logical in 0..dir.size.div_ceil(self.block_size as u64) {
+            let Some(block_id) = self.lookup_block(dir, logical)? else {
+                continue;
+            };
+            self.read_block(block_id, &mut block, true);
+            for raw in dir_entry::raw_entries(&block) {
+                let raw = raw?;
+                if raw.ino != 0 {
+                    ret.push(raw.to_dir_entry(&block, self.has_file_type)?);
+                }
+            }
+        }
+        Ok(ret)
+    }
+
+    pub fn lookup(&self, dir: &DiskInode, name: &str) -> KResult<Option<DirEntry>> {
+        let mut block = vec![0; self.block_size];
+        for logical in 0..dir.size.div_ceil(self.block_size as u64) {
+            let Some(block_id) = self.lookup_block(dir, logical)? else {
+                continue;
+            };
+            self.read_block(block_id, &mut block, true);
+            for raw in dir_entry::raw_entries(&block) {
+                let raw = raw?;
+                if raw.ino != 0 && raw.name(&block) == name.as_bytes() {
+                    return raw.to_dir_entry(&block, self.has_file_type).map(Some);
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// 目录中是否只有 `.` 和 `..`
+    pub fn is_dir_empty(&self, dir: &DiskInode) -> KResult<bool> {
+        Ok(self
+            .read_dir(dir)?
+            .iter()
+            .all(|entry| entry.name == "." || entry.name == ".."))
+    }
+
+    /// 向目录中添加一个目录项，不检查是否重名。
+    ///
+    /// 只修改 `dir`，需要调用者将其写回磁盘
+    pub fn add_dir_entry(&self, dir: &mut DiskInode, name: &str, ino: u32, file_type: FileType) -> KResult<()> {
+        if name.is_empty() {
+            return Err(errno::ENOENT);
+        }
+        if name.len() > NAME_MAX {
+            return Err(errno::ENAMETOOLONG);
+        }
+        let need = dir_entry::rec_len_for(name.len());
+        let type_byte = if self.has_file_type {
+            file_type.dir_entry_type()
+        } else {
+            0
+        };
+        // 修改目录后不再维护哈希树索引
+        dir.flags &= !INDEX_FL;
+
+        let mut block = vec![0; self.block_size];
+        let block_count = dir.size.div_ceil(self.block_size as u64);
+        for logical in 0..block_count {
+            let Some(block_id) = self.lookup_block(dir, logical)? else {
+                continue;
+            };
+            self.read_block(block_id, &mut block, true);
+            let mut slot = None;
+            for raw in dir_entry::raw_entries(&block) {
+                let raw = raw?;
+                if raw.rec_len - raw.actual_len() >= need {
+                    slot = Some((raw.offset, raw.actual_len(), raw.rec_len));
+                    break;
+                }
+            }
+            if let Some((offset, actual_len, rec_len)) = slot {
+                if actual_len != 0 {
+                    // 将已有目录项末尾的空闲空间拆分出来
+                    write_u16(&mut block, offset + 4, actual_len as u16);
+                }
+                dir_entry::write_entry(
+                    &mut block,
+                    offset + actual_len,
+                    ino,
+                    rec_len - actual_len,
+                    name.as_bytes(),
+                    type_byte,
+                );
+                self.write_block(block_id, &block);
+                return Ok(());
+            }
+        }
+
+        // 没有足够的空闲空间，在目录末尾追加一个块
+        let block_id = self.get_or_alloc_block(dir, block_count)?;
+        block.fill(0);
+        dir_entry::write_entry(&mut block, 0, ino, self.block_size, name.as_bytes(), type_byte);
+        self.write_block(block_id, &block);
+        dir.size += self.block_size as u64;
+        Ok(())
+    }
+
+    /// 从目录中移除名为 `name` 的目录项，返回被移除的目录项。
+    ///
+    /// 只修改 `dir`，需要调用者将其写回磁盘
+    pub fn remove_dir_entry(&self, dir: &mut DiskInode, name: &str) -> KResult<DirEntry> {
+        dir.flags &= !INDEX_FL;
+        let mut block = vec![0; self.block_size];
+        for logical in 0..dir.size.div_ceil(self.block_size as u64) {
+            let Some(block_id) = self.lookup_block(dir, logical)? else {
+                continue;
+            };
+            self.read_block(block_id, &mut block, true);
+            let mut prev: Option<(usize, usize)> = None;
+            let mut found = None;
+            for raw in dir_entry::raw_entries(&block) {
+                let raw = raw?;
+                if raw.ino != 0 && raw.name(&block) == name.as_bytes() {
+                    found = Some((raw.to_dir_entry(&block, self.has_file_type)?, raw.offset, raw.rec_len));
+                    break;
+                }
+                prev = Some((raw.offset, raw.rec_len));
+            }
+            let Some((entry, offset, rec_len)) = found else {
+                continue;
+            };
+            match prev {
+                // 并入前一个目录项的空闲空间
+                Some((prev_offset, prev_rec_len)) => {
+                    write_u16(&mut block, prev_offset + 4, (prev_rec_len + rec_len) as u16);
+                }
+                // 块中的第一个目录项，只能标记为未使用
+                None => write_u32(&mut block, offset, 0),
+            }
+            self.write_block(block_id, &block);
+            return Ok(entry);
+        }
+        Err(errno::ENOENT)
+    }
+
+    /// 在目录 `parent_ino` 中创建名为 `name` 的文件，`mode` 需包含文件类型。返回新文件的 inode 号和 inode。
+    ///
+    /// `parent` 需要与磁盘上的 `parent_ino` 一致，修改后会被写回磁盘
+    pub fn create(
+        &self,
+        parent_ino: u32,
+        parent: &mut DiskInode,
+        name: &str,
+        mode: u16,
+        time: u32,
+    ) -> KResult<(u32, DiskInode)> {
+        let file_type = FileType::from_mode(mode).ok_or(errno::EINVAL)?;
+        if self.lookup(parent, name)?.is_some() {
+            return Err(errno::EEXIST);
+        }
+        let is_dir = file_type == FileType::Dir;
+        let ino = self.alloc_inode(is_dir)?;
+        let mut inode = DiskInode::empty(mode, time);
+        inode.links_count = 1;
+
+        if let Err(e) = self.link_new_inode(parent_ino, parent, name, ino, &mut inode) {
+            self.release_inode(ino, &mut inode, time)?;
+            return Err(e);
+        }
+
+        if is_dir {
+            // 新目录的 `..`
+            parent.links_count += 1;
+        }
+        parent.mtime = time;
+        parent.ctime = time;
+        self.write_inode(parent_ino, parent)?;
+        Ok((ino, inode))
+    }
+
+    fn link_new_inode(
+        &self,
+        parent_ino: u32,
+        parent: &mut DiskInode,
+        name: &str,
+        ino: u32,
+        inode: &mut DiskInode,
+    ) -> KResult<()> {
+        let file_type = inode.file_type().ok_or(errno::EINVAL)?;
+        if file_type == FileType::Dir {
+            // `.` 和父目录中的目录项各算一个链接
+            inode.links_count = 2;
+            self.init_dir_block(inode, ino, parent_ino)?;
+        }
+        self.write_inode(ino, inode)?;
+        self.add_dir_entry(parent, name, ino, file_type)
+    }
+
+    /// 写入新目录的第一个块，其中包含 `.` 和 `..`
+    fn init_dir_block(&self, dir: &mut DiskInode, ino: u32, parent_ino: u32) -> KResult<()> {
+        let block_id = self.get_or_alloc_block(dir, 0)?;
+        let type_byte = if self.has_file_type {
+            FileType::Dir.dir_entry_type()
+        } else {
+            0
+        };
+        let mut block = vec![0; self.block_size];
+        let dot_len = dir_entry::rec_len_for(1);
+        dir_entry::write_entry(&mut block, 0, ino, dot_len, b".", type_byte);
+        dir_entry::write_entry(
+            &mut block,
+            dot_len,
+            parent_ino,
+            self.block_size - dot_len,
+            b"..",
+            type_byte,
+        );
+        self.write_block(block_id, &block);
+        dir.size = self.block_size as u64;
+        Ok(())
+    }
+
+    /// 在目录 `parent_ino` 中创建名为 `name`、指向非目录文件 `ino` 的目录项（即硬链接），并增加其链接数。
+    ///
+    /// 返回修改后的 inode（已写回磁盘）
+    pub fn link(&self, parent_ino: u32, parent: &mut DiskInode, name: &str, ino: u32, time: u32) -> KResult<DiskInode> {
+        let mut inode = self.read_inode(ino)?;
+        let file_type = inode.file_type().ok_or(errno::EINVAL)?;
+        if file_type == FileType::Dir {
+            return Err(errno::EPERM);
+        }
+        if inode.links_count == 0 {
+            // 已经被删除，只是还有引用的文件
+            return Err(errno::ENOENT);
+        }
+        if inode.links_count >= LINK_MAX {
+            return Err(errno::EMLINK);
+        }
+        if self.lookup(parent, name)?.is_some() {
+            return Err(errno::EEXIST);
+        }
+        self.add_dir_entry(parent, name, ino, file_type)?;
+        parent.mtime = time;
+        parent.ctime = time;
+        self.write_inode(parent_ino, parent)?;
+
+        inode.links_count += 1;
+        inode.ctime = time;
+        self.write_inode(ino, &inode)?;
+        Ok(inode)
+    }
+
+    /// 移除目录 `parent_ino` 中名为 `name` 的非目录文件的目录项，并减少其链接数。
+    ///
+    /// 返回该文件的 inode 号和修改后的 inode（已写回磁盘）。链接数减为 0 时，
+    /// 调用者应在没有其他引用后调用 [`Self::release_inode()`] 释放其存储空间
+    pub fn unlink(&self, parent_ino: u32, parent: &mut DiskInode, name: &str, time: u32) -> KResult<(u32, DiskInode)> {
+        let entry = self.lookup(parent, name)?.ok_or(errno::ENOENT)?;
+        let mut inode = self.read_inode(entry.ino)?;
+        if inode.is_dir() {
+            return Err(errno::EISDIR);
+        }
+        self.remove_dir_entry(parent, name)?;
+        parent.mtime = time;
+        parent.ctime = time;
+        self.write_inode(parent_ino, parent)?;
+
+        inode.links_count = inode.links_count.saturating_sub(1);
+        inode.ctime = time;
+        self.write_inode(entry.ino, &inode)?;
+        Ok((entry.ino, inode))
+    }
+
+    /// 移除目录 `parent_ino` 中名为 `name` 的空目录，并释放其存储空间
+    pub fn rmdir(&self, parent_ino: u32, parent: &mut DiskInode, name: &str, time: u32) -> KResult<()> {
+        if name == "." || name == ".." {
+            return Err(errno::EINVAL);
+        }
+        let entry = self.lookup(parent, name)?.ok_or(errno::ENOENT)?;
+        let mut inode = self.read_inode(entry.ino)?;
+        if !inode.is_dir() {
+            return Err(errno::ENOTDIR);
+        }
+        if !self.is_dir_empty(&inode)? {
+            return Err(errno::ENOTEMPTY);
+        }
+        self.remove_dir_entry(parent, name)?;
+        parent.links_count = parent.links_count.saturating_sub(1);
+        parent.mtime = time;
+        parent.ctime = time;
+        self.write_inode(parent_ino, parent)?;
+        self.release_inode(entry.ino, &mut inode, time)
+    }
+
+    /// 将目录 `old_parent_ino` 中名为 `old_name` 的文件移动到目录 `new_parent` 中，并命名为 `new_name`。
+    ///
+    /// `new_parent` 为 `None` 时表示在同一目录中重命名。`new_name` 已存在时会被替换，此时二者需同为目录或同为
+    /// 非目录，被替换的目录需为空。移动目录时会修改其 `..` 以及新旧父目录的链接数，被移动的 inode 本身不变。
+    ///
+    /// 返回被替换的文件的 inode 号和修改后的 inode（已写回磁盘）。被替换的目录会直接释放；非目录文件链接数
+    /// 减为 0 时，调用者应在没有其他引用后调用 [`Self::release_inode()`]
+    pub fn rename(
+        &self,
+        old_parent_ino: u32,
+        old_parent: &mut DiskInode,
+        old_name: &str,
+        mut new_parent: Option<(u32, &mut DiskInode)>,
+        new_name: &str,
+        time: u32,
+    ) -> KResult<Option<(u32, DiskInode)>> {
+        if [old_name, new_name].iter().any(|name| *name == "." || *name == "..") {
+            return Err(errno::EINVAL);
+        }
+        let entry = self.lookup(old_parent, old_name)?.ok_or(errno::ENOENT)?;
+        let moved = self.read_inode(entry.ino)?;
+        let file_type = moved.file_type().ok_or(errno::EINVAL)?;
+        let is_dir = file_type == FileType::Dir;
+
+        let replaced = {
+            let new_dir = match &mut new_parent {
+                Some((_, dir)) => &mut **dir,
+                None => &mut *old_parent,
+            };
+            let replaced = match self.lookup(new_dir, new_name)? {
+                // 新旧名字是同一个文件的硬链接时什么也不做
+                Some(target) if target.ino == entry.ino => return Ok(None),
+                Some(target) => Some(self.remove_target(new_dir, new_name, target.ino, is_dir, time)?),
+                None => None,
+            };
+            self.add_dir_entry(new_dir, new_name, entry.ino, file_type)?;
+            replaced
+        };
+        self.remove_dir_entry(old_parent, old_name)?;
+
+        if let Some((new_parent_ino, new_parent)) = &mut new_parent {
+            if is_dir {
+                self.set_parent_entry(&moved, *new_parent_ino)?;
+                old_parent.links_count = old_parent.links_count.saturating_sub(1);
+                new_parent.links_count += 1;
+            }
+            new_parent.mtime = time;
+            new_parent.ctime = time;
+            self.write_inode(*new_parent_ino, new_parent)?;
+        }
+        old_parent.mtime = time;
+        old_parent.ctime = time;
+        self.write_inode(old_parent_ino, old_parent)?;
+        Ok(replaced)
+    }
+
+    /// 移除重命名的目标 `name`，`ino` 是其 inode 号，`is_dir` 表示被移动的文件是否为目录
+    fn remove_target(
+        &self,
+        parent: &mut DiskInode,
+        name: &str,
+        ino: u32,
+        is_dir: bool,
+        time: u32,
+    ) -> KResult<(u32, DiskInode)> {
+        let mut inode = self.read_inode(ino)?;
+        match (is_dir, inode.is_dir()) {
+            (true, false) => return Err(errno::ENOTDIR),
+            (false, true) => return Err(errno::EISDIR),
+            (true, true) if !self.is_dir_empty(&inode)? => return Err(errno::ENOTEMPTY),
+            _ => {}
+        }
+        self.remove_dir_entry(parent, name)?;
+        if inode.is_dir() {
+            // 被替换的目录的 `..`
+            parent.links_count = parent.links_count.saturating_sub(1);
+            self.release_inode(ino, &mut inode, time)?;
+        } else {
+            inode.links_count = inode.links_count.saturating_sub(1);
+            inode.ctime = time;
+            self.write_inode(ino, &inode)?;
+        }
+        Ok((ino, inode))
+    }
+
+    /// 将目录 `dir` 的 `..` 改为指向 `parent_ino`
+    fn set_parent_entry(&self, dir: &DiskInode, parent_ino: u32) -> KResult<()> {
+        let block_id = self.lookup_block(dir, 0)?.ok_or(errno::EIO)?;
+        let mut block = vec![0; self.block_size];
+        self.read_block(block_id, &mut block, true);
+        let mut dot_dot = None;
+        for raw in dir_entry::raw_entries(&block) {
+            let raw = raw?;
+            if raw.ino != 0 && raw.name(&block) == b".." {
+                dot_dot = Some(raw.offset);
+                break;
+            }
+        }
+        let Some(offset) = dot_dot else {
+            warn!("ext2 dir without `..` entry");
+            return Err(errno::EIO);
+        };
+        write_u32(&mut block, offset, parent_ino);
+        self.write_block(block_id, &block);
+        Ok(())
+    }
+
+    /// 在目录 `parent_ino` 中创建名为 `name`、指向 `target` 的符号链接。返回新文件的 inode 号和 inode。
+    ///
+    /// 较短的目标路径直接存放在 `i_block` 中（即快速符号链接），否则存放在数据块中
+    pub fn symlink(
+        &self,
+        parent_ino: u32,
+        parent: &mut DiskInode,
+        name: &str,
+        target: &str,
+        time: u32,
+    ) -> KResult<(u32, DiskInode)> {
+        if target.is_empty() {
+            return Err(errno::ENOENT);
+        }
+        if target.len() >= self.block_size {
+            return Err(errno::ENAMETOOLONG);
+        }
+        let mode = FileType::SymbolLink.mode_bits() | 0o777;
+        let (ino, mut inode) = self.create(parent_ino, parent, name, mode, time)?;
+        if let Err(e) = self
+            .write_link_target(&mut inode, target)
+            .and_then(|()| self.write_inode(ino, &inode))
+        {
+            let (ino, mut inode) = self.unlink(parent_ino, parent, name, time)?;
+            self.release_inode(ino, &mut inode, time)?;
+            return Err(e);
+        }
+        Ok((ino, inode))
+    }
+
+    fn write_link_target(&self, inode: &mut DiskInode, target: &str) -> KResult<()> {
+        if target.len() < FAST_SYMLINK_MAX_LEN {
+            let mut buf = [0; FAST_SYMLINK_MAX_LEN];
+            buf[..target.len()].copy_from_slice(target.as_bytes());
+            for (pointer, bytes) in inode.block.iter_mut().zip(buf.chunks_exact(4)) {
+                *pointer = u32::from_le_bytes(bytes.try_into().unwrap());
+            }
+            inode.size = target.len() as u64;
+        } else if self.write_data(inode, 0, target.as_bytes())? < target.len() {
+            return Err(errno::ENOSPC);
+        }
+        Ok(())
+    }
+
+    /// 读取符号链接的目标路径
+    pub fn read_link(&self, inode: &DiskInode) -> KResult<Vec<u8>> {
+        if inode.file_type() != Some(FileType::SymbolLink) {
+            return Err(errno::EINVAL);
+        }
+        if is_fast_symlink(inode) {
+            let len = usize::min(inode.size as usize, FAST_SYMLINK_MAX_LEN);
+            let target = inode.block.iter().flat_map(|pointer| pointer.to_le_bytes());
+            return Ok(target.take(len).collect());
+        }
+        if inode.size >= self.block_size as u64 {
+            warn!("ext2 symlink too long: {}", inode.size);
+            return Err(errno::EIO);
+        }
+        let mut target = vec![0; inode.size as usize];
+        let len = self.read_data(inode, 0, &mut target)?;
+        target.truncate(len);
+        Ok(target)
+    }
+
+    /// 释放 inode 及其占用的所有块
+    pub fn release_inode(&self, ino: u32, inode: &mut DiskInode, time: u32) -> KResult<()> {
+        if !is_fast_symlink(inode) {
+            self.free_blocks_from(inode, 0)?;
+        }
+        let is_dir = inode.is_dir();
+        inode.links_count = 0;
+        inode.size = 0;
+        inode.dtime = time;
+        self.write_inode(ino, inode)?;
+        self.free_inode(ino, is_dir)
+    }
+}
+
+/// 一个 inode 最多的链接数，同 linux 的 `EXT2_LINK_MAX`
+const LINK_MAX: u16 = 32000;
+
+/// `i_block` 的大小。目标路径（不含结尾的 `\0`）比它短时才会存放为快速符号链接
+const FAST_SYMLINK_MAX_LEN: usize = BLOCK_POINTER_COUNT * 4;
+
+/// 快速符号链接的目标路径直接存放在 `i_block` 中，不占用数据块
+fn is_fast_symlink(inode: &DiskInode) -> bool {
+    inode.file_type() == Some(FileType::SymbolLink) && inode.blocks == 0
+}
+
+/// 读取设备上从 `offset` 字节开始的数据，可以不按扇区对齐
+fn read_device_bytes(device: &dyn BlockDevice, offset: u64, buf: &mut [u8], cached: bool) {
+    let mut sector = [0; SECTOR_SIZE];
+    let mut done = 0;
+    while done < buf.len() {
+        let pos = offset + done as u64;
+        let sector_id = (pos / SECTOR_SIZE as u64) as usize;
+        let sector_offset = (pos % SECTOR_SIZE as u64) as usize;
+        let n = usize::min(buf.len() - done, SECTOR_SIZE - sector_offset);
+        if cached {
+            device.read_block_cached(sector_id, &mut sector);
+        } else {
+            device.read_block(sector_id, &mut sector);
+        }
+        buf[done..done + n].copy_from_slice(&sector[sector_offset..sector_offset + n]);
+        done += n;
+    }
+}
+
+/// 向设备上从 `offset` 字节开始的位置写入数据，不对齐的部分会先读出扇区再写回
+fn write_device_bytes(device: &dyn BlockDevice, offset: u64, buf: &[u8]) {
+    let mut sector = [0; SECTOR_SIZE];
+    let mut done = 0;
+    while done < buf.len() {
+        let pos = offset + done as u64;
+        let sector_id = (pos / SECTOR_SIZE as u64) as usize;
+        let sector_offset = (pos % SECTOR_SIZE as u64) as usize;
+        let n = usize::min(buf.len() - done, SECTOR_SIZE - sector_offset);
+        if n != SECTOR_SIZE {
+            device.read_block_cached(sector_id, &mut sector);
+        }
+        sector[sector_offset..sector_offset + n].copy_from_slice(&buf[done..done + n]);
+        device.write_block(sector_id, &sector);
+        done += n;
+    }
+}
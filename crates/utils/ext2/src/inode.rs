@@ -0,0 +1,185 @@
+use crate::{read_u16, read_u32, write_u16, write_u32};
+
+/// 只读写 inode 的前 128 字节（即 revision 0 的 inode 大小），其后的扩展部分保持不变
+pub const DISK_INODE_SIZE: usize = 128;
+
+pub const DIRECT_BLOCK_COUNT: usize = 12;
+pub const INDIRECT_BLOCK_INDEX: usize = 12;
+pub const DOUBLE_INDIRECT_BLOCK_INDEX: usize = 13;
+pub const TRIPLE_INDIRECT_BLOCK_INDEX: usize = 14;
+pub const BLOCK_POINTER_COUNT: usize = 15;
+
+/// 目录使用了哈希树索引。修改目录时不维护索引，因此需要清除该标志，退化为线性目录
+pub const INDEX_FL: u32 = 0x1000;
+
+const S_IFMT: u16 = 0o170000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileType {
+    Regular,
+    Dir,
+    CharDevice,
+    BlockDevice,
+    Fifo,
+    Socket,
+    SymbolLink,
+}
+
+impl FileType {
+    pub fn from_mode(mode: u16) -> Option<Self> {
+        match mode & S_IFMT {
+            0o100000 => Some(Self::Regular),
+            0o040000 => Some(Self::Dir),
+            0o020000 => Some(Self::CharDevice),
+            0o060000 => Some(Self::BlockDevice),
+            0o010000 => Some(Self::Fifo),
+            0o140000 => Some(Self::Socket),
+            0o120000 => Some(Self::SymbolLink),
+            _ => None,
+        }
+    }
+
+    /// `i_mode` 中表示文件类型的高 4 位
+    pub fn mode_bits(self) -> u16 {
+        match self {
+            Self::Regular => 0o100000,
+            Self::Dir => 0o040000,
+            Self::CharDevice => 0o020000,
+            Self::BlockDevice => 0o060000,
+            Self::Fifo => 0o010000,
+            Self::Socket => 0o140000,
+            Self::SymbolLink => 0o120000,
+        }
+    }
+
+    /// 目录项中的 `file_type` 字段
+    pub fn dir_entry_type(self) -> u8 {
+        match self {
+            Self::Regular => 1,
+            Self::Dir => 2,
+            Self::CharDevice => 3,
+            Self::BlockDevice => 4,
+            Self::Fifo => 5,
+            Self::Socket => 6,
+            Self::SymbolLink => 7,
+        }
+    }
+
+    pub fn from_dir_entry_type(ty: u8) -> Option<Self> {
+        match ty {
+            1 => Some(Self::Regular),
+            2 => Some(Self::Dir),
+            3 => Some(Self::CharDevice),
+            4 => Some(Self::BlockDevice),
+            5 => Some(Self::Fifo),
+            6 => Some(Self::Socket),
+            7 => Some(Self::SymbolLink),
+            _ => None,
+        }
+    }
+}
+
+/// 磁盘上的 inode
+///
+/// 参考 <https://www.nongnu.org/ext2-doc/ext2.html#inode-table>
+#[derive(Clone, Debug)]
+pub struct DiskInode {
+    /// 文件类型与权限位
+    pub mode: u16,
+    pub uid: u32,
+    /// 文件大小。只有常规文件会使用高 32 位
+    pub size: u64,
+    pub atime: u32,
+    pub ctime: u32,
+    pub mtime: u32,
+    /// 删除时间，非 0 表示该 inode 已被释放
+    pub dtime: u32,
+    pub gid: u32,
+    pub links_count: u16,
+    /// 占用的 512 字节扇区数（而非块数），包括间接块
+    pub blocks: u32,
+    pub flags: u32,
+    /// 12 个直接块、1 个一级间接块、1 个二级间接块、1 个三级间接块
+    pub block: [u32; BLOCK_POINTER_COUNT],
+    /// 原始数据，用于保留未解析的字段
+    raw: [u8; DISK_INODE_SIZE],
+}
+
+impl DiskInode {
+    pub fn new(src: &[u8; DISK_INODE_SIZE]) -> Self {
+        let mode = read_u16(src, 0);
+        let mut size = read_u32(src, 4) as u64;
+        if FileType::from_mode(mode) == Some(FileType::Regular) {
+            size |= (read_u32(src, 108) as u64) << 32;
+        }
+        let mut block = [0; BLOCK_POINTER_COUNT];
+        for (i, block) in block.iter_mut().enumerate() {
+            *block = read_u32(src, 40 + i * 4);
+        }
+        Self {
+            mode,
+            uid: read_u16(src, 2) as u32 | (read_u16(src, 120) as u32) << 16,
+            size,
+            atime: read_u32(src, 8),
+            ctime: read_u32(src, 12),
+            mtime: read_u32(src, 16),
+            dtime: read_u32(src, 20),
+            gid: read_u16(src, 24) as u32 | (read_u16(src, 122) as u32) << 16,
+            links_count: read_u16(src, 26),
+            blocks: read_u32(src, 28),
+            flags: read_u32(src, 32),
+            block,
+            raw: *src,
+        }
+    }
+
+    /// 创建一个全新的 inode，`mode` 需包含文件类型
+    pub fn empty(mode: u16, time: u32) -> Self {
+        Self {
+            mode,
+            uid: 0,
+            size: 0,
+            atime: time,
+            ctime: time,
+            mtime: time,
+            dtime: 0,
+            gid: 0,
+            links_count: 0,
+            blocks: 0,
+            flags: 0,
+            block: [0; BLOCK_POINTER_COUNT],
+            raw: [0; DISK_INODE_SIZE],
+        }
+    }
+
+    pub fn write_to(&self, dst: &mut [u8; DISK_INODE_SIZE]) {
+        *dst = self.raw;
+        write_u16(dst, 0, self.mode);
+        write_u16(dst, 2, self.uid as u16);
+        write_u32(dst, 4, self.size as u32);
+        write_u32(dst, 8, self.atime);
+        write_u32(dst, 12, self.ctime);
+        write_u32(dst, 16, self.mtime);
+        write_u32(dst, 20, self.dtime);
+        write_u16(dst, 24, self.gid as u16);
+        write_u16(dst, 26, self.links_count);
+        write_u32(dst, 28, self.blocks);
+        write_u32(dst, 32, self.flags);
+        for (i, &block) in self.block.iter().enumerate() {
+            write_u32(dst, 40 + i * 4, block);
+        }
+        if self.file_type() == Some(FileType::Regular) {
+            write_u32(dst, 108, (self.size >> 32) as u32);
+        }
+        write_u16(dst, 120, (self.uid >> 16) as u16);
+        write_u16(dst, 122, (self.gid >> 16) as u16);
+    }
+
+    pub fn file_type(&self) -> Option<FileType> {
+        FileType::from_mode(self.mode)
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.file_type() == Some(FileType::Dir)
+    }
+}
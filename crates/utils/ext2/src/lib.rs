@@ -0,0 +1,53 @@
+//! ext2 文件系统的实现。
+//!
+//! 可以参考：
+//! - <https://www.nongnu.org/ext2-doc/ext2.html>
+//! - <https://wiki.osdev.org/Ext2>
+//! - <https://elixir.bootlin.com/linux/v6.6/source/fs/ext2/ext2.h>
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[macro_use]
+extern crate kernel_tracer;
+
+#[cfg(all(feature = "std", feature = "kernel"))]
+compile_error!("Feature `std` 与 `kernel` 互斥，只能开启其中之一");
+
+mod block_group;
+mod dir_entry;
+mod fs;
+mod inode;
+mod superblock;
+
+pub use block_group::{BlockGroupDesc, GROUP_DESC_SIZE};
+pub use dir_entry::{DirEntry, DIR_ENTRY_HEADER_SIZE};
+pub use fs::Ext2FileSystem;
+pub use inode::{DiskInode, FileType, DISK_INODE_SIZE};
+pub use superblock::{IncompatFeatures, RoCompatFeatures, SuperBlock, EXT2_MAGIC};
+
+pub const SECTOR_SIZE: usize = hal::block_device::BLOCK_SIZE;
+/// 超级块总是位于设备的第 1024 字节处，与块大小无关
+pub const SUPERBLOCK_OFFSET: usize = 1024;
+pub const SUPERBLOCK_SIZE: usize = 1024;
+/// 根目录的 inode 号
+pub const ROOT_INO: u32 = 2;
+/// 文件名最大长度
+pub const NAME_MAX: usize = 255;
+
+fn read_u16(src: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(src[offset..offset + 2].try_into().unwrap())
+}
+
+fn read_u32(src: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(src[offset..offset + 4].try_into().unwrap())
+}
+
+fn write_u16(dst: &mut [u8], offset: usize, value: u16) {
+    dst[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn write_u32(dst: &mut [u8], offset: usize, value: u32) {
+    dst[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
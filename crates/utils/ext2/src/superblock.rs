@@ -0,0 +1,144 @@
+use bitflags::bitflags;
+use defines::error::{errno, KResult};
+
+use crate::{read_u16, read_u32, write_u32, SUPERBLOCK_SIZE};
+
+pub const EXT2_MAGIC: u16 = 0xEF53;
+
+/// revision 0 的文件系统中，这些字段是固定的
+const GOOD_OLD_FIRST_INO: u32 = 11;
+const GOOD_OLD_INODE_SIZE: u16 = 128;
+
+bitflags! {
+    /// 不支持其中某个特性的实现不应挂载该文件系统
+    #[derive(Clone, Copy, Debug)]
+    pub struct IncompatFeatures: u32 {
+        const COMPRESSION = 0x0001;
+        /// 目录项中记录了文件类型
+        const FILETYPE = 0x0002;
+        const RECOVER = 0x0004;
+        const JOURNAL_DEV = 0x0008;
+        const META_BG = 0x0010;
+        const _ = !0;
+    }
+}
+
+bitflags! {
+    /// 不支持其中某个特性的实现只能以只读方式挂载该文件系统
+    #[derive(Clone, Copy, Debug)]
+    pub struct RoCompatFeatures: u32 {
+        /// 只有部分块组中有超级块和块组描述符表的备份
+        const SPARSE_SUPER = 0x0001;
+        /// 文件大小可以超过 4GiB，高 32 位存放在 `i_dir_acl` 中
+        const LARGE_FILE = 0x0002;
+        const BTREE_DIR = 0x0004;
+        const _ = !0;
+    }
+}
+
+/// 参考 <https://www.nongnu.org/ext2-doc/ext2.html#superblock>
+///
+/// 只解析了需要用到的字段，写回时只会修改空闲计数
+#[derive(Clone, Debug)]
+pub struct SuperBlock {
+    pub inodes_count: u32,
+    pub blocks_count: u32,
+    pub free_blocks_count: u32,
+    pub free_inodes_count: u32,
+    /// 第一个数据块，也就是超级块所在的块。块大小为 1024 时是 1，否则是 0
+    pub first_data_block: u32,
+    /// 块大小为 `1024 << log_block_size`
+    pub log_block_size: u32,
+    pub blocks_per_group: u32,
+    pub inodes_per_group: u32,
+    pub magic: u16,
+    pub rev_level: u32,
+    /// 第一个非保留的 inode 号
+    pub first_ino: u32,
+    pub inode_size: u16,
+    pub feature_incompat: IncompatFeatures,
+    pub feature_ro_compat: RoCompatFeatures,
+}
+
+impl SuperBlock {
+    pub fn new(src: &[u8; SUPERBLOCK_SIZE]) -> KResult<Self> {
+        let magic = read_u16(src, 56);
+        if magic != EXT2_MAGIC {
+            warn!("invalid ext2 magic: {magic:#x}");
+            return Err(errno::EINVAL);
+        }
+        let rev_level = read_u32(src, 76);
+        let (first_ino, inode_size) = if rev_level == 0 {
+            (GOOD_OLD_FIRST_INO, GOOD_OLD_INODE_SIZE)
+        } else {
+            (read_u32(src, 84), read_u16(src, 88))
+        };
+        let ret = Self {
+            inodes_count: read_u32(src, 0),
+            blocks_count: read_u32(src, 4),
+            free_blocks_count: read_u32(src, 12),
+            free_inodes_count: read_u32(src, 16),
+            first_data_block: read_u32(src, 20),
+            log_block_size: read_u32(src, 24),
+            blocks_per_group: read_u32(src, 32),
+            inodes_per_group: read_u32(src, 40),
+            magic,
+            rev_level,
+            first_ino,
+            inode_size,
+            feature_incompat: IncompatFeatures::from_bits_retain(read_u32(src, 96)),
+            feature_ro_compat: RoCompatFeatures::from_bits_retain(read_u32(src, 100)),
+        };
+        ret.validate()?;
+        debug!("{ret:?}");
+        Ok(ret)
+    }
+
+    fn validate(&self) -> KResult<()> {
+        // 只支持 1KiB 到 4KiB 的块大小，更大的块无法放进一页
+        if self.log_block_size > 2
+            || self.blocks_count == 0
+            || self.inodes_count == 0
+            || self.blocks_per_group == 0
+            || self.inodes_per_group == 0
+            || self.blocks_per_group > self.block_size() as u32 * 8
+            || self.inodes_per_group > self.block_size() as u32 * 8
+            || self.first_data_block >= self.blocks_count
+            || self.first_ino <= crate::ROOT_INO
+            || self.inode_size < GOOD_OLD_INODE_SIZE
+            || !self.inode_size.is_power_of_two()
+            || self.inode_size as usize > self.block_size()
+        {
+            warn!("invalid ext2 superblock: {self:?}");
+            return Err(errno::EINVAL);
+        }
+        let unsupported = self.feature_incompat.difference(IncompatFeatures::FILETYPE);
+        if !unsupported.is_empty() {
+            warn!("unsupported ext2 incompat features: {unsupported:?}");
+            return Err(errno::EINVAL);
+        }
+        // TODO: [low] 存在不支持的 ro_compat 特性时可以只读挂载，而不是直接拒绝
+        let unsupported = self
+            .feature_ro_compat
+            .difference(RoCompatFeatures::SPARSE_SUPER | RoCompatFeatures::LARGE_FILE);
+        if !unsupported.is_empty() {
+            warn!("unsupported ext2 ro_compat features: {unsupported:?}");
+            return Err(errno::EINVAL);
+        }
+        Ok(())
+    }
+
+    /// 将空闲计数写回原始的超级块数据中，其余字段保持不变
+    pub fn write_to(&self, dst: &mut [u8; SUPERBLOCK_SIZE]) {
+        write_u32(dst, 12, self.free_blocks_count);
+        write_u32(dst, 16, self.free_inodes_count);
+    }
+
+    pub fn block_size(&self) -> usize {
+        1024 << self.log_block_size
+    }
+
+    pub fn group_count(&self) -> u32 {
+        (self.blocks_count - self.first_data_block).div_ceil(self.blocks_per_group)
+    }
+}
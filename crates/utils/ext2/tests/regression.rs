@@ -0,0 +1,454 @@
+use std::sync::RwLock;
+
+use defines::error::errno;
+use ext2::{DiskInode, Ext2FileSystem, FileType, ROOT_INO, SECTOR_SIZE};
+use hal::block_device::BlockDevice;
+
+const BLOCK_SIZE: usize = 1024;
+const TOTAL_BLOCKS: usize = 256;
+const INODES_COUNT: u32 = 32;
+const FIRST_INO: u32 = 11;
+/// 块 1..=9 分别是超级块、块组描述符表、块位图、inode 位图、inode 表（4 块）、根目录
+const USED_BLOCKS: u32 = 9;
+const ROOT_DIR_BLOCK: u32 = 9;
+
+struct MemBlockDevice {
+    sectors: RwLock<Vec<[u8; SECTOR_SIZE]>>,
+}
+
+impl BlockDevice for MemBlockDevice {
+    fn read_block(&self, block_id: usize, buf: &mut [u8; SECTOR_SIZE]) {
+        let sectors = self.sectors.read().unwrap();
+        buf.copy_from_slice(&sectors[block_id]);
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8; SECTOR_SIZE]) {
+        let mut sectors = self.sectors.write().unwrap();
+        sectors[block_id].copy_from_slice(buf);
+    }
+
+    fn num_blocks(&self) -> usize {
+        self.sectors.read().unwrap().len()
+    }
+}
+
+fn put_u16(image: &mut [u8], offset: usize, value: u16) {
+    image[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(image: &mut [u8], offset: usize, value: u32) {
+    image[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+/// 手工构造一个只有一个块组、块大小为 1024 的 ext2 镜像，其中只有根目录
+fn make_image() -> Vec<u8> {
+    let mut image = vec![0u8; TOTAL_BLOCKS * BLOCK_SIZE];
+
+    let sb = BLOCK_SIZE;
+    put_u32(&mut image, sb, INODES_COUNT);
+    put_u32(&mut image, sb + 4, TOTAL_BLOCKS as u32);
+    put_u32(&mut image, sb + 12, TOTAL_BLOCKS as u32 - 1 - USED_BLOCKS);
+    put_u32(&mut image, sb + 16, INODES_COUNT - (FIRST_INO - 1));
+    put_u32(&mut image, sb + 20, 1);
+    put_u32(&mut image, sb + 24, 0);
+    put_u32(&mut image, sb + 32, 8192);
+    put_u32(&mut image, sb + 36, 8192);
+    put_u32(&mut image, sb + 40, INODES_COUNT);
+    put_u16(&mut image, sb + 56, 0xEF53);
+    put_u16(&mut image, sb + 58, 1);
+    put_u32(&mut image, sb + 76, 1);
+    put_u32(&mut image, sb + 84, FIRST_INO);
+    put_u16(&mut image, sb + 88, 128);
+    put_u32(&mut image, sb + 96, 0x2);
+    put_u32(&mut image, sb + 100, 0x1 | 0x2);
+
+    let gd = 2 * BLOCK_SIZE;
+    put_u32(&mut image, gd, 3);
+    put_u32(&mut image, gd + 4, 4);
+    put_u32(&mut image, gd + 8, 5);
+    put_u16(&mut image, gd + 12, (TOTAL_BLOCKS as u32 - 1 - USED_BLOCKS) as u16);
+    put_u16(&mut image, gd + 14, (INODES_COUNT - (FIRST_INO - 1)) as u16);
+    put_u16(&mut image, gd + 16, 1);
+
+    // 块位图的第 i 位对应块 i + 1
+    for bit in 0..USED_BLOCKS as usize {
+        image[3 * BLOCK_SIZE + bit / 8] |= 1 << (bit % 8);
+    }
+    for bit in 0..(FIRST_INO - 1) as usize {
+        image[4 * BLOCK_SIZE + bit / 8] |= 1 << (bit % 8);
+    }
+
+    let root = 5 * BLOCK_SIZE + (ROOT_INO as usize - 1) * 128;
+    put_u16(&mut image, root, 0o40755);
+    put_u32(&mut image, root + 4, BLOCK_SIZE as u32);
+    put_u16(&mut image, root + 26, 2);
+    put_u32(&mut image, root + 28, (BLOCK_SIZE / SECTOR_SIZE) as u32);
+    put_u32(&mut image, root + 40, ROOT_DIR_BLOCK);
+
+    let dir = ROOT_DIR_BLOCK as usize * BLOCK_SIZE;
+    put_u32(&mut image, dir, ROOT_INO);
+    put_u16(&mut image, dir + 4, 12);
+    image[dir + 6] = 1;
+    image[dir + 7] = 2;
+    image[dir + 8] = b'.';
+    put_u32(&mut image, dir + 12, ROOT_INO);
+    put_u16(&mut image, dir + 16, (BLOCK_SIZE - 12) as u16);
+    image[dir + 18] = 2;
+    image[dir + 19] = 2;
+    image[dir + 20..dir + 22].copy_from_slice(b"..");
+
+    image
+}
+
+fn make_device(image: Vec<u8>) -> &'static MemBlockDevice {
+    let sectors = image
+        .chunks_exact(SECTOR_SIZE)
+        .map(|sector| sector.try_into().unwrap())
+        .collect();
+    Box::leak(Box::new(MemBlockDevice {
+        sectors: RwLock::new(sectors),
+    }))
+}
+
+fn mount(device: &'static MemBlockDevice) -> (Ext2FileSystem, DiskInode) {
+    let fs = Ext2FileSystem::new(device).expect("mount should succeed");
+    let root = fs.read_inode(ROOT_INO).expect("root inode should be readable");
+    (fs, root)
+}
+
+fn pattern(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i % 251) as u8).collect()
+}
+
+// 测试超级块魔数不对时应拒绝挂载
+#[test]
+fn mount_should_reject_bad_magic() {
+    let mut image = make_image();
+    put_u16(&mut image, BLOCK_SIZE + 56, 0x1234);
+    let result = Ext2FileSystem::new(make_device(image));
+    assert_eq!(result.err(), Some(errno::EINVAL));
+}
+
+// 测试存在不支持的 incompat 特性（如 extents）时应拒绝挂载
+#[test]
+fn mount_should_reject_unsupported_incompat_feature() {
+    let mut image = make_image();
+    put_u32(&mut image, BLOCK_SIZE + 96, 0x2 | 0x40);
+    let result = Ext2FileSystem::new(make_device(image));
+    assert_eq!(result.err(), Some(errno::EINVAL));
+}
+
+// 测试根目录中应只有 `.` 和 `..`，且都指向根目录
+#[test]
+fn root_dir_should_contain_dot_entries() {
+    let (fs, root) = mount(make_device(make_image()));
+    let entries = fs.read_dir(&root).unwrap();
+    let names = entries.iter().map(|entry| entry.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, [".", ".."]);
+    assert!(entries.iter().all(|entry| entry.ino == ROOT_INO));
+    assert!(entries.iter().all(|entry| entry.file_type == Some(FileType::Dir)));
+}
+
+// 测试超过 12 个块的文件需要用到一级间接块，且读回的内容与写入一致
+#[test]
+fn write_then_read_should_cross_indirect_blocks() {
+    let (fs, mut root) = mount(make_device(make_image()));
+    let (ino, mut inode) = fs.create(ROOT_INO, &mut root, "big", 0o100644, 0).unwrap();
+    let data = pattern(20 * BLOCK_SIZE + 100);
+    assert_eq!(fs.write_data(&mut inode, 0, &data).unwrap(), data.len());
+    fs.write_inode(ino, &inode).unwrap();
+
+    assert_eq!(inode.size, data.len() as u64);
+    // 21 个数据块加 1 个间接块
+    assert_eq!(inode.blocks, 22 * (BLOCK_SIZE / SECTOR_SIZE) as u32);
+
+    let mut buf = vec![0; data.len() + 50];
+    assert_eq!(fs.read_data(&inode, 0, &mut buf).unwrap(), data.len());
+    assert_eq!(&buf[..data.len()], &data[..]);
+
+    let mut buf = vec![0; 300];
+    fs.read_data(&inode, 13 * BLOCK_SIZE as u64 - 150, &mut buf).unwrap();
+    assert_eq!(&buf[..], &data[13 * BLOCK_SIZE - 150..13 * BLOCK_SIZE + 150]);
+}
+
+// 测试写入的数据和目录项需要落盘，重新挂载后仍然可见
+#[test]
+fn data_should_persist_after_remount() {
+    let device = make_device(make_image());
+    let data = pattern(3000);
+    {
+        let (fs, mut root) = mount(device);
+        let (ino, mut inode) = fs.create(ROOT_INO, &mut root, "hello.txt", 0o100644, 0).unwrap();
+        fs.write_data(&mut inode, 0, &data).unwrap();
+        fs.write_inode(ino, &inode).unwrap();
+    }
+
+    let (fs, root) = mount(device);
+    let entry = fs.lookup(&root, "hello.txt").unwrap().expect("file should exist");
+    assert_eq!(entry.file_type, Some(FileType::Regular));
+    let inode = fs.read_inode(entry.ino).unwrap();
+    let mut buf = vec![0; data.len()];
+    fs.read_data(&inode, 0, &mut buf).unwrap();
+    assert_eq!(buf, data);
+}
+
+// 测试重命名需要落盘：跨目录移动文件和目录后重新挂载，旧名字消失，新名字可见，`..` 和链接数正确
+#[test]
+fn rename_should_persist_after_remount() {
+    let device = make_device(make_image());
+    let data = pattern(1500);
+    let (d1, d2) = {
+        let (fs, mut root) = mount(device);
+        let (ino, mut inode) = fs.create(ROOT_INO, &mut root, "a", 0o100644, 0).unwrap();
+        fs.write_data(&mut inode, 0, &data).unwrap();
+        fs.write_inode(ino, &inode).unwrap();
+        let (d1, mut dir1) = fs.create(ROOT_INO, &mut root, "d1", 0o40755, 0).unwrap();
+        let (d2, mut dir2) = fs.create(ROOT_INO, &mut root, "d2", 0o40755, 0).unwrap();
+
+        let replaced = fs
+            .rename(ROOT_INO, &mut root, "a", Some((d1, &mut dir1)), "b", 0)
+            .unwrap();
+        assert!(replaced.is_none());
+        fs.rename(ROOT_INO, &mut root, "d1", Some((d2, &mut dir2)), "sub", 0)
+            .unwrap();
+        assert_eq!(root.links_count, 3);
+        assert_eq!(dir2.links_count, 3);
+        (d1, d2)
+    };
+
+    let (fs, root) = mount(device);
+    assert_eq!(root.links_count, 3);
+    assert!(fs.lookup(&root, "a").unwrap().is_none());
+    assert!(fs.lookup(&root, "d1").unwrap().is_none());
+    let dir2 = fs.read_inode(d2).unwrap();
+    assert_eq!(dir2.links_count, 3);
+    assert_eq!(fs.lookup(&dir2, "sub").unwrap().unwrap().ino, d1);
+    let dir1 = fs.read_inode(d1).unwrap();
+    assert_eq!(fs.lookup(&dir1, "..").unwrap().unwrap().ino, d2);
+    let entry = fs.lookup(&dir1, "b").unwrap().expect("renamed file should exist");
+    let mut buf = vec![0; data.len()];
+    fs.read_data(&fs.read_inode(entry.ino).unwrap(), 0, &mut buf).unwrap();
+    assert_eq!(buf, data);
+}
+
+// 测试重命名到已存在的名字时替换目标：文件替换文件，目录替换空目录，类型不符或目录非空时失败
+#[test]
+fn rename_should_replace_existing_target() {
+    let (fs, mut root) = mount(make_device(make_image()));
+    let free_before = fs.free_counts();
+    let (a, _) = fs.create(ROOT_INO, &mut root, "a", 0o100644, 0).unwrap();
+    fs.create(ROOT_INO, &mut root, "b", 0o100644, 0).unwrap();
+
+    let (b, mut replaced) = fs.rename(ROOT_INO, &mut root, "a", None, "b", 0).unwrap().unwrap();
+    assert_eq!(replaced.links_count, 0);
+    fs.release_inode(b, &mut replaced, 0).unwrap();
+    assert_eq!(fs.lookup(&root, "b").unwrap().unwrap().ino, a);
+    assert!(fs.lookup(&root, "a").unwrap().is_none());
+
+    let (d1, _) = fs.create(ROOT_INO, &mut root, "d1", 0o40755, 0).unwrap();
+    let (d2, mut dir2) = fs.create(ROOT_INO, &mut root, "d2", 0o40755, 0).unwrap();
+    assert_eq!(
+        fs.rename(ROOT_INO, &mut root, "b", None, "d1", 0).err(),
+        Some(errno::EISDIR)
+    );
+    assert_eq!(
+        fs.rename(ROOT_INO, &mut root, "d1", None, "b", 0).err(),
+        Some(errno::ENOTDIR)
+    );
+    fs.create(d2, &mut dir2, "file", 0o100644, 0).unwrap();
+    assert_eq!(
+        fs.rename(ROOT_INO, &mut root, "d1", None, "d2", 0).err(),
+        Some(errno::ENOTEMPTY)
+    );
+    let (file, mut file_inode) = fs.unlink(d2, &mut dir2, "file", 0).unwrap();
+    fs.release_inode(file, &mut file_inode, 0).unwrap();
+
+    fs.rename(ROOT_INO, &mut root, "d1", None, "d2", 0).unwrap();
+    assert_eq!(fs.lookup(&root, "d2").unwrap().unwrap().ino, d1);
+    assert_eq!(root.links_count, 3);
+
+    fs.rmdir(ROOT_INO, &mut root, "d2", 0).unwrap();
+    let (b, mut inode) = fs.unlink(ROOT_INO, &mut root, "b", 0).unwrap();
+    fs.release_inode(b, &mut inode, 0).unwrap();
+    assert_eq!(fs.free_counts(), free_before);
+}
+
+// 测试 mkdir 和 rmdir 需要维护父目录的链接数，并正确分配和回收 inode
+#[test]
+fn mkdir_and_rmdir_should_maintain_link_counts() {
+    let (fs, mut root) = mount(make_device(make_image()));
+    let free_before = fs.free_counts();
+
+    let (ino, dir) = fs.create(ROOT_INO, &mut root, "sub", 0o40755, 0).unwrap();
+    assert_eq!(dir.links_count, 2);
+    assert_eq!(root.links_count, 3);
+    let entries = fs.read_dir(&dir).unwrap();
+    assert_eq!(entries[0].ino, ino);
+    assert_eq!(entries[1].name, "..");
+    assert_eq!(entries[1].ino, ROOT_INO);
+    assert!(fs.is_dir_empty(&dir).unwrap());
+
+    let mut dir = dir;
+    fs.create(ino, &mut dir, "file", 0o100644, 0).unwrap();
+    assert_eq!(fs.rmdir(ROOT_INO, &mut root, "sub", 0).err(), Some(errno::ENOTEMPTY));
+    let (file_ino, mut file) = fs.unlink(ino, &mut dir, "file", 0).unwrap();
+    fs.release_inode(file_ino, &mut file, 0).unwrap();
+
+    fs.rmdir(ROOT_INO, &mut root, "sub", 0).unwrap();
+    assert_eq!(root.links_count, 2);
+    assert!(fs.lookup(&root, "sub").unwrap().is_none());
+    assert_eq!(fs.free_counts(), free_before);
+}
+
+// 测试删除文件并释放 inode 后，所有数据块和间接块都应被回收
+#[test]
+fn release_should_free_all_blocks() {
+    let (fs, mut root) = mount(make_device(make_image()));
+    let free_before = fs.free_counts();
+
+    let (ino, mut inode) = fs.create(ROOT_INO, &mut root, "big", 0o100644, 0).unwrap();
+    fs.write_data(&mut inode, 0, &pattern(40 * BLOCK_SIZE)).unwrap();
+    fs.write_inode(ino, &inode).unwrap();
+    assert!(fs.free_counts().0 < free_before.0);
+
+    let (ino, mut inode) = fs.unlink(ROOT_INO, &mut root, "big", 0).unwrap();
+    assert_eq!(inode.links_count, 0);
+    fs.release_inode(ino, &mut inode, 0).unwrap();
+    assert_eq!(fs.free_counts(), free_before);
+    assert_eq!(fs.unlink(ROOT_INO, &mut root, "big", 0).err(), Some(errno::ENOENT));
+}
+
+// 测试截断需要释放多余的块，之后再扩展时，原来末尾之后的部分应读出 0
+#[test]
+fn truncate_should_free_blocks_and_zero_tail() {
+    let (fs, mut root) = mount(make_device(make_image()));
+    let (_, mut inode) = fs.create(ROOT_INO, &mut root, "file", 0o100644, 0).unwrap();
+    fs.write_data(&mut inode, 0, &vec![0xAA; 3000]).unwrap();
+    let free_blocks = fs.free_counts().0;
+
+    fs.truncate(&mut inode, 100).unwrap();
+    assert_eq!(fs.free_counts().0, free_blocks + 2);
+    assert_eq!(inode.blocks, (BLOCK_SIZE / SECTOR_SIZE) as u32);
+
+    fs.truncate(&mut inode, 2000).unwrap();
+    let mut buf = vec![0xFF; 2000];
+    assert_eq!(fs.read_data(&inode, 0, &mut buf).unwrap(), 2000);
+    assert!(buf[..100].iter().all(|&b| b == 0xAA));
+    assert!(buf[100..].iter().all(|&b| b == 0), "tail and hole should read as zero");
+}
+
+// 测试删除目录项后空出的空间应被新目录项复用，而不是追加新块
+#[test]
+fn removed_entry_space_should_be_reused() {
+    let (fs, mut root) = mount(make_device(make_image()));
+    for name in ["a", "b", "c"] {
+        fs.create(ROOT_INO, &mut root, name, 0o100644, 0).unwrap();
+    }
+    let (ino, mut inode) = fs.unlink(ROOT_INO, &mut root, "b", 0).unwrap();
+    fs.release_inode(ino, &mut inode, 0).unwrap();
+    fs.create(ROOT_INO, &mut root, "d", 0o100644, 0).unwrap();
+
+    assert_eq!(root.size, BLOCK_SIZE as u64);
+    let names = fs
+        .read_dir(&root)
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.name)
+        .collect::<Vec<_>>();
+    assert_eq!(names, [".", "..", "a", "d", "c"]);
+}
+
+// 测试目录项占满一个块后，目录应扩展到新的块
+#[test]
+fn dir_should_grow_when_block_is_full() {
+    let (fs, mut root) = mount(make_device(make_image()));
+    let name_len = 100;
+    // 每个目录项占 108 字节，一个块放不下 10 个
+    for i in 0..10 {
+        let name = format!("{i:0>name_len$}");
+        fs.create(ROOT_INO, &mut root, &name, 0o100644, 0).unwrap();
+    }
+    assert_eq!(root.size, 2 * BLOCK_SIZE as u64);
+    assert_eq!(fs.read_dir(&root).unwrap().len(), 12);
+}
+
+// 测试重名时应返回 EEXIST，且不应泄漏 inode
+#[test]
+fn create_should_reject_duplicate_name() {
+    let (fs, mut root) = mount(make_device(make_image()));
+    fs.create(ROOT_INO, &mut root, "dup", 0o100644, 0).unwrap();
+    let free_before = fs.free_counts();
+    let result = fs.create(ROOT_INO, &mut root, "dup", 0o100644, 0);
+    assert_eq!(result.err(), Some(errno::EEXIST));
+    assert_eq!(fs.free_counts(), free_before);
+}
+
+// 测试短目标路径存放为快速符号链接，不占用数据块，重新挂载后仍能读出
+#[test]
+fn fast_symlink_should_not_use_data_block() {
+    let device = make_device(make_image());
+    {
+        let (fs, mut root) = mount(device);
+        let free_before = fs.free_counts();
+        let (_, inode) = fs.symlink(ROOT_INO, &mut root, "sh", "/bin/busybox", 0).unwrap();
+        assert_eq!(inode.blocks, 0);
+        assert_eq!(fs.free_counts().0, free_before.0);
+    }
+
+    let (fs, root) = mount(device);
+    let entry = fs.lookup(&root, "sh").unwrap().expect("symlink should exist");
+    assert_eq!(entry.file_type, Some(FileType::SymbolLink));
+    let inode = fs.read_inode(entry.ino).unwrap();
+    assert_eq!(fs.read_link(&inode).unwrap(), b"/bin/busybox");
+}
+
+// 测试长目标路径存放在数据块中，删除后数据块应被回收
+#[test]
+fn slow_symlink_should_store_target_in_block() {
+    let (fs, mut root) = mount(make_device(make_image()));
+    let free_before = fs.free_counts();
+    let target = "a/".repeat(100);
+    let (ino, inode) = fs.symlink(ROOT_INO, &mut root, "long", &target, 0).unwrap();
+    assert_ne!(inode.blocks, 0);
+    assert_eq!(fs.read_link(&fs.read_inode(ino).unwrap()).unwrap(), target.as_bytes());
+
+    let (ino, mut inode) = fs.unlink(ROOT_INO, &mut root, "long", 0).unwrap();
+    fs.release_inode(ino, &mut inode, 0).unwrap();
+    assert_eq!(fs.free_counts(), free_before);
+
+    let too_long = "a".repeat(BLOCK_SIZE);
+    let result = fs.symlink(ROOT_INO, &mut root, "too_long", &too_long, 0);
+    assert_eq!(result.err(), Some(errno::ENAMETOOLONG));
+}
+
+// 测试硬链接共享同一个 inode，删除其中一个链接后数据仍然可读
+#[test]
+fn hard_link_should_share_inode() {
+    let (fs, mut root) = mount(make_device(make_image()));
+    let free_before = fs.free_counts();
+    let data = pattern(2000);
+    let (ino, mut inode) = fs.create(ROOT_INO, &mut root, "a", 0o100644, 0).unwrap();
+    fs.write_data(&mut inode, 0, &data).unwrap();
+    fs.write_inode(ino, &inode).unwrap();
+
+    let inode = fs.link(ROOT_INO, &mut root, "b", ino, 0).unwrap();
+    assert_eq!(inode.links_count, 2);
+    assert_eq!(fs.lookup(&root, "b").unwrap().unwrap().ino, ino);
+    assert_eq!(fs.link(ROOT_INO, &mut root, "b", ino, 0).err(), Some(errno::EEXIST));
+    assert_eq!(
+        fs.link(ROOT_INO, &mut root, "root", ROOT_INO, 0).err(),
+        Some(errno::EPERM)
+    );
+
+    let (_, inode) = fs.unlink(ROOT_INO, &mut root, "a", 0).unwrap();
+    assert_eq!(inode.links_count, 1);
+    let mut buf = vec![0; data.len()];
+    fs.read_data(&fs.read_inode(ino).unwrap(), 0, &mut buf).unwrap();
+    assert_eq!(buf, data);
+
+    let (ino, mut inode) = fs.unlink(ROOT_INO, &mut root, "b", 0).unwrap();
+    assert_eq!(inode.links_count, 0);
+    assert_eq!(fs.link(ROOT_INO, &mut root, "c", ino, 0).err(), Some(errno::ENOENT));
+    fs.release_inode(ino, &mut inode, 0).unwrap();
+    assert_eq!(fs.free_counts(), free_before);
+}
@@ -26,6 +26,15 @@ impl BlockDevice for MemBlockDevice {
         let sectors = self.sectors.read().unwrap();
         buf.copy_from_slice(&sectors[block_id]);
     }
+
+    fn write_block(&self, block_id: usize, buf: &[u8; SECTOR_SIZE]) {
+        let mut sectors = self.sectors.write().unwrap();
+        sectors[block_id].copy_from_slice(buf);
+    }
+
+    fn num_blocks(&self) -> usize {
+        self.sectors.read().unwrap().len()
+    }
 }
 
 fn default_bpb() -> BiosParameterBlock {
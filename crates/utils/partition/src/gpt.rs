@@ -0,0 +1,101 @@
+use alloc::vec::Vec;
+
+use hal::block_device::{BlockDevice, BLOCK_SIZE};
+
+use crate::{in_disk, read_u32, read_u64, PartitionInfo};
+
+/// GPT 头总是位于 1 号扇区
+const HEADER_LBA: usize = 1;
+const SIGNATURE: &[u8; 8] = b"EFI PART";
+/// GPT 头的最小长度，即 UEFI 规范中定义的字段的总长度
+const MIN_HEADER_SIZE: usize = 92;
+/// 分区表项数量的上限，防止损坏的 GPT 头导致读取过多扇区。通常为 128
+const MAX_ENTRIES: usize = 1024;
+
+/// 解析 GPT 分区表，GPT 头或分区表项的校验和不正确时返回 `None`。
+///
+/// 只使用主 GPT，不会尝试使用位于磁盘末尾的备份 GPT
+pub(crate) fn parse(disk: &dyn BlockDevice) -> Option<Vec<PartitionInfo>> {
+    let mut sector = [0; BLOCK_SIZE];
+    disk.read_block(HEADER_LBA, &mut sector);
+    if &sector[..8] != SIGNATURE {
+        warn!("protective mbr found but gpt header is missing");
+        return None;
+    }
+    let header_size = read_u32(&sector, 12) as usize;
+    if !(MIN_HEADER_SIZE..=BLOCK_SIZE).contains(&header_size) {
+        return None;
+    }
+    let header_crc = read_u32(&sector, 16);
+    // 计算校验和时，校验和字段本身视为 0
+    sector[16..20].fill(0);
+    if crc32(!0, &sector[..header_size]) != !header_crc {
+        warn!("gpt header checksum mismatch");
+        return None;
+    }
+
+    let entries_lba = read_u64(&sector, 72) as usize;
+    let num_entries = read_u32(&sector, 80) as usize;
+    let entry_size = read_u32(&sector, 84) as usize;
+    let entries_crc = read_u32(&sector, 88);
+    // 规范要求表项大小为 128 * 2^n，这里还要求它不超过一个扇区，以便按扇区读取
+    if num_entries > MAX_ENTRIES || !entry_size.is_power_of_two() || !(128..=BLOCK_SIZE).contains(&entry_size) {
+        warn!("unsupported gpt with {num_entries} entries of size {entry_size}");
+        return None;
+    }
+
+    let mut partitions = Vec::new();
+    let mut crc = !0;
+    let entries_per_sector = BLOCK_SIZE / entry_size;
+    for sector_index in 0..num_entries.div_ceil(entries_per_sector) {
+        disk.read_block(entries_lba + sector_index, &mut sector);
+        let first_index = sector_index * entries_per_sector;
+        let count = usize::min(entries_per_sector, num_entries - first_index);
+        crc = crc32(crc, &sector[..count * entry_size]);
+        for (i, entry) in sector.chunks_exact(entry_size).take(count).enumerate() {
+            // 分区类型 GUID 为全 0 表示未使用的表项
+            if entry[..16].iter().all(|&b| b == 0) {
+                continue;
+            }
+            let number = first_index + i + 1;
+            let first_lba = read_u64(entry, 32) as usize;
+            // 结束位置是闭区间
+            let last_lba = read_u64(entry, 40) as usize;
+            let Some(num_blocks) = last_lba.checked_sub(first_lba).map(|len| len + 1) else {
+                warn!("gpt partition {number} ends before it starts, ignored");
+                continue;
+            };
+            if !in_disk(disk, first_lba, num_blocks) {
+                warn!("gpt partition {number} exceeds the disk, ignored");
+                continue;
+            }
+            partitions.push(PartitionInfo {
+                number,
+                start_block: first_lba,
+                num_blocks,
+            });
+        }
+    }
+    if crc != !entries_crc {
+        warn!("gpt partition entries checksum mismatch");
+        return None;
+    }
+    Some(partitions)
+}
+
+/// 以 `crc` 为初始状态继续计算 CRC-32（IEEE 802.3）。
+///
+/// 初始状态应为 `!0`，最终结果需要再取反
+fn crc32(mut crc: u32, bytes: &[u8]) -> u32 {
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 0 {
+                crc >> 1
+            } else {
+                (crc >> 1) ^ 0xEDB8_8320
+            };
+        }
+    }
+    crc
+}
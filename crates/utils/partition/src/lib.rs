@@ -0,0 +1,104 @@
+//! 磁盘分区表的解析，支持 MBR（包括扩展分区中的逻辑分区）和 GPT。
+//!
+//! 可以参考：
+//! - <https://wiki.osdev.org/MBR_(x86)>
+//! - <https://wiki.osdev.org/GPT>
+//! - <https://elixir.bootlin.com/linux/v6.6/source/block/partitions/msdos.c>
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[macro_use]
+extern crate kernel_tracer;
+
+#[cfg(all(feature = "std", feature = "kernel"))]
+compile_error!("Feature `std` 与 `kernel` 互斥，只能开启其中之一");
+
+mod gpt;
+mod mbr;
+
+use alloc::vec::Vec;
+
+use hal::block_device::{BlockDevice, BLOCK_SIZE};
+
+/// 分区表中的一个分区，单位均为块（扇区）
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PartitionInfo {
+    /// 分区号，从 1 开始。MBR 的逻辑分区从 5 开始编号，同 linux
+    pub number: usize,
+    pub start_block: usize,
+    pub num_blocks: usize,
+}
+
+/// 解析磁盘上的分区表，没有分区表或分区表无效时返回空
+pub fn scan(disk: &dyn BlockDevice) -> Vec<PartitionInfo> {
+    let mut sector = [0; BLOCK_SIZE];
+    disk.read_block(0, &mut sector);
+    match mbr::parse(disk, &sector) {
+        Some(mbr::Table::Protective) => gpt::parse(disk).unwrap_or_default(),
+        Some(mbr::Table::Partitions(partitions)) => partitions,
+        None => Vec::new(),
+    }
+}
+
+/// 磁盘上的一个分区，将分区内的块号转换为磁盘上的块号
+pub struct Partition {
+    disk: &'static dyn BlockDevice,
+    start_block: usize,
+    num_blocks: usize,
+}
+
+impl Partition {
+    pub fn new(disk: &'static dyn BlockDevice, info: &PartitionInfo) -> Self {
+        Self {
+            disk,
+            start_block: info.start_block,
+            num_blocks: info.num_blocks,
+        }
+    }
+
+    fn translate(&self, block_id: usize) -> usize {
+        assert!(
+            block_id < self.num_blocks,
+            "block {block_id} out of partition with {} blocks",
+            self.num_blocks
+        );
+        self.start_block + block_id
+    }
+}
+
+impl BlockDevice for Partition {
+    fn read_block(&self, block_id: usize, buf: &mut [u8; BLOCK_SIZE]) {
+        self.disk.read_block(self.translate(block_id), buf);
+    }
+
+    fn read_block_cached(&self, block_id: usize, buf: &mut [u8; BLOCK_SIZE]) {
+        self.disk.read_block_cached(self.translate(block_id), buf);
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8; BLOCK_SIZE]) {
+        self.disk.write_block(self.translate(block_id), buf);
+    }
+
+    fn num_blocks(&self) -> usize {
+        self.num_blocks
+    }
+}
+
+/// 分区是否完整地位于磁盘内
+fn in_disk(disk: &dyn BlockDevice, start_block: usize, num_blocks: usize) -> bool {
+    start_block > 0
+        && num_blocks > 0
+        && start_block
+            .checked_add(num_blocks)
+            .is_some_and(|end| end <= disk.num_blocks())
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
@@ -0,0 +1,124 @@
+use alloc::vec::Vec;
+
+use hal::block_device::{BlockDevice, BLOCK_SIZE};
+
+use crate::{in_disk, read_u32, PartitionInfo};
+
+const SIGNATURE_OFFSET: usize = 510;
+const SIGNATURE: [u8; 2] = [0x55, 0xAA];
+/// 分区表项的起始偏移，共 4 项，每项 16 字节
+const TABLE_OFFSET: usize = 446;
+const ENTRY_SIZE: usize = 16;
+
+/// GPT 磁盘的保护性 MBR 中唯一的分区类型
+const TYPE_GPT_PROTECTIVE: u8 = 0xEE;
+/// 扩展分区的类型，分别是 DOS、Windows（LBA）和 linux 的扩展分区
+const TYPE_EXTENDED: [u8; 3] = [0x05, 0x0F, 0x85];
+
+/// 逻辑分区的编号从 5 开始
+const FIRST_LOGICAL_NUMBER: usize = 5;
+/// 扩展分区中 EBR 链的最大长度，防止损坏的分区表形成环
+const MAX_LOGICAL_PARTITIONS: usize = 64;
+
+pub(crate) enum Table {
+    /// GPT 磁盘的保护性 MBR，真正的分区表在 GPT 中
+    Protective,
+    Partitions(Vec<PartitionInfo>),
+}
+
+/// MBR 中的一个分区表项，单位为扇区
+struct Entry {
+    status: u8,
+    partition_type: u8,
+    start_lba: u32,
+    num_sectors: u32,
+}
+
+impl Entry {
+    fn read(sector: &[u8; BLOCK_SIZE], index: usize) -> Self {
+        let entry = &sector[TABLE_OFFSET + index * ENTRY_SIZE..TABLE_OFFSET + (index + 1) * ENTRY_SIZE];
+        Self {
+            status: entry[0],
+            partition_type: entry[4],
+            start_lba: read_u32(entry, 8),
+            num_sectors: read_u32(entry, 12),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.partition_type == 0 || self.num_sectors == 0
+    }
+}
+
+/// 解析 0 号扇区中的 MBR，不是 MBR 时返回 `None`
+pub(crate) fn parse(disk: &dyn BlockDevice, sector: &[u8; BLOCK_SIZE]) -> Option<Table> {
+    if sector[SIGNATURE_OFFSET..] != SIGNATURE {
+        return None;
+    }
+    let entries: [Entry; 4] = core::array::from_fn(|i| Entry::read(sector, i));
+    // 活动标志只能是 0 或 0x80。FAT 等文件系统的引导扇区也以 0x55AA 结尾，
+    // 但这一区域是引导代码，借此可以排除大部分误判
+    if entries.iter().any(|entry| entry.status & 0x7f != 0) {
+        return None;
+    }
+    if entries.iter().any(|entry| entry.partition_type == TYPE_GPT_PROTECTIVE) {
+        return Some(Table::Protective);
+    }
+
+    let mut partitions = Vec::new();
+    for (number, entry) in (1..).zip(&entries) {
+        if entry.is_empty() {
+            continue;
+        }
+        let (start_block, num_blocks) = (entry.start_lba as usize, entry.num_sectors as usize);
+        if !in_disk(disk, start_block, num_blocks) {
+            warn!("mbr partition {number} exceeds the disk, ignored");
+            continue;
+        }
+        if TYPE_EXTENDED.contains(&entry.partition_type) {
+            parse_logical(disk, start_block, num_blocks, &mut partitions);
+        } else {
+            partitions.push(PartitionInfo {
+                number,
+                start_block,
+                num_blocks,
+            });
+        }
+    }
+    Some(Table::Partitions(partitions))
+}
+
+/// 沿着扩展分区中的 EBR 链解析逻辑分区。
+///
+/// 每个 EBR 的第一项是逻辑分区，起始位置相对于该 EBR；第二项指向下一个 EBR，起始位置相对于扩展分区
+fn parse_logical(disk: &dyn BlockDevice, ext_start: usize, ext_len: usize, partitions: &mut Vec<PartitionInfo>) {
+    let mut sector = [0; BLOCK_SIZE];
+    let mut ebr = ext_start;
+    for number in FIRST_LOGICAL_NUMBER..FIRST_LOGICAL_NUMBER + MAX_LOGICAL_PARTITIONS {
+        disk.read_block(ebr, &mut sector);
+        if sector[SIGNATURE_OFFSET..] != SIGNATURE {
+            warn!("invalid ebr at sector {ebr}");
+            return;
+        }
+        let logical = Entry::read(&sector, 0);
+        let next = Entry::read(&sector, 1);
+        if !logical.is_empty() {
+            let start_block = ebr + logical.start_lba as usize;
+            let num_blocks = logical.num_sectors as usize;
+            if in_disk(disk, start_block, num_blocks) && start_block + num_blocks <= ext_start + ext_len {
+                partitions.push(PartitionInfo {
+                    number,
+                    start_block,
+                    num_blocks,
+                });
+            } else {
+                warn!("logical partition {number} exceeds the extended partition, ignored");
+            }
+        }
+        if next.is_empty() || next.start_lba as usize >= ext_len {
+            return;
+        }
+        ebr = ext_start + next.start_lba as usize;
+    }
+    warn!("too many logical partitions, the rest are ignored");
+}
@@ -0,0 +1,214 @@
+use std::sync::RwLock;
+
+use hal::block_device::{BlockDevice, BLOCK_SIZE};
+use partition::{scan, Partition, PartitionInfo};
+
+const TOTAL_SECTORS: usize = 4096;
+
+struct MemBlockDevice {
+    sectors: RwLock<Vec<[u8; BLOCK_SIZE]>>,
+}
+
+impl MemBlockDevice {
+    fn new() -> Self {
+        Self {
+            sectors: RwLock::new(vec![[0; BLOCK_SIZE]; TOTAL_SECTORS]),
+        }
+    }
+
+    fn sector_mut(&self, block_id: usize, f: impl FnOnce(&mut [u8; BLOCK_SIZE])) {
+        f(&mut self.sectors.write().unwrap()[block_id]);
+    }
+}
+
+impl BlockDevice for MemBlockDevice {
+    fn read_block(&self, block_id: usize, buf: &mut [u8; BLOCK_SIZE]) {
+        let sectors = self.sectors.read().unwrap();
+        buf.copy_from_slice(&sectors[block_id]);
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8; BLOCK_SIZE]) {
+        let mut sectors = self.sectors.write().unwrap();
+        sectors[block_id].copy_from_slice(buf);
+    }
+
+    fn num_blocks(&self) -> usize {
+        self.sectors.read().unwrap().len()
+    }
+}
+
+fn put_u32(bytes: &mut [u8], offset: usize, value: u32) {
+    bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(bytes: &mut [u8], offset: usize, value: u64) {
+    bytes[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+}
+
+/// 写入一个 MBR（或 EBR）分区表项
+fn put_mbr_entry(sector: &mut [u8; BLOCK_SIZE], index: usize, partition_type: u8, start: u32, len: u32) {
+    let entry = &mut sector[446 + index * 16..446 + (index + 1) * 16];
+    entry[4] = partition_type;
+    put_u32(entry, 8, start);
+    put_u32(entry, 12, len);
+    sector[510] = 0x55;
+    sector[511] = 0xAA;
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 0 {
+                crc >> 1
+            } else {
+                (crc >> 1) ^ 0xEDB8_8320
+            };
+        }
+    }
+    !crc
+}
+
+/// 构造一个带保护性 MBR 的 GPT 磁盘，`partitions` 为 (表项下标, 起始扇区, 结束扇区)
+fn make_gpt(partitions: &[(usize, u64, u64)]) -> MemBlockDevice {
+    const NUM_ENTRIES: usize = 128;
+    const ENTRY_SIZE: usize = 128;
+    let disk = MemBlockDevice::new();
+    disk.sector_mut(0, |sector| put_mbr_entry(sector, 0, 0xEE, 1, TOTAL_SECTORS as u32 - 1));
+
+    let mut entries = vec![0; NUM_ENTRIES * ENTRY_SIZE];
+    for &(index, first, last) in partitions {
+        let entry = &mut entries[index * ENTRY_SIZE..(index + 1) * ENTRY_SIZE];
+        // 随意的非零类型 GUID
+        entry[..16].fill(0xAB);
+        put_u64(entry, 32, first);
+        put_u64(entry, 40, last);
+    }
+    for (i, chunk) in entries.chunks_exact(BLOCK_SIZE).enumerate() {
+        disk.sector_mut(2 + i, |sector| sector.copy_from_slice(chunk));
+    }
+
+    disk.sector_mut(1, |header| {
+        header[..8].copy_from_slice(b"EFI PART");
+        put_u32(header, 8, 0x0001_0000);
+        put_u32(header, 12, 92);
+        put_u64(header, 24, 1);
+        put_u64(header, 32, TOTAL_SECTORS as u64 - 1);
+        put_u64(header, 40, 34);
+        put_u64(header, 48, TOTAL_SECTORS as u64 - 34);
+        put_u64(header, 72, 2);
+        put_u32(header, 80, NUM_ENTRIES as u32);
+        put_u32(header, 84, ENTRY_SIZE as u32);
+        put_u32(header, 88, crc32(&entries));
+        let header_crc = crc32(&header[..92]);
+        put_u32(header, 16, header_crc);
+    });
+    disk
+}
+
+fn info(number: usize, start_block: usize, num_blocks: usize) -> PartitionInfo {
+    PartitionInfo {
+        number,
+        start_block,
+        num_blocks,
+    }
+}
+
+#[test]
+fn crc32_check_value() {
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+}
+
+#[test]
+fn no_partition_table() {
+    let disk = MemBlockDevice::new();
+    assert!(scan(&disk).is_empty());
+
+    // 带有 0x55AA 签名，但分区表区域是引导代码，类似于 FAT 的引导扇区
+    disk.sector_mut(0, |sector| {
+        sector[446..510].fill(0x90);
+        sector[510] = 0x55;
+        sector[511] = 0xAA;
+    });
+    assert!(scan(&disk).is_empty());
+}
+
+#[test]
+fn mbr_primary_and_logical_partitions() {
+    let disk = MemBlockDevice::new();
+    disk.sector_mut(0, |sector| {
+        put_mbr_entry(sector, 0, 0x0C, 2048, 1024);
+        put_mbr_entry(sector, 2, 0x83, 3072, 256);
+        // 扩展分区 3328..4096，其中有两个逻辑分区
+        put_mbr_entry(sector, 3, 0x05, 3328, 768);
+    });
+    disk.sector_mut(3328, |ebr| {
+        put_mbr_entry(ebr, 0, 0x83, 1, 100);
+        put_mbr_entry(ebr, 1, 0x05, 200, 300);
+    });
+    disk.sector_mut(3528, |ebr| put_mbr_entry(ebr, 0, 0x83, 1, 299));
+
+    assert_eq!(
+        scan(&disk),
+        [
+            info(1, 2048, 1024),
+            info(3, 3072, 256),
+            info(5, 3329, 100),
+            info(6, 3529, 299)
+        ]
+    );
+}
+
+#[test]
+fn mbr_partition_beyond_disk_is_ignored() {
+    let disk = MemBlockDevice::new();
+    disk.sector_mut(0, |sector| {
+        put_mbr_entry(sector, 0, 0x83, 2048, 4096);
+        put_mbr_entry(sector, 1, 0x83, 64, 64);
+    });
+    assert_eq!(scan(&disk), [info(2, 64, 64)]);
+}
+
+#[test]
+fn gpt_partitions() {
+    let disk = make_gpt(&[(0, 2048, 3071), (2, 3072, 4000)]);
+    assert_eq!(scan(&disk), [info(1, 2048, 1024), info(3, 3072, 929)]);
+}
+
+#[test]
+fn gpt_with_bad_checksum_is_rejected() {
+    let disk = make_gpt(&[(0, 2048, 3071)]);
+    // 修改表项而不更新校验和
+    disk.sector_mut(2, |sector| sector[32] ^= 1);
+    assert!(scan(&disk).is_empty());
+
+    let disk = make_gpt(&[(0, 2048, 3071)]);
+    disk.sector_mut(1, |header| header[80] ^= 1);
+    assert!(scan(&disk).is_empty());
+}
+
+#[test]
+fn partition_translates_block_ids() {
+    let disk: &'static MemBlockDevice = Box::leak(Box::new(MemBlockDevice::new()));
+    let part = Partition::new(disk, &info(1, 100, 10));
+    assert_eq!(part.num_blocks(), 10);
+
+    part.write_block(3, &[7; BLOCK_SIZE]);
+    let mut buf = [0; BLOCK_SIZE];
+    disk.read_block(103, &mut buf);
+    assert_eq!(buf, [7; BLOCK_SIZE]);
+
+    disk.write_block(100, &[9; BLOCK_SIZE]);
+    part.read_block(0, &mut buf);
+    assert_eq!(buf, [9; BLOCK_SIZE]);
+}
+
+#[test]
+#[should_panic]
+fn partition_rejects_out_of_range_block() {
+    let disk: &'static MemBlockDevice = Box::leak(Box::new(MemBlockDevice::new()));
+    let part = Partition::new(disk, &info(1, 100, 10));
+    let mut buf = [0; BLOCK_SIZE];
+    part.read_block(10, &mut buf);
+}
@@ -6,7 +6,7 @@ use crate::{
     cmd_util::Cmd,
     timing::TimerSession,
     tool,
-    variables::{FS_IMG_PATH, SBI_PATH},
+    variables::{DISK_IMG_PATH, ROOT_PARTITION, SBI_PATH},
     KERNEL_BIN_PATH,
 };
 
@@ -23,6 +23,10 @@ pub struct QemuArgs {
     /// 如果开启，QEMU 会阻塞并等待 GDB 连接
     #[clap(long)]
     debug: bool,
+    /// 将主机的 TCP 端口转发到客户机的同一端口，可以指定多次。
+    /// 如 `--fwd 12865` 后可在主机上用 netperf 连接客户机中的 netserver
+    #[clap(long)]
+    fwd: Vec<u16>,
 }
 
 impl QemuArgs {
@@ -36,8 +40,16 @@ impl QemuArgs {
 
         println!("Running qemu...");
 
+        // 使用 QEMU 用户态网络，不需要主机上的额外配置
+        let mut netdev = String::from("user,id=net0");
+        for port in &self.fwd {
+            netdev.push_str(&format!(",hostfwd=tcp::{port}-:{port}"));
+        }
+
         Self::base_qemu()
             .args(["-smp", &self.smp.to_string()])
+            .args(["-netdev", &netdev])
+            .args(["-device", "virtio-net-device,netdev=net0,bus=virtio-mmio-bus.1"])
             .optional_args(self.debug.then_some(["-s", "-S"]))
             .invoke();
     }
@@ -46,7 +58,8 @@ impl QemuArgs {
         let mut cmd = Cmd::new("qemu-system-riscv64");
         cmd.args(["-machine", "virt", "-m", "128M", "-nographic"])
             .args(["-kernel", KERNEL_BIN_PATH, "-bios", SBI_PATH])
-            .args(["-drive", formatcp!("file={FS_IMG_PATH},if=none,format=raw,id=x0")])
+            .args(["-append", formatcp!("root={ROOT_PARTITION}")])
+            .args(["-drive", formatcp!("file={DISK_IMG_PATH},if=none,format=raw,id=x0")])
             .args(["-device", "virtio-blk-device,drive=x0,bus=virtio-mmio-bus.0"]);
         cmd
     }
@@ -16,7 +16,7 @@ use crate::{
     cmd_util::Cmd,
     timing::ScopedTimer,
     tool,
-    variables::{FS_IMG_PATH, TARGET_ARCH},
+    variables::{DISK_IMG_PATH, TARGET_ARCH},
     KERNEL_BIN_PATH, KERNEL_ELF_PATH,
 };
 
@@ -121,7 +121,12 @@ pub fn prepare_env() {
     Cmd::parse("cargo install cargo-binutils").invoke();
 }
 
-// 将一系列 elf 打包入 fat32 镜像中
+/// 扇区大小
+const SECTOR_SIZE: usize = 512;
+/// 根文件系统分区的起始扇区，按 1 MiB 对齐，同常见的分区工具
+const ROOT_PARTITION_START: usize = 2048;
+
+// 将一系列 elf 打包入 fat32 镜像中，再作为唯一的分区放入磁盘镜像
 pub fn pack() {
     let _timer = ScopedTimer::start("pack filesystem");
     let mut data = vec![0; 64 * 1024 * 1024];
@@ -195,7 +200,26 @@ pub fn pack() {
             pg.write_all(&buf).unwrap();
         }
     }
-    fs::write(FS_IMG_PATH, data).unwrap();
+    fs::write(DISK_IMG_PATH, make_disk_image(&data)).unwrap();
+}
+
+/// 生成带有 MBR 分区表的磁盘镜像，`fs_image` 作为其中唯一的主分区
+fn make_disk_image(fs_image: &[u8]) -> Vec<u8> {
+    let _timer = ScopedTimer::start("pack: make disk image");
+    let start = ROOT_PARTITION_START * SECTOR_SIZE;
+    let mut disk = vec![0; start + fs_image.len()];
+    disk[start..].copy_from_slice(fs_image);
+
+    // 第一个分区表项。CHS 地址已经过时，内核只使用 LBA，因此保持为 0
+    let entry = &mut disk[446..462];
+    // 活动分区
+    entry[0] = 0x80;
+    // FAT32 (LBA)
+    entry[4] = 0x0C;
+    entry[8..12].copy_from_slice(&(ROOT_PARTITION_START as u32).to_le_bytes());
+    entry[12..16].copy_from_slice(&((fs_image.len() / SECTOR_SIZE) as u32).to_le_bytes());
+    disk[510..512].copy_from_slice(&[0x55, 0xAA]);
+    disk
 }
 
 pub fn lint() {
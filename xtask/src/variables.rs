@@ -1,3 +1,5 @@
 pub const TARGET_ARCH: &str = "riscv64gc-unknown-none-elf";
 pub const SBI_PATH: &str = "res/rustsbi-qemu.bin";
-pub const FS_IMG_PATH: &str = "res/fat32.img";
+pub const DISK_IMG_PATH: &str = "res/disk.img";
+/// 根文件系统所在的分区，通过启动参数 `root=` 传给内核
+pub const ROOT_PARTITION: &str = "/dev/vda1";